@@ -77,6 +77,10 @@ export function ConnectionModal({ isOpen, onClose, onSave, editingConnection, ex
         if (connMethod === 'url') return rawUrl;
         // Basic construction
         const protocol = type === 'mssql' ? 'sqlserver' : type;
+        // Named instances (host\INSTANCE) get their port from SQL Browser
+        if (type === 'mssql' && host.includes('\\')) {
+            return `${protocol}://${user}:${password}@${host}/${database}`;
+        }
         return `${protocol}://${user}:${password}@${host}:${port}/${database}`;
     };
 
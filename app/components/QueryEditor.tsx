@@ -172,6 +172,9 @@ export function QueryEditor({
 
     const formatValue = (val: unknown) => {
         if (val === null) return <span className="text-neutral-600 italic">null</span>;
+        if (typeof val === 'object' && '$decode_error' in val) {
+            return <span className="text-amber-500 italic">{`<${String((val as { $decode_error: unknown }).$decode_error)}>`}</span>;
+        }
         if (typeof val === 'boolean') return <span className={val ? "text-green-500" : "text-red-500"}>{val.toString()}</span>;
         if (typeof val === 'object') return JSON.stringify(val);
         return String(val);
@@ -98,12 +98,37 @@ export function SettingsView({ sidebarWidth, settings, setSettings, setIsResizin
                             />
                             <label className="text-xs font-semibold text-text-muted">Auto Format SQL</label>
                         </div>
+                        <div>
+                            <label className="text-xs font-semibold text-text-muted block mb-2">Timestamp Time Zone</label>
+                            <select
+                                value={settings.query.timezone}
+                                onChange={(e) => setSettings({ ...settings, query: { ...settings.query, timezone: e.target.value } })}
+                                className="w-full bg-item-bg border border-border-main text-text-main rounded px-3 py-2 text-sm"
+                            >
+                                <option value="utc">UTC</option>
+                                <option value="server">Server Local</option>
+                                <option value="client">Client Local</option>
+                            </select>
+                        </div>
                     </>
                 )}
                 {/* Other settings tabs placeholder */}
                 {activeSettingsTab === 'connection' && <div className="text-xs text-text-muted italic">Connection settings coming soon...</div>}
                 {activeSettingsTab === 'export' && <div className="text-xs text-text-muted italic">Export settings coming soon...</div>}
-                {activeSettingsTab === 'advanced' && <div className="text-xs text-text-muted italic">Advanced settings coming soon...</div>}
+                {activeSettingsTab === 'advanced' && (
+                    <div>
+                        <label className="text-xs font-semibold text-text-muted block mb-2">Profile</label>
+                        <select
+                            value={settings.profile}
+                            onChange={(e) => setSettings({ ...settings, profile: e.target.value as SettingsType['profile'] })}
+                            className="w-full bg-item-bg border border-border-main text-text-main rounded px-3 py-2 text-sm"
+                        >
+                            <option value="dba">DBA (everything)</option>
+                            <option value="analyst">Analyst (no schema changes)</option>
+                            <option value="read_only">Read-only</option>
+                        </select>
+                    </div>
+                )}
             </div>
             <div className="p-4 border-t border-border-main bg-item-bg/50">
                 <button
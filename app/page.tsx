@@ -3,6 +3,7 @@
 import { useState, useEffect, useRef, useCallback } from "react";
 import { save } from "@tauri-apps/plugin-dialog";
 import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { Pencil, Trash2, Copy, PowerOff, Database, Plus, RefreshCw } from "lucide-react";
 import { cn } from "@/app/lib/utils";
 import { Connection, SavedConnection, Settings, QueryTab, DbType } from "./types";
@@ -31,6 +32,7 @@ const SETTINGS_DEFAULTS: Settings = {
     auto_limit: 100,
     timeout_seconds: 30,
     auto_format: false,
+    timezone: 'utc',
   },
   connection: {
     auto_connect_on_startup: false,
@@ -41,12 +43,14 @@ const SETTINGS_DEFAULTS: Settings = {
     default_format: 'csv',
     csv_delimiter: ',',
     include_headers: true,
+    templates: [],
   },
   advanced: {
     enable_debug_logs: false,
     cache_table_list: true,
     max_cached_connections: 5,
   },
+  profile: 'dba',
 };
 
 export default function Home() {
@@ -188,6 +192,19 @@ export default function Home() {
     };
   }, [isResizing]);
 
+  // Connections closed by the backend to stay under max_cached_connections
+  useEffect(() => {
+    const unlisten = listen<string>("connection-evicted", (event) => {
+      console.log("Connection evicted:", event.payload);
+      setConnections(prev => prev.map(c =>
+        c.name === event.payload ? { ...c, status: 'disconnected' } as Connection : c
+      ));
+    });
+    return () => {
+      unlisten.then(f => f());
+    };
+  }, []);
+
   // Close context menu on click
   useEffect(() => {
     const handleClick = () => {
@@ -206,6 +223,7 @@ export default function Home() {
           url: s.url,
           type: s.conn_type as DbType,
           color: s.color,
+          replicas: s.replicas ?? [],
           status: 'disconnected' as const
         }));
 
@@ -244,6 +262,7 @@ export default function Home() {
           url: s.url,
           type: s.conn_type as DbType,
           color: s.color,
+          replicas: s.replicas ?? [],
           status: 'disconnected' as const
         }));
         setConnections(mapped);
@@ -407,14 +426,15 @@ export default function Home() {
       name: c.name,
       url: c.url,
       conn_type: c.type,
-      color: c.color
+      color: c.color,
+      replicas: c.replicas ?? []
     }));
     await invoke("save_connections", { connections: saved });
   }
 
   async function handleConnectionSaved(newConn: Connection) {
     const updatedConns = editingConnName
-      ? connections.map(c => c.name === editingConnName ? { ...newConn, status: c.status } : c)
+      ? connections.map(c => c.name === editingConnName ? { ...newConn, replicas: c.replicas, status: c.status } : c)
       : [...connections, newConn];
 
     setConnections(updatedConns);
@@ -459,7 +479,7 @@ export default function Home() {
     const updated = connections.filter(c => c.name !== name);
     setConnections(updated);
     if (activeConnName === name) setActiveConnName(null);
-    await saveConnectionsToBackend(updated);
+    await invoke("delete_connection", { name });
   }
 
   function handleEditConnection(name: string) {
@@ -609,20 +629,11 @@ export default function Home() {
 
     setTabs(prev => prev.map(t => t.id === targetTabId ? { ...t, isExecuting: true, error: null } : t));
 
-    let finalSql = tab.query;
-    if (settings.query.auto_limit > 0 && tab.query.trim().toUpperCase().startsWith('SELECT')) {
-      if (!tab.query.toUpperCase().includes('LIMIT') && !tab.query.toUpperCase().includes('TOP')) {
-        const conn = connections.find(c => c.name === targetConnName);
-        if (conn?.type === 'mssql') {
-          finalSql = `SELECT TOP ${settings.query.auto_limit} * FROM (${tab.query}) AS subqb`;
-        } else {
-          finalSql = `${tab.query.trim()} LIMIT ${settings.query.auto_limit}`;
-        }
-      }
-    }
+    // The backend applies settings.query.auto_limit to plain SELECTs
+    const finalSql = tab.query;
 
     try {
-      const res = await invoke<{ columns: string[]; rows: unknown[][] }>("execute_query", {
+      const res = await invoke<{ columns: string[]; rows: unknown[][]; truncated: boolean }>("execute_query", {
         name: targetConnName,
         sql: finalSql,
       });
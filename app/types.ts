@@ -5,6 +5,7 @@ export type Connection = {
     url: string;
     type: DbType;
     color: string;
+    replicas?: string[];
     status: 'connected' | 'disconnected' | 'connecting' | 'error';
     error?: string;
 };
@@ -14,6 +15,7 @@ export type SavedConnection = {
     url: string;
     conn_type: string;
     color: string;
+    replicas?: string[];
 };
 
 export type Settings = {
@@ -26,6 +28,7 @@ export type Settings = {
         auto_limit: number;
         timeout_seconds: number;
         auto_format: boolean;
+        timezone: string;
     };
     connection: {
         auto_connect_on_startup: boolean;
@@ -36,19 +39,35 @@ export type Settings = {
         default_format: string;
         csv_delimiter: string;
         include_headers: boolean;
+        templates?: {
+            name: string;
+            filename_pattern: string;
+            folder: string | null;
+            format: string | null;
+            csv_delimiter: string | null;
+            include_headers: boolean | null;
+        }[];
     };
     advanced: {
         enable_debug_logs: boolean;
         cache_table_list: boolean;
         max_cached_connections: number;
     };
+    profile: 'dba' | 'analyst' | 'read_only';
 };
 
 export type QueryTab = {
     id: string;
     title: string;
     query: string;
-    results: { columns: string[]; rows: unknown[][] } | null;
+    results: {
+        columns: string[];
+        rows: unknown[][];
+        truncated?: boolean;
+        rows_affected?: number | null;
+        execution_ms?: number;
+        statement_kind?: 'query' | 'insert' | 'update' | 'delete' | 'merge' | 'ddl' | 'other';
+    } | null;
     error: string | null;
     connName: string | null;
     isExecuting: boolean;
@@ -66,3 +85,23 @@ export type QueryTab = {
     };
     totalRows?: number;
 };
+
+export type Bookmark = {
+    id: string;
+    name: string;
+    sql: string;
+    description: string;
+    tags: string[];
+    folder: string | null;
+    connection: string | null;
+    created_at: string;
+    updated_at: string;
+};
+
+export type TrashItem = {
+    id: string;
+    kind: 'connection' | 'bookmark';
+    name: string;
+    deleted_at: string;
+    item: SavedConnection | Bookmark;
+};
@@ -0,0 +1,301 @@
+// Opt-in audit trail: every executed statement is appended as a JSON line to
+// a log file in app data, which rotates by size. The log can be searched and
+// exported for compliance reviews.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::future::Future;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Instant;
+use tauri::Manager;
+
+use crate::db::TxStep;
+use crate::settings::{AuditSettings, SettingsState};
+use crate::storage;
+
+pub const AUDIT_DIR: &str = "audit";
+const AUDIT_FILE: &str = "audit.log";
+
+pub const DEFAULT_AUDIT_LIMIT: usize = 500;
+
+// Appends from concurrent queries go one at a time, so lines never interleave
+static APPEND: StdMutex<()> = StdMutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    pub executed_at: DateTime<Utc>,
+    pub connection: String,
+    pub user: String,
+    pub sql: String,
+    pub duration_ms: u64,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    pub fn new(
+        settings: &AuditSettings,
+        connection: &str,
+        sql: &str,
+        executed_at: DateTime<Utc>,
+        duration_ms: u64,
+        outcome: Result<(), &str>,
+    ) -> Self {
+        let user = match settings.user_label.trim() {
+            "" => std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .unwrap_or_default(),
+            label => label.to_string(),
+        };
+        AuditEntry {
+            executed_at,
+            connection: connection.to_string(),
+            user,
+            sql: sql.to_string(),
+            duration_ms,
+            success: outcome.is_ok(),
+            error: outcome.err().map(str::to_string),
+        }
+    }
+}
+
+struct AuditTarget {
+    dir: PathBuf,
+    settings: AuditSettings,
+    connection: String,
+}
+
+// A connection's audit trail, handed to the code running its statements.
+// Does nothing while the audit log is off.
+#[derive(Clone, Default)]
+pub struct Auditor {
+    target: Option<Arc<AuditTarget>>,
+}
+
+impl Auditor {
+    pub fn for_connection(app: &tauri::AppHandle, connection: &str) -> Self {
+        let settings = app.state::<SettingsState>().get().audit;
+        if !settings.enabled {
+            return Auditor::default();
+        }
+        match storage::data_file(app, AUDIT_DIR) {
+            Ok(dir) => Auditor {
+                target: Some(Arc::new(AuditTarget {
+                    dir,
+                    settings,
+                    connection: connection.to_string(),
+                })),
+            },
+            Err(e) => {
+                log::warn!("Audit log unavailable for {}: {}", connection, e);
+                Auditor::default()
+            }
+        }
+    }
+
+    // The write happens on a blocking thread; a failure is logged rather than
+    // failing the statement
+    pub fn record(
+        &self,
+        sql: &str,
+        (executed_at, started): (DateTime<Utc>, Instant),
+        outcome: Result<(), &str>,
+    ) {
+        let Some(target) = self.target.clone() else {
+            return;
+        };
+        let duration_ms = started.elapsed().as_millis() as u64;
+        let entry = AuditEntry::new(
+            &target.settings,
+            &target.connection,
+            sql,
+            executed_at,
+            duration_ms,
+            outcome,
+        );
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = append(&target.dir, &target.settings, &entry) {
+                log::warn!(
+                    "Failed to write the audit log for {}: {}",
+                    target.connection,
+                    e
+                );
+            }
+        });
+    }
+
+    // A transaction's statements go in as one entry, as they succeed or fail
+    // together
+    pub async fn run_steps<T>(
+        &self,
+        steps: &[TxStep],
+        work: impl Future<Output = Result<T, String>>,
+    ) -> Result<T, String> {
+        let sql: Vec<&str> = steps.iter().map(|s| s.statement.as_str()).collect();
+        self.run(&sql.join(";\n"), work).await
+    }
+
+    // Runs the work executing `sql` and records how it went
+    pub async fn run<T, E: fmt::Display>(
+        &self,
+        sql: &str,
+        work: impl Future<Output = Result<T, E>>,
+    ) -> Result<T, E> {
+        let started = (Utc::now(), Instant::now());
+        let result = work.await;
+        let error = result.as_ref().err().map(ToString::to_string);
+        self.record(sql, started, error.as_deref().map_or(Ok(()), Err));
+        result
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AuditFilter {
+    pub connection: Option<String>,
+    pub text: Option<String>, // in the SQL, case-insensitive
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub success: Option<bool>,
+    pub limit: Option<usize>, // searches only; exports take every match
+}
+
+impl AuditFilter {
+    fn matches(&self, entry: &AuditEntry, needle: Option<&str>) -> bool {
+        self.connection
+            .as_deref()
+            .map_or(true, |c| entry.connection == c)
+            && needle.map_or(true, |n| entry.sql.to_lowercase().contains(n))
+            && self.since.map_or(true, |t| entry.executed_at >= t)
+            && self.until.map_or(true, |t| entry.executed_at < t)
+            && self.success.map_or(true, |s| entry.success == s)
+    }
+}
+
+// audit.log, then audit.1.log for the one before, and so on
+fn rotated(dir: &Path, n: usize) -> PathBuf {
+    match n {
+        0 => dir.join(AUDIT_FILE),
+        n => dir.join(format!("audit.{}.log", n)),
+    }
+}
+
+// Shifts every file one place older, dropping the oldest; `max_files` > 1
+fn rotate(dir: &Path, max_files: usize) -> Result<(), String> {
+    let oldest = rotated(dir, max_files - 1);
+    if oldest.exists() {
+        fs::remove_file(&oldest).map_err(|e| e.to_string())?;
+    }
+    for n in (0..max_files - 1).rev() {
+        let from = rotated(dir, n);
+        if from.exists() {
+            fs::rename(&from, rotated(dir, n + 1)).map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+pub fn append(dir: &Path, settings: &AuditSettings, entry: &AuditEntry) -> Result<(), String> {
+    let mut line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+    line.push('\n');
+    let _guard = APPEND.lock().unwrap();
+    fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    let current = rotated(dir, 0);
+    let max_bytes = settings.max_file_mb.max(1) as u64 * 1024 * 1024;
+    let size = fs::metadata(&current).map(|m| m.len()).unwrap_or(0);
+    if size > 0 && size + line.len() as u64 > max_bytes {
+        let max_files = settings.max_files.max(1) as usize;
+        if max_files == 1 {
+            fs::remove_file(&current).map_err(|e| e.to_string())?;
+        } else {
+            rotate(dir, max_files)?;
+        }
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&current)
+        .map_err(|e| format!("Failed to open the audit log: {}", e))?;
+    file.write_all(line.as_bytes())
+        .map_err(|e| format!("Failed to write the audit log: {}", e))
+}
+
+// Matching entries oldest first, across the rotated files. Lines that don't
+// parse, e.g. one cut short by a crash, are skipped.
+fn entries(dir: &Path, filter: &AuditFilter) -> Result<Vec<AuditEntry>, String> {
+    let mut rotated_count = 0;
+    while rotated(dir, rotated_count + 1).exists() {
+        rotated_count += 1;
+    }
+    let needle = filter.text.as_deref().map(str::to_lowercase);
+    let mut matched = Vec::new();
+    for n in (0..=rotated_count).rev() {
+        let path = rotated(dir, n);
+        let Ok(file) = File::open(&path) else {
+            continue;
+        };
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+            match serde_json::from_str::<AuditEntry>(&line) {
+                Ok(entry) if filter.matches(&entry, needle.as_deref()) => matched.push(entry),
+                _ => {}
+            }
+        }
+    }
+    Ok(matched)
+}
+
+// Newest first
+pub fn search(dir: &Path, filter: &AuditFilter) -> Result<Vec<AuditEntry>, String> {
+    let mut matched = entries(dir, filter)?;
+    matched.reverse();
+    matched.truncate(filter.limit.unwrap_or(DEFAULT_AUDIT_LIMIT));
+    Ok(matched)
+}
+
+// Writes the matching entries oldest first, as CSV or (for a .jsonl or .json
+// path) JSON lines; returns how many were written
+pub fn export(dir: &Path, filter: &AuditFilter, path: &str) -> Result<usize, String> {
+    let matched = entries(dir, filter)?;
+    let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    let lower = path.to_lowercase();
+    if lower.ends_with(".jsonl") || lower.ends_with(".json") {
+        let mut writer = BufWriter::new(file);
+        for entry in &matched {
+            serde_json::to_writer(&mut writer, entry).map_err(|e| e.to_string())?;
+            writer.write_all(b"\n").map_err(|e| e.to_string())?;
+        }
+        writer.flush().map_err(|e| e.to_string())?;
+    } else {
+        let mut writer = csv::Writer::from_writer(file);
+        writer
+            .write_record([
+                "executed_at",
+                "connection",
+                "user",
+                "duration_ms",
+                "success",
+                "error",
+                "sql",
+            ])
+            .map_err(|e| e.to_string())?;
+        for entry in &matched {
+            writer
+                .write_record([
+                    entry.executed_at.to_rfc3339(),
+                    entry.connection.clone(),
+                    entry.user.clone(),
+                    entry.duration_ms.to_string(),
+                    entry.success.to_string(),
+                    entry.error.clone().unwrap_or_default(),
+                    entry.sql.clone(),
+                ])
+                .map_err(|e| e.to_string())?;
+        }
+        writer.flush().map_err(|e| e.to_string())?;
+    }
+    Ok(matched.len())
+}
@@ -0,0 +1,196 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::storage;
+
+pub const BOOKMARKS_FILE: &str = "bookmarks.json";
+
+// A curated query, kept apart from the raw history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    #[serde(default)]
+    pub id: String, // empty when creating
+    pub name: String,
+    pub sql: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub folder: Option<String>, // "/"-separated path, e.g. "reports/monthly"
+    #[serde(default)]
+    pub connection: Option<String>, // saved connection the query targets
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BookmarkFilter {
+    pub folder: Option<String>,
+    pub tag: Option<String>,
+    pub connection: Option<String>,
+}
+
+impl BookmarkFilter {
+    fn matches(&self, bookmark: &Bookmark) -> bool {
+        // A folder filter includes its subfolders
+        let folder_ok = self.folder.as_deref().map_or(true, |folder| {
+            bookmark.folder.as_deref().is_some_and(|f| {
+                f == folder || f.starts_with(&format!("{}/", folder.trim_end_matches('/')))
+            })
+        });
+        let tag_ok = self.tag.as_deref().map_or(true, |tag| {
+            bookmark.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+        });
+        let connection_ok = self
+            .connection
+            .as_deref()
+            .map_or(true, |c| bookmark.connection.as_deref() == Some(c));
+        folder_ok && tag_ok && connection_ok
+    }
+}
+
+pub fn load(path: &Path) -> Result<Vec<Bookmark>, String> {
+    storage::read_json(path)
+}
+
+pub fn list(path: &Path, filter: &BookmarkFilter) -> Result<Vec<Bookmark>, String> {
+    let mut bookmarks: Vec<Bookmark> = load(path)?
+        .into_iter()
+        .filter(|b| filter.matches(b))
+        .collect();
+    bookmarks.sort_by(|a, b| {
+        (a.folder.as_deref().unwrap_or(""), a.name.to_lowercase())
+            .cmp(&(b.folder.as_deref().unwrap_or(""), b.name.to_lowercase()))
+    });
+    Ok(bookmarks)
+}
+
+// Create the bookmark when its id is empty or unknown, otherwise replace it
+pub fn save(path: &Path, mut bookmark: Bookmark) -> Result<Bookmark, String> {
+    if bookmark.name.trim().is_empty() {
+        return Err("Bookmark name is required".to_string());
+    }
+    let mut bookmarks = load(path)?;
+    let now = Utc::now();
+    bookmark.tags = normalize_tags(bookmark.tags);
+    bookmark.folder = bookmark
+        .folder
+        .map(|f| f.trim().trim_matches('/').to_string())
+        .filter(|f| !f.is_empty());
+    bookmark.updated_at = now;
+
+    match bookmarks
+        .iter_mut()
+        .find(|b| !bookmark.id.is_empty() && b.id == bookmark.id)
+    {
+        Some(existing) => {
+            bookmark.created_at = existing.created_at;
+            *existing = bookmark.clone();
+        }
+        None => {
+            if bookmark.id.is_empty() {
+                bookmark.id = uuid::Uuid::new_v4().to_string();
+            }
+            bookmark.created_at = now;
+            bookmarks.push(bookmark.clone());
+        }
+    }
+    storage::write_json(path, &bookmarks)?;
+    Ok(bookmark)
+}
+
+pub fn get(path: &Path, id: &str) -> Result<Bookmark, String> {
+    load(path)?
+        .into_iter()
+        .find(|b| b.id == id)
+        .ok_or_else(|| "Bookmark not found".to_string())
+}
+
+// Put a previously deleted bookmark back unchanged
+pub fn restore(path: &Path, bookmark: Bookmark) -> Result<(), String> {
+    let mut bookmarks = load(path)?;
+    if bookmarks.iter().any(|b| b.id == bookmark.id) {
+        return Err(format!("Bookmark '{}' already exists", bookmark.name));
+    }
+    bookmarks.push(bookmark);
+    storage::write_json(path, &bookmarks)
+}
+
+pub fn delete(path: &Path, id: &str) -> Result<Bookmark, String> {
+    let mut bookmarks = load(path)?;
+    let index = bookmarks
+        .iter()
+        .position(|b| b.id == id)
+        .ok_or("Bookmark not found")?;
+    let removed = bookmarks.remove(index);
+    storage::write_json(path, &bookmarks)?;
+    Ok(removed)
+}
+
+// Best matches first; name and tags weigh more than the description and SQL text
+pub fn search(path: &Path, query: &str) -> Result<Vec<Bookmark>, String> {
+    let query = query.trim();
+    if query.is_empty() {
+        return list(path, &BookmarkFilter::default());
+    }
+    let mut scored: Vec<(i64, Bookmark)> = load(path)?
+        .into_iter()
+        .filter_map(|b| {
+            let fields = [
+                (b.name.as_str(), 4),
+                (b.folder.as_deref().unwrap_or(""), 2),
+                (b.description.as_str(), 1),
+                (b.sql.as_str(), 1),
+            ];
+            let field_score = fields
+                .iter()
+                .filter_map(|(text, weight)| fuzzy_score(query, text).map(|s| s * weight))
+                .max();
+            let tag_score = b
+                .tags
+                .iter()
+                .filter_map(|t| fuzzy_score(query, t).map(|s| s * 3))
+                .max();
+            field_score.max(tag_score).map(|score| (score, b))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.name.cmp(&b.1.name)));
+    Ok(scored.into_iter().map(|(_, b)| b).collect())
+}
+
+// Case-insensitive subsequence match. Consecutive characters and matches at
+// word starts score higher; `None` when the query isn't a subsequence.
+fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut prev: Option<usize> = None;
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = pos + text[pos..].iter().position(|&c| c == q)?;
+        score += 1;
+        if prev.is_some_and(|p| p + 1 == found) {
+            score += 3;
+        }
+        if found == 0 || !text[found - 1].is_alphanumeric() {
+            score += 2;
+        }
+        prev = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+pub fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().to_string();
+        if !tag.is_empty() && !normalized.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+            normalized.push(tag);
+        }
+    }
+    normalized
+}
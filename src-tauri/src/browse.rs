@@ -0,0 +1,431 @@
+// Paged table reads for the data grid
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::Row;
+use std::collections::HashMap;
+
+use crate::db::{self, ColumnInfo, DbClient, Dialect, QueryOptions, QueryResponse};
+use crate::edits::{bind, TableRef};
+use crate::mongo;
+use crate::quoting;
+
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+const MAX_PAGE_SIZE: usize = 10_000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TablePartition {
+    pub schema: Option<String>, // Postgres partitions are tables of their own
+    pub name: String,
+    pub bound: Option<String>, // e.g. FOR VALUES FROM (...) TO (...), or VALUES LESS THAN
+    pub estimated_rows: Option<i64>,
+}
+
+pub async fn get_partitions(
+    client: &DbClient,
+    table: &TableRef,
+) -> Result<Vec<TablePartition>, String> {
+    match client {
+        DbClient::Postgres(pool) => {
+            let rows = sqlx::query(
+                "SELECT n.nspname, c.relname, pg_get_expr(c.relpartbound, c.oid), c.reltuples::bigint \
+                 FROM pg_inherits i \
+                 JOIN pg_class c ON c.oid = i.inhrelid \
+                 JOIN pg_namespace n ON n.oid = c.relnamespace \
+                 WHERE i.inhparent = $1::regclass \
+                 ORDER BY c.relname",
+            )
+            .bind(table.qualified(Dialect::Postgres))
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            Ok(rows
+                .iter()
+                .map(|r| TablePartition {
+                    schema: Some(r.get(0)),
+                    name: r.get(1),
+                    bound: r.get(2),
+                    // -1 until the partition has been analyzed
+                    estimated_rows: Some(r.get::<i64, _>(3)).filter(|n| *n >= 0),
+                })
+                .collect())
+        }
+        DbClient::Mysql(pool) => {
+            // Subpartitions show up as extra rows of the same partition
+            let rows = sqlx::query(
+                "SELECT PARTITION_NAME, MAX(PARTITION_DESCRIPTION), CAST(SUM(TABLE_ROWS) AS SIGNED) \
+                 FROM information_schema.PARTITIONS \
+                 WHERE TABLE_SCHEMA = COALESCE(?, DATABASE()) AND TABLE_NAME = ? \
+                   AND PARTITION_NAME IS NOT NULL \
+                 GROUP BY PARTITION_NAME, PARTITION_ORDINAL_POSITION \
+                 ORDER BY PARTITION_ORDINAL_POSITION",
+            )
+            .bind(db::mysql_database(table.schema.clone()))
+            .bind(&table.table)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            Ok(rows
+                .iter()
+                .map(|r| TablePartition {
+                    schema: None,
+                    name: r.get(0),
+                    bound: r.try_get(1).ok().flatten(),
+                    estimated_rows: r.try_get(2).ok().flatten(),
+                })
+                .collect())
+        }
+        _ => Err("Partition browsing is supported for PostgreSQL and MySQL".to_string()),
+    }
+}
+
+// The FROM target for reading `partition` of `table`; a Postgres partition is
+// checked to really belong to the table and read as the child table it is
+async fn partition_source(
+    client: &DbClient,
+    table: &TableRef,
+    partition: &str,
+) -> Result<String, String> {
+    match client {
+        DbClient::Postgres(_) => {
+            let child = get_partitions(client, table)
+                .await?
+                .into_iter()
+                .find(|p| p.name == partition)
+                .ok_or_else(|| format!("{} is not a partition of {}", partition, table.table))?;
+            Ok(quoting::qualified(
+                Dialect::Postgres,
+                child.schema.as_deref(),
+                &child.name,
+            ))
+        }
+        DbClient::Mysql(_) => Ok(format!(
+            "{} PARTITION ({})",
+            table.qualified(Dialect::Mysql),
+            quoting::quote_ident(Dialect::Mysql, partition)
+        )),
+        _ => Err("Partition browsing is supported for PostgreSQL and MySQL".to_string()),
+    }
+}
+
+// One page of the table's rows, optionally from a single partition;
+// `columns` are the table's as cached in metadata.rs
+pub async fn get_table_data(
+    client: &DbClient,
+    table: &TableRef,
+    columns: &[ColumnInfo],
+    partition: Option<&str>,
+    limit: usize,
+    offset: usize,
+    options: &QueryOptions,
+) -> Result<QueryResponse, String> {
+    let dialect = client
+        .dialect()
+        .ok_or("Table browsing is not supported for this database type")?;
+    let source = match partition {
+        Some(partition) => partition_source(client, table, partition).await?,
+        None => table.qualified(dialect),
+    };
+    let (select_list, hierarchy) = mssql_select_list(client, columns);
+    let sql = match dialect {
+        Dialect::Mssql => format!(
+            "SELECT {} FROM {} ORDER BY (SELECT NULL) OFFSET {} ROWS FETCH NEXT {} ROWS ONLY",
+            select_list, source, offset, limit
+        ),
+        _ => format!("SELECT * FROM {} LIMIT {} OFFSET {}", source, limit, offset),
+    };
+    let mut result = db::execute_query(client, sql, options).await?;
+    label_hierarchy_columns(&mut result, &hierarchy);
+    Ok(result)
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SortKey {
+    pub column: String,
+    #[serde(default)]
+    pub direction: SortDirection,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Like, // SQL LIKE pattern with % and _, also for Mongo
+    NotLike,
+    In, // value is an array
+    IsNull,
+    IsNotNull,
+}
+
+impl FilterOp {
+    fn operator(self) -> &'static str {
+        match self {
+            FilterOp::Eq => "=",
+            FilterOp::Ne => "<>",
+            FilterOp::Lt => "<",
+            FilterOp::Le => "<=",
+            FilterOp::Gt => ">",
+            FilterOp::Ge => ">=",
+            FilterOp::Like => "LIKE",
+            FilterOp::NotLike => "NOT LIKE",
+            FilterOp::In => "IN",
+            FilterOp::IsNull => "IS NULL",
+            FilterOp::IsNotNull => "IS NOT NULL",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ColumnFilter {
+    pub column: String,
+    pub op: FilterOp,
+    #[serde(default)]
+    pub value: Value, // unused by is_null and is_not_null
+}
+
+#[derive(Serialize)]
+pub struct TablePage {
+    pub page: usize, // 1-based
+    pub page_size: usize,
+    pub total_rows: u64, // matching the filters
+    pub result: QueryResponse,
+}
+
+// Column names mapped to the type a parameter compared with them is cast to;
+// only Postgres needs one, as it won't compare e.g. a date with text
+pub async fn known_columns(
+    client: &DbClient,
+    table: &TableRef,
+) -> Result<HashMap<String, Option<String>>, String> {
+    match client {
+        DbClient::Postgres(pool) => {
+            let rows = sqlx::query(
+                "SELECT attname::text, format_type(atttypid, atttypmod) FROM pg_attribute \
+                 WHERE attrelid = $1::regclass AND attnum > 0 AND NOT attisdropped",
+            )
+            .bind(table.qualified(Dialect::Postgres))
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            Ok(rows.iter().map(|r| (r.get(0), Some(r.get(1)))).collect())
+        }
+        _ => Ok(
+            db::get_column_names(client, table.schema.clone(), &table.table)
+                .await?
+                .into_iter()
+                .map(|c| (c, None))
+                .collect(),
+        ),
+    }
+}
+
+// The select list for reading a SQL Server table, from its cached `columns`.
+// The driver can't read user-defined types, so hierarchyid columns are
+// selected as their "/1/2/" path; they are returned so the result can be
+// labelled with the real type.
+fn mssql_select_list(client: &DbClient, columns: &[ColumnInfo]) -> (String, Vec<String>) {
+    let DbClient::Mssql(_) = client else {
+        return ("*".to_string(), Vec::new());
+    };
+    let is_hierarchy = |c: &ColumnInfo| c.data_type.eq_ignore_ascii_case("hierarchyid");
+    let hierarchy: Vec<String> = columns
+        .iter()
+        .filter(|c| is_hierarchy(c))
+        .map(|c| c.name.clone())
+        .collect();
+    if hierarchy.is_empty() {
+        return ("*".to_string(), hierarchy);
+    }
+    let list = columns
+        .iter()
+        .map(|c| {
+            let quoted = quoting::quote_ident(Dialect::Mssql, &c.name);
+            if is_hierarchy(c) {
+                format!("{q}.ToString() AS {q}", q = quoted)
+            } else {
+                quoted
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    (list, hierarchy)
+}
+
+// Gives columns read through mssql_select_list their original type back
+fn label_hierarchy_columns(response: &mut QueryResponse, hierarchy: &[String]) {
+    for meta in &mut response.column_meta {
+        if hierarchy.contains(&meta.name) {
+            meta.type_name = "hierarchyid".to_string();
+            meta.value_tag = Some("hierarchyid");
+        }
+    }
+}
+
+// WHERE and ORDER BY clauses for the page, with filter values left as
+// parameters; column names are checked against the table's own
+fn page_clauses(
+    dialect: Dialect,
+    columns: &HashMap<String, Option<String>>,
+    sort: &[SortKey],
+    filters: &[ColumnFilter],
+) -> Result<(String, String, Vec<Value>), String> {
+    let column = |name: &str| match columns.contains_key(name) {
+        true => Ok(quoting::quote_ident(dialect, name)),
+        false => Err(format!("Unknown column: {}", name)),
+    };
+    let mut params = Vec::new();
+    let mut conditions = Vec::new();
+    for filter in filters {
+        let quoted = column(&filter.column)?;
+        let cast = columns.get(&filter.column).cloned().flatten();
+        let operator = filter.op.operator();
+        let condition = match (filter.op, &filter.value) {
+            (FilterOp::IsNull | FilterOp::IsNotNull, _) => format!("{} {}", quoted, operator),
+            (FilterOp::In, Value::Array(values)) if values.is_empty() => "1 = 0".to_string(),
+            (FilterOp::In, Value::Array(values)) => {
+                let list: Vec<String> = values
+                    .iter()
+                    .map(|v| bind(dialect, &mut params, cast.as_deref(), v))
+                    .collect();
+                format!("{} IN ({})", quoted, list.join(", "))
+            }
+            (FilterOp::In, _) => {
+                return Err(format!(
+                    "Filter on {} needs a list of values",
+                    filter.column
+                ))
+            }
+            // Matched as text whatever the column type
+            (FilterOp::Like | FilterOp::NotLike, Value::String(_)) => {
+                let target = match dialect {
+                    Dialect::Postgres => format!("{}::text", quoted),
+                    _ => quoted,
+                };
+                let pattern = bind(dialect, &mut params, None, &filter.value);
+                format!("{} {} {}", target, operator, pattern)
+            }
+            (FilterOp::Like | FilterOp::NotLike, _) => {
+                return Err(format!("Filter on {} needs a text pattern", filter.column))
+            }
+            (_, Value::Null) => {
+                return Err(format!(
+                    "Filter on {} needs a value; use is_null for NULL",
+                    filter.column
+                ))
+            }
+            (_, value) => {
+                let value = bind(dialect, &mut params, cast.as_deref(), value);
+                format!("{} {} {}", quoted, operator, value)
+            }
+        };
+        conditions.push(condition);
+    }
+    let where_clause = match conditions.is_empty() {
+        true => String::new(),
+        false => format!(" WHERE {}", conditions.join(" AND ")),
+    };
+
+    let mut order = Vec::new();
+    for key in sort {
+        let direction = match key.direction {
+            SortDirection::Asc => "ASC",
+            SortDirection::Desc => "DESC",
+        };
+        order.push(format!("{} {}", column(&key.column)?, direction));
+    }
+    let order_clause = match (order.is_empty(), dialect) {
+        (false, _) => format!(" ORDER BY {}", order.join(", ")),
+        // OFFSET needs an ORDER BY on SQL Server
+        (true, Dialect::Mssql) => " ORDER BY (SELECT NULL)".to_string(),
+        (true, _) => String::new(),
+    };
+    Ok((where_clause, order_clause, params))
+}
+
+// COUNT(*) comes back as a number, or a string when past the JSON safe range
+fn count_of(response: &QueryResponse) -> u64 {
+    match response
+        .rows
+        .first()
+        .and_then(|r| r.first())
+        .and_then(|c| c.value())
+    {
+        Some(Value::Number(n)) => n.as_u64().unwrap_or_default(),
+        Some(Value::String(s)) => s.parse().unwrap_or_default(),
+        _ => 0,
+    }
+}
+
+// A sorted, filtered page of the table plus the number of matching rows.
+// For MongoDB the table is a collection and its schema the database.
+pub async fn browse_table(
+    client: &DbClient,
+    table: &TableRef,
+    table_columns: &[ColumnInfo], // as cached in metadata.rs
+    (page, page_size): (usize, usize),
+    sort: &[SortKey],
+    filters: &[ColumnFilter],
+    options: &QueryOptions,
+) -> Result<TablePage, String> {
+    let page = page.max(1);
+    let page_size = page_size.clamp(1, MAX_PAGE_SIZE);
+    let offset = (page - 1) * page_size;
+    if let DbClient::Mongo(_) = client {
+        let (result, total_rows) =
+            mongo::browse_collection(client, table, offset, page_size, sort, filters).await?;
+        return Ok(TablePage {
+            page,
+            page_size,
+            total_rows,
+            result,
+        });
+    }
+
+    let dialect = client
+        .dialect()
+        .ok_or("Table browsing is not supported for this database type")?;
+    let columns = known_columns(client, table).await?;
+    if columns.is_empty() {
+        return Err(format!("Table {} not found", table.table));
+    }
+    let (where_clause, order_clause, params) = page_clauses(dialect, &columns, sort, filters)?;
+    let source = table.qualified(dialect);
+    let (select_list, hierarchy) = mssql_select_list(client, table_columns);
+    let sql = match dialect {
+        Dialect::Mssql => format!(
+            "SELECT {} FROM {}{}{} OFFSET {} ROWS FETCH NEXT {} ROWS ONLY",
+            select_list, source, where_clause, order_clause, offset, page_size
+        ),
+        _ => format!(
+            "SELECT * FROM {}{}{} LIMIT {} OFFSET {}",
+            source, where_clause, order_clause, page_size, offset
+        ),
+    };
+    let count_sql = format!("SELECT COUNT(*) FROM {}{}", source, where_clause);
+
+    let options = QueryOptions {
+        params,
+        ..options.clone()
+    };
+    let mut result = db::execute_query(client, sql, &options).await?;
+    label_hierarchy_columns(&mut result, &hierarchy);
+    let total_rows = count_of(&db::execute_query(client, count_sql, &options).await?);
+    Ok(TablePage {
+        page,
+        page_size,
+        total_rows,
+        result,
+    })
+}
@@ -0,0 +1,63 @@
+// What each backend supports, so the frontend enables features per engine
+// rather than assuming them
+use serde::Serialize;
+
+use crate::db::{self, DbClient, Dialect};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Engine {
+    Postgres,
+    Mysql,
+    Mssql,
+    Mongo,
+    Redis,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Capabilities {
+    pub engine: Engine,
+    pub sql: bool,
+    pub transactions: bool, // begin/commit/rollback and grid edits applied in one
+    pub explain: bool,
+    // Running statements are stopped on the server; elsewhere cancelling only
+    // stops waiting for the result
+    pub cancel: bool,
+    pub schemas: bool,    // MySQL has databases only
+    pub databases: bool,  // listed by get_databases
+    pub procedures: bool, // functions and procedures with their source
+    pub editing: bool,    // grid edits, row commands and imports
+    pub partitions: bool,
+    pub temporal_tables: bool, // SQL Server temporal tables, MariaDB system versioning
+}
+
+// Asks a MySQL server whether it is MariaDB; a failed check leaves out
+// what only MariaDB supports
+pub async fn of(client: &DbClient) -> Capabilities {
+    let engine = match client {
+        DbClient::Postgres(_) => Engine::Postgres,
+        DbClient::Mysql(_) => Engine::Mysql,
+        DbClient::Mssql(_) => Engine::Mssql,
+        DbClient::Mongo(_) => Engine::Mongo,
+        DbClient::Redis(_) => Engine::Redis,
+    };
+    let dialect = client.dialect();
+    let sql = dialect.is_some();
+    Capabilities {
+        engine,
+        sql,
+        transactions: sql,
+        explain: sql,
+        cancel: sql,
+        schemas: matches!(dialect, Some(Dialect::Postgres | Dialect::Mssql)),
+        databases: sql,
+        procedures: sql,
+        editing: sql,
+        partitions: matches!(dialect, Some(Dialect::Postgres | Dialect::Mysql)),
+        temporal_tables: match client {
+            DbClient::Mssql(_) => true,
+            DbClient::Mysql(pool) => db::is_mariadb(pool).await.unwrap_or(false),
+            _ => false,
+        },
+    }
+}
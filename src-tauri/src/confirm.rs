@@ -0,0 +1,81 @@
+// Two-phase confirmation for destructive commands. The first call only
+// describes what would run and hands out a token; the statements run when the
+// command is called again with that token. A token is single-use, expires,
+// and is bound to the exact statements and connection it was issued for.
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex as StdMutex;
+use std::time::{Duration, Instant};
+
+const TOKEN_TTL: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Confirmation {
+    pub token: String,
+    pub action: String, // human-readable, e.g. "Drop database sales"
+    pub statements: Vec<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+// What a guarded command returns: a confirmation request, or the outcome
+// once confirmed
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Guarded<T> {
+    ConfirmationRequired(Confirmation),
+    Done { result: T },
+}
+
+struct Pending {
+    connection: String,
+    statements: Vec<String>,
+    expires: Instant,
+}
+
+#[derive(Default)]
+pub struct Confirmations {
+    pending: StdMutex<HashMap<String, Pending>>,
+}
+
+impl Confirmations {
+    // Without a token, issues one and returns the confirmation to send back.
+    // With a token, returns None when it authorizes exactly these statements.
+    pub fn check(
+        &self,
+        connection: &str,
+        action: String,
+        statements: &[String],
+        token: Option<&str>,
+    ) -> Result<Option<Confirmation>, String> {
+        let mut pending = self.pending.lock().unwrap();
+        let now = Instant::now();
+        pending.retain(|_, p| p.expires > now);
+
+        let Some(token) = token else {
+            let token = uuid::Uuid::new_v4().to_string();
+            pending.insert(
+                token.clone(),
+                Pending {
+                    connection: connection.to_string(),
+                    statements: statements.to_vec(),
+                    expires: now + TOKEN_TTL,
+                },
+            );
+            return Ok(Some(Confirmation {
+                token,
+                action,
+                statements: statements.to_vec(),
+                expires_at: Utc::now() + chrono::Duration::from_std(TOKEN_TTL).unwrap_or_default(),
+            }));
+        };
+
+        let issued = pending
+            .remove(token)
+            .ok_or("Confirmation token is unknown or has expired")?;
+        if issued.connection != connection || issued.statements != statements {
+            return Err("Confirmation token was issued for a different action".to_string());
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,540 @@
+// Import of a CSV file into an existing table: column types are inferred from
+// the first rows (or given per column) and rows go in as batched, parameterized
+// INSERTs, or one upsert per row when conflict columns are given. Rows the file or the table rejects are reported rather than failing
+// the whole import.
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc;
+
+use crate::audit::Auditor;
+use crate::browse;
+use crate::db::{self, ColumnInfo, DbClient, Dialect, QueryOptions};
+use crate::edits::{self, TableRef};
+use crate::paste_import::{self, InferredType};
+use crate::quoting::quote_ident;
+
+// Rows read ahead to infer the column types from
+const INFER_ROWS: usize = 1000;
+const DEFAULT_BATCH_ROWS: usize = 500;
+// SQL Server takes at most 2100 parameters and 1000 VALUES rows per statement
+const MAX_PARAMS: usize = 2000;
+const MAX_BATCH_ROWS: usize = 1000;
+// Rejected rows listed in the result; all of them are counted
+const MAX_REPORTED: usize = 1000;
+// Records handed over from the reading thread at a time, and chunks in flight
+const READ_CHUNK: usize = 500;
+const READ_AHEAD: usize = 4;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct CsvColumn {
+    // Table column the CSV column goes to; None skips it
+    #[serde(default)]
+    pub column: Option<String>,
+    // Inferred from the file when not given
+    #[serde(default)]
+    pub data_type: Option<InferredType>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct CsvImportOptions {
+    pub delimiter: Option<String>, // "," unless given; "\t" or "tab" for tabs
+    pub quote: Option<String>,     // '"' unless given
+    // Without it, the first row is a header when every cell in it names a column
+    pub has_header: Option<bool>,
+    // One per CSV column, in file order. Without them columns are matched by
+    // header name, or else taken in table order.
+    pub columns: Vec<CsvColumn>,
+    pub batch_size: Option<usize>,
+    // With these, rows that match an existing one on them update it instead
+    pub conflict_columns: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CsvImportProgress {
+    pub path: String,
+    pub table: String,
+    pub read: u64,
+    pub inserted: u64,
+    pub rejected: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RejectedRow {
+    pub line: u64, // 1-based line in the file
+    pub error: String,
+    pub cells: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CsvImportResult {
+    pub has_header: bool,
+    pub columns: Vec<String>, // table columns written, in insert order
+    pub types: Vec<InferredType>,
+    pub read: u64,
+    pub inserted: u64,
+    pub rejected: u64,
+    pub rejected_rows: Vec<RejectedRow>, // the first MAX_REPORTED
+}
+
+fn single_byte(value: Option<&str>, default: u8, what: &str) -> Result<u8, String> {
+    match value {
+        None => Ok(default),
+        Some("\\t") | Some("tab") => Ok(b'\t'),
+        Some(v) if v.len() == 1 => Ok(v.as_bytes()[0]),
+        Some(v) => Err(format!("Invalid {}: {}", what, v)),
+    }
+}
+
+// A table column named case-insensitively, as the table spells it
+fn table_column(table_columns: &[String], name: &str) -> Option<String> {
+    table_columns
+        .iter()
+        .find(|c| c.eq_ignore_ascii_case(name))
+        .cloned()
+}
+
+// Pairs each imported CSV column (by position) with its table column; files
+// without a header or mapping fill the `writable` columns in order
+fn mapping(
+    options: &CsvImportOptions,
+    table: &TableRef,
+    table_columns: &[String],
+    writable: &[String],
+    header: Option<&[String]>,
+    width: usize,
+) -> Result<Vec<(usize, String)>, String> {
+    if !options.columns.is_empty() {
+        let mut mapped = Vec::new();
+        for (i, csv_column) in options.columns.iter().enumerate() {
+            let Some(name) = &csv_column.column else {
+                continue;
+            };
+            let column = table_column(table_columns, name)
+                .ok_or_else(|| format!("Column {} not found in {}", name, table.table))?;
+            mapped.push((i, column));
+        }
+        if mapped.is_empty() {
+            return Err("No columns selected for import".to_string());
+        }
+        return Ok(mapped);
+    }
+    if let Some(header) = header {
+        let unknown: Vec<&String> = header
+            .iter()
+            .filter(|h| table_column(table_columns, h).is_none())
+            .collect();
+        if !unknown.is_empty() {
+            return Err(format!(
+                "Columns not found in {}: {:?}",
+                table.table, unknown
+            ));
+        }
+        return Ok(header
+            .iter()
+            .enumerate()
+            .filter_map(|(i, h)| Some((i, table_column(table_columns, h)?)))
+            .collect());
+    }
+    if width > writable.len() {
+        return Err(format!(
+            "The file has {} columns but {} takes values for only {}",
+            width,
+            table.table,
+            writable.len()
+        ));
+    }
+    Ok(writable[..width].iter().cloned().enumerate().collect())
+}
+
+fn type_name(data_type: InferredType) -> String {
+    format!("{:?}", data_type).to_lowercase()
+}
+
+// A cell for a column of the given type; explicit and inferred types alike are
+// checked here, so a bad value names its column instead of failing the batch
+fn convert(cell: &str, data_type: InferredType) -> Result<Value, String> {
+    use InferredType::*;
+    if !cell.is_empty() && data_type != Text {
+        match (data_type, paste_import::cell_type(cell)) {
+            (a, b) if a == b => {}
+            (Decimal, Integer) | (Timestamp, Date) => {}
+            (Boolean, Integer) if cell == "0" || cell == "1" => {}
+            _ => {
+                return Err(format!(
+                    "'{}' is not a valid {}",
+                    cell,
+                    type_name(data_type)
+                ))
+            }
+        }
+    }
+    Ok(paste_import::cell_value(cell, data_type))
+}
+
+type Record = Result<csv::StringRecord, csv::Error>;
+
+// Records of the file, parsed on a blocking thread and passed over in chunks.
+// The thread stops once this is dropped, e.g. when the import is cancelled.
+struct Records {
+    chunks: mpsc::Receiver<Vec<Record>>,
+    current: std::vec::IntoIter<Record>,
+}
+
+impl Records {
+    async fn open(path: &str, builder: csv::ReaderBuilder) -> Result<Self, String> {
+        let file = path.to_string();
+        let reader = tokio::task::spawn_blocking(move || builder.from_path(file))
+            .await
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("Failed to open {}: {}", path, e))?;
+        let (sender, chunks) = mpsc::channel(READ_AHEAD);
+        tokio::task::spawn_blocking(move || {
+            let mut records = reader.into_records();
+            loop {
+                let chunk: Vec<Record> = records.by_ref().take(READ_CHUNK).collect();
+                if chunk.is_empty() || sender.blocking_send(chunk).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Records {
+            chunks,
+            current: Vec::new().into_iter(),
+        })
+    }
+
+    async fn next(&mut self) -> Option<Record> {
+        loop {
+            if let Some(record) = self.current.next() {
+                return Some(record);
+            }
+            self.current = self.chunks.recv().await?.into_iter();
+        }
+    }
+
+    // Returns records already taken, to be read again first
+    fn put_back(&mut self, records: Vec<Record>) {
+        let rest = std::mem::take(&mut self.current);
+        self.current = records
+            .into_iter()
+            .chain(rest)
+            .collect::<Vec<_>>()
+            .into_iter();
+    }
+}
+
+struct Batch {
+    lines: Vec<u64>,
+    cells: Vec<Vec<String>>,
+    values: Vec<Vec<Value>>,
+}
+
+impl Batch {
+    fn new() -> Self {
+        Batch {
+            lines: Vec::new(),
+            cells: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+}
+
+struct Importer<'a> {
+    client: &'a DbClient,
+    auditor: &'a Auditor,
+    // INSERT INTO t (a, b) VALUES, then one value per column, cast where needed
+    prefix: String,
+    casts: Vec<Option<String>>,
+    // Upsert of a single row, taking its values as parameters
+    upsert: Option<String>,
+    dialect: Dialect,
+    progress: CsvImportProgress,
+    rejected_rows: Vec<RejectedRow>,
+}
+
+impl Importer<'_> {
+    fn reject(&mut self, line: u64, error: String, cells: Vec<String>) {
+        self.progress.rejected += 1;
+        if self.rejected_rows.len() < MAX_REPORTED {
+            self.rejected_rows.push(RejectedRow { line, error, cells });
+        }
+    }
+
+    async fn insert(&self, rows: &[Vec<Value>]) -> Result<u64, String> {
+        let mut params = Vec::new();
+        let mut tuples = Vec::new();
+        for row in rows {
+            let values: Vec<String> = row
+                .iter()
+                .zip(&self.casts)
+                .map(|(value, cast)| {
+                    params.push(value.clone());
+                    let placeholder = edits::placeholder(self.dialect, params.len());
+                    match cast {
+                        Some(cast) => format!("CAST({} AS {})", placeholder, cast),
+                        None => placeholder,
+                    }
+                })
+                .collect();
+            tuples.push(format!("({})", values.join(", ")));
+        }
+        let sql = format!("{} {}", self.prefix, tuples.join(", "));
+        let rows_affected = self.execute(sql, params).await?;
+        Ok(rows_affected.unwrap_or(rows.len() as u64))
+    }
+
+    async fn execute(&self, sql: String, params: Vec<Value>) -> Result<Option<u64>, String> {
+        let options = QueryOptions {
+            params,
+            ..Default::default()
+        };
+        let work = db::execute_query(self.client, sql.clone(), &options);
+        let response = self.auditor.run(&sql, work).await?;
+        Ok(response.rows_affected)
+    }
+
+    // A row that is skipped as a duplicate counts as nothing written, one that
+    // replaces another as a single row
+    async fn write_row(&self, values: Vec<Value>) -> Result<u64, String> {
+        match &self.upsert {
+            Some(sql) => {
+                let rows_affected = self.execute(sql.clone(), values).await?;
+                Ok(rows_affected.unwrap_or(1).min(1))
+            }
+            None => self.insert(std::slice::from_ref(&values)).await,
+        }
+    }
+
+    // Inserts the batch in one statement. When the table rejects it, the rows
+    // are retried one by one to find the ones at fault. Upserts always go one
+    // row at a time.
+    async fn flush(&mut self, batch: Batch) {
+        if batch.values.is_empty() {
+            return;
+        }
+        if self.upsert.is_none() {
+            if let Ok(inserted) = self.insert(&batch.values).await {
+                self.progress.inserted += inserted;
+                return;
+            }
+        }
+        let rows = batch.lines.into_iter().zip(batch.cells).zip(batch.values);
+        for ((line, cells), values) in rows {
+            match self.write_row(values).await {
+                Ok(inserted) => self.progress.inserted += inserted,
+                Err(e) => self.reject(line, e, cells),
+            }
+        }
+    }
+}
+
+// Imports the CSV file at `path` into `table`, calling `on_progress` after
+// each batch. Rows already inserted stay when later ones are rejected.
+pub async fn import_csv(
+    client: &DbClient,
+    table: &TableRef,
+    path: &str,
+    options: CsvImportOptions,
+    auditor: &Auditor,
+    on_progress: impl Fn(&CsvImportProgress),
+) -> Result<CsvImportResult, String> {
+    let dialect = client
+        .dialect()
+        .ok_or("Import is not supported for this database type")?;
+    let delimiter = single_byte(options.delimiter.as_deref(), b',', "delimiter")?;
+    let quote = single_byte(options.quote.as_deref(), b'"', "quote character")?;
+    let mut builder = csv::ReaderBuilder::new();
+    builder
+        .delimiter(delimiter)
+        .quote(quote)
+        .has_headers(false)
+        .flexible(true);
+    let mut records = Records::open(path, builder).await?;
+
+    let info = db::get_column_info(client, table.schema.clone(), &table.table).await?;
+    if info.is_empty() {
+        return Err(format!("Table {} not found", table.table));
+    }
+    let table_columns: Vec<String> = info.iter().map(|c| c.name.clone()).collect();
+    let declared = paste_import::declared_types(&info);
+    let writable: Vec<String> = info
+        .into_iter()
+        .filter(ColumnInfo::is_writable)
+        .map(|c| c.name)
+        .collect();
+    let casts = browse::known_columns(client, table).await?;
+
+    // Read ahead for the header and type inference; malformed rows are
+    // rejected as they come up, not here
+    let mut sample = Vec::new();
+    while let Some(record) = records.next().await {
+        let failed = record.is_err();
+        sample.push(record);
+        if !failed && sample.len() >= INFER_ROWS {
+            break;
+        }
+    }
+    let first: Option<Vec<String>> = sample
+        .iter()
+        .find_map(|r| r.as_ref().ok())
+        .map(|r| r.iter().map(str::to_string).collect());
+    let Some(first) = first else {
+        return Err("Nothing to import".to_string());
+    };
+    let has_header = options.has_header.unwrap_or(
+        options.columns.is_empty()
+            && paste_import::header_columns(&first, &table_columns).is_some(),
+    );
+    if has_header {
+        // The header is the first row that parsed, already read as `first`
+        let position = sample.iter().position(|r| r.is_ok()).unwrap_or(0);
+        let _ = sample.remove(position);
+    }
+    let header = has_header.then_some(first.as_slice());
+    let mapped = mapping(
+        &options,
+        table,
+        &table_columns,
+        &writable,
+        header,
+        first.len(),
+    )?;
+
+    let types: Vec<InferredType> = mapped
+        .iter()
+        .map(|(i, column)| {
+            let given = options.columns.get(*i).and_then(|c| c.data_type);
+            given
+                .or_else(|| declared.get(column).copied())
+                .unwrap_or_else(|| {
+                    paste_import::column_type(
+                        sample.iter().filter_map(|r| r.as_ref().ok()?.get(*i)),
+                    )
+                })
+        })
+        .collect();
+    let columns: Vec<String> = mapped.iter().map(|(_, c)| c.clone()).collect();
+    let prefix = format!(
+        "INSERT INTO {} ({}) VALUES",
+        table.qualified(dialect),
+        columns
+            .iter()
+            .map(|c| quote_ident(dialect, c))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let casts: Vec<Option<String>> = columns
+        .iter()
+        .map(|c| casts.get(c).cloned().flatten())
+        .collect();
+    let upsert = match &options.conflict_columns {
+        Some(conflict_columns) => {
+            let values: Vec<String> = casts
+                .iter()
+                .enumerate()
+                .map(|(i, cast)| {
+                    let placeholder = edits::placeholder(dialect, i + 1);
+                    match cast {
+                        Some(cast) => format!("CAST({} AS {})", placeholder, cast),
+                        None => placeholder,
+                    }
+                })
+                .collect();
+            Some(edits::upsert_values(
+                dialect,
+                table,
+                &columns,
+                conflict_columns,
+                &values,
+            )?)
+        }
+        None => None,
+    };
+    let batch_rows = options
+        .batch_size
+        .unwrap_or(DEFAULT_BATCH_ROWS)
+        .min(MAX_PARAMS / columns.len())
+        .clamp(1, MAX_BATCH_ROWS);
+    let expected = if options.columns.is_empty() {
+        first.len()
+    } else {
+        options.columns.len()
+    };
+
+    let mut importer = Importer {
+        client,
+        auditor,
+        prefix,
+        casts,
+        upsert,
+        dialect,
+        progress: CsvImportProgress {
+            path: path.to_string(),
+            table: table.table.clone(),
+            read: 0,
+            inserted: 0,
+            rejected: 0,
+        },
+        rejected_rows: Vec::new(),
+    };
+    let mut batch = Batch::new();
+    records.put_back(sample);
+    while let Some(record) = records.next().await {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                importer.progress.read += 1;
+                let line = e.position().map(|p| p.line()).unwrap_or_default();
+                importer.reject(line, e.to_string(), Vec::new());
+                continue;
+            }
+        };
+        // Blank lines are skipped by the reader; rows of empty cells are too
+        if record.iter().all(str::is_empty) {
+            continue;
+        }
+        importer.progress.read += 1;
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+        let cells: Vec<String> = record.iter().map(str::to_string).collect();
+        if cells.len() > expected {
+            let error = format!("Row has {} cells, expected {}", cells.len(), expected);
+            importer.reject(line, error, cells);
+            continue;
+        }
+        let values: Result<Vec<Value>, String> = mapped
+            .iter()
+            .zip(&types)
+            .map(|((i, column), t)| {
+                let cell = cells.get(*i).map(String::as_str).unwrap_or_default();
+                convert(cell, *t).map_err(|e| format!("{}: {}", column, e))
+            })
+            .collect();
+        match values {
+            Ok(values) => {
+                batch.lines.push(line);
+                batch.cells.push(cells);
+                batch.values.push(values);
+            }
+            Err(e) => importer.reject(line, e, cells),
+        }
+        if batch.values.len() >= batch_rows {
+            importer
+                .flush(std::mem::replace(&mut batch, Batch::new()))
+                .await;
+            on_progress(&importer.progress);
+        }
+    }
+    importer.flush(batch).await;
+    on_progress(&importer.progress);
+
+    Ok(CsvImportResult {
+        has_header,
+        columns,
+        types,
+        read: importer.progress.read,
+        inserted: importer.progress.inserted,
+        rejected: importer.progress.rejected,
+        rejected_rows: importer.rejected_rows,
+    })
+}
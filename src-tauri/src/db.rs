@@ -1,44 +1,528 @@
 use chrono;
 
-use serde::Serialize;
-use serde_json::{json, Value};
-use sqlx::{Column, Row};
+use crate::confirm::Confirmations;
+use crate::ddl_history;
+use crate::edits::{StagedChanges, UndoJournal};
+use crate::errors::{self, DbError};
+use crate::export_columns::ColumnShaper;
+use crate::jobs::Jobs;
+use crate::keepalive::KeepAlives;
+use crate::metadata::MetadataCache;
+use crate::mongo;
+use crate::mssql_pool::{MssqlPool, MSSQL_POOL_SIZE};
+use crate::quoting::{quote_ansi_ident, sql_literal};
+use crate::redis_tools;
+use crate::running::RunningQueries;
+use crate::schemawatch::SchemaWatches;
+use crate::scripts;
+use crate::settings::{ExportSettings, Settings};
+use crate::sqltext::{self, StatementKind};
+use crate::tls::{self, TlsOptions};
+use crate::transactions::Transactions;
+use crate::values::{
+    mssql_type_name, mysql_row_values, pg_row_values, serialize_mssql_row, Cell, RenderZone,
+};
+use crate::xlsx::XlsxSink;
+use chrono::FixedOffset;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use sqlx::postgres::PgPoolCopyExt;
+use sqlx::{Column, ConnectOptions, Row, TypeInfo};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex as StdMutex};
-use tiberius::{Client, Config};
+use std::time::{Duration, Instant};
+use tiberius::{Client, Config, SqlBrowser};
+use tokio::io::AsyncWriteExt;
 use tokio::net::TcpStream;
-use tokio::sync::Mutex as AsyncMutex;
 use tokio_util::compat::{Compat, TokioAsyncWriteCompatExt};
 use url::Url; // Added chrono import
 
+pub type MssqlClient = Client<Compat<TcpStream>>;
+
 // Enum to hold different client types
 #[derive(Clone)]
 pub enum DbClient {
-    Mssql(Arc<AsyncMutex<Client<Compat<TcpStream>>>>),
+    Mssql(Arc<MssqlPool>),
     Mysql(sqlx::MySqlPool),
     Postgres(sqlx::PgPool),
     Mongo(mongodb::Client),
     Redis(redis::Client),
 }
 
+// SQL flavour of a client, used when generating statements
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dialect {
+    Postgres,
+    Mysql,
+    Mssql,
+}
+
+impl DbClient {
+    pub fn dialect(&self) -> Option<Dialect> {
+        match self {
+            DbClient::Postgres(_) => Some(Dialect::Postgres),
+            DbClient::Mysql(_) => Some(Dialect::Mysql),
+            DbClient::Mssql(_) => Some(Dialect::Mssql),
+            _ => None,
+        }
+    }
+}
+
 pub struct DatabaseState {
     pub connections: StdMutex<HashMap<String, DbClient>>,
+    pub replicas: StdMutex<HashMap<String, Arc<ReplicaSet>>>,
+    pub last_used: StdMutex<HashMap<String, Instant>>,
+    pub urls: StdMutex<HashMap<String, String>>,
+    pub running: RunningQueries,
+    pub jobs: Jobs,
+    pub watches: SchemaWatches,
+    pub transactions: Transactions,
+    pub undo: UndoJournal,
+    pub staged: StagedChanges,
+    pub confirmations: Confirmations,
+    pub metadata: MetadataCache,
+    pub keep_alives: KeepAlives,
 }
 
 impl Default for DatabaseState {
     fn default() -> Self {
         Self {
             connections: StdMutex::new(HashMap::new()),
+            replicas: StdMutex::new(HashMap::new()),
+            last_used: StdMutex::new(HashMap::new()),
+            urls: StdMutex::new(HashMap::new()),
+            running: RunningQueries::default(),
+            jobs: Jobs::default(),
+            watches: SchemaWatches::default(),
+            transactions: Transactions::default(),
+            undo: UndoJournal::default(),
+            staged: StagedChanges::default(),
+            confirmations: Confirmations::default(),
+            metadata: MetadataCache::default(),
+            keep_alives: KeepAlives::default(),
+        }
+    }
+}
+
+impl DatabaseState {
+    // Looks up an open connection and marks it as recently used
+    pub fn client(&self, name: &str) -> Result<DbClient, String> {
+        let client = {
+            let pools = self.connections.lock().unwrap();
+            pools.get(name).cloned().ok_or("Connection not found")?
+        };
+        self.last_used
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), Instant::now());
+        Ok(client)
+    }
+
+    // Looks up an open connection without counting it as a use, for
+    // background checks that shouldn't keep an idle connection alive
+    pub fn peek(&self, name: &str) -> Result<DbClient, String> {
+        let pools = self.connections.lock().unwrap();
+        Ok(pools.get(name).cloned().ok_or("Connection not found")?)
+    }
+
+    pub fn insert(&self, name: &str, url: &str, client: DbClient) {
+        self.connections
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), client);
+        self.urls
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), url.to_string());
+        self.last_used
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), Instant::now());
+    }
+
+    // Drops a connection together with everything kept for it
+    pub fn remove(&self, name: &str) -> Option<DbClient> {
+        let client = self.connections.lock().unwrap().remove(name);
+        self.replicas.lock().unwrap().remove(name);
+        self.last_used.lock().unwrap().remove(name);
+        self.urls.lock().unwrap().remove(name);
+        self.watches.stop(name);
+        self.transactions.discard(name);
+        self.undo.clear(name);
+        self.staged.clear(name);
+        self.metadata.forget(name);
+        self.keep_alives.stop(name);
+        client
+    }
+
+    // Closes least-recently-used connections until at most `max` remain, never
+    // touching `keep`. Returns the names that were closed.
+    pub fn evict_lru(&self, max: usize, keep: &str) -> Vec<String> {
+        let mut candidates: Vec<(String, Instant)> = {
+            let pools = self.connections.lock().unwrap();
+            let last_used = self.last_used.lock().unwrap();
+            pools
+                .keys()
+                .filter(|name| name.as_str() != keep)
+                .map(|name| {
+                    let used = last_used.get(name).copied().unwrap_or_else(Instant::now);
+                    (name.clone(), used)
+                })
+                .collect()
+        };
+        let excess = (candidates.len() + 1).saturating_sub(max);
+        candidates.sort_by_key(|(_, used)| *used);
+        candidates
+            .into_iter()
+            .take(excess)
+            .map(|(name, _)| {
+                self.remove(&name);
+                name
+            })
+            .collect()
+    }
+
+    // Client grid edits run on: an open transaction's connection, flagged so
+    // the edits join that transaction rather than start one of their own
+    pub fn edit_client(&self, name: &str) -> Result<(DbClient, bool), String> {
+        let primary = self.client(name)?;
+        Ok(match self.transactions.client(name) {
+            Some(tx) => (tx, true),
+            None => (primary, false),
+        })
+    }
+
+    // Client a query should run on: inside an open transaction that's its
+    // connection; otherwise read-only statements go to a replica when the
+    // connection has any, unless `route` says otherwise
+    pub fn routed_client(
+        &self,
+        name: &str,
+        sql: &str,
+        route: QueryRoute,
+    ) -> Result<DbClient, String> {
+        let primary = self.client(name)?;
+        if let Some(tx) = self.transactions.client(name) {
+            return Ok(tx);
         }
+        let replicas = self.replicas.lock().unwrap().get(name).cloned();
+        match (route, replicas) {
+            (QueryRoute::Primary, _) => Ok(primary),
+            (QueryRoute::Replica, None) => Err(format!("Connection {} has no read replicas", name)),
+            (QueryRoute::Replica, Some(replicas)) => Ok(replicas.pick()),
+            (QueryRoute::Auto, Some(replicas)) if sqltext::is_read_query(sql) => {
+                Ok(replicas.pick())
+            }
+            (QueryRoute::Auto, _) => Ok(primary),
+        }
+    }
+}
+
+// Per-query override of replica routing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryRoute {
+    #[default]
+    Auto,
+    Primary,
+    Replica,
+}
+
+// Read replicas of one connection, used round-robin
+pub struct ReplicaSet {
+    clients: Vec<DbClient>,
+    next: AtomicUsize,
+}
+
+impl ReplicaSet {
+    pub fn new(clients: Vec<DbClient>) -> Option<Self> {
+        (!clients.is_empty()).then(|| Self {
+            clients,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    fn pick(&self) -> DbClient {
+        let i = self.next.fetch_add(1, Ordering::Relaxed) % self.clients.len();
+        self.clients[i].clone()
     }
 }
 
 #[derive(Serialize)]
 pub struct QueryResponse {
     pub columns: Vec<String>,
-    pub rows: Vec<Vec<Value>>,
+    pub rows: Vec<Vec<Cell>>,
+    pub column_meta: Vec<ColumnMeta>,
+    pub truncated: bool, // more rows matched than the row limit let through
+    // As reported by the server; None for queries, DDL, and when not reported
+    pub rows_affected: Option<u64>,
+    pub execution_ms: u64,
+    pub statement_kind: StatementKind,
+    pub retries: u32, // runs repeated after a serialization failure or deadlock
+}
+
+impl QueryResponse {
+    pub fn new(columns: Vec<String>, rows: Vec<Vec<Cell>>, column_meta: Vec<ColumnMeta>) -> Self {
+        Self {
+            columns,
+            rows,
+            column_meta,
+            truncated: false,
+            rows_affected: None,
+            execution_ms: 0,
+            statement_kind: StatementKind::Other,
+            retries: 0,
+        }
+    }
+
+    pub fn counted(mut self, kind: StatementKind, rows_affected: Option<u64>) -> Self {
+        self.statement_kind = kind;
+        self.rows_affected =
+            rows_affected.filter(|_| !matches!(kind, StatementKind::Query | StatementKind::Ddl));
+        self
+    }
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ColumnMeta {
+    pub name: String,
+    pub type_name: String,
+    // Offset that time zone aware values were rendered in, e.g. "+07:00" or "local"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    // "decimal": values are exact decimal strings; "int64": values outside the
+    // JavaScript safe integer range are sent as strings. SQL Server temporal
+    // columns are tagged "date", "time", "datetime", "smalldatetime" or
+    // "datetimeoffset", and hierarchyid columns "hierarchyid" ("/1/2/" paths).
+    // Postgres network and interval columns are "inet", "macaddr" and
+    // "interval", as text in Postgres' own notation.
+    // MongoDB fields are "extjson": values are relaxed Extended JSON (see
+    // mongo::extended_json) and are sent back as they came.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_tag: Option<&'static str>,
+}
+
+// Which zone timestamps with a time zone are returned in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimezoneMode {
+    #[default]
+    Utc,
+    Server,
+    Client,
+}
+
+impl TimezoneMode {
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "server" => TimezoneMode::Server,
+            "client" => TimezoneMode::Client,
+            _ => TimezoneMode::Utc,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct QueryOptions {
+    pub timezone: TimezoneMode,
+    // Cap on returned rows for plain SELECTs, applied in SQL; None runs queries as written
+    pub row_limit: Option<usize>,
+    // Values for the $1 / ? / @P1 placeholders, bound by the driver
+    pub params: Vec<Value>,
+    // How often a statement failing on a serialization conflict or deadlock is
+    // run again; only safe outside an explicit transaction
+    pub conflict_retries: u32,
+    // The statement is cancelled on the server once it runs longer
+    pub timeout: Option<Duration>,
+}
+
+impl QueryOptions {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            timezone: TimezoneMode::parse(&settings.query.timezone),
+            row_limit: None,
+            params: Vec::new(),
+            conflict_retries: settings.query.conflict_retries.max(0) as u32,
+            timeout: (settings.query.timeout_seconds > 0)
+                .then(|| Duration::from_secs(settings.query.timeout_seconds as u64)),
+        }
+    }
+
+    // Exports read the whole result however long that takes; the timeout is
+    // meant for statements run in the editor
+    pub fn for_export(settings: &Settings) -> Self {
+        Self {
+            timeout: None,
+            ..Self::from_settings(settings)
+        }
+    }
+}
+
+// Binds editor parameters by JSON type. Postgres infers nothing from a
+// string, so placeholders for other types need a cast, e.g. $1::date.
+fn bind_params<'q, DB>(
+    mut query: sqlx::query::Query<'q, DB, <DB as sqlx::Database>::Arguments<'q>>,
+    params: &'q [Value],
+) -> sqlx::query::Query<'q, DB, <DB as sqlx::Database>::Arguments<'q>>
+where
+    DB: sqlx::Database,
+    bool: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    i64: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    f64: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    Option<&'q str>: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    sqlx::types::Json<&'q Value>: sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+{
+    for param in params {
+        query = match param {
+            Value::Null => query.bind(None::<&str>),
+            Value::Bool(b) => query.bind(*b),
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => query.bind(i),
+                None => query.bind(n.as_f64().unwrap_or_default()),
+            },
+            Value::String(s) => query.bind(Some(s.as_str())),
+            other => query.bind(sqlx::types::Json(other)),
+        };
+    }
+    query
+}
+
+// Without parameters the batch is sent as is rather than through
+// sp_executesql, which would scope temp tables to the call
+async fn mssql_send<'a>(
+    client: &'a mut MssqlClient,
+    sql: &'a str,
+    params: &'a [Value],
+) -> Result<tiberius::QueryStream<'a>, DbError> {
+    if params.is_empty() {
+        return client.simple_query(sql).await.map_err(errors::mssql_error);
+    }
+    mssql_query(sql, params)
+        .query(client)
+        .await
+        .map_err(errors::mssql_error)
+}
+
+fn mssql_query<'a>(sql: &'a str, params: &'a [Value]) -> tiberius::Query<'a> {
+    let mut query = tiberius::Query::new(sql);
+    for param in params {
+        match param {
+            Value::Null => query.bind(None::<&str>),
+            Value::Bool(b) => query.bind(*b),
+            Value::Number(n) => match n.as_i64() {
+                Some(i) => query.bind(i),
+                None => query.bind(n.as_f64()),
+            },
+            Value::String(s) => query.bind(s.as_str()),
+            other => query.bind(other.to_string()),
+        }
+    }
+    query
+}
+
+// fetch_all, but keeping the server's summary (rows affected) alongside the rows
+async fn fetch_counted<'e, 'q: 'e, DB, E>(
+    executor: E,
+    query: sqlx::query::Query<'q, DB, <DB as sqlx::Database>::Arguments<'q>>,
+) -> Result<(Vec<DB::Row>, DB::QueryResult), DbError>
+where
+    DB: sqlx::Database,
+    E: sqlx::Executor<'e, Database = DB>,
+    <DB as sqlx::Database>::Arguments<'q>: sqlx::IntoArguments<'q, DB>,
+{
+    let mut stream = executor.fetch_many(query);
+    let mut rows = Vec::new();
+    let mut result = DB::QueryResult::default();
+    while let Some(step) = stream.next().await {
+        match step.map_err(errors::sql_error)? {
+            sqlx::Either::Left(done) => result.extend([done]),
+            sqlx::Either::Right(row) => rows.push(row),
+        }
+    }
+    Ok((rows, result))
+}
+
+// Column types whose values carry an absolute instant
+fn is_zoned_type(type_name: &str) -> bool {
+    matches!(type_name, "TIMESTAMPTZ" | "TIMESTAMP" | "DatetimeOffsetn")
+}
+
+fn value_tag(type_name: &str) -> Option<&'static str> {
+    match type_name {
+        "NUMERIC" | "DECIMAL" | "DECIMAL UNSIGNED" | "MONEY" | "Decimaln" | "Numericn"
+        | "Money" | "Money4" => Some("decimal"),
+        "INT8" | "BIGINT" | "BIGINT UNSIGNED" | "Int8" => Some("int64"),
+        // SQL Server's temporal types, told apart for formatting
+        "Daten" => Some("date"),
+        "Timen" => Some("time"),
+        "Datetime4" => Some("smalldatetime"),
+        "Datetime" | "Datetimen" | "Datetime2" => Some("datetime"),
+        "DatetimeOffsetn" => Some("datetimeoffset"),
+        // Postgres network and interval types, sent as Postgres prints them
+        "INET" | "CIDR" => Some("inet"),
+        "MACADDR" | "MACADDR8" => Some("macaddr"),
+        "INTERVAL" => Some("interval"),
+        _ => None,
+    }
+}
+
+fn column_meta(name: &str, type_name: String, zone: RenderZone) -> ColumnMeta {
+    let timezone = is_zoned_type(&type_name).then(|| zone.label());
+    ColumnMeta {
+        name: name.to_string(),
+        value_tag: value_tag(&type_name),
+        type_name,
+        timezone,
+    }
+}
+
+pub async fn resolve_render_zone(
+    client: &DbClient,
+    mode: TimezoneMode,
+) -> Result<RenderZone, String> {
+    let seconds: i64 = match mode {
+        TimezoneMode::Utc => return Ok(RenderZone::utc()),
+        TimezoneMode::Client => return Ok(RenderZone::Local),
+        TimezoneMode::Server => match client {
+            DbClient::Postgres(pool) => {
+                sqlx::query_scalar("SELECT EXTRACT(TIMEZONE FROM now())::bigint")
+                    .fetch_one(pool)
+                    .await
+                    .map_err(|e| e.to_string())?
+            }
+            DbClient::Mysql(pool) => {
+                // The session zone is pinned to UTC by the driver, so ask for the server's own zone
+                sqlx::query_scalar(
+                    "SELECT TIMESTAMPDIFF(SECOND, UTC_TIMESTAMP(), CONVERT_TZ(UTC_TIMESTAMP(), '+00:00', IF(@@global.time_zone = 'SYSTEM', @@system_time_zone, @@global.time_zone)))",
+                )
+                .fetch_one(pool)
+                .await
+                .map_err(|e| e.to_string())?
+            }
+            DbClient::Mssql(pool) => {
+                let mut client = pool.get().await?;
+                let row = client
+                    .simple_query("SELECT DATEPART(TZOFFSET, SYSDATETIMEOFFSET())")
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .into_row()
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let minutes = row
+                    .and_then(|r| r.try_get::<i32, _>(0).ok().flatten())
+                    .unwrap_or(0);
+                i64::from(minutes) * 60
+            }
+            _ => 0,
+        },
+    };
+    Ok(FixedOffset::east_opt(seconds as i32)
+        .map(RenderZone::Fixed)
+        .unwrap_or_else(RenderZone::utc))
 }
 
 // Export Helper Structs
@@ -56,48 +540,109 @@ struct XmlData {
     rows: Vec<XmlRow>,
 }
 
-pub async fn create_client(conn_str: &str) -> Result<DbClient, String> {
-    let url = Url::parse(conn_str).map_err(|e| format!("Invalid URL: {}", e))?;
-    let scheme = url.scheme();
+// `sqlserver://host\INSTANCE/db` isn't a valid URL, so the instance name is
+// taken out of the authority before parsing. Returns the URL without it.
+fn split_mssql_instance(conn_str: &str) -> (String, Option<String>) {
+    let Some(rest) = conn_str.strip_prefix("sqlserver://") else {
+        return (conn_str.to_string(), None);
+    };
+    let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+    let authority = &rest[..authority_end];
+    let host_start = authority.rfind('@').map_or(0, |i| i + 1);
+    let host = &authority[host_start..];
+    let separator = host
+        .find('\\')
+        .map(|i| (i, 1))
+        .or_else(|| host.to_ascii_lowercase().find("%5c").map(|i| (i, 3)));
+    let Some((at, len)) = separator else {
+        return (conn_str.to_string(), None);
+    };
+    let after = &host[at + len..];
+    let (instance, port) = after.split_at(after.find(':').unwrap_or(after.len()));
+    if instance.is_empty() {
+        return (conn_str.to_string(), None);
+    }
+    let url = format!(
+        "sqlserver://{}{}{}{}",
+        &authority[..host_start],
+        &host[..at],
+        port,
+        &rest[authority_end..]
+    );
+    (url, Some(instance.to_string()))
+}
 
-    match scheme {
-        "sqlserver" => {
-            let host = url.host_str().ok_or("Missing host")?;
-            let port = url.port().unwrap_or(1433);
-            let username = url.username();
-            let password = url.password().unwrap_or("");
-            let database = url.path().trim_start_matches('/');
-
-            let mut config = Config::new();
-            config.host(host);
-            config.port(port);
-            if !username.is_empty() {
-                config.authentication(tiberius::AuthMethod::sql_server(username, password));
-            }
-            config.trust_cert();
+// One SQL Server connection; DbClient::Mssql pools these, see mssql_pool.rs
+pub async fn connect_mssql(conn_str: &str, tls: &TlsOptions) -> Result<MssqlClient, String> {
+    let (conn_str, instance) = split_mssql_instance(conn_str);
+    let url = Url::parse(&conn_str).map_err(|e| format!("Invalid URL: {}", e))?;
+    let host = url.host_str().ok_or("Missing host")?;
+    let username = url.username();
+    let password = url.password().unwrap_or("");
+    let database = url.path().trim_start_matches('/');
 
-            if !database.is_empty() {
-                config.database(database);
-            }
+    let mut config = Config::new();
+    config.host(host);
+    if !username.is_empty() {
+        config.authentication(tiberius::AuthMethod::sql_server(username, password));
+    }
+    tls::apply_mssql(&mut config, tls)?;
+
+    if !database.is_empty() {
+        config.database(database);
+    }
 
+    let tcp = match (&instance, url.port()) {
+        // Named instances listen on dynamic ports; the SQL Browser
+        // service (UDP 1434) tells us which one. An explicit port skips the lookup.
+        (Some(instance), None) => {
+            config.instance_name(instance);
+            TcpStream::connect_named(&config).await.map_err(|e| {
+                format!(
+                    "Failed to resolve instance '{}' via SQL Browser: {}",
+                    instance, e
+                )
+            })?
+        }
+        (_, port) => {
+            let port = port.unwrap_or(1433);
+            config.port(port);
             let tcp = TcpStream::connect((host, port))
                 .await
                 .map_err(|e| e.to_string())?;
             tcp.set_nodelay(true).map_err(|e| e.to_string())?;
+            tcp
+        }
+    };
 
-            let client = Client::connect(config, tcp.compat_write())
-                .await
-                .map_err(|e| e.to_string())?;
-            Ok(DbClient::Mssql(Arc::new(AsyncMutex::new(client))))
+    Client::connect(config, tcp.compat_write())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+pub async fn create_client(conn_str: &str) -> Result<DbClient, String> {
+    create_client_with_tls(conn_str, &TlsOptions::default()).await
+}
+
+// `tls` applies to Postgres, MySQL and SQL Server; Mongo and Redis take it from the URL
+pub async fn create_client_with_tls(conn_str: &str, tls: &TlsOptions) -> Result<DbClient, String> {
+    let (parsed, _) = split_mssql_instance(conn_str);
+    let url = Url::parse(&parsed).map_err(|e| format!("Invalid URL: {}", e))?;
+    let scheme = url.scheme();
+
+    match scheme {
+        "sqlserver" => {
+            let pool = MssqlPool::open(conn_str, tls, MSSQL_POOL_SIZE).await?;
+            Ok(DbClient::Mssql(pool))
         }
         "mysql" | "mariadb" => {
-            let pool = sqlx::MySqlPool::connect(conn_str)
+            let pool = sqlx::MySqlPool::connect_with(tls::mysql_options(conn_str, tls)?)
                 .await
                 .map_err(|e| e.to_string())?;
             Ok(DbClient::Mysql(pool))
         }
         "postgres" | "postgresql" => {
-            let pool = sqlx::PgPool::connect(conn_str)
+            let pool = sqlx::PgPool::connect_with(tls::postgres_options(conn_str, tls)?)
                 .await
                 .map_err(|e| e.to_string())?;
             Ok(DbClient::Postgres(pool))
@@ -118,19 +663,202 @@ pub async fn create_client(conn_str: &str) -> Result<DbClient, String> {
     }
 }
 
-pub async fn execute_query(client: &DbClient, sql: String) -> Result<QueryResponse, String> {
+pub async fn execute_query(
+    client: &DbClient,
+    sql: String,
+    options: &QueryOptions,
+) -> Result<QueryResponse, DbError> {
+    execute_query_reporting(client, sql, options, None).await
+}
+
+// Same as execute_query, but first reports the server-side id of the session
+// running the statement (backend pid, connection id or SPID) so it can be cancelled
+pub async fn execute_query_reporting(
+    client: &DbClient,
+    sql: String,
+    options: &QueryOptions,
+    on_backend: Option<&(dyn Fn(i64) + Sync)>,
+) -> Result<QueryResponse, DbError> {
+    let started = Instant::now();
+    // One row more than the limit is fetched to tell whether anything was cut off
+    let limited = options.row_limit.and_then(|limit| {
+        let sql = sqltext::limit_rows(&sql, client.dialect()?, limit + 1)?;
+        Some((sql, limit))
+    });
+    // A batch may have committed its earlier statements before the deadlock,
+    // so only a lone statement is safe to run again
+    let retryable = client.dialect().map_or(true, |dialect| {
+        scripts::split_statements(dialect, &sql).is_ok_and(|statements| statements.len() == 1)
+    });
+    let mut retries = 0;
+    let mut response = loop {
+        let attempt = match &limited {
+            Some((limited_sql, limit)) => {
+                fetch_within(client, limited_sql.clone(), options, on_backend)
+                    .await
+                    .map(|mut response| {
+                        response.truncated = response.rows.len() > *limit;
+                        response.rows.truncate(*limit);
+                        response
+                    })
+            }
+            None => fetch_within(client, sql.clone(), options, on_backend).await,
+        };
+        match attempt {
+            Err(e) if retryable && retries < options.conflict_retries && e.is_conflict() => {
+                tokio::time::sleep(conflict_backoff(retries)).await;
+                retries += 1;
+            }
+            attempt => break attempt?,
+        }
+    };
+    response.execution_ms = started.elapsed().as_millis() as u64;
+    response.retries = retries;
+    Ok(response)
+}
+
+// How long a timed out statement gets to stop after it is cancelled
+const CANCEL_GRACE: Duration = Duration::from_secs(5);
+
+// fetch_response bounded by the query timeout. Past it the statement is
+// cancelled on the server, through the session id it reported, and then
+// given a moment to fail so its connection is left idle. The session id costs
+// a round trip, so without a timeout it is only asked for when the caller
+// wants it.
+async fn fetch_within(
+    client: &DbClient,
+    sql: String,
+    options: &QueryOptions,
+    on_backend: Option<&(dyn Fn(i64) + Sync)>,
+) -> Result<QueryResponse, DbError> {
+    let Some(limit) = options.timeout else {
+        return fetch_response(client, sql, options, on_backend).await;
+    };
+    let backend = StdMutex::new(None);
+    let report = |id: i64| {
+        *backend.lock().unwrap() = Some(id);
+        if let Some(on_backend) = on_backend {
+            on_backend(id);
+        }
+    };
+    let run = fetch_response(client, sql, options, Some(&report));
+    tokio::pin!(run);
+    tokio::select! {
+        result = &mut run => result,
+        _ = tokio::time::sleep(limit) => {
+            let id = *backend.lock().unwrap();
+            if let Some(id) = id {
+                if let Err(e) = cancel_backend(client, id).await {
+                    log::warn!("Failed to cancel a timed out query: {}", e);
+                }
+                let _ = tokio::time::timeout(CANCEL_GRACE, &mut run).await;
+            }
+            Err(DbError::Timeout {
+                message: format!("Query timed out after {} s", limit.as_secs()),
+            })
+        }
+    }
+}
+
+// The server-side id (backend pid, connection id or SPID) of the session
+// behind a client of one connection, such as a transaction's, for
+// cancel_backend
+pub async fn session_id(client: &DbClient) -> Result<i64, String> {
+    let sql = match client.dialect() {
+        Some(Dialect::Postgres) => "SELECT pg_backend_pid()",
+        Some(Dialect::Mysql) => "SELECT CONNECTION_ID()",
+        Some(Dialect::Mssql) => "SELECT CAST(@@SPID AS bigint)",
+        None => return Err("This database type has no SQL sessions".to_string()),
+    };
+    let response = execute_query(client, sql.to_string(), &QueryOptions::default()).await?;
+    let id = response
+        .rows
+        .into_iter()
+        .next()
+        .and_then(|row| row.into_iter().next())
+        .map(Cell::into_value);
+    id.as_ref()
+        .and_then(|v| v.as_i64().or_else(|| v.as_str()?.parse().ok()))
+        .ok_or_else(|| "The server did not report a session id".to_string())
+}
+
+// Asks the server to stop whatever session `backend` is running. The cancel
+// goes over a connection of its own: the query's pool may have none to spare,
+// e.g. the single one of an open transaction.
+pub async fn cancel_backend(client: &DbClient, backend: i64) -> Result<(), String> {
     match client {
         DbClient::Postgres(pool) => {
-            let rows = sqlx::query(&sql)
-                .fetch_all(pool)
+            let mut conn = pool
+                .connect_options()
+                .connect()
                 .await
                 .map_err(|e| e.to_string())?;
+            sqlx::query("SELECT pg_cancel_backend($1::int)")
+                .bind(backend as i32)
+                .execute(&mut conn)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        DbClient::Mysql(pool) => {
+            let mut conn = pool
+                .connect_options()
+                .connect()
+                .await
+                .map_err(|e| e.to_string())?;
+            sqlx::query(&format!("KILL QUERY {}", backend))
+                .execute(&mut conn)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        // tiberius can't interrupt a running request, so the session is killed
+        // and its connection dropped instead of going back to the pool
+        DbClient::Mssql(pool) => {
+            pool.forget_session(backend);
+            let (mut fresh, _) = pool.connect().await?;
+            // Not an error if the session already went away
+            if let Ok(stream) = fresh.simple_query(format!("KILL {}", backend)).await {
+                let _ = stream.into_results().await;
+            };
+        }
+        _ => return Err("Queries can't be cancelled on this database type".to_string()),
+    }
+    Ok(())
+}
+
+// 100 ms before the first retry, doubling up to 2 s
+fn conflict_backoff(retry: u32) -> Duration {
+    Duration::from_millis((100u64 << retry.min(5)).min(2000))
+}
+
+async fn fetch_response(
+    client: &DbClient,
+    sql: String,
+    options: &QueryOptions,
+    on_backend: Option<&(dyn Fn(i64) + Sync)>,
+) -> Result<QueryResponse, DbError> {
+    let zone = resolve_render_zone(client, options.timezone).await?;
+    let kind = sqltext::statement_kind(&sql);
+    match client {
+        DbClient::Postgres(pool) => {
+            let (rows, result) = match on_backend {
+                Some(report) => {
+                    let mut conn = pool.acquire().await.map_err(errors::sql_error)?;
+                    let pid: i32 = sqlx::query_scalar("SELECT pg_backend_pid()")
+                        .fetch_one(&mut *conn)
+                        .await
+                        .map_err(errors::sql_error)?;
+                    report(pid as i64);
+                    fetch_counted(&mut *conn, bind_params(sqlx::query(&sql), &options.params))
+                        .await?
+                }
+                None => {
+                    fetch_counted(pool, bind_params(sqlx::query(&sql), &options.params)).await?
+                }
+            };
+            let affected = Some(result.rows_affected());
 
             if rows.is_empty() {
-                return Ok(QueryResponse {
-                    columns: vec![],
-                    rows: vec![],
-                });
+                return Ok(QueryResponse::new(vec![], vec![], vec![]).counted(kind, affected));
             }
 
             let columns: Vec<String> = rows[0]
@@ -139,62 +867,35 @@ pub async fn execute_query(client: &DbClient, sql: String) -> Result<QueryRespon
                 .map(|c| c.name().to_string())
                 .collect();
 
-            let mut result_rows = Vec::new();
-
-            for row in rows {
-                let mut current_row = Vec::new();
-                for (i, _) in columns.iter().enumerate() {
-                    // Try to decode as various types
-                    // Simplified: check type info or try generic decode
-                    // This is a bit hacky in generic sqlx types without reflection.
-                    // Better approach: use `try_get` for common types.
-
-                    // Helper to convert PG value to JSON Value
-                    let val: Value = if let Ok(v) = row.try_get::<i32, _>(i) {
-                        json!(v)
-                    } else if let Ok(v) = row.try_get::<i64, _>(i) {
-                        json!(v)
-                    } else if let Ok(v) = row.try_get::<f64, _>(i) {
-                        json!(v)
-                    } else if let Ok(v) = row.try_get::<bool, _>(i) {
-                        json!(v)
-                    } else if let Ok(v) = row.try_get::<String, _>(i) {
-                        json!(v)
-                    } else if let Ok(v) = row.try_get::<chrono::DateTime<chrono::Utc>, _>(i) {
-                        json!(v.to_rfc3339())
-                    } else if let Ok(v) = row.try_get::<chrono::NaiveDateTime, _>(i) {
-                        json!(v.to_string())
-                    } else if let Ok(v) = row.try_get::<chrono::NaiveDate, _>(i) {
-                        json!(v.to_string())
-                    } else if let Ok(v) = row.try_get::<serde_json::Value, _>(i) {
-                        v
-                    } else {
-                        // Fallback to string if possible, or null
-                        // Note: sqlx doesn't easy provide "any string" conversion without knows types.
-                        // We can try getting raw bytes or try string again (handled above).
-                        json!(null)
-                    };
-                    current_row.push(val);
-                }
-                result_rows.push(current_row);
-            }
+            let column_meta = rows[0]
+                .columns()
+                .iter()
+                .map(|c| column_meta(c.name(), c.type_info().name().to_string(), zone))
+                .collect();
 
-            Ok(QueryResponse {
-                columns,
-                rows: result_rows,
-            })
+            let rows = rows.iter().map(|r| pg_row_values(r, zone)).collect();
+            Ok(QueryResponse::new(columns, rows, column_meta).counted(kind, affected))
         }
         DbClient::Mysql(pool) => {
-            let rows = sqlx::query(&sql)
-                .fetch_all(pool)
-                .await
-                .map_err(|e| e.to_string())?;
+            let (rows, result) = match on_backend {
+                Some(report) => {
+                    let mut conn = pool.acquire().await.map_err(errors::sql_error)?;
+                    let id: u64 = sqlx::query_scalar("SELECT CONNECTION_ID()")
+                        .fetch_one(&mut *conn)
+                        .await
+                        .map_err(errors::sql_error)?;
+                    report(id as i64);
+                    fetch_counted(&mut *conn, bind_params(sqlx::query(&sql), &options.params))
+                        .await?
+                }
+                None => {
+                    fetch_counted(pool, bind_params(sqlx::query(&sql), &options.params)).await?
+                }
+            };
+            let affected = Some(result.rows_affected());
 
             if rows.is_empty() {
-                return Ok(QueryResponse {
-                    columns: vec![],
-                    rows: vec![],
-                });
+                return Ok(QueryResponse::new(vec![], vec![], vec![]).counted(kind, affected));
             }
             let columns: Vec<String> = rows[0]
                 .columns()
@@ -202,50 +903,53 @@ pub async fn execute_query(client: &DbClient, sql: String) -> Result<QueryRespon
                 .map(|c| c.name().to_string())
                 .collect();
 
-            let mut result_rows = Vec::new();
-            for row in rows {
-                let mut current_row = Vec::new();
-                for (i, _) in columns.iter().enumerate() {
-                    let val: Value = if let Ok(v) = row.try_get::<i32, _>(i) {
-                        json!(v)
-                    } else if let Ok(v) = row.try_get::<i64, _>(i) {
-                        json!(v)
-                    } else if let Ok(v) = row.try_get::<f64, _>(i) {
-                        json!(v)
-                    } else if let Ok(v) = row.try_get::<bool, _>(i) {
-                        // MySQL bool is tinyint
-                        json!(v)
-                    } else if let Ok(v) = row.try_get::<String, _>(i) {
-                        json!(v)
-                    } else if let Ok(v) = row.try_get::<chrono::DateTime<chrono::Utc>, _>(i) {
-                        json!(v.to_rfc3339())
-                    } else {
-                        json!(null)
-                    };
-                    current_row.push(val);
-                }
-                result_rows.push(current_row);
-            }
-            Ok(QueryResponse {
-                columns,
-                rows: result_rows,
-            })
+            let column_meta = rows[0]
+                .columns()
+                .iter()
+                .map(|c| column_meta(c.name(), c.type_info().name().to_string(), zone))
+                .collect();
+
+            let rows = rows.iter().map(|r| mysql_row_values(r, zone)).collect();
+            Ok(QueryResponse::new(columns, rows, column_meta).counted(kind, affected))
         }
-        DbClient::Mssql(client_mutex) => {
-            let mut client = client_mutex.lock().await;
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
+
+            if let Some(report) = on_backend {
+                report(client.spid());
+            }
 
-            let result = client.simple_query(&sql).await.map_err(|e| e.to_string())?;
+            // Counts only come back from execute, which discards result sets,
+            // so it's used for a lone DML statement that can't return rows
+            let dml = matches!(
+                kind,
+                StatementKind::Insert
+                    | StatementKind::Update
+                    | StatementKind::Delete
+                    | StatementKind::Merge
+            );
+            let single = scripts::split_statements(Dialect::Mssql, &sql)
+                .is_ok_and(|statements| statements.len() == 1);
+            if dml && single && !sqltext::words(&sql).iter().any(|w| w == "OUTPUT") {
+                let affected = mssql_query(&sql, &options.params)
+                    .execute(&mut *client)
+                    .await
+                    .map_err(errors::mssql_error)?
+                    .total();
+                return Ok(QueryResponse::new(vec![], vec![], vec![]).counted(kind, Some(affected)));
+            }
+
+            let result = mssql_send(&mut client, &sql, &options.params).await?;
 
             let rows: Vec<tiberius::Row> = result
                 .into_first_result()
                 .await
-                .map_err(|e| e.to_string())?;
+                .map_err(errors::mssql_error)?;
+            // OUTPUT returns one row per affected row
+            let affected = dml.then_some(rows.len() as u64);
 
             if rows.is_empty() {
-                return Ok(QueryResponse {
-                    columns: vec![],
-                    rows: vec![],
-                });
+                return Ok(QueryResponse::new(vec![], vec![], vec![]).counted(kind, affected));
             }
 
             let columns: Vec<String> = rows[0]
@@ -254,293 +958,1489 @@ pub async fn execute_query(client: &DbClient, sql: String) -> Result<QueryRespon
                 .map(|c| c.name().to_string())
                 .collect();
 
-            let mut result_rows = Vec::new();
+            let column_meta = rows[0]
+                .columns()
+                .iter()
+                .map(|c| column_meta(c.name(), mssql_type_name(c), zone))
+                .collect();
 
-            for row in rows {
-                let mut current_row = Vec::new();
-                for i in 0..columns.len() {
-                    let val: Value = if let Ok(Some(v)) = row.try_get::<i32, _>(i) {
-                        json!(v)
-                    } else if let Ok(Some(v)) = row.try_get::<i64, _>(i) {
-                        json!(v)
-                    } else if let Ok(Some(v)) = row.try_get::<f64, _>(i) {
-                        json!(v)
-                    } else if let Ok(Some(v)) = row.try_get::<bool, _>(i) {
-                        json!(v)
-                    } else if let Ok(Some(v)) = row.try_get::<&str, _>(i) {
-                        json!(v)
-                    } else if let Ok(Some(v)) = row.try_get::<chrono::NaiveDateTime, _>(i) {
-                        json!(v.to_string())
-                    } else if let Ok(Some(v)) = row.try_get::<chrono::NaiveDate, _>(i) {
-                        json!(v.to_string())
-                    } else {
-                        json!(null)
-                    };
-                    current_row.push(val);
-                }
-                result_rows.push(current_row);
+            let rows = rows.iter().map(|r| serialize_mssql_row(r, zone)).collect();
+            Ok(QueryResponse::new(columns, rows, column_meta).counted(kind, affected))
+        }
+        DbClient::Mongo(_) => Ok(mongo::run_command(client, &sql).await?),
+        DbClient::Redis(_) => Ok(redis_tools::run_command(client, &sql).await?),
+    }
+}
+
+pub const DEFAULT_STREAM_BATCH: usize = 500;
+
+// One chunk of a streamed result; the column list only comes with the first
+// (for Mongo aggregations, again whenever later documents add fields)
+#[derive(Serialize, Clone)]
+pub struct RowBatch {
+    pub index: u64,
+    pub columns: Option<Vec<String>>,
+    pub column_meta: Option<Vec<ColumnMeta>>,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+struct RowBatcher<'a> {
+    size: usize,
+    index: u64,
+    total: u64,
+    header: Option<(Vec<String>, Vec<ColumnMeta>)>,
+    rows: Vec<Vec<Cell>>,
+    emit: &'a (dyn Fn(RowBatch) -> Result<(), String> + Sync),
+}
+
+impl RowBatcher<'_> {
+    fn push(
+        &mut self,
+        header: impl FnOnce() -> (Vec<String>, Vec<ColumnMeta>),
+        row: Vec<Cell>,
+    ) -> Result<(), String> {
+        if self.total == 0 {
+            self.header = Some(header());
+        }
+        self.total += 1;
+        self.rows.push(row);
+        if self.rows.len() >= self.size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let (columns, column_meta) = self.header.take().unzip();
+        (self.emit)(RowBatch {
+            index: self.index,
+            columns,
+            column_meta,
+            rows: std::mem::take(&mut self.rows),
+        })?;
+        self.index += 1;
+        Ok(())
+    }
+}
+
+// Like execute_query_reporting, but rows are read from a cursor and handed to
+// `emit` in batches instead of being collected; an error from `emit` stops
+// the query. Returns the number of rows.
+pub async fn stream_query(
+    client: &DbClient,
+    sql: String,
+    options: &QueryOptions,
+    batch_size: usize,
+    on_backend: Option<&(dyn Fn(i64) + Sync)>,
+    emit: &(dyn Fn(RowBatch) -> Result<(), String> + Sync),
+) -> Result<u64, DbError> {
+    let zone = resolve_render_zone(client, options.timezone).await?;
+    let mut batcher = RowBatcher {
+        size: batch_size.max(1),
+        index: 0,
+        total: 0,
+        header: None,
+        rows: Vec::new(),
+        emit,
+    };
+    match client {
+        DbClient::Postgres(pool) => {
+            let mut conn = pool.acquire().await.map_err(errors::sql_error)?;
+            if let Some(report) = on_backend {
+                let pid: i32 = sqlx::query_scalar("SELECT pg_backend_pid()")
+                    .fetch_one(&mut *conn)
+                    .await
+                    .map_err(errors::sql_error)?;
+                report(pid as i64);
+            }
+            let mut rows = bind_params(sqlx::query(&sql), &options.params).fetch(&mut *conn);
+            while let Some(row) = rows.next().await {
+                let row = row.map_err(errors::sql_error)?;
+                let header = || {
+                    row.columns()
+                        .iter()
+                        .map(|c| {
+                            let meta =
+                                column_meta(c.name(), c.type_info().name().to_string(), zone);
+                            (c.name().to_string(), meta)
+                        })
+                        .unzip()
+                };
+                batcher.push(header, pg_row_values(&row, zone))?;
+            }
+        }
+        DbClient::Mysql(pool) => {
+            let mut conn = pool.acquire().await.map_err(errors::sql_error)?;
+            if let Some(report) = on_backend {
+                let id: u64 = sqlx::query_scalar("SELECT CONNECTION_ID()")
+                    .fetch_one(&mut *conn)
+                    .await
+                    .map_err(errors::sql_error)?;
+                report(id as i64);
+            }
+            let mut rows = bind_params(sqlx::query(&sql), &options.params).fetch(&mut *conn);
+            while let Some(row) = rows.next().await {
+                let row = row.map_err(errors::sql_error)?;
+                let header = || {
+                    row.columns()
+                        .iter()
+                        .map(|c| {
+                            let meta =
+                                column_meta(c.name(), c.type_info().name().to_string(), zone);
+                            (c.name().to_string(), meta)
+                        })
+                        .unzip()
+                };
+                batcher.push(header, mysql_row_values(&row, zone))?;
+            }
+        }
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
+            if let Some(report) = on_backend {
+                report(client.spid());
             }
+            // Only the first result set is streamed, as with execute_query, but the
+            // rest still has to be read off the connection
+            let mut items = mssql_send(&mut client, &sql, &options.params).await?;
+            while let Some(item) = items.next().await {
+                let row = match item.map_err(errors::mssql_error)? {
+                    tiberius::QueryItem::Row(row) if row.result_index() == 0 => row,
+                    _ => continue,
+                };
+                let header = || {
+                    row.columns()
+                        .iter()
+                        .map(|c| {
+                            let meta = column_meta(c.name(), mssql_type_name(c), zone);
+                            (c.name().to_string(), meta)
+                        })
+                        .unzip()
+                };
+                batcher.push(header, serialize_mssql_row(&row, zone))?;
+            }
+        }
+        _ => return Err("Unsupported database type for query execution".into()),
+    }
+    batcher.flush()?;
+    Ok(batcher.total)
+}
 
-            Ok(QueryResponse {
-                columns,
-                rows: result_rows,
-            })
+pub async fn get_tables(
+    client: &DbClient,
+    schema: Option<String>,
+    include_temp: bool,
+) -> Result<Vec<String>, String> {
+    let mut tables = get_base_tables(client, schema).await?;
+    if include_temp {
+        for table in get_temp_tables(client).await? {
+            if !tables.contains(&table) {
+                tables.push(table);
+            }
+        }
+    }
+    Ok(tables)
+}
+
+// Temporary tables the current login can see. Postgres and MySQL connections are
+// pooled, so on Postgres this covers temp tables from every session of the pool;
+// MySQL doesn't expose other sessions' temp tables at all.
+async fn get_temp_tables(client: &DbClient) -> Result<Vec<String>, String> {
+    match client {
+        DbClient::Postgres(pool) => {
+            let rows = sqlx::query(
+                "SELECT c.relname::text FROM pg_class c \
+                 WHERE c.relpersistence = 't' AND c.relkind IN ('r', 'p') \
+                 AND pg_has_role(c.relowner, 'USAGE') ORDER BY c.relname",
+            )
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            Ok(rows.iter().map(|r| r.get(0)).collect())
+        }
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
+            // Local temp tables are stored as #name____...<suffix>; only the ones
+            // OBJECT_ID resolves belong to this session
+            let query = "SELECT n.name FROM tempdb.sys.tables t \
+                 CROSS APPLY (SELECT LEFT(t.name, CHARINDEX('_____', t.name + '_____') - 1) AS name) n \
+                 WHERE t.name LIKE '#%' AND t.object_id = OBJECT_ID('tempdb..' + n.name) \
+                 ORDER BY n.name";
+            let rows = client
+                .simple_query(query)
+                .await
+                .map_err(|e| e.to_string())?
+                .into_first_result()
+                .await
+                .map_err(|e| e.to_string())?;
+            let mut tables = Vec::new();
+            for r in rows {
+                if let Ok(Some(name)) = r.try_get::<&str, _>(0) {
+                    tables.push(name.to_string());
+                }
+            }
+            Ok(tables)
+        }
+        _ => Ok(vec![]),
+    }
+}
+
+// The database a MySQL lookup is for. The sidebar sends "*" for all schemas
+// and "def", the one schema get_schemas reports, both meaning the connected
+// database. Every MySQL schema bind and qualified name goes through this.
+pub fn mysql_database(schema: Option<String>) -> Option<String> {
+    schema.filter(|s| !matches!(s.as_str(), "" | "*" | "def"))
+}
+
+async fn get_base_tables(client: &DbClient, schema: Option<String>) -> Result<Vec<String>, String> {
+    match client {
+        DbClient::Postgres(pool) => {
+            let schema_filter = schema.unwrap_or_else(|| "public".to_string());
+            let rows = sqlx::query(
+                "SELECT table_name FROM information_schema.tables WHERE table_schema = $1 AND table_type = 'BASE TABLE'",
+            )
+            .bind(schema_filter)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+            Ok(rows.iter().map(|r| r.get(0)).collect())
+        }
+        DbClient::Mysql(pool) => {
+            let rows = sqlx::query(
+                "SELECT table_name FROM information_schema.tables WHERE table_schema = COALESCE(?, DATABASE()) AND table_type = 'BASE TABLE'"
+            )
+            .bind(mysql_database(schema))
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            Ok(rows.iter().map(|r| r.get(0)).collect())
+        }
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
+            let schema_filter = schema.unwrap_or_else(|| "dbo".to_string());
+            let query = "SELECT TABLE_NAME FROM INFORMATION_SCHEMA.TABLES WHERE TABLE_TYPE = 'BASE TABLE' AND TABLE_SCHEMA = @P1";
+            let rows = client
+                .query(query, &[&schema_filter])
+                .await
+                .map_err(|e| e.to_string())?
+                .into_first_result()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            let mut tables = Vec::new();
+            for r in rows {
+                if let Ok(Some(name)) = r.try_get::<&str, _>(0) {
+                    tables.push(name.to_string());
+                }
+            }
+            Ok(tables)
+        }
+        _ => Ok(vec![]),
+    }
+}
+
+pub async fn get_views(client: &DbClient, schema: Option<String>) -> Result<Vec<String>, String> {
+    match client {
+        DbClient::Postgres(pool) => {
+            let schema_filter = schema.unwrap_or_else(|| "public".to_string());
+            let rows = sqlx::query(
+                "SELECT table_name FROM information_schema.views WHERE table_schema = $1",
+            )
+            .bind(schema_filter)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            Ok(rows.iter().map(|r| r.get(0)).collect())
+        }
+        DbClient::Mysql(pool) => {
+            let rows = sqlx::query(
+                "SELECT table_name FROM information_schema.views WHERE table_schema = COALESCE(?, DATABASE())",
+            )
+            .bind(mysql_database(schema))
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            Ok(rows.iter().map(|r| r.get(0)).collect())
+        }
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
+            let schema_filter = schema.unwrap_or_else(|| "dbo".to_string());
+            let query = "SELECT TABLE_NAME FROM INFORMATION_SCHEMA.VIEWS WHERE TABLE_SCHEMA = @P1";
+            let rows = client
+                .query(query, &[&schema_filter])
+                .await
+                .map_err(|e| e.to_string())?
+                .into_first_result()
+                .await
+                .map_err(|e| e.to_string())?;
+            let mut views = Vec::new();
+            for r in rows {
+                if let Ok(Some(name)) = r.try_get::<&str, _>(0) {
+                    views.push(name.to_string());
+                }
+            }
+            Ok(views)
+        }
+        _ => Ok(vec![]),
+    }
+}
+
+pub async fn get_functions(
+    client: &DbClient,
+    schema: Option<String>,
+) -> Result<Vec<String>, String> {
+    match client {
+        DbClient::Postgres(pool) => {
+            let schema_filter = schema.unwrap_or_else(|| "public".to_string());
+            let rows = sqlx::query(
+                "SELECT routine_name FROM information_schema.routines WHERE routine_type = 'FUNCTION' AND routine_schema = $1"
+            )
+            .bind(schema_filter)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            Ok(rows.iter().map(|r| r.get(0)).collect())
+        }
+        DbClient::Mysql(pool) => {
+            let rows = sqlx::query(
+                "SELECT routine_name FROM information_schema.routines WHERE routine_type = 'FUNCTION' AND routine_schema = COALESCE(?, DATABASE())"
+            )
+            .bind(mysql_database(schema))
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            Ok(rows.iter().map(|r| r.get(0)).collect())
+        }
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
+            let schema_filter = schema.unwrap_or_else(|| "dbo".to_string());
+            let query = "SELECT ROUTINE_NAME FROM INFORMATION_SCHEMA.ROUTINES WHERE ROUTINE_TYPE = 'FUNCTION' AND ROUTINE_SCHEMA = @P1";
+            let rows = client
+                .query(query, &[&schema_filter])
+                .await
+                .map_err(|e| e.to_string())?
+                .into_first_result()
+                .await
+                .map_err(|e| e.to_string())?;
+            let mut funcs = Vec::new();
+            for r in rows {
+                if let Ok(Some(name)) = r.try_get::<&str, _>(0) {
+                    funcs.push(name.to_string());
+                }
+            }
+            Ok(funcs)
+        }
+        _ => Ok(vec![]),
+    }
+}
+
+// CREATE statement of a view or materialized view, as the server reconstructs it
+pub async fn get_view_definition(
+    client: &DbClient,
+    schema: Option<String>,
+    name: &str,
+) -> Result<String, String> {
+    if client.dialect().is_none() {
+        return Err("Views are only supported for SQL databases".to_string());
+    }
+    ddl_history::object_definition(client, "VIEW", schema.as_deref(), name)
+        .await?
+        .map(|definitions| definitions.join("\n\n"))
+        .ok_or_else(|| format!("View {} not found", name))
+}
+
+// CREATE statement of a function or procedure. Postgres overloads share the
+// name; each one's definition is included, separated by a blank line.
+pub async fn get_routine_definition(
+    client: &DbClient,
+    schema: Option<String>,
+    name: &str,
+) -> Result<String, String> {
+    let kind = match client {
+        // SHOW CREATE needs to know which of the two it is
+        DbClient::Mysql(pool) => sqlx::query_scalar(
+            "SELECT routine_type FROM information_schema.routines \
+             WHERE routine_schema = COALESCE(?, DATABASE()) AND routine_name = ?",
+        )
+        .bind(mysql_database(schema.clone()))
+        .bind(name)
+        .fetch_optional(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .unwrap_or_else(|| "FUNCTION".to_string()),
+        DbClient::Postgres(_) | DbClient::Mssql(_) => "FUNCTION".to_string(),
+        _ => return Err("Routines are only supported for SQL databases".to_string()),
+    };
+    ddl_history::object_definition(client, &kind, schema.as_deref(), name)
+        .await?
+        .map(|definitions| definitions.join("\n\n"))
+        .ok_or_else(|| format!("Routine {} not found", name))
+}
+
+pub async fn get_schemas(client: &DbClient) -> Result<Vec<String>, String> {
+    match client {
+        DbClient::Postgres(pool) => {
+            let rows = sqlx::query(
+                "SELECT schema_name FROM information_schema.schemata WHERE schema_name NOT IN ('information_schema', 'pg_catalog', 'pg_toast')"
+            )
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            Ok(rows.iter().map(|r| r.get(0)).collect())
+        }
+        DbClient::Mysql(_) => {
+            // MySQL uses databases as schemas generally.
+            Ok(vec!["def".to_string()]) // Or list databases?
+        }
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
+            let query = "SELECT SCHEMA_NAME FROM INFORMATION_SCHEMA.SCHEMATA WHERE SCHEMA_NAME NOT IN ('information_schema', 'sys', 'guest', 'users')";
+            let rows = client
+                .query(query, &[])
+                .await
+                .map_err(|e| e.to_string())?
+                .into_first_result()
+                .await
+                .map_err(|e| e.to_string())?;
+            let mut schemas = Vec::new();
+            for r in rows {
+                if let Ok(Some(name)) = r.try_get::<&str, _>(0) {
+                    schemas.push(name.to_string());
+                }
+            }
+            Ok(schemas)
+        }
+        _ => Ok(vec![]),
+    }
+}
+
+pub async fn get_databases(client: &DbClient) -> Result<Vec<String>, String> {
+    match client {
+        DbClient::Postgres(pool) => {
+            let rows = sqlx::query("SELECT datname FROM pg_database WHERE datistemplate = false;")
+                .fetch_all(pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(rows.iter().map(|r| r.get(0)).collect())
+        }
+        DbClient::Mysql(pool) => {
+            let rows = sqlx::query("SHOW DATABASES")
+                .fetch_all(pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            // First column is Database
+            Ok(rows.iter().map(|r| r.get(0)).collect())
+        }
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
+            let query = "SELECT name FROM sys.databases WHERE name NOT IN ('master', 'tempdb', 'model', 'msdb')";
+            let rows = client
+                .query(query, &[])
+                .await
+                .map_err(|e| e.to_string())?
+                .into_first_result()
+                .await
+                .map_err(|e| e.to_string())?;
+            let mut dbs = Vec::new();
+            for r in rows {
+                if let Ok(Some(name)) = r.try_get::<&str, _>(0) {
+                    dbs.push(name.to_string());
+                }
+            }
+            Ok(dbs)
+        }
+        _ => Ok(vec![]),
+    }
+}
+
+// Column names of a table in definition order
+pub async fn get_column_names(
+    client: &DbClient,
+    schema: Option<String>,
+    table: &str,
+) -> Result<Vec<String>, String> {
+    match client {
+        DbClient::Postgres(pool) => {
+            let schema_filter = schema.unwrap_or_else(|| "public".to_string());
+            let rows = sqlx::query(
+                "SELECT column_name FROM information_schema.columns WHERE table_schema = $1 AND table_name = $2 ORDER BY ordinal_position",
+            )
+            .bind(schema_filter)
+            .bind(table)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            Ok(rows.iter().map(|r| r.get(0)).collect())
+        }
+        DbClient::Mysql(pool) => {
+            let rows = sqlx::query(
+                "SELECT column_name FROM information_schema.columns WHERE table_schema = COALESCE(?, DATABASE()) AND table_name = ? ORDER BY ordinal_position",
+            )
+            .bind(mysql_database(schema))
+            .bind(table)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            Ok(rows.iter().map(|r| r.get(0)).collect())
+        }
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
+            let schema_filter = schema.unwrap_or_else(|| "dbo".to_string());
+            let query = "SELECT COLUMN_NAME FROM INFORMATION_SCHEMA.COLUMNS WHERE TABLE_SCHEMA = @P1 AND TABLE_NAME = @P2 ORDER BY ORDINAL_POSITION";
+            let rows = client
+                .query(query, &[&schema_filter, &table])
+                .await
+                .map_err(|e| e.to_string())?
+                .into_first_result()
+                .await
+                .map_err(|e| e.to_string())?;
+            let mut columns = Vec::new();
+            for r in rows {
+                if let Ok(Some(name)) = r.try_get::<&str, _>(0) {
+                    columns.push(name.to_string());
+                }
+            }
+            Ok(columns)
+        }
+        _ => Ok(vec![]),
+    }
+}
+
+// Column names of every table and view in `schema`, in column order, from a
+// single catalog query
+pub async fn get_schema_column_names(
+    client: &DbClient,
+    schema: Option<String>,
+) -> Result<HashMap<String, Vec<String>>, String> {
+    let pairs: Vec<(String, String)> = match client {
+        DbClient::Postgres(pool) => {
+            let schema_filter = schema.unwrap_or_else(|| "public".to_string());
+            let rows = sqlx::query(
+                "SELECT table_name::text, column_name::text FROM information_schema.columns \
+                 WHERE table_schema = $1 ORDER BY table_name, ordinal_position",
+            )
+            .bind(schema_filter)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            rows.iter().map(|r| (r.get(0), r.get(1))).collect()
+        }
+        DbClient::Mysql(pool) => {
+            let rows = sqlx::query(
+                "SELECT table_name, column_name FROM information_schema.columns \
+                 WHERE table_schema = COALESCE(?, DATABASE()) ORDER BY table_name, ordinal_position",
+            )
+            .bind(mysql_database(schema))
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            rows.iter().map(|r| (r.get(0), r.get(1))).collect()
+        }
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
+            let schema_filter = schema.unwrap_or_else(|| "dbo".to_string());
+            let query = "SELECT TABLE_NAME, COLUMN_NAME FROM INFORMATION_SCHEMA.COLUMNS \
+                 WHERE TABLE_SCHEMA = @P1 ORDER BY TABLE_NAME, ORDINAL_POSITION";
+            let rows = client
+                .query(query, &[&schema_filter])
+                .await
+                .map_err(|e| e.to_string())?
+                .into_first_result()
+                .await
+                .map_err(|e| e.to_string())?;
+            rows.iter()
+                .filter_map(|r| {
+                    let table = r.try_get::<&str, _>(0).ok().flatten()?;
+                    let column = r.try_get::<&str, _>(1).ok().flatten()?;
+                    Some((table.to_string(), column.to_string()))
+                })
+                .collect()
         }
-        _ => Err("Unsupported database type for query execution".to_string()),
+        _ => Vec::new(),
+    };
+    let mut columns: HashMap<String, Vec<String>> = HashMap::new();
+    for (table, column) in pairs {
+        columns.entry(table).or_default().push(column);
     }
+    Ok(columns)
 }
 
-pub async fn get_tables(client: &DbClient, schema: Option<String>) -> Result<Vec<String>, String> {
+// Primary key columns of a table in key order; empty when the table has none
+pub async fn get_primary_key(
+    client: &DbClient,
+    schema: Option<String>,
+    table: &str,
+) -> Result<Vec<String>, String> {
     match client {
         DbClient::Postgres(pool) => {
             let schema_filter = schema.unwrap_or_else(|| "public".to_string());
             let rows = sqlx::query(
-                "SELECT table_name FROM information_schema.tables WHERE table_schema = $1 AND table_type = 'BASE TABLE'",
+                "SELECT kcu.column_name FROM information_schema.table_constraints tc \
+                 JOIN information_schema.key_column_usage kcu \
+                 ON tc.constraint_name = kcu.constraint_name AND tc.table_schema = kcu.table_schema AND tc.table_name = kcu.table_name \
+                 WHERE tc.constraint_type = 'PRIMARY KEY' AND tc.table_schema = $1 AND tc.table_name = $2 \
+                 ORDER BY kcu.ordinal_position",
             )
             .bind(schema_filter)
+            .bind(table)
             .fetch_all(pool)
             .await
             .map_err(|e| e.to_string())?;
-
             Ok(rows.iter().map(|r| r.get(0)).collect())
         }
         DbClient::Mysql(pool) => {
-            // MySQL doesn't have multiple schemas in the PG sense (schema = database usually).
-            // We can ignore schema arg or treat it as database if needed, but usually we connect to a DB.
-            // If we want to filter by connected DB:
             let rows = sqlx::query(
-                "SELECT table_name FROM information_schema.tables WHERE table_schema = DATABASE() AND table_type = 'BASE TABLE'"
+                "SELECT column_name FROM information_schema.key_column_usage \
+                 WHERE constraint_name = 'PRIMARY' AND table_schema = COALESCE(?, DATABASE()) AND table_name = ? \
+                 ORDER BY ordinal_position",
             )
+            .bind(mysql_database(schema))
+            .bind(table)
             .fetch_all(pool)
             .await
             .map_err(|e| e.to_string())?;
             Ok(rows.iter().map(|r| r.get(0)).collect())
         }
-        DbClient::Mssql(client_mutex) => {
-            let mut client = client_mutex.lock().await;
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
             let schema_filter = schema.unwrap_or_else(|| "dbo".to_string());
-            // Tiberius query params are 1-based P1, P2... or just replace in string (risky for injection).
-            // Safer to use Param.
-            // For simplicity, we assume schema is safe or use simple format, but technically should binding.
-            // Tiberius supports binding.
-            let query = "SELECT TABLE_NAME FROM INFORMATION_SCHEMA.TABLES WHERE TABLE_TYPE = 'BASE TABLE' AND TABLE_SCHEMA = @P1";
+            let query = "SELECT kcu.COLUMN_NAME FROM INFORMATION_SCHEMA.TABLE_CONSTRAINTS tc \
+                 JOIN INFORMATION_SCHEMA.KEY_COLUMN_USAGE kcu \
+                 ON tc.CONSTRAINT_NAME = kcu.CONSTRAINT_NAME AND tc.TABLE_SCHEMA = kcu.TABLE_SCHEMA AND tc.TABLE_NAME = kcu.TABLE_NAME \
+                 WHERE tc.CONSTRAINT_TYPE = 'PRIMARY KEY' AND tc.TABLE_SCHEMA = @P1 AND tc.TABLE_NAME = @P2 \
+                 ORDER BY kcu.ORDINAL_POSITION";
             let rows = client
-                .query(query, &[&schema_filter])
+                .query(query, &[&schema_filter, &table])
                 .await
                 .map_err(|e| e.to_string())?
                 .into_first_result()
                 .await
                 .map_err(|e| e.to_string())?;
-
-            let mut tables = Vec::new();
+            let mut columns = Vec::new();
             for r in rows {
                 if let Ok(Some(name)) = r.try_get::<&str, _>(0) {
-                    tables.push(name.to_string());
+                    columns.push(name.to_string());
                 }
             }
-            Ok(tables)
+            Ok(columns)
         }
         _ => Ok(vec![]),
     }
 }
 
-pub async fn get_views(client: &DbClient, schema: Option<String>) -> Result<Vec<String>, String> {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForeignKey {
+    pub name: String,
+    pub schema: String,
+    pub table: String,
+    pub columns: Vec<String>,
+    pub referenced_schema: String,
+    pub referenced_table: String,
+    pub referenced_columns: Vec<String>, // same order as `columns`
+}
+
+// Foreign keys declared on the table as well as those referencing it
+pub async fn get_foreign_keys(
+    client: &DbClient,
+    schema: Option<String>,
+    table: &str,
+) -> Result<Vec<ForeignKey>, String> {
     match client {
         DbClient::Postgres(pool) => {
             let schema_filter = schema.unwrap_or_else(|| "public".to_string());
             let rows = sqlx::query(
-                "SELECT table_name FROM information_schema.views WHERE table_schema = $1",
+                "SELECT con.conname::text, sn.nspname::text, s.relname::text, \
+                 ARRAY(SELECT a.attname::text FROM unnest(con.conkey) WITH ORDINALITY k(attnum, ord) \
+                   JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = k.attnum ORDER BY k.ord), \
+                 rn.nspname::text, r.relname::text, \
+                 ARRAY(SELECT a.attname::text FROM unnest(con.confkey) WITH ORDINALITY k(attnum, ord) \
+                   JOIN pg_attribute a ON a.attrelid = con.confrelid AND a.attnum = k.attnum ORDER BY k.ord) \
+                 FROM pg_constraint con \
+                 JOIN pg_class s ON s.oid = con.conrelid JOIN pg_namespace sn ON sn.oid = s.relnamespace \
+                 JOIN pg_class r ON r.oid = con.confrelid JOIN pg_namespace rn ON rn.oid = r.relnamespace \
+                 WHERE con.contype = 'f' \
+                 AND ((sn.nspname = $1 AND s.relname = $2) OR (rn.nspname = $1 AND r.relname = $2)) \
+                 ORDER BY sn.nspname, s.relname, con.conname",
             )
             .bind(schema_filter)
+            .bind(table)
             .fetch_all(pool)
             .await
             .map_err(|e| e.to_string())?;
-            Ok(rows.iter().map(|r| r.get(0)).collect())
+            Ok(rows
+                .iter()
+                .map(|r| ForeignKey {
+                    name: r.get(0),
+                    schema: r.get(1),
+                    table: r.get(2),
+                    columns: r.get(3),
+                    referenced_schema: r.get(4),
+                    referenced_table: r.get(5),
+                    referenced_columns: r.get(6),
+                })
+                .collect())
         }
         DbClient::Mysql(pool) => {
+            let database = mysql_database(schema);
             let rows = sqlx::query(
-                "SELECT table_name FROM information_schema.views WHERE table_schema = DATABASE()",
+                "SELECT constraint_name, table_schema, table_name, column_name, \
+                 referenced_table_schema, referenced_table_name, referenced_column_name \
+                 FROM information_schema.key_column_usage \
+                 WHERE referenced_table_name IS NOT NULL \
+                 AND ((table_schema = COALESCE(?, DATABASE()) AND table_name = ?) \
+                 OR (referenced_table_schema = COALESCE(?, DATABASE()) AND referenced_table_name = ?)) \
+                 ORDER BY table_schema, table_name, constraint_name, ordinal_position",
             )
+            .bind(&database)
+            .bind(table)
+            .bind(&database)
+            .bind(table)
             .fetch_all(pool)
             .await
             .map_err(|e| e.to_string())?;
-            Ok(rows.iter().map(|r| r.get(0)).collect())
+            let columns = rows.iter().map(|r| {
+                (0..7)
+                    .map(|i| r.get::<String, _>(i))
+                    .collect::<Vec<String>>()
+            });
+            Ok(group_foreign_key_columns(columns))
         }
-        DbClient::Mssql(client_mutex) => {
-            let mut client = client_mutex.lock().await;
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
             let schema_filter = schema.unwrap_or_else(|| "dbo".to_string());
-            let query = "SELECT TABLE_NAME FROM INFORMATION_SCHEMA.VIEWS WHERE TABLE_SCHEMA = @P1";
+            let query = "SELECT fk.name, SCHEMA_NAME(p.schema_id), p.name, pc.name, \
+                 SCHEMA_NAME(r.schema_id), r.name, rc.name \
+                 FROM sys.foreign_keys fk \
+                 JOIN sys.foreign_key_columns fkc ON fkc.constraint_object_id = fk.object_id \
+                 JOIN sys.objects p ON p.object_id = fk.parent_object_id \
+                 JOIN sys.columns pc ON pc.object_id = fkc.parent_object_id AND pc.column_id = fkc.parent_column_id \
+                 JOIN sys.objects r ON r.object_id = fk.referenced_object_id \
+                 JOIN sys.columns rc ON rc.object_id = fkc.referenced_object_id AND rc.column_id = fkc.referenced_column_id \
+                 WHERE OBJECT_ID(QUOTENAME(@P1) + '.' + QUOTENAME(@P2)) IN (fk.parent_object_id, fk.referenced_object_id) \
+                 ORDER BY SCHEMA_NAME(p.schema_id), p.name, fk.name, fkc.constraint_column_id";
             let rows = client
-                .query(query, &[&schema_filter])
+                .query(query, &[&schema_filter, &table])
                 .await
                 .map_err(|e| e.to_string())?
                 .into_first_result()
                 .await
                 .map_err(|e| e.to_string())?;
-            let mut views = Vec::new();
-            for r in rows {
-                if let Ok(Some(name)) = r.try_get::<&str, _>(0) {
-                    views.push(name.to_string());
-                }
-            }
-            Ok(views)
+            let columns = rows.iter().map(|r| {
+                (0..7)
+                    .map(|i| {
+                        r.try_get::<&str, _>(i)
+                            .ok()
+                            .flatten()
+                            .unwrap_or_default()
+                            .to_string()
+                    })
+                    .collect::<Vec<String>>()
+            });
+            Ok(group_foreign_key_columns(columns))
         }
         _ => Ok(vec![]),
     }
 }
 
-pub async fn get_functions(
+// Folds one-row-per-column results (name, schema, table, column, referenced
+// schema, table, column), ordered by constraint, into foreign keys
+fn group_foreign_key_columns(rows: impl Iterator<Item = Vec<String>>) -> Vec<ForeignKey> {
+    let mut keys: Vec<ForeignKey> = Vec::new();
+    for row in rows {
+        let [name, schema, table, column, ref_schema, ref_table, ref_column]: [String; 7] =
+            match row.try_into() {
+                Ok(row) => row,
+                Err(_) => continue,
+            };
+        match keys.last_mut() {
+            Some(fk) if fk.name == name && fk.schema == schema && fk.table == table => {
+                fk.columns.push(column);
+                fk.referenced_columns.push(ref_column);
+            }
+            _ => keys.push(ForeignKey {
+                name,
+                schema,
+                table,
+                columns: vec![column],
+                referenced_schema: ref_schema,
+                referenced_table: ref_table,
+                referenced_columns: vec![ref_column],
+            }),
+        }
+    }
+    keys
+}
+
+// A unique or check constraint. Checks carry their expression and, where the
+// database ties them to one, the column.
+#[derive(Debug, Clone, Serialize)]
+pub struct NamedConstraint {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub definition: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TableConstraints {
+    pub primary_key: Vec<String>,
+    pub foreign_keys: Vec<ForeignKey>,  // from this table
+    pub referenced_by: Vec<ForeignKey>, // from other tables (or this one) to it
+    pub unique: Vec<NamedConstraint>,
+    pub checks: Vec<NamedConstraint>,
+}
+
+// Folds (name, column, definition) rows, ordered by name, into constraints
+fn group_named_constraints(
+    rows: impl Iterator<Item = (String, Option<String>, Option<String>)>,
+) -> Vec<NamedConstraint> {
+    let mut constraints: Vec<NamedConstraint> = Vec::new();
+    for (name, column, definition) in rows {
+        match constraints.last_mut() {
+            Some(c) if c.name == name => c.columns.extend(column),
+            _ => constraints.push(NamedConstraint {
+                name,
+                columns: column.into_iter().collect(),
+                definition,
+            }),
+        }
+    }
+    constraints
+}
+
+async fn get_unique_and_check_constraints(
     client: &DbClient,
     schema: Option<String>,
-) -> Result<Vec<String>, String> {
+    table: &str,
+) -> Result<(Vec<NamedConstraint>, Vec<NamedConstraint>), String> {
     match client {
         DbClient::Postgres(pool) => {
             let schema_filter = schema.unwrap_or_else(|| "public".to_string());
             let rows = sqlx::query(
-                "SELECT routine_name FROM information_schema.routines WHERE routine_type = 'FUNCTION' AND routine_schema = $1"
+                "SELECT con.contype::text, con.conname::text, \
+                 ARRAY(SELECT a.attname::text FROM unnest(con.conkey) WITH ORDINALITY k(attnum, ord) \
+                   JOIN pg_attribute a ON a.attrelid = con.conrelid AND a.attnum = k.attnum ORDER BY k.ord), \
+                 pg_get_constraintdef(con.oid) \
+                 FROM pg_constraint con \
+                 JOIN pg_class c ON c.oid = con.conrelid JOIN pg_namespace n ON n.oid = c.relnamespace \
+                 WHERE con.contype IN ('u', 'c') AND n.nspname = $1 AND c.relname = $2 \
+                 ORDER BY con.conname",
             )
             .bind(schema_filter)
+            .bind(table)
             .fetch_all(pool)
             .await
             .map_err(|e| e.to_string())?;
-            Ok(rows.iter().map(|r| r.get(0)).collect())
+            let (mut unique, mut checks) = (Vec::new(), Vec::new());
+            for r in &rows {
+                let kind: String = r.get(0);
+                let constraint = NamedConstraint {
+                    name: r.get(1),
+                    columns: r.get(2),
+                    definition: r.get(3),
+                };
+                match kind.as_str() {
+                    "u" => unique.push(constraint),
+                    _ => checks.push(constraint),
+                }
+            }
+            Ok((unique, checks))
         }
         DbClient::Mysql(pool) => {
-            let rows = sqlx::query(
-                "SELECT routine_name FROM information_schema.routines WHERE routine_type = 'FUNCTION' AND routine_schema = DATABASE()"
+            let database = mysql_database(schema);
+            let unique = sqlx::query(
+                "SELECT tc.constraint_name, kcu.column_name \
+                 FROM information_schema.table_constraints tc \
+                 JOIN information_schema.key_column_usage kcu \
+                 ON kcu.constraint_schema = tc.constraint_schema AND kcu.constraint_name = tc.constraint_name \
+                 AND kcu.table_name = tc.table_name \
+                 WHERE tc.constraint_type = 'UNIQUE' AND tc.table_schema = COALESCE(?, DATABASE()) AND tc.table_name = ? \
+                 ORDER BY tc.constraint_name, kcu.ordinal_position",
             )
+            .bind(&database)
+            .bind(table)
             .fetch_all(pool)
             .await
             .map_err(|e| e.to_string())?;
-            Ok(rows.iter().map(|r| r.get(0)).collect())
+            // check_constraints only exists from MySQL 8.0.16 / MariaDB 10.2
+            let checks = sqlx::query(
+                "SELECT cc.constraint_name, cc.check_clause \
+                 FROM information_schema.table_constraints tc \
+                 JOIN information_schema.check_constraints cc \
+                 ON cc.constraint_schema = tc.constraint_schema AND cc.constraint_name = tc.constraint_name \
+                 WHERE tc.constraint_type = 'CHECK' AND tc.table_schema = COALESCE(?, DATABASE()) AND tc.table_name = ? \
+                 ORDER BY cc.constraint_name",
+            )
+            .bind(&database)
+            .bind(table)
+            .fetch_all(pool)
+            .await
+            .unwrap_or_default();
+            Ok((
+                group_named_constraints(unique.iter().map(|r| (r.get(0), Some(r.get(1)), None))),
+                group_named_constraints(checks.iter().map(|r| (r.get(0), None, r.get(1)))),
+            ))
         }
-        DbClient::Mssql(client_mutex) => {
-            let mut client = client_mutex.lock().await;
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
             let schema_filter = schema.unwrap_or_else(|| "dbo".to_string());
-            let query = "SELECT ROUTINE_NAME FROM INFORMATION_SCHEMA.ROUTINES WHERE ROUTINE_TYPE = 'FUNCTION' AND ROUTINE_SCHEMA = @P1";
+            let query = "SELECT 'u', kc.name, c.name, NULL FROM sys.key_constraints kc \
+                 JOIN sys.index_columns ic ON ic.object_id = kc.parent_object_id AND ic.index_id = kc.unique_index_id \
+                 JOIN sys.columns c ON c.object_id = ic.object_id AND c.column_id = ic.column_id \
+                 WHERE kc.type = 'UQ' AND kc.parent_object_id = OBJECT_ID(QUOTENAME(@P1) + '.' + QUOTENAME(@P2)) \
+                 UNION ALL \
+                 SELECT 'c', cc.name, COL_NAME(cc.parent_object_id, cc.parent_column_id), cc.definition \
+                 FROM sys.check_constraints cc \
+                 WHERE cc.parent_object_id = OBJECT_ID(QUOTENAME(@P1) + '.' + QUOTENAME(@P2)) \
+                 ORDER BY 1, 2";
             let rows = client
-                .query(query, &[&schema_filter])
+                .query(query, &[&schema_filter, &table])
                 .await
                 .map_err(|e| e.to_string())?
                 .into_first_result()
                 .await
                 .map_err(|e| e.to_string())?;
-            let mut funcs = Vec::new();
-            for r in rows {
-                if let Ok(Some(name)) = r.try_get::<&str, _>(0) {
-                    funcs.push(name.to_string());
-                }
-            }
-            Ok(funcs)
+            let text =
+                |r: &tiberius::Row, i| r.try_get::<&str, _>(i).ok().flatten().map(str::to_string);
+            let row = |r: &tiberius::Row| (text(r, 1).unwrap_or_default(), text(r, 2), text(r, 3));
+            let is_unique = |r: &&tiberius::Row| text(r, 0).as_deref() == Some("u");
+            Ok((
+                group_named_constraints(rows.iter().filter(is_unique).map(row)),
+                group_named_constraints(rows.iter().filter(|r| !is_unique(r)).map(row)),
+            ))
         }
-        _ => Ok(vec![]),
+        _ => Err("Constraints are not supported for this database type".to_string()),
     }
 }
 
-pub async fn get_schemas(client: &DbClient) -> Result<Vec<String>, String> {
+// Every key and constraint of a table, in one call for the structure view
+pub async fn get_constraints(
+    client: &DbClient,
+    schema: Option<String>,
+    table: &str,
+) -> Result<TableConstraints, String> {
+    let (unique, checks) = get_unique_and_check_constraints(client, schema.clone(), table).await?;
+    let primary_key = get_primary_key(client, schema.clone(), table).await?;
+    let (foreign_keys, referenced_by) = get_foreign_keys(client, schema.clone(), table)
+        .await?
+        .into_iter()
+        .partition(|fk| fk.table == table && schema.as_deref().map_or(true, |s| fk.schema == s));
+    Ok(TableConstraints {
+        primary_key,
+        foreign_keys,
+        referenced_by,
+        unique,
+        checks,
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub data_type: String,
+    pub nullable: bool,
+    pub default: Option<String>,
+    pub is_identity: bool, // identity, serial or auto-increment
+    pub identity_generation: Option<String>, // "ALWAYS" or "BY DEFAULT"
+    pub is_generated: bool, // computed from an expression (or a MSSQL rowversion)
+    pub generation_expression: Option<String>,
+}
+
+impl ColumnInfo {
+    // Whether statements may supply a value for this column
+    pub fn is_writable(&self) -> bool {
+        !self.is_generated && self.identity_generation.as_deref() != Some("ALWAYS")
+    }
+}
+
+// MySQL 8 reports DEFAULT CURRENT_TIMESTAMP columns as DEFAULT_GENERATED in
+// EXTRA, though they take values like any other column
+fn mysql_generated(extra: &str, expression: Option<&str>) -> bool {
+    let extra = extra.to_uppercase();
+    extra.contains("VIRTUAL GENERATED")
+        || extra.contains("STORED GENERATED")
+        || expression.is_some_and(|e| !e.is_empty())
+}
+
+// Column details including identity and generated-column flags, in table order
+pub async fn get_column_info(
+    client: &DbClient,
+    schema: Option<String>,
+    table: &str,
+) -> Result<Vec<ColumnInfo>, String> {
     match client {
         DbClient::Postgres(pool) => {
+            let schema_filter = schema.unwrap_or_else(|| "public".to_string());
             let rows = sqlx::query(
-                "SELECT schema_name FROM information_schema.schemata WHERE schema_name NOT IN ('information_schema', 'pg_catalog', 'pg_toast')"
+                "SELECT column_name::text, data_type::text, is_nullable = 'YES', column_default::text, \
+                 is_identity = 'YES', identity_generation::text, is_generated = 'ALWAYS', generation_expression::text \
+                 FROM information_schema.columns WHERE table_schema = $1 AND table_name = $2 \
+                 ORDER BY ordinal_position",
             )
+            .bind(schema_filter)
+            .bind(table)
             .fetch_all(pool)
             .await
             .map_err(|e| e.to_string())?;
-            Ok(rows.iter().map(|r| r.get(0)).collect())
+            Ok(rows
+                .iter()
+                .map(|r| {
+                    let default: Option<String> = r.get(3);
+                    // serial columns are identities in all but name
+                    let serial = default
+                        .as_deref()
+                        .is_some_and(|d| d.starts_with("nextval("));
+                    let is_identity = r.get::<bool, _>(4) || serial;
+                    ColumnInfo {
+                        name: r.get(0),
+                        data_type: r.get(1),
+                        nullable: r.get(2),
+                        default,
+                        is_identity,
+                        identity_generation: r
+                            .get::<Option<String>, _>(5)
+                            .or_else(|| serial.then(|| "BY DEFAULT".to_string())),
+                        is_generated: r.get(6),
+                        generation_expression: r.get(7),
+                    }
+                })
+                .collect())
         }
-        DbClient::Mysql(_) => {
-            // MySQL uses databases as schemas generally.
-            Ok(vec!["def".to_string()]) // Or list databases?
+        DbClient::Mysql(pool) => {
+            let rows = sqlx::query(
+                "SELECT column_name, column_type, is_nullable, column_default, extra, generation_expression \
+                 FROM information_schema.columns WHERE table_schema = COALESCE(?, DATABASE()) AND table_name = ? \
+                 ORDER BY ordinal_position",
+            )
+            .bind(mysql_database(schema))
+            .bind(table)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            Ok(rows
+                .iter()
+                .map(|r| {
+                    let extra = r.get::<String, _>(4);
+                    let expression = r.get::<Option<String>, _>(5);
+                    let is_identity = extra.to_uppercase().contains("AUTO_INCREMENT");
+                    let is_generated = mysql_generated(&extra, expression.as_deref());
+                    ColumnInfo {
+                        name: r.get(0),
+                        data_type: r.get(1),
+                        nullable: r.get::<String, _>(2) == "YES",
+                        default: r.get(3),
+                        is_identity,
+                        identity_generation: is_identity.then(|| "BY DEFAULT".to_string()),
+                        is_generated,
+                        generation_expression: expression.filter(|e| is_generated && !e.is_empty()),
+                    }
+                })
+                .collect())
         }
-        DbClient::Mssql(client_mutex) => {
-            let mut client = client_mutex.lock().await;
-            let query = "SELECT SCHEMA_NAME FROM INFORMATION_SCHEMA.SCHEMATA WHERE SCHEMA_NAME NOT IN ('information_schema', 'sys', 'guest', 'users')";
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
+            let schema_filter = schema.unwrap_or_else(|| "dbo".to_string());
+            let query = "SELECT c.name, TYPE_NAME(c.user_type_id), c.is_nullable, OBJECT_DEFINITION(c.default_object_id), \
+                 c.is_identity, CAST(CASE WHEN c.is_computed = 1 OR TYPE_NAME(c.system_type_id) = 'timestamp' THEN 1 ELSE 0 END AS bit), cc.definition \
+                 FROM sys.columns c \
+                 LEFT JOIN sys.computed_columns cc ON cc.object_id = c.object_id AND cc.column_id = c.column_id \
+                 WHERE c.object_id = OBJECT_ID(QUOTENAME(@P1) + '.' + QUOTENAME(@P2)) \
+                 ORDER BY c.column_id";
             let rows = client
-                .query(query, &[])
+                .query(query, &[&schema_filter, &table])
                 .await
                 .map_err(|e| e.to_string())?
                 .into_first_result()
                 .await
                 .map_err(|e| e.to_string())?;
-            let mut schemas = Vec::new();
-            for r in rows {
-                if let Ok(Some(name)) = r.try_get::<&str, _>(0) {
-                    schemas.push(name.to_string());
-                }
-            }
-            Ok(schemas)
+            let text = |r: &tiberius::Row, i: usize| {
+                r.try_get::<&str, _>(i)
+                    .ok()
+                    .flatten()
+                    .map(|s| s.to_string())
+            };
+            let flag = |r: &tiberius::Row, i: usize| {
+                r.try_get::<bool, _>(i).ok().flatten().unwrap_or(false)
+            };
+            Ok(rows
+                .iter()
+                .map(|r| {
+                    // Identity columns only accept values under IDENTITY_INSERT
+                    let is_identity = flag(r, 4);
+                    ColumnInfo {
+                        name: text(r, 0).unwrap_or_default(),
+                        data_type: text(r, 1).unwrap_or_default(),
+                        nullable: flag(r, 2),
+                        default: text(r, 3),
+                        is_identity,
+                        identity_generation: is_identity.then(|| "ALWAYS".to_string()),
+                        is_generated: flag(r, 5),
+                        generation_expression: text(r, 6),
+                    }
+                })
+                .collect())
         }
         _ => Ok(vec![]),
     }
 }
 
-pub async fn get_databases(client: &DbClient) -> Result<Vec<String>, String> {
+// Column structure as shown by the schema browser
+#[derive(Debug, Clone, Serialize)]
+pub struct TableColumn {
+    #[serde(flatten)]
+    pub info: ColumnInfo,
+    pub is_primary_key: bool,
+}
+
+// For MongoDB `schema` is the database and the columns are fields inferred
+// from a sample of the collection, in dot notation
+pub async fn get_columns(
+    client: &DbClient,
+    schema: Option<String>,
+    table: &str,
+) -> Result<Vec<TableColumn>, String> {
+    if let DbClient::Mongo(_) = client {
+        let inferred =
+            crate::mongo::infer_collection_schema(client, schema, table.to_string(), None).await?;
+        return Ok(inferred
+            .fields
+            .into_iter()
+            .map(|field| {
+                let types: Vec<String> = field.types.into_iter().map(|t| t.type_name).collect();
+                TableColumn {
+                    is_primary_key: field.path == "_id",
+                    info: ColumnInfo {
+                        nullable: field.optional || types.iter().any(|t| t == "null"),
+                        name: field.path,
+                        data_type: types.join(" | "),
+                        default: None,
+                        is_identity: false,
+                        identity_generation: None,
+                        is_generated: false,
+                        generation_expression: None,
+                    },
+                }
+            })
+            .collect());
+    }
+
+    let primary_key = get_primary_key(client, schema.clone(), table).await?;
+    Ok(get_column_info(client, schema, table)
+        .await?
+        .into_iter()
+        .map(|info| TableColumn {
+            is_primary_key: primary_key.contains(&info.name),
+            info,
+        })
+        .collect())
+}
+
+// One statement of a transactional batch. When `capture` is set, that SELECT runs
+// first inside the same transaction and its rows are returned alongside. When
+// `expect_rows` is set, any other affected row count rolls the whole batch back.
+pub struct TxStep {
+    pub capture: Option<String>,
+    pub statement: String,
+    pub expect_rows: Option<u64>,
+}
+
+pub struct TxStepResult {
+    pub captured: Vec<Map<String, Value>>,
+    pub rows_affected: u64,
+}
+
+// A row keyed by column name, as captured for the undo journal
+pub fn row_map(columns: &[String], cells: Vec<Cell>) -> Result<Map<String, Value>, String> {
+    columns
+        .iter()
+        .cloned()
+        .zip(cells)
+        .map(|(column, cell)| {
+            let value = cell
+                .try_into_value()
+                .map_err(|e| format!("{} in column {}", e, column))?;
+            Ok((column, value))
+        })
+        .collect()
+}
+
+fn check_rows_affected(step: &TxStep, affected: u64) -> Result<(), String> {
+    match step.expect_rows {
+        Some(expected) if expected != affected => Err(format!(
+            "Expected {} row(s) to be affected but {} were, changes rolled back: {}",
+            expected, affected, step.statement
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Run statements atomically; returns the total number of affected rows
+pub async fn execute_in_transaction(
+    client: &DbClient,
+    statements: &[String],
+) -> Result<u64, String> {
+    let steps: Vec<TxStep> = statements
+        .iter()
+        .map(|s| TxStep {
+            capture: None,
+            statement: s.clone(),
+            expect_rows: None,
+        })
+        .collect();
+    let results = execute_steps_in_transaction(client, &steps, false).await?;
+    Ok(results.iter().map(|r| r.rows_affected).sum())
+}
+
+// Runs the steps atomically. With `nested` the client is the connection of a
+// transaction opened with BEGIN (see transactions.rs): the steps then run under
+// a savepoint of it, so a failure undoes only them and the transaction is left
+// open, and nothing is committed.
+pub async fn execute_steps_in_transaction(
+    client: &DbClient,
+    steps: &[TxStep],
+    nested: bool,
+) -> Result<Vec<TxStepResult>, String> {
     match client {
+        DbClient::Postgres(pool) if nested => {
+            let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+            sqlx::raw_sql("SAVEPOINT dbms_steps")
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| e.to_string())?;
+            let results = run_pg_steps(&mut conn, steps).await;
+            end_savepoint(&mut *conn, results).await
+        }
         DbClient::Postgres(pool) => {
-            let rows = sqlx::query("SELECT datname FROM pg_database WHERE datistemplate = false;")
-                .fetch_all(pool)
+            let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+            let results = run_pg_steps(&mut tx, steps).await?;
+            tx.commit().await.map_err(|e| e.to_string())?;
+            Ok(results)
+        }
+        DbClient::Mysql(pool) if nested => {
+            let mut conn = pool.acquire().await.map_err(|e| e.to_string())?;
+            sqlx::raw_sql("SAVEPOINT dbms_steps")
+                .execute(&mut *conn)
                 .await
                 .map_err(|e| e.to_string())?;
-            Ok(rows.iter().map(|r| r.get(0)).collect())
+            let results = run_mysql_steps(&mut conn, steps).await;
+            end_savepoint(&mut *conn, results).await
         }
         DbClient::Mysql(pool) => {
-            let rows = sqlx::query("SHOW DATABASES")
-                .fetch_all(pool)
+            let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+            let results = run_mysql_steps(&mut tx, steps).await?;
+            tx.commit().await.map_err(|e| e.to_string())?;
+            Ok(results)
+        }
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
+            let (begin, commit, rollback) = if nested {
+                (
+                    "SAVE TRANSACTION dbms_steps",
+                    None,
+                    "ROLLBACK TRANSACTION dbms_steps",
+                )
+            } else {
+                (
+                    "BEGIN TRANSACTION",
+                    Some("COMMIT TRANSACTION"),
+                    "ROLLBACK TRANSACTION",
+                )
+            };
+            client
+                .simple_query(begin)
+                .await
+                .map_err(|e| e.to_string())?
+                .into_results()
                 .await
                 .map_err(|e| e.to_string())?;
-            // First column is Database
-            Ok(rows.iter().map(|r| r.get(0)).collect())
+            match run_mssql_steps(&mut client, steps).await {
+                Ok(done) => {
+                    if let Some(commit) = commit {
+                        client
+                            .simple_query(commit)
+                            .await
+                            .map_err(|e| e.to_string())?
+                            .into_results()
+                            .await
+                            .map_err(|e| e.to_string())?;
+                    }
+                    Ok(done)
+                }
+                Err(e) => {
+                    // Leave the session usable even if the rollback itself fails
+                    if let Ok(stream) = client.simple_query(rollback).await {
+                        let _ = stream.into_results().await;
+                    }
+                    Err(e)
+                }
+            }
         }
-        DbClient::Mssql(client_mutex) => {
-            let mut client = client_mutex.lock().await;
-            let query = "SELECT name FROM sys.databases WHERE name NOT IN ('master', 'tempdb', 'model', 'msdb')";
+        _ => Err("Transactions are not supported for this database type".to_string()),
+    }
+}
+
+// Releases the savepoint the steps ran under, or rolls back to it on failure
+async fn end_savepoint<'e, E: sqlx::Executor<'e>>(
+    conn: E,
+    results: Result<Vec<TxStepResult>, String>,
+) -> Result<Vec<TxStepResult>, String> {
+    let sql = match results {
+        Ok(_) => "RELEASE SAVEPOINT dbms_steps",
+        Err(_) => "ROLLBACK TO SAVEPOINT dbms_steps",
+    };
+    let ended = sqlx::raw_sql(sql).execute(conn).await;
+    let results = results?;
+    ended.map_err(|e| e.to_string())?;
+    Ok(results)
+}
+
+async fn run_pg_steps(
+    conn: &mut sqlx::PgConnection,
+    steps: &[TxStep],
+) -> Result<Vec<TxStepResult>, String> {
+    let mut results = Vec::new();
+    for step in steps {
+        let mut captured = Vec::new();
+        if let Some(capture) = &step.capture {
+            let rows = sqlx::query(capture)
+                .fetch_all(&mut *conn)
+                .await
+                .map_err(|e| e.to_string())?;
+            for row in &rows {
+                let columns: Vec<String> =
+                    row.columns().iter().map(|c| c.name().to_string()).collect();
+                captured.push(row_map(&columns, pg_row_values(row, RenderZone::utc()))?);
+            }
+        }
+        let result = sqlx::query(&step.statement)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        check_rows_affected(step, result.rows_affected())?;
+        results.push(TxStepResult {
+            captured,
+            rows_affected: result.rows_affected(),
+        });
+    }
+    Ok(results)
+}
+
+async fn run_mysql_steps(
+    conn: &mut sqlx::MySqlConnection,
+    steps: &[TxStep],
+) -> Result<Vec<TxStepResult>, String> {
+    let mut results = Vec::new();
+    for step in steps {
+        let mut captured = Vec::new();
+        if let Some(capture) = &step.capture {
+            let rows = sqlx::query(capture)
+                .fetch_all(&mut *conn)
+                .await
+                .map_err(|e| e.to_string())?;
+            for row in &rows {
+                let columns: Vec<String> =
+                    row.columns().iter().map(|c| c.name().to_string()).collect();
+                captured.push(row_map(&columns, mysql_row_values(row, RenderZone::utc()))?);
+            }
+        }
+        let result = sqlx::query(&step.statement)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| e.to_string())?;
+        check_rows_affected(step, result.rows_affected())?;
+        results.push(TxStepResult {
+            captured,
+            rows_affected: result.rows_affected(),
+        });
+    }
+    Ok(results)
+}
+
+async fn run_mssql_steps(
+    client: &mut MssqlClient,
+    steps: &[TxStep],
+) -> Result<Vec<TxStepResult>, String> {
+    let mut results = Vec::new();
+    for step in steps {
+        let mut captured = Vec::new();
+        if let Some(capture) = &step.capture {
             let rows = client
-                .query(query, &[])
+                .query(capture.as_str(), &[])
                 .await
                 .map_err(|e| e.to_string())?
                 .into_first_result()
                 .await
                 .map_err(|e| e.to_string())?;
-            let mut dbs = Vec::new();
-            for r in rows {
-                if let Ok(Some(name)) = r.try_get::<&str, _>(0) {
-                    dbs.push(name.to_string());
-                }
+            for row in &rows {
+                let columns: Vec<String> =
+                    row.columns().iter().map(|c| c.name().to_string()).collect();
+                captured.push(row_map(
+                    &columns,
+                    serialize_mssql_row(row, RenderZone::utc()),
+                )?);
             }
-            Ok(dbs)
         }
-        _ => Ok(vec![]),
+        let rows_affected = client
+            .execute(step.statement.as_str(), &[])
+            .await
+            .map_err(|e| e.to_string())?
+            .total();
+        check_rows_affected(step, rows_affected)?;
+        results.push(TxStepResult {
+            captured,
+            rows_affected,
+        });
     }
+    Ok(results)
 }
 
-// Test Connection
-pub async fn test_connection(conn_str: &str) -> Result<String, String> {
-    let client = create_client(conn_str).await?;
-    // Try simple query
+// Every connection of the pool is checked out, so the server is answering
+// queries; a ping would only wait for one of them
+fn pool_busy<DB: sqlx::Database>(pool: &sqlx::Pool<DB>) -> bool {
+    pool.num_idle() == 0 && pool.size() >= pool.options().get_max_connections()
+}
+
+// MariaDB shares the MySQL protocol, and tells itself apart in its version
+pub async fn is_mariadb(pool: &sqlx::MySqlPool) -> Result<bool, String> {
+    let version: String = sqlx::query_scalar("SELECT VERSION()")
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(version.contains("MariaDB"))
+}
+
+// The cheapest round trip each server supports. A connection whose pool is
+// busy counts as answering rather than waiting for a free slot.
+pub async fn ping(client: &DbClient) -> Result<(), String> {
     match client {
+        DbClient::Postgres(pool) if pool_busy(pool) => {}
+        DbClient::Mysql(pool) if pool_busy(pool) => {}
+        DbClient::Mssql(pool) if pool.busy() => {}
         DbClient::Postgres(pool) => {
             sqlx::query("SELECT 1")
-                .fetch_one(&pool)
+                .fetch_one(pool)
                 .await
                 .map_err(|e| e.to_string())?;
         }
         DbClient::Mysql(pool) => {
             sqlx::query("SELECT 1")
-                .fetch_one(&pool)
+                .fetch_one(pool)
                 .await
                 .map_err(|e| e.to_string())?;
         }
-        DbClient::Mssql(client_mutex) => {
-            let mut client = client_mutex.lock().await;
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
             client
                 .simple_query("SELECT 1")
                 .await
                 .map_err(|e| e.to_string())?;
         }
         DbClient::Mongo(client) => {
-            // Check list database names
             client
-                .list_database_names()
+                .database("admin")
+                .run_command(mongodb::bson::doc! { "ping": 1 })
                 .await
                 .map_err(|e| e.to_string())?;
         }
@@ -555,6 +2455,13 @@ pub async fn test_connection(conn_str: &str) -> Result<String, String> {
                 .map_err(|e| e.to_string())?;
         }
     }
+    Ok(())
+}
+
+// Test Connection
+pub async fn test_connection(conn_str: &str, tls: &TlsOptions) -> Result<String, String> {
+    let client = create_client_with_tls(conn_str, tls).await?;
+    ping(&client).await?;
     Ok("Connection successful".to_string())
 }
 
@@ -563,10 +2470,30 @@ pub async fn export_data(
     sql: String,
     format: String,
     path: String,
-) -> Result<(), String> {
-    let result = execute_query(client, sql).await?;
-    let columns = result.columns;
-    let rows = result.rows;
+    export: &ExportSettings,
+    options: &QueryOptions,
+    shaper: &ColumnShaper,
+) -> Result<u64, String> {
+    let result = execute_query(client, sql, options).await?;
+    let count = result.rows.len() as u64;
+    let batch = shaper.apply(RowBatch {
+        index: 0,
+        columns: Some(result.columns),
+        column_meta: Some(result.column_meta),
+        rows: result.rows,
+    });
+    if matches!(format.as_str(), "excel" | "xlsx") {
+        let sink = XlsxSink::create(&path, export.include_headers);
+        sink.write(batch)?;
+        sink.finish()?;
+        return Ok(count);
+    }
+    let columns = batch.columns.unwrap_or_default();
+    let rows: Vec<Vec<Value>> = batch
+        .rows
+        .into_iter()
+        .map(|r| r.into_iter().map(Cell::try_into_value).collect())
+        .collect::<Result<_, _>>()?;
     let file = File::create(&path).map_err(|e| e.to_string())?;
     let mut writer = BufWriter::new(file);
 
@@ -600,29 +2527,22 @@ pub async fn export_data(
             let delimiter = match format.as_str() {
                 "csv_semicolon" => b';',
                 "tsv" => b'\t',
-                _ => b',',
+                _ => csv_delimiter(export),
             };
             let mut csv_writer = csv::WriterBuilder::new()
                 .delimiter(delimiter)
                 .from_writer(writer);
 
             // Write Headers
-            csv_writer
-                .write_record(&columns)
-                .map_err(|e| e.to_string())?;
+            if export.include_headers {
+                csv_writer
+                    .write_record(&columns)
+                    .map_err(|e| e.to_string())?;
+            }
 
             // Write Rows
             for row in rows {
-                let record: Vec<String> = row
-                    .iter()
-                    .map(|v| match v {
-                        Value::Null => "".to_string(),
-                        Value::String(s) => s.clone(),
-                        Value::Bool(b) => b.to_string(),
-                        Value::Number(n) => n.to_string(),
-                        _ => v.to_string(),
-                    })
-                    .collect();
+                let record: Vec<String> = row.iter().map(csv_field).collect();
                 csv_writer
                     .write_record(&record)
                     .map_err(|e| e.to_string())?;
@@ -634,27 +2554,13 @@ pub async fn export_data(
             // Needed: Table Name. But we only have query.
             // We'll use "EXPORT_TABLE" as placeholder or try to parse (hard).
             // Let's use "export_table".
+            let quoted_columns: Vec<String> = columns.iter().map(|c| quote_ansi_ident(c)).collect();
             for row in rows {
-                let values: Vec<String> = row
-                    .iter()
-                    .map(|v| match v {
-                        Value::Null => "NULL".to_string(),
-                        Value::String(s) => format!("'{}'", s.replace("'", "''")),
-                        Value::Bool(b) => {
-                            if *b {
-                                "TRUE".to_string()
-                            } else {
-                                "FALSE".to_string()
-                            }
-                        }
-                        Value::Number(n) => n.to_string(),
-                        _ => format!("'{}'", v.to_string().replace("'", "''")),
-                    })
-                    .collect();
+                let values: Vec<String> = row.iter().map(|v| sql_literal(None, v)).collect();
 
                 let sql = format!(
                     "INSERT INTO export_table ({}) VALUES ({});\n",
-                    columns.join(", "),
+                    quoted_columns.join(", "),
                     values.join(", ")
                 );
                 writer
@@ -679,63 +2585,182 @@ pub async fn export_data(
                 .write_all(xml_str.as_bytes())
                 .map_err(|e| e.to_string())?;
         }
-        "excel" => {
-            // rust_xlsxwriter needs separate file handling, it creates its own file.
-            // So we close our file/writer and pass path to workbook.
-            // Actually `Workbook::new()` doesn't take path, `save(path)` does.
-            // So drop writer first.
-            drop(writer);
+        _ => return Err(format!("Unsupported format: {}", format)),
+    }
 
-            let mut workbook = rust_xlsxwriter::Workbook::new();
-            let sheet = workbook.add_worksheet();
+    Ok(count)
+}
 
-            // Headers
-            for (i, col) in columns.iter().enumerate() {
-                sheet
-                    .write_string(0, i as u16, col)
-                    .map_err(|e| e.to_string())?;
-            }
+// The delimiter from the export settings or template, "\t" or "tab" for tabs
+fn csv_delimiter(export: &ExportSettings) -> u8 {
+    match export.csv_delimiter.as_str() {
+        "\\t" | "tab" => b'\t',
+        other => other.bytes().next().unwrap_or(b','),
+    }
+}
 
-            // Rows
-            for (r, row) in rows.iter().enumerate() {
-                for (c, val) in row.iter().enumerate() {
-                    let row_idx = (r + 1) as u32;
-                    let col_idx = c as u16;
-                    match val {
-                        Value::Null => {}
-                        Value::String(s) => {
-                            sheet
-                                .write_string(row_idx, col_idx, s)
-                                .map_err(|e| e.to_string())?;
-                        }
-                        Value::Bool(b) => {
-                            sheet
-                                .write_boolean(row_idx, col_idx, *b)
-                                .map_err(|e| e.to_string())?;
-                        }
-                        Value::Number(n) => {
-                            if let Some(f) = n.as_f64() {
-                                sheet
-                                    .write_number(row_idx, col_idx, f)
-                                    .map_err(|e| e.to_string())?;
-                            } else if let Some(i) = n.as_i64() {
-                                sheet
-                                    .write_number(row_idx, col_idx, i as f64)
-                                    .map_err(|e| e.to_string())?; // Excel uses f64
-                            }
-                        }
-                        _ => {
-                            sheet
-                                .write_string(row_idx, col_idx, val.to_string())
-                                .map_err(|e| e.to_string())?;
-                        }
-                    }
-                }
+fn csv_field(value: &Value) -> String {
+    match value {
+        Value::Null => "".to_string(),
+        Value::String(s) => s.clone(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        _ => value.to_string(),
+    }
+}
+
+// Writes streamed row batches straight to a CSV file, so exports never hold
+// the whole result in memory
+pub struct CsvSink {
+    writer: StdMutex<csv::Writer<BufWriter<File>>>,
+    include_headers: bool,
+}
+
+impl CsvSink {
+    pub fn create(path: &str, export: &ExportSettings) -> Result<Self, String> {
+        let delimiter = csv_delimiter(export);
+        let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+        let writer = csv::WriterBuilder::new()
+            .delimiter(delimiter)
+            .from_writer(BufWriter::new(file));
+        Ok(CsvSink {
+            writer: StdMutex::new(writer),
+            include_headers: export.include_headers,
+        })
+    }
+
+    pub fn write(&self, batch: RowBatch) -> Result<(), String> {
+        let mut writer = self.writer.lock().unwrap();
+        if let (Some(columns), true) = (&batch.columns, self.include_headers) {
+            writer.write_record(columns).map_err(|e| e.to_string())?;
+        }
+        for row in batch.rows {
+            let record: Vec<String> = row
+                .into_iter()
+                .map(|cell| Ok(csv_field(&cell.try_into_value()?)))
+                .collect::<Result<_, String>>()?;
+            writer.write_record(&record).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        let mut writer = self.writer.into_inner().unwrap();
+        writer.flush().map_err(|e| e.to_string())
+    }
+}
+
+// Streams row batches to a file as one JSON array of objects, or as
+// newline-delimited objects, keyed by column name
+pub struct JsonSink {
+    writer: StdMutex<(BufWriter<File>, Vec<String>, u64)>, // with the columns and rows written
+    ndjson: bool,
+}
+
+impl JsonSink {
+    pub fn create(path: &str, ndjson: bool) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+        let mut writer = BufWriter::new(file);
+        if !ndjson {
+            writer.write_all(b"[").map_err(|e| e.to_string())?;
+        }
+        Ok(JsonSink {
+            writer: StdMutex::new((writer, Vec::new(), 0)),
+            ndjson,
+        })
+    }
+
+    pub fn write(&self, batch: RowBatch) -> Result<(), String> {
+        let mut guard = self.writer.lock().unwrap();
+        let (writer, columns, written) = &mut *guard;
+        if let Some(names) = batch.columns {
+            *columns = names;
+        }
+        for row in batch.rows {
+            let object = row_map(columns, row)?;
+            let separator: &[u8] = match (self.ndjson, *written) {
+                (true, _) => b"",
+                (false, 0) => b"\n",
+                (false, _) => b",\n",
+            };
+            writer.write_all(separator).map_err(|e| e.to_string())?;
+            serde_json::to_writer(&mut *writer, &object).map_err(|e| e.to_string())?;
+            if self.ndjson {
+                writer.write_all(b"\n").map_err(|e| e.to_string())?;
             }
-            workbook.save(path).map_err(|e| e.to_string())?;
+            *written += 1;
         }
-        _ => return Err(format!("Unsupported format: {}", format)),
+        Ok(())
     }
 
-    Ok(())
+    pub fn finish(self) -> Result<(), String> {
+        let (mut writer, _, _) = self.writer.into_inner().unwrap();
+        if !self.ndjson {
+            writer.write_all(b"\n]\n").map_err(|e| e.to_string())?;
+        }
+        writer.flush().map_err(|e| e.to_string())
+    }
+}
+
+// Stream the raw output of a Postgres `COPY ... TO STDOUT` straight into a file,
+// leaving format, delimiter, header and encoding to the COPY options.
+// Returns the number of bytes written.
+pub async fn copy_to_file(client: &DbClient, sql: String, path: String) -> Result<u64, String> {
+    let DbClient::Postgres(pool) = client else {
+        return Err("COPY TO STDOUT is only supported for PostgreSQL".to_string());
+    };
+    let normalized = sql.to_uppercase();
+    if !normalized.trim_start().starts_with("COPY") || !normalized.contains("STDOUT") {
+        return Err("Expected a COPY ... TO STDOUT statement".to_string());
+    }
+
+    let mut stream = pool.copy_out_raw(&sql).await.map_err(|e| e.to_string())?;
+    let file = tokio::fs::File::create(&path)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    let mut writer = tokio::io::BufWriter::new(file);
+    let mut written = 0u64;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| e.to_string())?;
+        writer.write_all(&chunk).await.map_err(|e| e.to_string())?;
+        written += chunk.len() as u64;
+    }
+    writer.flush().await.map_err(|e| e.to_string())?;
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_options_ignore_the_editor_auto_limit() {
+        let mut settings = Settings::default();
+        settings.query.auto_limit = 100;
+        let options = QueryOptions::for_export(&settings);
+        assert_eq!(options.row_limit, None);
+    }
+
+    #[test]
+    fn default_generated_mysql_columns_stay_writable() {
+        assert!(!mysql_generated("DEFAULT_GENERATED", None));
+        assert!(!mysql_generated(
+            "DEFAULT_GENERATED on update CURRENT_TIMESTAMP",
+            Some("")
+        ));
+        assert!(mysql_generated("VIRTUAL GENERATED", Some("`a` + 1")));
+        assert!(mysql_generated("STORED GENERATED", None));
+        assert!(mysql_generated("", Some("`a` + 1")));
+    }
+
+    #[test]
+    fn export_options_have_no_timeout() {
+        let mut settings = Settings::default();
+        settings.query.timeout_seconds = 30;
+        assert_eq!(
+            QueryOptions::from_settings(&settings).timeout,
+            Some(Duration::from_secs(30))
+        );
+        assert_eq!(QueryOptions::for_export(&settings).timeout, None);
+    }
 }
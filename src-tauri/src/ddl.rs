@@ -0,0 +1,190 @@
+use serde::Deserialize;
+
+use crate::audit::Auditor;
+use crate::db::{self, DbClient, Dialect, QueryOptions};
+use crate::edits::TableRef;
+use crate::quoting::{quote_ident, quote_text};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DatabaseSpec {
+    pub name: String,
+    pub owner: Option<String>,     // Postgres, MSSQL
+    pub encoding: Option<String>,  // Postgres ENCODING, MySQL CHARACTER SET
+    pub collation: Option<String>, // Postgres LC_COLLATE, MySQL/MSSQL COLLATE
+    pub template: Option<String>,  // Postgres
+}
+
+// Charset and collation names are spliced in bare on MySQL and MSSQL
+fn option_name(value: &str) -> Result<&str, String> {
+    if !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        Ok(value)
+    } else {
+        Err(format!("Invalid charset or collation name: {}", value))
+    }
+}
+
+pub fn create_database_sql(dialect: Dialect, spec: &DatabaseSpec) -> Result<Vec<String>, String> {
+    if spec.name.trim().is_empty() {
+        return Err("Database name is required".to_string());
+    }
+    let name = quote_ident(dialect, &spec.name);
+    match dialect {
+        Dialect::Postgres => {
+            let mut sql = format!("CREATE DATABASE {}", name);
+            if let Some(owner) = &spec.owner {
+                sql += &format!(" OWNER {}", quote_ident(dialect, owner));
+            }
+            if let Some(template) = &spec.template {
+                sql += &format!(" TEMPLATE {}", quote_ident(dialect, template));
+            }
+            if let Some(encoding) = &spec.encoding {
+                sql += &format!(" ENCODING {}", quote_text(Some(dialect), encoding));
+            }
+            if let Some(collation) = &spec.collation {
+                let collation = quote_text(Some(dialect), collation);
+                sql += &format!(" LC_COLLATE {} LC_CTYPE {}", collation, collation);
+            }
+            Ok(vec![sql])
+        }
+        Dialect::Mysql => {
+            if spec.owner.is_some() || spec.template.is_some() {
+                return Err("MySQL databases have no owner or template".to_string());
+            }
+            let mut sql = format!("CREATE DATABASE {}", name);
+            if let Some(encoding) = &spec.encoding {
+                sql += &format!(" CHARACTER SET {}", option_name(encoding)?);
+            }
+            if let Some(collation) = &spec.collation {
+                sql += &format!(" COLLATE {}", option_name(collation)?);
+            }
+            Ok(vec![sql])
+        }
+        Dialect::Mssql => {
+            if spec.encoding.is_some() || spec.template.is_some() {
+                return Err(
+                    "SQL Server sets the encoding through the collation and has no templates"
+                        .to_string(),
+                );
+            }
+            let mut create = format!("CREATE DATABASE {}", name);
+            if let Some(collation) = &spec.collation {
+                create += &format!(" COLLATE {}", option_name(collation)?);
+            }
+            let mut statements = vec![create];
+            if let Some(owner) = &spec.owner {
+                statements.push(format!(
+                    "ALTER AUTHORIZATION ON DATABASE::{} TO {}",
+                    name,
+                    quote_ident(dialect, owner)
+                ));
+            }
+            Ok(statements)
+        }
+    }
+}
+
+// `force` disconnects other sessions first instead of failing while they're connected
+pub fn drop_database_sql(dialect: Dialect, database: &str, force: bool) -> Vec<String> {
+    let name = quote_ident(dialect, database);
+    match (dialect, force) {
+        (Dialect::Postgres, true) => vec![format!("DROP DATABASE {} WITH (FORCE)", name)],
+        (Dialect::Mssql, true) => vec![
+            format!(
+                "ALTER DATABASE {} SET SINGLE_USER WITH ROLLBACK IMMEDIATE",
+                name
+            ),
+            format!("DROP DATABASE {}", name),
+        ],
+        _ => vec![format!("DROP DATABASE {}", name)],
+    }
+}
+
+pub fn create_schema_sql(
+    dialect: Dialect,
+    schema: &str,
+    owner: Option<&str>,
+) -> Result<Vec<String>, String> {
+    if schema.trim().is_empty() {
+        return Err("Schema name is required".to_string());
+    }
+    let mut sql = format!("CREATE SCHEMA {}", quote_ident(dialect, schema));
+    match (dialect, owner) {
+        // On MySQL a schema is a database, which has no owner
+        (Dialect::Mysql, Some(_)) => return Err("MySQL schemas have no owner".to_string()),
+        (_, Some(owner)) => sql += &format!(" AUTHORIZATION {}", quote_ident(dialect, owner)),
+        (_, None) => {}
+    }
+    Ok(vec![sql])
+}
+
+// `cascade` also drops the objects inside; SQL Server requires the schema to be empty
+pub fn drop_schema_sql(
+    dialect: Dialect,
+    schema: &str,
+    cascade: bool,
+) -> Result<Vec<String>, String> {
+    let name = quote_ident(dialect, schema);
+    match dialect {
+        Dialect::Postgres => Ok(vec![format!(
+            "DROP SCHEMA {} {}",
+            name,
+            if cascade { "CASCADE" } else { "RESTRICT" }
+        )]),
+        Dialect::Mysql => Ok(vec![format!("DROP SCHEMA {}", name)]),
+        Dialect::Mssql if cascade => Err(
+            "SQL Server can only drop empty schemas; transfer or drop its objects first"
+                .to_string(),
+        ),
+        Dialect::Mssql => Ok(vec![format!("DROP SCHEMA {}", name)]),
+    }
+}
+
+// Moves a table, view or other securable into another schema (SQL Server)
+pub fn transfer_schema_sql(
+    dialect: Dialect,
+    schema: &str,
+    object: &str,
+    target_schema: &str,
+) -> Result<Vec<String>, String> {
+    if dialect != Dialect::Mssql {
+        return Err("Schema transfer is only supported for SQL Server".to_string());
+    }
+    Ok(vec![format!(
+        "ALTER SCHEMA {} TRANSFER {}.{}",
+        quote_ident(dialect, target_schema),
+        quote_ident(dialect, schema),
+        quote_ident(dialect, object)
+    )])
+}
+
+// Drops must be confirmed by typing the exact object name
+pub fn check_confirmation(object: &str, confirmation: &str) -> Result<(), String> {
+    if confirmation != object {
+        return Err(format!("Type '{}' to confirm the drop", object));
+    }
+    Ok(())
+}
+
+pub fn truncate_table_sql(dialect: Dialect, table: &TableRef) -> Vec<String> {
+    vec![format!("TRUNCATE TABLE {}", table.qualified(dialect))]
+}
+
+// Runs the statements one by one; most of them can't run inside a transaction
+pub async fn run_statements(
+    client: &DbClient,
+    statements: &[String],
+    auditor: &Auditor,
+) -> Result<(), String> {
+    let options = QueryOptions::default();
+    for sql in statements {
+        let work = db::execute_query(client, sql.clone(), &options);
+        auditor.run(sql, work).await?;
+    }
+    Ok(())
+}
+
+pub fn dialect(client: &DbClient) -> Result<Dialect, String> {
+    client
+        .dialect()
+        .ok_or_else(|| "This operation is not supported for this database type".to_string())
+}
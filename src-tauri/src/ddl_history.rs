@@ -0,0 +1,782 @@
+// DDL run from the editor, with the definition each object had before and a
+// best-effort script that puts it back
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use std::path::Path;
+
+use crate::db::{self, DbClient, Dialect};
+use crate::edits::TableRef;
+use crate::quoting::{qualified, quote_ident, quote_text};
+use crate::scripts;
+use crate::sqltext::{self, DdlAction, DdlTarget, TableChange};
+use crate::storage;
+
+pub const DDL_HISTORY_FILE: &str = "ddl_history.json";
+
+// Oldest entries are dropped beyond this
+const MAX_DDL_ENTRIES: usize = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DdlHistoryEntry {
+    pub id: String,
+    pub connection: String,
+    pub sql: String,
+    pub executed_at: DateTime<Utc>,
+    pub object_type: String,
+    pub schema: Option<String>,
+    pub name: String,
+    // Statements that recreate the object as it was; None when it didn't
+    // exist or its definition couldn't be read
+    pub prior_definition: Option<Vec<String>>,
+    // Run in order to revert the statement; empty when no rollback is known
+    pub rollback: Vec<String>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct ColumnDefinition {
+    name: String,
+    data_type: String,
+    nullable: bool,
+    default: Option<String>,
+    definition: String, // everything after the name, as ADD COLUMN takes it
+}
+
+// An object's state read just before a DDL statement ran
+pub struct Captured {
+    sql: String,
+    dialect: Dialect,
+    target: DdlTarget,
+    definition: Option<Vec<String>>,
+    columns: Vec<ColumnDefinition>,
+}
+
+fn text(row: &tiberius::Row, i: usize) -> Option<String> {
+    row.try_get::<&str, _>(i).ok().flatten().map(str::to_string)
+}
+
+async fn mssql_rows(
+    client: &DbClient,
+    query: &str,
+    param: &str,
+) -> Result<Vec<tiberius::Row>, String> {
+    let DbClient::Mssql(pool) = client else {
+        return Ok(vec![]);
+    };
+    let mut conn = pool.get().await?;
+    let rows = conn
+        .query(query, &[&param])
+        .await
+        .map_err(|e| e.to_string())?
+        .into_first_result()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(rows)
+}
+
+async fn table_columns(client: &DbClient, object: &str) -> Result<Vec<ColumnDefinition>, String> {
+    match client {
+        DbClient::Postgres(pool) => {
+            let rows = sqlx::query(
+                "SELECT a.attname::text, format_type(a.atttypid, a.atttypmod), NOT a.attnotnull, \
+                 pg_get_expr(d.adbin, d.adrelid), a.attidentity::text FROM pg_attribute a \
+                 LEFT JOIN pg_attrdef d ON d.adrelid = a.attrelid AND d.adnum = a.attnum \
+                 WHERE a.attrelid = to_regclass($1) AND a.attnum > 0 AND NOT a.attisdropped \
+                 ORDER BY a.attnum",
+            )
+            .bind(object)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            Ok(rows
+                .iter()
+                .map(|r| {
+                    let data_type: String = r.get(1);
+                    let nullable: bool = r.get(2);
+                    let default: Option<String> = r.get(3);
+                    let identity: String = r.get(4);
+                    let mut definition = data_type.clone();
+                    definition += match identity.as_str() {
+                        "a" => " GENERATED ALWAYS AS IDENTITY",
+                        "d" => " GENERATED BY DEFAULT AS IDENTITY",
+                        _ => "",
+                    };
+                    if !nullable {
+                        definition += " NOT NULL";
+                    }
+                    if let Some(default) = &default {
+                        definition += &format!(" DEFAULT {}", default);
+                    }
+                    ColumnDefinition {
+                        name: r.get(0),
+                        data_type,
+                        nullable,
+                        default,
+                        definition,
+                    }
+                })
+                .collect())
+        }
+        DbClient::Mysql(pool) => {
+            // Column lines of SHOW CREATE TABLE are exact definitions
+            let create = match sqlx::query(&format!("SHOW CREATE TABLE {}", object))
+                .fetch_optional(pool)
+                .await
+            {
+                Ok(Some(row)) => row.get::<String, _>(1),
+                _ => return Ok(vec![]),
+            };
+            Ok(create
+                .lines()
+                .filter_map(|line| {
+                    let line = line.trim().trim_end_matches(',');
+                    let rest = line.strip_prefix('`')?;
+                    let end = rest.find('`')?;
+                    let definition = rest[end + 1..].trim().to_string();
+                    Some(ColumnDefinition {
+                        name: rest[..end].to_string(),
+                        data_type: definition.split_whitespace().next()?.to_string(),
+                        nullable: !definition.contains("NOT NULL"),
+                        default: None,
+                        definition,
+                    })
+                })
+                .collect())
+        }
+        DbClient::Mssql(_) => {
+            let query = "SELECT c.name, TYPE_NAME(c.user_type_id) + CASE \
+                 WHEN TYPE_NAME(c.user_type_id) IN ('varchar', 'char', 'varbinary', 'binary') \
+                   THEN '(' + CASE WHEN c.max_length = -1 THEN 'max' ELSE CAST(c.max_length AS varchar(10)) END + ')' \
+                 WHEN TYPE_NAME(c.user_type_id) IN ('nvarchar', 'nchar') \
+                   THEN '(' + CASE WHEN c.max_length = -1 THEN 'max' ELSE CAST(c.max_length / 2 AS varchar(10)) END + ')' \
+                 WHEN TYPE_NAME(c.user_type_id) IN ('decimal', 'numeric') \
+                   THEN '(' + CAST(c.precision AS varchar(10)) + ', ' + CAST(c.scale AS varchar(10)) + ')' \
+                 ELSE '' END, c.is_nullable, OBJECT_DEFINITION(c.default_object_id), \
+                 CASE WHEN c.is_identity = 1 THEN 'IDENTITY(' + CAST(IDENT_SEED(@P1) AS varchar(40)) \
+                   + ', ' + CAST(IDENT_INCR(@P1) AS varchar(40)) + ')' END \
+                 FROM sys.columns c WHERE c.object_id = OBJECT_ID(@P1) ORDER BY c.column_id";
+            let rows = mssql_rows(client, query, object).await?;
+            Ok(rows
+                .iter()
+                .map(|r| {
+                    let data_type = text(r, 1).unwrap_or_default();
+                    let nullable = r.try_get::<bool, _>(2).ok().flatten().unwrap_or(true);
+                    let default = text(r, 3);
+                    let mut definition = data_type.clone();
+                    if let Some(identity) = text(r, 4) {
+                        definition += &format!(" {}", identity);
+                    }
+                    definition += if nullable { " NULL" } else { " NOT NULL" };
+                    if let Some(default) = &default {
+                        definition += &format!(" DEFAULT {}", default);
+                    }
+                    ColumnDefinition {
+                        name: text(r, 0).unwrap_or_default(),
+                        data_type,
+                        nullable,
+                        default,
+                        definition,
+                    }
+                })
+                .collect())
+        }
+        _ => Ok(vec![]),
+    }
+}
+
+// Key, foreign key and check constraints of a SQL Server table as table
+// constraint lines, and its other indexes as CREATE INDEX statements
+async fn mssql_constraints(
+    client: &DbClient,
+    object: &str,
+) -> Result<(Vec<String>, Vec<String>), String> {
+    let q = |name: &str| quote_ident(Dialect::Mssql, name);
+    // Rows come one per column, in order; consecutive rows of the same name
+    // are the same constraint or index
+    fn grouped(rows: &[tiberius::Row]) -> Vec<(String, Vec<&tiberius::Row>)> {
+        let mut groups: Vec<(String, Vec<&tiberius::Row>)> = Vec::new();
+        for row in rows {
+            let name = text(row, 0).unwrap_or_default();
+            match groups.last_mut() {
+                Some((last, members)) if *last == name => members.push(row),
+                _ => groups.push((name, vec![row])),
+            }
+        }
+        groups
+    }
+    let descending = |r: &tiberius::Row, i: usize| {
+        let column = q(&text(r, 2).unwrap_or_default());
+        match r.try_get::<bool, _>(i).ok().flatten() {
+            Some(true) => format!("{} DESC", column),
+            _ => column,
+        }
+    };
+    let mut lines = Vec::new();
+
+    let keys = mssql_rows(
+        client,
+        "SELECT kc.name, kc.type, c.name, ic.is_descending_key FROM sys.key_constraints kc \
+         JOIN sys.index_columns ic ON ic.object_id = kc.parent_object_id AND ic.index_id = kc.unique_index_id \
+         JOIN sys.columns c ON c.object_id = ic.object_id AND c.column_id = ic.column_id \
+         WHERE kc.parent_object_id = OBJECT_ID(@P1) ORDER BY kc.type, kc.name, ic.key_ordinal",
+        object,
+    )
+    .await?;
+    for (name, rows) in grouped(&keys) {
+        let kind = match text(rows[0], 1).as_deref().map(str::trim) {
+            Some("PK") => "PRIMARY KEY",
+            _ => "UNIQUE",
+        };
+        let columns: Vec<String> = rows.iter().map(|r| descending(r, 3)).collect();
+        lines.push(format!(
+            "  CONSTRAINT {} {} ({})",
+            q(&name),
+            kind,
+            columns.join(", ")
+        ));
+    }
+
+    let foreign = mssql_rows(
+        client,
+        "SELECT fk.name, OBJECT_SCHEMA_NAME(fk.referenced_object_id), pc.name, \
+         OBJECT_NAME(fk.referenced_object_id), rc.name, \
+         fk.delete_referential_action_desc, fk.update_referential_action_desc \
+         FROM sys.foreign_keys fk \
+         JOIN sys.foreign_key_columns fkc ON fkc.constraint_object_id = fk.object_id \
+         JOIN sys.columns pc ON pc.object_id = fkc.parent_object_id AND pc.column_id = fkc.parent_column_id \
+         JOIN sys.columns rc ON rc.object_id = fkc.referenced_object_id AND rc.column_id = fkc.referenced_column_id \
+         WHERE fk.parent_object_id = OBJECT_ID(@P1) ORDER BY fk.name, fkc.constraint_column_id",
+        object,
+    )
+    .await?;
+    for (name, rows) in grouped(&foreign) {
+        let first = rows[0];
+        let columns: Vec<String> = rows
+            .iter()
+            .map(|r| q(&text(r, 2).unwrap_or_default()))
+            .collect();
+        let referenced: Vec<String> = rows
+            .iter()
+            .map(|r| q(&text(r, 4).unwrap_or_default()))
+            .collect();
+        let mut line = format!(
+            "  CONSTRAINT {} FOREIGN KEY ({}) REFERENCES {} ({})",
+            q(&name),
+            columns.join(", "),
+            qualified(
+                Dialect::Mssql,
+                text(first, 1).as_deref(),
+                &text(first, 3).unwrap_or_default()
+            ),
+            referenced.join(", ")
+        );
+        for (clause, i) in [("ON DELETE", 5), ("ON UPDATE", 6)] {
+            match text(first, i) {
+                Some(action) if action != "NO_ACTION" => {
+                    line += &format!(" {} {}", clause, action.replace('_', " "));
+                }
+                _ => {}
+            }
+        }
+        lines.push(line);
+    }
+
+    let checks = mssql_rows(
+        client,
+        "SELECT name, definition FROM sys.check_constraints \
+         WHERE parent_object_id = OBJECT_ID(@P1) ORDER BY name",
+        object,
+    )
+    .await?;
+    for r in &checks {
+        lines.push(format!(
+            "  CONSTRAINT {} CHECK {}",
+            q(&text(r, 0).unwrap_or_default()),
+            text(r, 1).unwrap_or_default()
+        ));
+    }
+
+    // Only row-store indexes are scripted; XML, spatial and columnstore
+    // indexes are left out
+    let indexes = mssql_rows(
+        client,
+        "SELECT i.name, i.type_desc, c.name, ic.is_descending_key, ic.is_included_column, i.is_unique \
+         FROM sys.indexes i \
+         JOIN sys.index_columns ic ON ic.object_id = i.object_id AND ic.index_id = i.index_id \
+         JOIN sys.columns c ON c.object_id = ic.object_id AND c.column_id = ic.column_id \
+         WHERE i.object_id = OBJECT_ID(@P1) AND i.is_primary_key = 0 AND i.is_unique_constraint = 0 \
+         AND i.type_desc IN ('CLUSTERED', 'NONCLUSTERED') \
+         ORDER BY i.name, ic.is_included_column, ic.key_ordinal",
+        object,
+    )
+    .await?;
+    let mut statements = Vec::new();
+    for (name, rows) in grouped(&indexes) {
+        let first = rows[0];
+        let included = |r: &tiberius::Row| r.try_get::<bool, _>(4).ok().flatten() == Some(true);
+        let keys: Vec<String> = rows
+            .iter()
+            .filter(|r| !included(r))
+            .map(|r| descending(r, 3))
+            .collect();
+        let include: Vec<String> = rows
+            .iter()
+            .filter(|r| included(r))
+            .map(|r| q(&text(r, 2).unwrap_or_default()))
+            .collect();
+        let unique = match first.try_get::<bool, _>(5).ok().flatten() {
+            Some(true) => "UNIQUE ",
+            _ => "",
+        };
+        let mut statement = format!(
+            "CREATE {}{} INDEX {} ON {} ({})",
+            unique,
+            text(first, 1).unwrap_or_default(),
+            q(&name),
+            object,
+            keys.join(", ")
+        );
+        if !include.is_empty() {
+            statement += &format!(" INCLUDE ({})", include.join(", "));
+        }
+        statements.push(statement);
+    }
+    Ok((lines, statements))
+}
+
+// CREATE TABLE (with constraints and indexes on Postgres and SQL Server) for
+// an existing table
+async fn table_definition(
+    client: &DbClient,
+    dialect: Dialect,
+    object: &str,
+    columns: &[ColumnDefinition],
+) -> Result<Option<Vec<String>>, String> {
+    if columns.is_empty() {
+        return Ok(None);
+    }
+    if let DbClient::Mysql(pool) = client {
+        let row = sqlx::query(&format!("SHOW CREATE TABLE {}", object))
+            .fetch_one(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        return Ok(Some(vec![row.get(1)]));
+    }
+    let mut lines: Vec<String> = columns
+        .iter()
+        .map(|c| format!("  {} {}", quote_ident(dialect, &c.name), c.definition))
+        .collect();
+    let mut extra = Vec::new();
+    if let DbClient::Postgres(pool) = client {
+        let constraints = sqlx::query(
+            "SELECT conname::text, pg_get_constraintdef(oid) FROM pg_constraint \
+             WHERE conrelid = to_regclass($1) ORDER BY contype DESC, conname",
+        )
+        .bind(object)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        for r in &constraints {
+            let name: String = r.get(0);
+            let definition: String = r.get(1);
+            lines.push(format!(
+                "  CONSTRAINT {} {}",
+                quote_ident(dialect, &name),
+                definition
+            ));
+        }
+        // Indexes that back a constraint come back with the constraint
+        let indexes = sqlx::query(
+            "SELECT pg_get_indexdef(i.indexrelid) FROM pg_index i \
+             WHERE i.indrelid = to_regclass($1) \
+             AND NOT EXISTS (SELECT 1 FROM pg_constraint c WHERE c.conindid = i.indexrelid)",
+        )
+        .bind(object)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        extra.extend(indexes.iter().map(|r| r.get::<String, _>(0)));
+    }
+    if let DbClient::Mssql(_) = client {
+        let (constraints, indexes) = mssql_constraints(client, object).await?;
+        lines.extend(constraints);
+        extra.extend(indexes);
+    }
+    let mut statements = vec![format!(
+        "CREATE TABLE {} (\n{}\n)",
+        object,
+        lines.join(",\n")
+    )];
+    statements.extend(extra);
+    Ok(Some(statements))
+}
+
+// MySQL's "doesn't exist" for tables and views, routines and triggers
+fn is_missing_object(error: &sqlx::Error) -> bool {
+    error
+        .as_database_error()
+        .and_then(|e| e.try_downcast_ref::<sqlx::mysql::MySqlDatabaseError>())
+        .is_some_and(|e| matches!(e.number(), 1146 | 1305 | 1360))
+}
+
+// Definition of a view, routine, trigger or index in `schema` (the current
+// one when None), as statements that recreate it: one per overload of a
+// Postgres function. None when it doesn't exist or isn't supported.
+pub async fn object_definition(
+    client: &DbClient,
+    kind: &str,
+    schema: Option<&str>,
+    name: &str,
+) -> Result<Option<Vec<String>>, String> {
+    let Some(dialect) = client.dialect() else {
+        return Ok(None);
+    };
+    let database = db::mysql_database(schema.map(str::to_string));
+    let schema = match dialect {
+        Dialect::Mysql => database.as_deref(),
+        _ => schema,
+    };
+    let object = qualified(dialect, schema, name);
+    let definitions = match client {
+        DbClient::Postgres(pool) => match kind {
+            "VIEW" | "MATERIALIZED VIEW" => sqlx::query_scalar(
+                "SELECT CASE c.relkind WHEN 'm' THEN 'CREATE MATERIALIZED VIEW ' \
+                 ELSE 'CREATE OR REPLACE VIEW ' END || $1 || E' AS\n' \
+                 || pg_get_viewdef(c.oid, true) \
+                 FROM pg_class c WHERE c.oid = to_regclass($1) AND c.relkind IN ('v', 'm')",
+            )
+            .bind(&object)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?,
+            "INDEX" => {
+                sqlx::query_scalar::<_, Option<String>>("SELECT pg_get_indexdef(to_regclass($1))")
+                    .bind(&object)
+                    .fetch_optional(pool)
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .flatten()
+                    .into_iter()
+                    .collect()
+            }
+            // Aggregates have no definition to show
+            "FUNCTION" | "PROCEDURE" => sqlx::query_scalar(
+                "SELECT pg_get_functiondef(p.oid) FROM pg_proc p \
+                 JOIN pg_namespace n ON n.oid = p.pronamespace \
+                 WHERE p.proname = $1 AND n.nspname = COALESCE($2, current_schema()) \
+                 AND p.prokind <> 'a' ORDER BY p.oid",
+            )
+            .bind(name)
+            .bind(schema)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?,
+            _ => return Ok(None),
+        },
+        DbClient::Mysql(pool) => {
+            let (show, column) = match kind {
+                "VIEW" => ("SHOW CREATE VIEW", 1),
+                "FUNCTION" => ("SHOW CREATE FUNCTION", 2),
+                "PROCEDURE" => ("SHOW CREATE PROCEDURE", 2),
+                "TRIGGER" => ("SHOW CREATE TRIGGER", 2),
+                _ => return Ok(None),
+            };
+            let row = match sqlx::query(&format!("{} {}", show, object))
+                .fetch_optional(pool)
+                .await
+            {
+                Ok(row) => row,
+                Err(e) if is_missing_object(&e) => None,
+                Err(e) => return Err(e.to_string()),
+            };
+            // NULL without the privileges to see a routine's body
+            row.map(|row| row.try_get::<Option<String>, _>(column))
+                .transpose()
+                .map_err(|e| e.to_string())?
+                .flatten()
+                .into_iter()
+                .collect()
+        }
+        DbClient::Mssql(_) => {
+            if !matches!(kind, "VIEW" | "FUNCTION" | "PROCEDURE" | "TRIGGER") {
+                return Ok(None);
+            }
+            let rows =
+                mssql_rows(client, "SELECT OBJECT_DEFINITION(OBJECT_ID(@P1))", &object).await?;
+            rows.first().and_then(|r| text(r, 0)).into_iter().collect()
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(definitions).filter(|d: &Vec<String>| !d.is_empty()))
+}
+
+// The statements that recreate an existing table, for copying its definition
+pub async fn table_ddl(client: &DbClient, table: &TableRef) -> Result<Vec<String>, String> {
+    let dialect = client
+        .dialect()
+        .ok_or("Table DDL is not available for this database type")?;
+    let object = qualified(dialect, table.schema.as_deref(), &table.table);
+    let columns = table_columns(client, &object).await?;
+    table_definition(client, dialect, &object, &columns)
+        .await?
+        .ok_or_else(|| format!("Table {} not found", table.table))
+}
+
+async fn capture_one(client: &DbClient, dialect: Dialect, sql: &str) -> Option<Captured> {
+    let target = sqltext::ddl_target(sql, dialect)?;
+    let object = qualified(dialect, target.schema.as_deref(), &target.name);
+    let read = async {
+        if target.object == "TABLE" {
+            let columns = table_columns(client, &object).await?;
+            let definition = table_definition(client, dialect, &object, &columns).await?;
+            Ok::<_, String>((definition, columns))
+        } else {
+            let schema = target.schema.as_deref();
+            let definition = object_definition(client, &target.object, schema, &target.name);
+            Ok((definition.await?, vec![]))
+        }
+    };
+    let (definition, columns) = match read.await {
+        Ok(read) => read,
+        Err(e) => {
+            log::warn!("Failed to read the definition of {}: {}", object, e);
+            (None, vec![])
+        }
+    };
+    Some(Captured {
+        sql: sql.to_string(),
+        dialect,
+        target,
+        definition,
+        columns,
+    })
+}
+
+// Reads the current definition of every object the DDL statements in `sql`
+// touch; call before running it. Never fails: what can't be read is left out.
+pub async fn capture(client: &DbClient, sql: &str) -> Vec<Captured> {
+    let Some(dialect) = client.dialect() else {
+        return vec![];
+    };
+    if !sqltext::is_ddl(sql) {
+        return vec![];
+    }
+    let statements = scripts::split_statements(dialect, sql)
+        .map(|statements| statements.into_iter().map(|s| s.sql).collect())
+        .unwrap_or_else(|_| vec![sql.to_string()]);
+    let mut captured = Vec::new();
+    for statement in statements {
+        if let Some(one) = capture_one(client, dialect, &statement).await {
+            captured.push(one);
+        }
+    }
+    captured
+}
+
+fn drop_statement(dialect: Dialect, target: &DdlTarget, object: &str) -> String {
+    match (&target.table, dialect) {
+        // Index names are per table on MySQL and SQL Server
+        (Some((schema, table)), Dialect::Mysql | Dialect::Mssql) if target.object == "INDEX" => {
+            format!(
+                "DROP INDEX {} ON {}",
+                quote_ident(dialect, &target.name),
+                qualified(dialect, schema.as_deref(), table)
+            )
+        }
+        _ => format!("DROP {} {}", target.object, object),
+    }
+}
+
+fn alter_column_rollback(dialect: Dialect, table: &str, column: &ColumnDefinition) -> Vec<String> {
+    let name = quote_ident(dialect, &column.name);
+    match dialect {
+        Dialect::Postgres => vec![
+            format!(
+                "ALTER TABLE {} ALTER COLUMN {} TYPE {}",
+                table, name, column.data_type
+            ),
+            format!(
+                "ALTER TABLE {} ALTER COLUMN {} {} NOT NULL",
+                table,
+                name,
+                if column.nullable { "DROP" } else { "SET" }
+            ),
+            match &column.default {
+                Some(default) => format!(
+                    "ALTER TABLE {} ALTER COLUMN {} SET DEFAULT {}",
+                    table, name, default
+                ),
+                None => format!("ALTER TABLE {} ALTER COLUMN {} DROP DEFAULT", table, name),
+            },
+        ],
+        Dialect::Mysql => vec![format!(
+            "ALTER TABLE {} MODIFY COLUMN {} {}",
+            table, name, column.definition
+        )],
+        Dialect::Mssql => vec![format!(
+            "ALTER TABLE {} ALTER COLUMN {} {} {}",
+            table,
+            name,
+            column.data_type,
+            if column.nullable { "NULL" } else { "NOT NULL" }
+        )],
+    }
+}
+
+// The rollback for an ALTER TABLE, and a note on what it can't restore
+fn table_change_rollback(
+    captured: &Captured,
+    object: &str,
+    change: &TableChange,
+) -> (Vec<String>, Option<String>) {
+    let dialect = captured.dialect;
+    let column = |name: &str| {
+        captured
+            .columns
+            .iter()
+            .find(|c| c.name.eq_ignore_ascii_case(name))
+    };
+    match change {
+        TableChange::AddColumn { column } => (
+            vec![format!(
+                "ALTER TABLE {} DROP COLUMN {}",
+                object,
+                quote_ident(dialect, column)
+            )],
+            None,
+        ),
+        TableChange::DropColumn { column: name } => match column(name) {
+            Some(column) => (
+                vec![format!(
+                    "ALTER TABLE {} ADD {} {}",
+                    object,
+                    quote_ident(dialect, &column.name),
+                    column.definition
+                )],
+                Some("The column comes back empty; its data isn't restored".to_string()),
+            ),
+            None => (
+                vec![],
+                Some(format!("The definition of {} wasn't captured", name)),
+            ),
+        },
+        TableChange::AlterColumn { column: name } => match column(name) {
+            Some(column) => (alter_column_rollback(dialect, object, column), None),
+            None => (
+                vec![],
+                Some(format!("The definition of {} wasn't captured", name)),
+            ),
+        },
+        TableChange::RenameTo { name } => {
+            let renamed = qualified(dialect, captured.target.schema.as_deref(), name);
+            let statement = match dialect {
+                Dialect::Mssql => format!(
+                    "EXEC sp_rename {}, {}",
+                    quote_text(Some(dialect), &renamed),
+                    quote_text(Some(dialect), &captured.target.name)
+                ),
+                _ => format!(
+                    "ALTER TABLE {} RENAME TO {}",
+                    renamed,
+                    quote_ident(dialect, &captured.target.name)
+                ),
+            };
+            (vec![statement], None)
+        }
+        TableChange::RenameColumn { from, to } => (
+            vec![format!(
+                "ALTER TABLE {} RENAME COLUMN {} TO {}",
+                object,
+                quote_ident(dialect, to),
+                quote_ident(dialect, from)
+            )],
+            None,
+        ),
+    }
+}
+
+fn rollback(captured: &Captured) -> (Vec<String>, Option<String>) {
+    let dialect = captured.dialect;
+    let target = &captured.target;
+    let object = qualified(dialect, target.schema.as_deref(), &target.name);
+    let prior = captured.definition.clone();
+    match (target.action, prior) {
+        (DdlAction::Create, _) | (DdlAction::CreateOrReplace, None) => {
+            (vec![drop_statement(dialect, target, &object)], None)
+        }
+        (DdlAction::Truncate, _) => (
+            vec![],
+            Some("Rows removed by TRUNCATE can't be restored".to_string()),
+        ),
+        (DdlAction::Drop, Some(prior)) => {
+            let note = (target.object == "TABLE")
+                .then(|| "The table is recreated empty; its rows aren't restored".to_string());
+            (prior, note)
+        }
+        (DdlAction::Alter, _) if target.object == "TABLE" => match &target.change {
+            Some(change) => table_change_rollback(captured, &object, change),
+            None => (
+                vec![],
+                Some("No automatic rollback for this change; see the prior definition".to_string()),
+            ),
+        },
+        // Postgres definitions already replace in place
+        (DdlAction::Alter | DdlAction::CreateOrReplace, Some(prior))
+            if dialect == Dialect::Postgres && target.object != "INDEX" =>
+        {
+            (prior, None)
+        }
+        (DdlAction::Alter | DdlAction::CreateOrReplace, Some(prior)) => {
+            let mut statements = vec![drop_statement(dialect, target, &object)];
+            statements.extend(prior);
+            (statements, None)
+        }
+        (_, None) => (
+            vec![],
+            Some("The prior definition couldn't be read".to_string()),
+        ),
+    }
+}
+
+// Stores one entry per captured statement once the DDL has succeeded
+pub fn record(path: &Path, connection: &str, captured: Vec<Captured>) -> Result<(), String> {
+    if captured.is_empty() {
+        return Ok(());
+    }
+    let mut entries: Vec<DdlHistoryEntry> = storage::read_json(path)?;
+    let executed_at = Utc::now();
+    for captured in captured {
+        let (rollback, note) = rollback(&captured);
+        entries.push(DdlHistoryEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            connection: connection.to_string(),
+            sql: captured.sql,
+            executed_at,
+            object_type: captured.target.object,
+            schema: captured.target.schema,
+            name: captured.target.name,
+            prior_definition: captured.definition,
+            rollback,
+            note,
+        });
+    }
+    let excess = entries.len().saturating_sub(MAX_DDL_ENTRIES);
+    entries.drain(..excess);
+    storage::write_json(path, &entries)
+}
+
+// Newest first, optionally for one connection
+pub fn list(
+    path: &Path,
+    connection: Option<&str>,
+    limit: Option<usize>,
+) -> Result<Vec<DdlHistoryEntry>, String> {
+    let entries: Vec<DdlHistoryEntry> = storage::read_json(path)?;
+    Ok(entries
+        .into_iter()
+        .rev()
+        .filter(|e| connection.map_or(true, |c| e.connection == c))
+        .take(limit.unwrap_or(crate::history::DEFAULT_HISTORY_LIMIT))
+        .collect())
+}
@@ -0,0 +1,1064 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::db::{DbClient, Dialect};
+use crate::mssql_pool::MssqlPool;
+use crate::quoting;
+use crate::storage;
+
+pub const STAT_SNAPSHOTS_FILE: &str = "stat_snapshots.json";
+
+fn postgres_pool(client: &DbClient) -> Result<&PgPool, String> {
+    match client {
+        DbClient::Postgres(pool) => Ok(pool),
+        _ => Err("This operation is only supported for PostgreSQL".to_string()),
+    }
+}
+
+// Cumulative pg_stat_statements counters for one statement
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatementStat {
+    pub userid: i64,
+    pub queryid: i64,
+    pub query: String,
+    pub calls: i64,
+    pub total_time_ms: f64,
+    pub rows: i64,
+    pub shared_blks_hit: i64,
+    pub shared_blks_read: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatSnapshot {
+    pub id: String,
+    pub connection: String,
+    pub label: Option<String>,
+    pub taken_at: DateTime<Utc>,
+    pub statements: Vec<StatementStat>,
+}
+
+// What list_stat_snapshots returns; the statements themselves can be large
+#[derive(Debug, Serialize)]
+pub struct StatSnapshotSummary {
+    pub id: String,
+    pub connection: String,
+    pub label: Option<String>,
+    pub taken_at: DateTime<Utc>,
+    pub statements: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatementDelta {
+    pub queryid: i64,
+    pub query: String,
+    pub calls: i64,
+    pub total_time_ms: f64,
+    pub mean_time_ms: f64,
+    pub rows: i64,
+    pub shared_blks_hit: i64,
+    pub shared_blks_read: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatDiff {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub elapsed_seconds: i64,
+    pub total_calls: i64,
+    pub total_time_ms: f64,
+    pub statements: Vec<StatementDelta>,
+}
+
+async fn ensure_pg_stat_statements(pool: &PgPool) -> Result<(), String> {
+    let installed: bool = sqlx::query_scalar(
+        "SELECT EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'pg_stat_statements')",
+    )
+    .fetch_one(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    if !installed {
+        return Err(
+            "pg_stat_statements is not installed; run CREATE EXTENSION pg_stat_statements"
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+// Current counters for statements run against the connected database
+pub async fn read_statement_stats(client: &DbClient) -> Result<Vec<StatementStat>, String> {
+    let pool = postgres_pool(client)?;
+    ensure_pg_stat_statements(pool).await?;
+    let version: i32 = sqlx::query_scalar("SELECT current_setting('server_version_num')::int")
+        .fetch_one(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    // total_time was split into plan and exec time in 13
+    let total_time = if version >= 130000 {
+        "total_exec_time"
+    } else {
+        "total_time"
+    };
+    let sql = format!(
+        "SELECT userid::bigint, COALESCE(queryid, 0)::bigint, COALESCE(query, ''), calls, {}, rows, \
+         shared_blks_hit, shared_blks_read \
+         FROM pg_stat_statements \
+         WHERE dbid = (SELECT oid FROM pg_database WHERE datname = current_database())",
+        total_time
+    );
+    let rows = sqlx::query(&sql)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(rows
+        .iter()
+        .map(|r| StatementStat {
+            userid: r.get(0),
+            queryid: r.get(1),
+            query: r.get(2),
+            calls: r.get(3),
+            total_time_ms: r.get(4),
+            rows: r.get(5),
+            shared_blks_hit: r.get(6),
+            shared_blks_read: r.get(7),
+        })
+        .collect())
+}
+
+pub async fn reset_statement_stats(client: &DbClient) -> Result<(), String> {
+    let pool = postgres_pool(client)?;
+    ensure_pg_stat_statements(pool).await?;
+    sqlx::query("SELECT pg_stat_statements_reset()")
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn load_snapshots(path: &Path) -> Result<Vec<StatSnapshot>, String> {
+    storage::read_json(path)
+}
+
+pub async fn take_snapshot(
+    path: &Path,
+    client: &DbClient,
+    connection: &str,
+    label: Option<String>,
+) -> Result<StatSnapshotSummary, String> {
+    let statements = read_statement_stats(client).await?;
+    let snapshot = StatSnapshot {
+        id: uuid::Uuid::new_v4().to_string(),
+        connection: connection.to_string(),
+        label: label.filter(|l| !l.trim().is_empty()),
+        taken_at: Utc::now(),
+        statements,
+    };
+    let summary = summarize(&snapshot);
+    let mut snapshots = load_snapshots(path)?;
+    snapshots.push(snapshot);
+    storage::write_json(path, &snapshots)?;
+    Ok(summary)
+}
+
+fn summarize(snapshot: &StatSnapshot) -> StatSnapshotSummary {
+    StatSnapshotSummary {
+        id: snapshot.id.clone(),
+        connection: snapshot.connection.clone(),
+        label: snapshot.label.clone(),
+        taken_at: snapshot.taken_at,
+        statements: snapshot.statements.len(),
+    }
+}
+
+pub fn list_snapshots(
+    path: &Path,
+    connection: Option<&str>,
+) -> Result<Vec<StatSnapshotSummary>, String> {
+    let mut summaries: Vec<StatSnapshotSummary> = load_snapshots(path)?
+        .iter()
+        .filter(|s| connection.map_or(true, |c| s.connection == c))
+        .map(summarize)
+        .collect();
+    summaries.sort_by_key(|s| std::cmp::Reverse(s.taken_at));
+    Ok(summaries)
+}
+
+pub fn delete_snapshot(path: &Path, id: &str) -> Result<(), String> {
+    let mut snapshots = load_snapshots(path)?;
+    let before = snapshots.len();
+    snapshots.retain(|s| s.id != id);
+    if snapshots.len() == before {
+        return Err("Snapshot not found".to_string());
+    }
+    storage::write_json(path, &snapshots)
+}
+
+// Per-statement activity between two snapshots, busiest first. The snapshots
+// may be given in either order.
+pub fn diff_snapshots(path: &Path, from_id: &str, to_id: &str) -> Result<StatDiff, String> {
+    let snapshots = load_snapshots(path)?;
+    let find = |id: &str| {
+        snapshots
+            .iter()
+            .find(|s| s.id == id)
+            .ok_or_else(|| format!("Snapshot {} not found", id))
+    };
+    let (mut from, mut to) = (find(from_id)?, find(to_id)?);
+    if from.connection != to.connection {
+        return Err("Snapshots belong to different connections".to_string());
+    }
+    if from.taken_at > to.taken_at {
+        std::mem::swap(&mut from, &mut to);
+    }
+
+    let before: HashMap<(i64, i64), &StatementStat> = from
+        .statements
+        .iter()
+        .map(|s| ((s.userid, s.queryid), s))
+        .collect();
+    let mut statements: Vec<StatementDelta> = to
+        .statements
+        .iter()
+        .filter_map(|after| {
+            // A statement whose counters went backwards was reset or evicted
+            // in between, so everything it has now happened inside the window
+            let base = before
+                .get(&(after.userid, after.queryid))
+                .filter(|b| b.calls <= after.calls);
+            let calls = after.calls - base.map_or(0, |b| b.calls);
+            if calls == 0 {
+                return None;
+            }
+            let total_time_ms = after.total_time_ms - base.map_or(0.0, |b| b.total_time_ms);
+            Some(StatementDelta {
+                queryid: after.queryid,
+                query: after.query.clone(),
+                calls,
+                total_time_ms,
+                mean_time_ms: total_time_ms / calls as f64,
+                rows: after.rows - base.map_or(0, |b| b.rows),
+                shared_blks_hit: after.shared_blks_hit - base.map_or(0, |b| b.shared_blks_hit),
+                shared_blks_read: after.shared_blks_read - base.map_or(0, |b| b.shared_blks_read),
+            })
+        })
+        .collect();
+    statements.sort_by(|a, b| b.total_time_ms.total_cmp(&a.total_time_ms));
+
+    Ok(StatDiff {
+        from: from.taken_at,
+        to: to.taken_at,
+        elapsed_seconds: (to.taken_at - from.taken_at).num_seconds(),
+        total_calls: statements.iter().map(|s| s.calls).sum(),
+        total_time_ms: statements.iter().map(|s| s.total_time_ms).sum(),
+        statements,
+    })
+}
+
+// Catalog-based bloat estimates (after the widely used ioguix queries). They
+// rely on planner statistics, so tables that were never ANALYZEd come out wrong.
+const TABLE_BLOAT_SQL: &str = "
+SELECT schemaname, tblname, bs * tblpages AS real_size,
+  CASE WHEN tblpages - est_tblpages_ff > 0 THEN (tblpages - est_tblpages_ff) * bs ELSE 0 END AS bloat_size,
+  CASE WHEN tblpages > 0 AND tblpages - est_tblpages_ff > 0
+    THEN 100 * (tblpages - est_tblpages_ff) / tblpages::float ELSE 0 END AS bloat_pct,
+  fillfactor, is_na
+FROM (
+  SELECT ceil(reltuples / ((bs - page_hdr) * fillfactor / (tpl_size * 100))) + ceil(toasttuples / 4) AS est_tblpages_ff,
+    tblpages, fillfactor, bs, schemaname, tblname, is_na
+  FROM (
+    SELECT (4 + tpl_hdr_size + tpl_data_size + (2 * ma)
+        - CASE WHEN tpl_hdr_size % ma = 0 THEN ma ELSE tpl_hdr_size % ma END
+        - CASE WHEN ceil(tpl_data_size)::int % ma = 0 THEN ma ELSE ceil(tpl_data_size)::int % ma END
+      ) AS tpl_size,
+      (heappages + toastpages) AS tblpages, reltuples, toasttuples, bs, page_hdr,
+      schemaname, tblname, fillfactor, is_na
+    FROM (
+      SELECT tbl.oid AS tblid, ns.nspname AS schemaname, tbl.relname AS tblname, tbl.reltuples,
+        tbl.relpages AS heappages, coalesce(toast.relpages, 0) AS toastpages,
+        coalesce(toast.reltuples, 0) AS toasttuples,
+        coalesce(substring(array_to_string(tbl.reloptions, ' ') FROM 'fillfactor=([0-9]+)')::smallint, 100) AS fillfactor,
+        current_setting('block_size')::numeric AS bs,
+        CASE WHEN version() ~ 'mingw32' OR version() ~ '64-bit|x86_64|ppc64|ia64|amd64' THEN 8 ELSE 4 END AS ma,
+        24 AS page_hdr,
+        23 + CASE WHEN max(coalesce(s.null_frac, 0)) > 0 THEN (7 + count(s.attname)) / 8 ELSE 0::int END
+          + CASE WHEN bool_or(att.attname = 'oid' AND att.attnum < 0) THEN 4 ELSE 0 END AS tpl_hdr_size,
+        sum((1 - coalesce(s.null_frac, 0)) * coalesce(s.avg_width, 0)) AS tpl_data_size,
+        bool_or(att.atttypid = 'pg_catalog.name'::regtype)
+          OR sum(CASE WHEN att.attnum > 0 THEN 1 ELSE 0 END) <> count(s.attname) AS is_na
+      FROM pg_attribute AS att
+        JOIN pg_class AS tbl ON att.attrelid = tbl.oid
+        JOIN pg_namespace AS ns ON ns.oid = tbl.relnamespace
+        LEFT JOIN pg_stats AS s ON s.schemaname = ns.nspname
+          AND s.tablename = tbl.relname AND s.inherited = false AND s.attname = att.attname
+        LEFT JOIN pg_class AS toast ON tbl.reltoastrelid = toast.oid
+      WHERE NOT att.attisdropped AND tbl.relkind IN ('r', 'm')
+      GROUP BY 1, 2, 3, 4, 5, 6, 7, 8, 9, 10
+    ) AS s
+  ) AS s2
+) AS s3";
+
+// B-tree indexes only; other access methods have no comparable estimate
+const INDEX_BLOAT_SQL: &str = "
+SELECT nspname AS schemaname, tblname, idxname, bs * relpages::bigint AS real_size,
+  CASE WHEN relpages > est_pages_ff THEN bs * (relpages - est_pages_ff) ELSE 0 END AS bloat_size,
+  CASE WHEN relpages > est_pages_ff THEN 100 * (relpages - est_pages_ff)::float / relpages ELSE 0 END AS bloat_pct,
+  fillfactor, is_na
+FROM (
+  SELECT coalesce(1 + ceil(reltuples / floor((bs - pageopqdata - pagehdr) * fillfactor / (100 * (4 + nulldatahdrwidth)::float))), 0) AS est_pages_ff,
+    bs, nspname, tblname, idxname, relpages, fillfactor, is_na
+  FROM (
+    SELECT maxalign, bs, nspname, tblname, idxname, reltuples, relpages, fillfactor,
+      (index_tuple_hdr_bm + maxalign
+        - CASE WHEN index_tuple_hdr_bm % maxalign = 0 THEN maxalign ELSE index_tuple_hdr_bm % maxalign END
+        + nulldatawidth + maxalign
+        - CASE WHEN nulldatawidth = 0 THEN 0
+            WHEN nulldatawidth::integer % maxalign = 0 THEN maxalign
+            ELSE nulldatawidth::integer % maxalign END
+      )::numeric AS nulldatahdrwidth,
+      pagehdr, pageopqdata, is_na
+    FROM (
+      SELECT n.nspname, i.tblname, i.idxname, i.reltuples, i.relpages, i.idxoid, i.fillfactor,
+        current_setting('block_size')::numeric AS bs,
+        CASE WHEN version() ~ 'mingw32' OR version() ~ '64-bit|x86_64|ppc64|ia64|amd64' THEN 8 ELSE 4 END AS maxalign,
+        24 AS pagehdr,
+        16 AS pageopqdata,
+        CASE WHEN max(coalesce(s.null_frac, 0)) = 0 THEN 8 ELSE 8 + ((32 + 8 - 1) / 8) END AS index_tuple_hdr_bm,
+        sum((1 - coalesce(s.null_frac, 0)) * coalesce(s.avg_width, 1024)) AS nulldatawidth,
+        max(CASE WHEN i.atttypid = 'pg_catalog.name'::regtype THEN 1 ELSE 0 END) > 0 AS is_na
+      FROM (
+        SELECT ct.relname AS tblname, ct.relnamespace, ic.idxname, ic.attpos, ic.indkey,
+          ic.indkey[ic.attpos], ic.reltuples, ic.relpages, ic.tbloid, ic.idxoid, ic.fillfactor,
+          coalesce(a1.attnum, a2.attnum) AS attnum, coalesce(a1.attname, a2.attname) AS attname,
+          coalesce(a1.atttypid, a2.atttypid) AS atttypid,
+          CASE WHEN a1.attnum IS NULL THEN ic.idxname ELSE ct.relname END AS attrelname
+        FROM (
+          SELECT idxname, reltuples, relpages, tbloid, idxoid, fillfactor, indkey,
+            pg_catalog.generate_series(1, indnatts) AS attpos
+          FROM (
+            SELECT ci.relname AS idxname, ci.reltuples, ci.relpages, i.indrelid AS tbloid,
+              i.indexrelid AS idxoid,
+              coalesce(substring(array_to_string(ci.reloptions, ' ') FROM 'fillfactor=([0-9]+)')::smallint, 90) AS fillfactor,
+              i.indnatts,
+              pg_catalog.string_to_array(pg_catalog.textin(pg_catalog.int2vectorout(i.indkey)), ' ')::int[] AS indkey
+            FROM pg_catalog.pg_index i
+            JOIN pg_catalog.pg_class ci ON ci.oid = i.indexrelid
+            WHERE ci.relam = (SELECT oid FROM pg_am WHERE amname = 'btree') AND ci.relpages > 0
+          ) AS idx_data
+        ) AS ic
+        JOIN pg_catalog.pg_class ct ON ct.oid = ic.tbloid
+        LEFT JOIN pg_catalog.pg_attribute a1 ON ic.indkey[ic.attpos] <> 0
+          AND a1.attrelid = ic.tbloid AND a1.attnum = ic.indkey[ic.attpos]
+        LEFT JOIN pg_catalog.pg_attribute a2 ON ic.indkey[ic.attpos] = 0
+          AND a2.attrelid = ic.idxoid AND a2.attnum = ic.attpos
+      ) i
+      JOIN pg_catalog.pg_namespace n ON n.oid = i.relnamespace
+      JOIN pg_catalog.pg_stats s ON s.schemaname = n.nspname
+        AND s.tablename = i.attrelname AND s.attname = i.attname
+      GROUP BY 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11
+    ) AS rows_data_stats
+  ) AS rows_hdr_pdg_stats
+) AS relation_stats";
+
+#[derive(Debug, Serialize)]
+pub struct BloatEntry {
+    pub schema: String,
+    pub table: String,
+    pub index: Option<String>, // None for the table itself
+    pub real_size: i64,
+    pub bloat_size: i64,
+    pub bloat_pct: f64,
+    pub fillfactor: i32,
+    // The estimate is known to be unreliable (missing stats, `name` columns, ...)
+    pub estimate_unreliable: bool,
+    // VACUUM FULL or REINDEX statement when the bloat crosses the thresholds
+    pub recommendation: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BloatReport {
+    pub tables: Vec<BloatEntry>,
+    pub indexes: Vec<BloatEntry>,
+}
+
+// Thresholds below which bloat isn't worth a rewrite
+const BLOAT_PCT_THRESHOLD: f64 = 30.0;
+const BLOAT_SIZE_THRESHOLD: i64 = 10 * 1024 * 1024;
+
+pub async fn bloat_report(
+    client: &DbClient,
+    schema: Option<String>,
+) -> Result<BloatReport, String> {
+    let pool = postgres_pool(client)?;
+    let filter = "WHERE ($1::text IS NULL OR schemaname = $1) \
+                  AND schemaname NOT IN ('pg_catalog', 'information_schema') \
+                  AND schemaname NOT LIKE 'pg_toast%' \
+                  ORDER BY bloat_size DESC";
+
+    let table_sql = format!(
+        "SELECT schemaname::text, tblname::text, NULL::text, real_size::bigint, bloat_size::bigint, \
+         bloat_pct::float8, fillfactor::int, is_na FROM ({}) b {}",
+        TABLE_BLOAT_SQL, filter
+    );
+    let index_sql = format!(
+        "SELECT schemaname::text, tblname::text, idxname::text, real_size::bigint, bloat_size::bigint, \
+         bloat_pct::float8, fillfactor::int, is_na FROM ({}) b {}",
+        INDEX_BLOAT_SQL, filter
+    );
+
+    let mut entries = Vec::new();
+    for sql in [table_sql, index_sql] {
+        let rows = sqlx::query(&sql)
+            .bind(schema.as_deref())
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        entries.push(rows.iter().map(bloat_entry).collect::<Vec<_>>());
+    }
+    let indexes = entries.pop().unwrap_or_default();
+    let tables = entries.pop().unwrap_or_default();
+    Ok(BloatReport { tables, indexes })
+}
+
+fn bloat_entry(row: &sqlx::postgres::PgRow) -> BloatEntry {
+    let schema: String = row.get(0);
+    let table: String = row.get(1);
+    let index: Option<String> = row.get(2);
+    let bloat_size: i64 = row.get(4);
+    let bloat_pct: f64 = row.get(5);
+    let recommendation = (bloat_pct >= BLOAT_PCT_THRESHOLD && bloat_size >= BLOAT_SIZE_THRESHOLD)
+        .then(|| match &index {
+            Some(index) => format!(
+                "REINDEX INDEX {}",
+                quoting::qualified(Dialect::Postgres, Some(&schema), index)
+            ),
+            None => format!(
+                "VACUUM FULL {}",
+                quoting::qualified(Dialect::Postgres, Some(&schema), &table)
+            ),
+        });
+    BloatEntry {
+        schema,
+        table,
+        index,
+        real_size: row.get(3),
+        bloat_size,
+        bloat_pct,
+        fillfactor: row.get(6),
+        estimate_unreliable: row.get::<Option<bool>, _>(7).unwrap_or(true),
+        recommendation,
+    }
+}
+
+fn mssql_pool(client: &DbClient) -> Result<&Arc<MssqlPool>, String> {
+    match client {
+        DbClient::Mssql(pool) => Ok(pool),
+        _ => Err("This operation is only supported for SQL Server".to_string()),
+    }
+}
+
+fn mssql_text(row: &tiberius::Row, i: usize) -> Option<String> {
+    row.try_get::<&str, _>(i)
+        .ok()
+        .flatten()
+        .map(|s| s.to_string())
+}
+
+fn mssql_i64(row: &tiberius::Row, i: usize) -> i64 {
+    row.try_get::<i64, _>(i).ok().flatten().unwrap_or(0)
+}
+
+#[derive(Debug, Serialize)]
+pub struct WaitStat {
+    pub wait_type: String,
+    pub waiting_tasks: i64,
+    pub wait_time_ms: i64,
+    pub signal_wait_ms: i64, // time spent waiting for CPU after the resource was available
+    pub resource_wait_ms: i64,
+    pub avg_wait_ms: f64,
+    pub pct: f64, // share of all non-idle wait time
+}
+
+// Idle and background waits that say nothing about workload performance
+const BENIGN_WAITS: &str = "'BROKER_EVENTHANDLER', 'BROKER_RECEIVE_WAITFOR', 'BROKER_TASK_STOP', \
+    'BROKER_TO_FLUSH', 'BROKER_TRANSMITTER', 'CHECKPOINT_QUEUE', 'CHKPT', 'CLR_AUTO_EVENT', \
+    'CLR_MANUAL_EVENT', 'CLR_SEMAPHORE', 'DBMIRROR_DBM_EVENT', 'DBMIRROR_EVENTS_QUEUE', \
+    'DBMIRROR_WORKER_QUEUE', 'DBMIRRORING_CMD', 'DIRTY_PAGE_POLL', 'DISPATCHER_QUEUE_SEMAPHORE', \
+    'EXECSYNC', 'FSAGENT', 'FT_IFTS_SCHEDULER_IDLE_WAIT', 'FT_IFTSHC_MUTEX', \
+    'HADR_CLUSAPI_CALL', 'HADR_FILESTREAM_IOMGR_IOCOMPLETION', 'HADR_LOGCAPTURE_WAIT', \
+    'HADR_NOTIFICATION_DEQUEUE', 'HADR_TIMER_TASK', 'HADR_WORK_QUEUE', 'KSOURCE_WAKEUP', \
+    'LAZYWRITER_SLEEP', 'LOGMGR_QUEUE', 'MEMORY_ALLOCATION_EXT', 'ONDEMAND_TASK_QUEUE', \
+    'PARALLEL_REDO_DRAIN_WORKER', 'PARALLEL_REDO_LOG_CACHE', 'PARALLEL_REDO_TRAN_LIST', \
+    'PARALLEL_REDO_WORKER_SYNC', 'PARALLEL_REDO_WORKER_WAIT_WORK', 'PREEMPTIVE_XE_GETTARGETSTATE', \
+    'PWAIT_ALL_COMPONENTS_INITIALIZED', 'PWAIT_DIRECTLOGCONSUMER_GETNEXT', \
+    'QDS_PERSIST_TASK_MAIN_LOOP_SLEEP', 'QDS_ASYNC_QUEUE', \
+    'QDS_CLEANUP_STALE_QUERIES_TASK_MAIN_LOOP_SLEEP', 'QDS_SHUTDOWN_QUEUE', \
+    'REDO_THREAD_PENDING_WORK', 'REQUEST_FOR_DEADLOCK_SEARCH', 'RESOURCE_QUEUE', \
+    'SERVER_IDLE_CHECK', 'SLEEP_BPOOL_FLUSH', 'SLEEP_DBSTARTUP', 'SLEEP_DCOMSTARTUP', \
+    'SLEEP_MASTERDBREADY', 'SLEEP_MASTERMDREADY', 'SLEEP_MASTERUPGRADED', 'SLEEP_MSDBSTARTUP', \
+    'SLEEP_SYSTEMTASK', 'SLEEP_TASK', 'SLEEP_TEMPDBSTARTUP', 'SNI_HTTP_ACCEPT', \
+    'SOS_WORK_DISPATCHER', 'SP_SERVER_DIAGNOSTICS_SLEEP', 'SQLTRACE_BUFFER_FLUSH', \
+    'SQLTRACE_INCREMENTAL_FLUSH_SLEEP', 'SQLTRACE_WAIT_ENTRIES', 'WAIT_FOR_RESULTS', 'WAITFOR', \
+    'WAITFOR_TASKSHUTDOWN', 'WAIT_XTP_RECOVERY', 'WAIT_XTP_HOST_WAIT', \
+    'WAIT_XTP_OFFLINE_CKPT_NEW_LOG', 'WAIT_XTP_CKPT_CLOSE', 'XE_DISPATCHER_JOIN', \
+    'XE_DISPATCHER_WAIT', 'XE_TIMER_EVENT'";
+
+// Cumulative waits since the last restart (or DBCC SQLPERF clear), largest first
+pub async fn wait_stats(client: &DbClient, limit: Option<usize>) -> Result<Vec<WaitStat>, String> {
+    let mut client = mssql_pool(client)?.get().await?;
+    let query = format!(
+        "SELECT wait_type, CAST(waiting_tasks_count AS bigint), CAST(wait_time_ms AS bigint), \
+         CAST(signal_wait_time_ms AS bigint) \
+         FROM sys.dm_os_wait_stats \
+         WHERE wait_time_ms > 0 AND wait_type NOT IN ({}) AND wait_type NOT LIKE 'SLEEP_%' \
+         ORDER BY wait_time_ms DESC",
+        BENIGN_WAITS
+    );
+    let rows = client
+        .simple_query(query)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_first_result()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let total: i64 = rows.iter().map(|r| mssql_i64(r, 2)).sum();
+    Ok(rows
+        .iter()
+        .take(limit.unwrap_or(25))
+        .map(|r| {
+            let waiting_tasks = mssql_i64(r, 1);
+            let wait_time_ms = mssql_i64(r, 2);
+            let signal_wait_ms = mssql_i64(r, 3);
+            WaitStat {
+                wait_type: mssql_text(r, 0).unwrap_or_default(),
+                waiting_tasks,
+                wait_time_ms,
+                signal_wait_ms,
+                resource_wait_ms: wait_time_ms - signal_wait_ms,
+                avg_wait_ms: if waiting_tasks > 0 {
+                    wait_time_ms as f64 / waiting_tasks as f64
+                } else {
+                    0.0
+                },
+                pct: if total > 0 {
+                    100.0 * wait_time_ms as f64 / total as f64
+                } else {
+                    0.0
+                },
+            }
+        })
+        .collect())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockingSession {
+    pub session_id: i64,
+    pub blocking_session_id: Option<i64>,
+    pub login: Option<String>,
+    pub host: Option<String>,
+    pub program: Option<String>,
+    pub database: Option<String>,
+    pub status: Option<String>,
+    pub command: Option<String>, // None for an idle session holding locks
+    pub wait_type: Option<String>,
+    pub wait_time_ms: i64,
+    pub wait_resource: Option<String>,
+    pub sql_text: Option<String>, // current statement, or the last one for idle sessions
+    pub blocked: Vec<BlockingSession>,
+}
+
+// Sessions involved in blocking, arranged as trees under their head blockers
+pub async fn blocking_tree(client: &DbClient) -> Result<Vec<BlockingSession>, String> {
+    let mut client = mssql_pool(client)?.get().await?;
+    let query = "SELECT CAST(s.session_id AS bigint), CAST(NULLIF(r.blocking_session_id, 0) AS bigint), \
+         s.login_name, s.host_name, s.program_name, DB_NAME(COALESCE(r.database_id, s.database_id)), \
+         COALESCE(r.status, s.status), r.command, r.wait_type, CAST(r.wait_time AS bigint), \
+         r.wait_resource, t.text \
+         FROM sys.dm_exec_sessions s \
+         LEFT JOIN sys.dm_exec_requests r ON r.session_id = s.session_id \
+         LEFT JOIN sys.dm_exec_connections c ON c.session_id = s.session_id \
+         OUTER APPLY sys.dm_exec_sql_text(COALESCE(r.sql_handle, c.most_recent_sql_handle)) t \
+         WHERE r.blocking_session_id <> 0 \
+         OR s.session_id IN (SELECT blocking_session_id FROM sys.dm_exec_requests WHERE blocking_session_id <> 0)";
+    let rows = client
+        .simple_query(query)
+        .await
+        .map_err(|e| e.to_string())?
+        .into_first_result()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let sessions: Vec<BlockingSession> = rows
+        .iter()
+        .map(|r| BlockingSession {
+            session_id: mssql_i64(r, 0),
+            blocking_session_id: r.try_get::<i64, _>(1).ok().flatten(),
+            login: mssql_text(r, 2),
+            host: mssql_text(r, 3),
+            program: mssql_text(r, 4),
+            database: mssql_text(r, 5),
+            status: mssql_text(r, 6),
+            command: mssql_text(r, 7),
+            wait_type: mssql_text(r, 8),
+            wait_time_ms: mssql_i64(r, 9),
+            wait_resource: mssql_text(r, 10),
+            sql_text: mssql_text(r, 11),
+            blocked: Vec::new(),
+        })
+        .collect();
+    Ok(build_blocking_tree(sessions))
+}
+
+// Ends another session, e.g. a head blocker; the session id is the backend
+// pid on Postgres and the connection id on MySQL
+pub fn kill_session_sql(dialect: Dialect, session_id: i64) -> Vec<String> {
+    match dialect {
+        Dialect::Postgres => vec![format!("SELECT pg_terminate_backend({})", session_id)],
+        Dialect::Mysql | Dialect::Mssql => vec![format!("KILL {}", session_id)],
+    }
+}
+
+fn build_blocking_tree(sessions: Vec<BlockingSession>) -> Vec<BlockingSession> {
+    let ids: HashSet<i64> = sessions.iter().map(|s| s.session_id).collect();
+    let mut children: HashMap<i64, Vec<BlockingSession>> = HashMap::new();
+    let mut roots = Vec::new();
+    for session in sessions {
+        match session.blocking_session_id {
+            Some(blocker) if ids.contains(&blocker) => {
+                children.entry(blocker).or_default().push(session)
+            }
+            _ => roots.push(session),
+        }
+    }
+
+    fn attach(node: &mut BlockingSession, children: &mut HashMap<i64, Vec<BlockingSession>>) {
+        // Removing the entry as it is attached also stops at cycles
+        node.blocked = children.remove(&node.session_id).unwrap_or_default();
+        for child in &mut node.blocked {
+            attach(child, children);
+        }
+    }
+    for root in &mut roots {
+        attach(root, &mut children);
+    }
+    // Whatever is left is a cycle with no head blocker, i.e. a deadlock the
+    // monitor hasn't resolved yet; report each member at the top level
+    let mut leftover: Vec<BlockingSession> = children.into_values().flatten().collect();
+    for session in &mut leftover {
+        session.blocked.clear();
+    }
+    roots.extend(leftover);
+    roots.sort_by_key(|s| std::cmp::Reverse(s.blocked.len()));
+    roots
+}
+
+// One node of the storage tree: schema > table > data/index. `bytes` of a parent
+// is the sum of its children; `pct` is relative to the whole breakdown.
+#[derive(Debug, Serialize)]
+pub struct StorageNode {
+    pub name: String,
+    pub kind: String, // "schema", "table", "data" or "index"
+    pub bytes: i64,
+    pub pct: f64,
+    pub children: Vec<StorageNode>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StorageBreakdown {
+    pub total_bytes: i64,
+    pub schemas: Vec<StorageNode>,
+}
+
+// Size of a table's data (index None) or of one of its indexes
+struct RelationSize {
+    schema: String,
+    table: String,
+    index: Option<String>,
+    bytes: i64,
+}
+
+// Where the space goes, largest first at every level. `schema` limits the
+// breakdown to one schema (database on MySQL); otherwise system schemas are skipped.
+pub async fn storage_breakdown(
+    client: &DbClient,
+    schema: Option<String>,
+) -> Result<StorageBreakdown, String> {
+    let sizes = match client {
+        DbClient::Postgres(pool) => postgres_sizes(pool, schema).await?,
+        DbClient::Mysql(pool) => mysql_sizes(pool, schema).await?,
+        DbClient::Mssql(pool) => mssql_sizes(pool, schema).await?,
+        _ => return Err("Storage breakdown is only supported for SQL databases".to_string()),
+    };
+    Ok(build_storage_tree(sizes))
+}
+
+async fn postgres_sizes(
+    pool: &PgPool,
+    schema: Option<String>,
+) -> Result<Vec<RelationSize>, String> {
+    // pg_table_size covers the heap plus TOAST, free space and visibility maps
+    let rows = sqlx::query(
+        "SELECT n.nspname::text, c.relname::text, NULL::text, pg_table_size(c.oid) \
+         FROM pg_class c JOIN pg_namespace n ON n.oid = c.relnamespace \
+         WHERE c.relkind IN ('r', 'm') \
+         AND ($1::text IS NULL OR n.nspname = $1) \
+         AND n.nspname NOT IN ('pg_catalog', 'information_schema') AND n.nspname NOT LIKE 'pg_toast%' \
+         UNION ALL \
+         SELECT n.nspname::text, t.relname::text, i.relname::text, pg_relation_size(i.oid) \
+         FROM pg_index x \
+         JOIN pg_class i ON i.oid = x.indexrelid \
+         JOIN pg_class t ON t.oid = x.indrelid \
+         JOIN pg_namespace n ON n.oid = t.relnamespace \
+         WHERE t.relkind IN ('r', 'm') \
+         AND ($1::text IS NULL OR n.nspname = $1) \
+         AND n.nspname NOT IN ('pg_catalog', 'information_schema') AND n.nspname NOT LIKE 'pg_toast%'",
+    )
+    .bind(schema)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(rows
+        .iter()
+        .map(|r| RelationSize {
+            schema: r.get(0),
+            table: r.get(1),
+            index: r.get(2),
+            bytes: r.get(3),
+        })
+        .collect())
+}
+
+async fn mysql_sizes(
+    pool: &sqlx::MySqlPool,
+    schema: Option<String>,
+) -> Result<Vec<RelationSize>, String> {
+    let schema_filter = "((? IS NULL AND {col} NOT IN ('mysql', 'information_schema', 'performance_schema', 'sys')) OR {col} = ?)";
+    let rows = sqlx::query(&format!(
+        "SELECT table_schema, table_name, CAST(data_length AS SIGNED), CAST(index_length AS SIGNED) \
+         FROM information_schema.tables WHERE table_type = 'BASE TABLE' AND {}",
+        schema_filter.replace("{col}", "table_schema")
+    ))
+    .bind(schema.as_deref())
+    .bind(schema.as_deref())
+    .fetch_all(pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    // Per-index sizes come from InnoDB's persistent statistics, which need
+    // read access to the mysql schema; without it each table gets one index entry
+    let index_rows = sqlx::query(&format!(
+        "SELECT database_name, table_name, index_name, CAST(stat_value * @@innodb_page_size AS SIGNED) \
+         FROM mysql.innodb_index_stats WHERE stat_name = 'size' AND index_name <> 'PRIMARY' AND {}",
+        schema_filter.replace("{col}", "database_name")
+    ))
+    .bind(schema.as_deref())
+    .bind(schema.as_deref())
+    .fetch_all(pool)
+    .await
+    .ok();
+
+    let mut sizes = Vec::new();
+    let mut indexed: HashSet<(String, String)> = HashSet::new();
+    for r in index_rows.iter().flatten() {
+        let (schema, table): (String, String) = (r.get(0), r.get(1));
+        indexed.insert((schema.clone(), table.clone()));
+        sizes.push(RelationSize {
+            schema,
+            table,
+            index: Some(r.get(2)),
+            bytes: r.get(3),
+        });
+    }
+    for r in &rows {
+        let (schema, table): (String, String) = (r.get(0), r.get(1));
+        let index_bytes: Option<i64> = r.get(3);
+        if !indexed.contains(&(schema.clone(), table.clone())) && index_bytes.unwrap_or(0) > 0 {
+            sizes.push(RelationSize {
+                schema: schema.clone(),
+                table: table.clone(),
+                index: Some("(indexes)".to_string()),
+                bytes: index_bytes.unwrap_or(0),
+            });
+        }
+        sizes.push(RelationSize {
+            schema,
+            table,
+            index: None,
+            bytes: r.get::<Option<i64>, _>(2).unwrap_or(0),
+        });
+    }
+    Ok(sizes)
+}
+
+async fn mssql_sizes(
+    pool: &Arc<MssqlPool>,
+    schema: Option<String>,
+) -> Result<Vec<RelationSize>, String> {
+    let mut client = pool.get().await?;
+    // index_id 0 is a heap and 1 a clustered index; both hold the table's data
+    let query = "SELECT s.name, o.name, CASE WHEN i.index_id > 1 THEN i.name END, \
+         CAST(SUM(ps.reserved_page_count) * 8192 AS bigint) \
+         FROM sys.dm_db_partition_stats ps \
+         JOIN sys.objects o ON o.object_id = ps.object_id \
+         JOIN sys.schemas s ON s.schema_id = o.schema_id \
+         JOIN sys.indexes i ON i.object_id = ps.object_id AND i.index_id = ps.index_id \
+         WHERE o.is_ms_shipped = 0 AND (@P1 IS NULL OR s.name = @P1) \
+         GROUP BY s.name, o.name, CASE WHEN i.index_id > 1 THEN i.name END";
+    let rows = client
+        .query(query, &[&schema])
+        .await
+        .map_err(|e| e.to_string())?
+        .into_first_result()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(rows
+        .iter()
+        .map(|r| RelationSize {
+            schema: mssql_text(r, 0).unwrap_or_default(),
+            table: mssql_text(r, 1).unwrap_or_default(),
+            index: mssql_text(r, 2),
+            bytes: mssql_i64(r, 3),
+        })
+        .collect())
+}
+
+fn build_storage_tree(sizes: Vec<RelationSize>) -> StorageBreakdown {
+    let mut schemas: HashMap<String, HashMap<String, Vec<StorageNode>>> = HashMap::new();
+    for size in sizes {
+        let (name, kind) = match size.index {
+            Some(index) => (index, "index"),
+            None => (size.table.clone(), "data"),
+        };
+        schemas
+            .entry(size.schema)
+            .or_default()
+            .entry(size.table)
+            .or_default()
+            .push(storage_node(name, kind, Vec::new(), size.bytes));
+    }
+
+    let mut schemas: Vec<StorageNode> = schemas
+        .into_iter()
+        .map(|(schema, tables)| {
+            let tables = tables
+                .into_iter()
+                .map(|(table, parts)| storage_node(table, "table", parts, 0))
+                .collect();
+            storage_node(schema, "schema", tables, 0)
+        })
+        .collect();
+    schemas.sort_by_key(|s| std::cmp::Reverse(s.bytes));
+
+    let total_bytes = schemas.iter().map(|s| s.bytes).sum();
+    for schema in &mut schemas {
+        set_storage_pct(schema, total_bytes);
+    }
+    StorageBreakdown {
+        total_bytes,
+        schemas,
+    }
+}
+
+// Leaves carry their own size; parents take the sum of their children
+fn storage_node(
+    name: String,
+    kind: &str,
+    mut children: Vec<StorageNode>,
+    bytes: i64,
+) -> StorageNode {
+    children.sort_by_key(|c| std::cmp::Reverse(c.bytes));
+    let bytes = if children.is_empty() {
+        bytes
+    } else {
+        children.iter().map(|c| c.bytes).sum()
+    };
+    StorageNode {
+        name,
+        kind: kind.to_string(),
+        bytes,
+        pct: 0.0,
+        children,
+    }
+}
+
+fn set_storage_pct(node: &mut StorageNode, total: i64) {
+    node.pct = if total > 0 {
+        100.0 * node.bytes as f64 / total as f64
+    } else {
+        0.0
+    };
+    for child in &mut node.children {
+        set_storage_pct(child, total);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionStatus {
+    Connected,
+    Failed, // the server didn't answer; the connection is probably stale
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ServerInfo {
+    pub server_version: Option<String>,
+    pub database: Option<String>,
+    pub user: Option<String>,
+    pub uptime_secs: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConnectionInfo {
+    pub status: ConnectionStatus,
+    pub latency_ms: u64, // round trip of the info queries
+    #[serde(flatten)]
+    pub server: ServerInfo,
+    pub error: Option<String>,
+}
+
+async fn server_info(client: &DbClient) -> Result<ServerInfo, String> {
+    match client {
+        DbClient::Postgres(pool) => {
+            let row = sqlx::query(
+                "SELECT version(), current_database()::text, current_user::text, \
+                 EXTRACT(EPOCH FROM now() - pg_postmaster_start_time())::bigint",
+            )
+            .fetch_one(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            Ok(ServerInfo {
+                server_version: row.get(0),
+                database: row.get(1),
+                user: row.get(2),
+                uptime_secs: row.get(3),
+            })
+        }
+        DbClient::Mysql(pool) => {
+            let row = sqlx::query("SELECT VERSION(), DATABASE(), CURRENT_USER()")
+                .fetch_one(pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            let uptime = sqlx::query("SHOW GLOBAL STATUS LIKE 'Uptime'")
+                .fetch_optional(pool)
+                .await
+                .map_err(|e| e.to_string())?
+                .and_then(|r| r.get::<String, _>(1).parse().ok());
+            Ok(ServerInfo {
+                server_version: row.get(0),
+                database: row.get(1),
+                user: row.get(2),
+                uptime_secs: uptime,
+            })
+        }
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
+            // tempdb is recreated at startup and, unlike sys.dm_os_sys_info,
+            // needs no VIEW SERVER STATE
+            let query = "SELECT @@VERSION, DB_NAME(), SUSER_SNAME(), \
+                 CAST(DATEDIFF(SECOND, create_date, GETDATE()) AS bigint) \
+                 FROM sys.databases WHERE name = 'tempdb'";
+            let row = client
+                .simple_query(query)
+                .await
+                .map_err(|e| e.to_string())?
+                .into_row()
+                .await
+                .map_err(|e| e.to_string())?
+                .ok_or("The server returned no information")?;
+            Ok(ServerInfo {
+                server_version: mssql_text(&row, 0),
+                database: mssql_text(&row, 1),
+                user: mssql_text(&row, 2),
+                uptime_secs: row.try_get::<i64, _>(3).ok().flatten(),
+            })
+        }
+        DbClient::Mongo(client) => {
+            let admin = client.database("admin");
+            let build = admin
+                .run_command(mongodb::bson::doc! { "buildInfo": 1 })
+                .await
+                .map_err(|e| e.to_string())?;
+            // Both need privileges the login may lack, so failures are ignored
+            let status = admin
+                .run_command(mongodb::bson::doc! { "connectionStatus": 1 })
+                .await
+                .ok();
+            let uptime = admin
+                .run_command(mongodb::bson::doc! { "serverStatus": 1 })
+                .await
+                .ok()
+                .and_then(|s| s.get("uptime").and_then(mongodb::bson::Bson::as_f64));
+            let user = status.as_ref().and_then(|s| {
+                s.get_document("authInfo")
+                    .ok()?
+                    .get_array("authenticatedUsers")
+                    .ok()?
+                    .first()?
+                    .as_document()?
+                    .get_str("user")
+                    .ok()
+                    .map(str::to_string)
+            });
+            Ok(ServerInfo {
+                server_version: build.get_str("version").ok().map(str::to_string),
+                database: client.default_database().map(|db| db.name().to_string()),
+                user,
+                uptime_secs: uptime.map(|u| u as i64),
+            })
+        }
+        DbClient::Redis(client) => {
+            let mut con = client
+                .get_multiplexed_async_connection()
+                .await
+                .map_err(|e| e.to_string())?;
+            let info: String = redis::cmd("INFO")
+                .arg("server")
+                .query_async(&mut con)
+                .await
+                .map_err(|e| e.to_string())?;
+            let field = |name: &str| {
+                info.lines()
+                    .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+                    .map(|value| value.trim().to_string())
+            };
+            // ACL WHOAMI needs Redis 6
+            let user: Option<String> = redis::cmd("ACL")
+                .arg("WHOAMI")
+                .query_async(&mut con)
+                .await
+                .ok();
+            Ok(ServerInfo {
+                server_version: field("redis_version"),
+                database: Some(client.get_connection_info().redis.db.to_string()),
+                user,
+                uptime_secs: field("uptime_in_seconds").and_then(|u| u.parse().ok()),
+            })
+        }
+    }
+}
+
+// Server details for the status bar. A server that doesn't answer is reported
+// as Failed rather than as an error, so stale connections can be flagged.
+pub async fn connection_info(client: &DbClient) -> ConnectionInfo {
+    let started = std::time::Instant::now();
+    let result = server_info(client).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+    match result {
+        Ok(server) => ConnectionInfo {
+            status: ConnectionStatus::Connected,
+            latency_ms,
+            server,
+            error: None,
+        },
+        Err(e) => ConnectionInfo {
+            status: ConnectionStatus::Failed,
+            latency_ms,
+            server: ServerInfo::default(),
+            error: Some(e),
+        },
+    }
+}
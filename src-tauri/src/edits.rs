@@ -0,0 +1,939 @@
+use crate::audit::Auditor;
+use crate::browse;
+use crate::db::{self, ColumnInfo, DbClient, Dialect, QueryOptions, TxStep};
+use crate::quoting::{self, quote_ident, sql_literal};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::Mutex as StdMutex;
+use std::time::{Duration, Instant};
+
+// How long an applied change can still be undone
+pub const UNDO_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+// Postgres physical row locator, used for tables without a primary key
+pub const CTID: &str = "ctid";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableRef {
+    pub schema: Option<String>,
+    pub table: String,
+}
+
+impl TableRef {
+    pub fn qualified(&self, dialect: Dialect) -> String {
+        let schema = match dialect {
+            Dialect::Mysql => db::mysql_database(self.schema.clone()),
+            _ => self.schema.clone(),
+        };
+        quoting::qualified(dialect, schema.as_deref(), &self.table)
+    }
+}
+
+// A row-level change as it was applied, with enough prior state to reverse it
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RowChange {
+    // `key` identifies the row after the update, `before` holds the overwritten values
+    Update {
+        table: TableRef,
+        key: Map<String, Value>,
+        before: Map<String, Value>,
+    },
+    Insert {
+        table: TableRef,
+        key: Map<String, Value>,
+    },
+    // `row` is the full row as it was before deletion
+    Delete {
+        table: TableRef,
+        row: Map<String, Value>,
+    },
+}
+
+impl RowChange {
+    // Statement that reverts this change; `columns` describes the table of a
+    // deleted row and is unused for the other kinds
+    pub fn compensating_sql(&self, dialect: Dialect, columns: &[ColumnInfo]) -> String {
+        match self {
+            RowChange::Update { table, key, before } => format!(
+                "UPDATE {} SET {} WHERE {}",
+                table.qualified(dialect),
+                set_clause(dialect, before),
+                where_clause(dialect, key)
+            ),
+            RowChange::Insert { table, key } => format!(
+                "DELETE FROM {} WHERE {}",
+                table.qualified(dialect),
+                where_clause(dialect, key)
+            ),
+            RowChange::Delete { table, row } => reinsert_statement(dialect, table, row, columns),
+        }
+    }
+}
+
+pub fn set_clause(dialect: Dialect, values: &Map<String, Value>) -> String {
+    values
+        .iter()
+        .map(|(col, val)| {
+            format!(
+                "{} = {}",
+                quote_ident(dialect, col),
+                sql_literal(Some(dialect), val)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn where_clause(dialect: Dialect, key: &Map<String, Value>) -> String {
+    key.iter()
+        .map(|(col, val)| match val {
+            Value::Null => format!("{} IS NULL", quote_ident(dialect, col)),
+            _ => format!(
+                "{} = {}",
+                quote_ident(dialect, col),
+                sql_literal(Some(dialect), val)
+            ),
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+pub fn insert_statement(dialect: Dialect, table: &TableRef, row: &Map<String, Value>) -> String {
+    let (columns, values) = insert_lists(dialect, row);
+    format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        table.qualified(dialect),
+        columns,
+        values
+    )
+}
+
+// Quoted column list and literal value list of an INSERT
+fn insert_lists(dialect: Dialect, row: &Map<String, Value>) -> (String, String) {
+    let columns: Vec<String> = row.keys().map(|c| quote_ident(dialect, c)).collect();
+    let values: Vec<String> = row
+        .values()
+        .map(|v| sql_literal(Some(dialect), v))
+        .collect();
+    (columns.join(", "), values.join(", "))
+}
+
+// Puts a deleted row back. Computed columns are left out, while identity
+// columns keep their old values so the row gets its original key back
+fn reinsert_statement(
+    dialect: Dialect,
+    table: &TableRef,
+    row: &Map<String, Value>,
+    columns: &[ColumnInfo],
+) -> String {
+    let info = |name: &String| columns.iter().find(|c| &c.name == name);
+    let row: Map<String, Value> = row
+        .iter()
+        .filter(|(name, _)| info(name).map_or(true, |c| c.is_writable() || c.is_identity))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    let identity = |always: bool| {
+        row.keys().filter_map(info).any(|c| {
+            c.is_identity && (!always || c.identity_generation.as_deref() == Some("ALWAYS"))
+        })
+    };
+    let target = table.qualified(dialect);
+    let (names, values) = insert_lists(dialect, &row);
+    match dialect {
+        Dialect::Mssql if identity(false) => format!(
+            "SET IDENTITY_INSERT {target} ON; \
+             INSERT INTO {target} ({names}) VALUES ({values}); \
+             SET IDENTITY_INSERT {target} OFF"
+        ),
+        Dialect::Postgres if identity(true) => {
+            format!("INSERT INTO {target} ({names}) OVERRIDING SYSTEM VALUE VALUES ({values})")
+        }
+        _ => format!("INSERT INTO {target} ({names}) VALUES ({values})"),
+    }
+}
+
+struct JournalEntry {
+    applied_at: Instant,
+    changes: Vec<RowChange>,
+}
+
+// Per-connection stack of applied edit batches, newest last
+#[derive(Default)]
+pub struct UndoJournal {
+    entries: StdMutex<HashMap<String, Vec<JournalEntry>>>,
+}
+
+impl UndoJournal {
+    pub fn record(&self, connection: &str, changes: Vec<RowChange>) {
+        if changes.is_empty() {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        let stack = entries.entry(connection.to_string()).or_default();
+        stack.retain(|e| e.applied_at.elapsed() < UNDO_WINDOW);
+        stack.push(JournalEntry {
+            applied_at: Instant::now(),
+            changes,
+        });
+    }
+
+    // Removes and returns the most recent batch that is still within the undo window
+    pub fn take_last(&self, connection: &str) -> Option<Vec<RowChange>> {
+        let mut entries = self.entries.lock().unwrap();
+        let stack = entries.get_mut(connection)?;
+        stack.retain(|e| e.applied_at.elapsed() < UNDO_WINDOW);
+        stack.pop().map(|e| e.changes)
+    }
+
+    // Puts a batch back, e.g. when running its compensation failed
+    pub fn restore(&self, connection: &str, changes: Vec<RowChange>) {
+        self.record(connection, changes);
+    }
+
+    pub fn clear(&self, connection: &str) {
+        self.entries.lock().unwrap().remove(connection);
+    }
+}
+
+// A grid edit waiting to be applied
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum StagedChange {
+    UpdateCell {
+        table: TableRef,
+        key: Map<String, Value>,
+        column: String,
+        value: Value,
+    },
+    InsertRow {
+        table: TableRef,
+        values: Map<String, Value>,
+    },
+    DeleteRow {
+        table: TableRef,
+        key: Map<String, Value>,
+    },
+}
+
+impl StagedChange {
+    pub fn sql(&self, dialect: Dialect) -> String {
+        match self {
+            StagedChange::UpdateCell {
+                table,
+                key,
+                column,
+                value,
+            } => {
+                let mut values = Map::new();
+                values.insert(column.clone(), value.clone());
+                format!(
+                    "UPDATE {} SET {} WHERE {}",
+                    table.qualified(dialect),
+                    set_clause(dialect, &values),
+                    where_clause(dialect, key)
+                )
+            }
+            StagedChange::InsertRow { table, values } => insert_statement(dialect, table, values),
+            StagedChange::DeleteRow { table, key } => format!(
+                "DELETE FROM {} WHERE {}",
+                table.qualified(dialect),
+                where_clause(dialect, key)
+            ),
+        }
+    }
+
+    // SELECT of the row as it is before this change, so the change can be journaled
+    fn capture_sql(&self, dialect: Dialect) -> Option<String> {
+        match self {
+            StagedChange::UpdateCell { table, key, .. }
+            | StagedChange::DeleteRow { table, key } => Some(format!(
+                "SELECT * FROM {} WHERE {}",
+                table.qualified(dialect),
+                where_clause(dialect, key)
+            )),
+            StagedChange::InsertRow { .. } => None,
+        }
+    }
+
+    fn journal_entries(&self, captured: Vec<Map<String, Value>>) -> Vec<RowChange> {
+        match self {
+            StagedChange::UpdateCell {
+                table,
+                key,
+                column,
+                value,
+            } => captured
+                .into_iter()
+                .map(|row| {
+                    // A ctid changes whenever the row is rewritten, so undo has to
+                    // find the updated row by its full contents instead
+                    let mut new_key = if key.contains_key(CTID) {
+                        row.clone()
+                    } else {
+                        key.clone()
+                    };
+                    if new_key.contains_key(column) {
+                        new_key.insert(column.clone(), value.clone());
+                    }
+                    let mut before = Map::new();
+                    before.insert(
+                        column.clone(),
+                        row.get(column).cloned().unwrap_or(Value::Null),
+                    );
+                    RowChange::Update {
+                        table: table.clone(),
+                        key: new_key,
+                        before,
+                    }
+                })
+                .collect(),
+            StagedChange::InsertRow { table, values } => vec![RowChange::Insert {
+                table: table.clone(),
+                key: values.clone(),
+            }],
+            StagedChange::DeleteRow { table, .. } => captured
+                .into_iter()
+                .map(|row| RowChange::Delete {
+                    table: table.clone(),
+                    row,
+                })
+                .collect(),
+        }
+    }
+}
+
+// Per-connection list of edits staged but not yet applied
+#[derive(Default)]
+pub struct StagedChanges {
+    pending: StdMutex<HashMap<String, Vec<StagedChange>>>,
+}
+
+impl StagedChanges {
+    // Returns the number of pending changes after staging this one
+    pub fn stage(&self, connection: &str, change: StagedChange) -> usize {
+        let mut pending = self.pending.lock().unwrap();
+        let changes = pending.entry(connection.to_string()).or_default();
+        changes.push(change);
+        changes.len()
+    }
+
+    pub fn list(&self, connection: &str) -> Vec<StagedChange> {
+        self.pending
+            .lock()
+            .unwrap()
+            .get(connection)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn take(&self, connection: &str) -> Vec<StagedChange> {
+        self.pending
+            .lock()
+            .unwrap()
+            .remove(connection)
+            .unwrap_or_default()
+    }
+
+    pub fn clear(&self, connection: &str) {
+        self.pending.lock().unwrap().remove(connection);
+    }
+}
+
+// Apply staged changes in a single transaction, or with `nested` within the
+// one open on `client`. Every change must touch exactly one row, otherwise the
+// whole set is rolled back. Returns the rows affected and the journal entries
+// needed to undo the batch.
+pub async fn apply_changes(
+    client: &DbClient,
+    nested: bool,
+    changes: &[StagedChange],
+    auditor: &Auditor,
+) -> Result<(u64, Vec<RowChange>), String> {
+    let dialect = client
+        .dialect()
+        .ok_or("Editing is not supported for this database type")?;
+    let steps: Vec<TxStep> = changes
+        .iter()
+        .map(|c| TxStep {
+            capture: c.capture_sql(dialect),
+            statement: c.sql(dialect),
+            expect_rows: Some(1),
+        })
+        .collect();
+
+    let work = db::execute_steps_in_transaction(client, &steps, nested);
+    let results = auditor.run_steps(&steps, work).await?;
+    let mut affected = 0;
+    let mut journal = Vec::new();
+    for (change, result) in changes.iter().zip(results) {
+        affected += result.rows_affected;
+        journal.extend(change.journal_entries(result.captured));
+    }
+    Ok((affected, journal))
+}
+
+// Run the compensating statements for a journaled batch, newest change first.
+// Each one must hit exactly one row so undo never touches unrelated data.
+pub async fn revert_changes(
+    client: &DbClient,
+    nested: bool,
+    changes: &[RowChange],
+    auditor: &Auditor,
+) -> Result<Vec<String>, String> {
+    let dialect = client
+        .dialect()
+        .ok_or("Undo is not supported for this database type")?;
+    // Deleted rows are re-inserted according to their table's columns, looked
+    // up once per table
+    let mut tables: HashMap<String, Vec<ColumnInfo>> = HashMap::new();
+    let mut statements = Vec::new();
+    for change in changes.iter().rev() {
+        let columns: &[ColumnInfo] = match change {
+            RowChange::Delete { table, .. } => {
+                let target = table.qualified(dialect);
+                if !tables.contains_key(&target) {
+                    let info =
+                        db::get_column_info(client, table.schema.clone(), &table.table).await?;
+                    tables.insert(target.clone(), info);
+                }
+                &tables[&target]
+            }
+            _ => &[],
+        };
+        statements.push(change.compensating_sql(dialect, columns));
+    }
+    let steps: Vec<TxStep> = statements
+        .iter()
+        .map(|s| TxStep {
+            capture: None,
+            statement: s.clone(),
+            expect_rows: Some(1),
+        })
+        .collect();
+    let work = db::execute_steps_in_transaction(client, &steps, nested);
+    auditor.run_steps(&steps, work).await?;
+    Ok(statements)
+}
+
+// How rows of a table are addressed when editing from the grid
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RowIdentity {
+    PrimaryKey { columns: Vec<String> },
+    // Postgres tables without a primary key; the grid selects `ctid::text AS ctid` with the row
+    Ctid,
+    // Every column value is matched, and the match must be unique
+    AllColumns,
+}
+
+pub async fn resolve_row_identity(
+    client: &DbClient,
+    table: &TableRef,
+) -> Result<RowIdentity, String> {
+    let columns = db::get_primary_key(client, table.schema.clone(), &table.table).await?;
+    if !columns.is_empty() {
+        return Ok(RowIdentity::PrimaryKey { columns });
+    }
+    match client {
+        DbClient::Postgres(_) => Ok(RowIdentity::Ctid),
+        _ => Ok(RowIdentity::AllColumns),
+    }
+}
+
+// Narrow the key of an update/delete to the table's row identity and make sure
+// it addresses exactly one row before the change is staged
+pub async fn prepare_change(
+    client: &DbClient,
+    change: StagedChange,
+) -> Result<StagedChange, String> {
+    let (table, key) = match &change {
+        StagedChange::UpdateCell { table, key, .. } | StagedChange::DeleteRow { table, key } => {
+            (table.clone(), key.clone())
+        }
+        StagedChange::InsertRow { .. } => return Ok(change),
+    };
+    let dialect = client
+        .dialect()
+        .ok_or("Editing is not supported for this database type")?;
+
+    let key = match resolve_row_identity(client, &table).await? {
+        RowIdentity::PrimaryKey { columns } => {
+            let mut pk = Map::new();
+            for col in &columns {
+                let val = key
+                    .get(col)
+                    .ok_or(format!("Missing primary key column: {}", col))?;
+                pk.insert(col.clone(), val.clone());
+            }
+            // Primary key values are unique by definition
+            return Ok(change.with_key(pk));
+        }
+        RowIdentity::Ctid if key.contains_key(CTID) => {
+            let mut locator = Map::new();
+            locator.insert(CTID.to_string(), key[CTID].clone());
+            return Ok(change.with_key(locator));
+        }
+        RowIdentity::Ctid | RowIdentity::AllColumns => key,
+    };
+
+    let sql = format!(
+        "SELECT COUNT(*) FROM {} WHERE {}",
+        table.qualified(dialect),
+        where_clause(dialect, &key)
+    );
+    let result = db::execute_query(client, sql, &QueryOptions::default()).await?;
+    let matches = result
+        .rows
+        .first()
+        .and_then(|r| r.first())
+        .and_then(|c| c.value())
+        .and_then(|v| v.as_i64())
+        .unwrap_or(0);
+    if matches != 1 {
+        return Err(format!(
+            "Row cannot be identified uniquely without a primary key ({} matching rows)",
+            matches
+        ));
+    }
+    Ok(change.with_key(key))
+}
+
+impl StagedChange {
+    fn with_key(self, new_key: Map<String, Value>) -> Self {
+        match self {
+            StagedChange::UpdateCell {
+                table,
+                column,
+                value,
+                ..
+            } => StagedChange::UpdateCell {
+                table,
+                key: new_key,
+                column,
+                value,
+            },
+            StagedChange::DeleteRow { table, .. } => StagedChange::DeleteRow {
+                table,
+                key: new_key,
+            },
+            insert => insert,
+        }
+    }
+}
+
+// Bind placeholder for the n-th (1-based) parameter
+pub fn placeholder(dialect: Dialect, n: usize) -> String {
+    match dialect {
+        Dialect::Postgres => format!("${}", n),
+        Dialect::Mysql => "?".to_string(),
+        Dialect::Mssql => format!("@P{}", n),
+    }
+}
+
+// Adds `value` as the next parameter and returns its placeholder
+pub fn bind(
+    dialect: Dialect,
+    params: &mut Vec<Value>,
+    cast: Option<&str>,
+    value: &Value,
+) -> String {
+    params.push(value.clone());
+    let placeholder = placeholder(dialect, params.len());
+    match cast {
+        Some(cast) => format!("CAST({} AS {})", placeholder, cast),
+        None => placeholder,
+    }
+}
+
+// Insert-or-update statement with one bind placeholder per column, in `columns` order
+pub fn upsert_sql(
+    dialect: Dialect,
+    table: &TableRef,
+    columns: &[String],
+    conflict_columns: &[String],
+) -> Result<String, String> {
+    let params: Vec<String> = (1..=columns.len())
+        .map(|n| placeholder(dialect, n))
+        .collect();
+    upsert_values(dialect, table, columns, conflict_columns, &params)
+}
+
+// Insert-or-update statement taking `values`, one SQL expression per column
+pub fn upsert_values(
+    dialect: Dialect,
+    table: &TableRef,
+    columns: &[String],
+    conflict_columns: &[String],
+    values: &[String],
+) -> Result<String, String> {
+    if conflict_columns.is_empty() {
+        return Err("At least one conflict column is required".to_string());
+    }
+    if let Some(missing) = conflict_columns.iter().find(|c| !columns.contains(c)) {
+        return Err(format!(
+            "Conflict column {} is not part of the insert",
+            missing
+        ));
+    }
+
+    let q = |c: &String| quote_ident(dialect, c);
+    let target = table.qualified(dialect);
+    let column_list = columns.iter().map(q).collect::<Vec<_>>().join(", ");
+    let params = values.join(", ");
+    let updates: Vec<&String> = columns
+        .iter()
+        .filter(|c| !conflict_columns.contains(c))
+        .collect();
+
+    let sql = match dialect {
+        Dialect::Postgres => {
+            let action = if updates.is_empty() {
+                "DO NOTHING".to_string()
+            } else {
+                format!(
+                    "DO UPDATE SET {}",
+                    updates
+                        .iter()
+                        .map(|c| format!("{} = EXCLUDED.{}", q(c), q(c)))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            };
+            format!(
+                "INSERT INTO {} ({}) VALUES ({}) ON CONFLICT ({}) {}",
+                target,
+                column_list,
+                params,
+                conflict_columns
+                    .iter()
+                    .map(q)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                action
+            )
+        }
+        // MySQL resolves conflicts against whatever unique key is hit
+        Dialect::Mysql => {
+            if updates.is_empty() {
+                format!(
+                    "INSERT IGNORE INTO {} ({}) VALUES ({})",
+                    target, column_list, params
+                )
+            } else {
+                format!(
+                    "INSERT INTO {} ({}) VALUES ({}) ON DUPLICATE KEY UPDATE {}",
+                    target,
+                    column_list,
+                    params,
+                    updates
+                        .iter()
+                        .map(|c| format!("{} = VALUES({})", q(c), q(c)))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            }
+        }
+        Dialect::Mssql => {
+            let on = conflict_columns
+                .iter()
+                .map(|c| format!("target.{} = source.{}", q(c), q(c)))
+                .collect::<Vec<_>>()
+                .join(" AND ");
+            let matched = if updates.is_empty() {
+                String::new()
+            } else {
+                format!(
+                    " WHEN MATCHED THEN UPDATE SET {}",
+                    updates
+                        .iter()
+                        .map(|c| format!("target.{} = source.{}", q(c), q(c)))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            };
+            format!(
+                "MERGE INTO {} WITH (HOLDLOCK) AS target USING (VALUES ({})) AS source ({}) ON {}{} WHEN NOT MATCHED THEN INSERT ({}) VALUES ({});",
+                target,
+                params,
+                column_list,
+                on,
+                matched,
+                column_list,
+                columns
+                    .iter()
+                    .map(|c| format!("source.{}", q(c)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        }
+    };
+    Ok(sql)
+}
+
+// Upsert for a table, pre-filled with all of its columns unless a subset is given
+pub async fn generate_upsert(
+    client: &DbClient,
+    table: &TableRef,
+    conflict_columns: &[String],
+    columns: Option<Vec<String>>,
+) -> Result<String, String> {
+    let dialect = client
+        .dialect()
+        .ok_or("Upserts are not supported for this database type")?;
+    // Identity and computed columns take no values, listed or not
+    let writable: Vec<String> = db::get_column_info(client, table.schema.clone(), &table.table)
+        .await?
+        .into_iter()
+        .filter(ColumnInfo::is_writable)
+        .map(|c| c.name)
+        .collect();
+    let columns = match columns {
+        Some(columns) => columns
+            .into_iter()
+            .filter(|c| writable.contains(c))
+            .collect(),
+        None => writable,
+    };
+    if columns.is_empty() {
+        return Err(format!("Table {} has no columns", table.table));
+    }
+    upsert_sql(dialect, table, &columns, conflict_columns)
+}
+
+// Columns of `table` with the type their bound values are cast to (Postgres
+// only), for the direct row edits below
+async fn edit_target(
+    client: &DbClient,
+    table: &TableRef,
+) -> Result<(Dialect, HashMap<String, Option<String>>), String> {
+    let dialect = client
+        .dialect()
+        .ok_or("Editing is not supported for this database type")?;
+    let columns = browse::known_columns(client, table).await?;
+    if columns.is_empty() {
+        return Err(format!("Table {} not found", table.table));
+    }
+    Ok((dialect, columns))
+}
+
+// `column = value` terms with the values bound as parameters; names are
+// checked against the table's own
+fn bound_terms(
+    dialect: Dialect,
+    columns: &HashMap<String, Option<String>>,
+    values: &Map<String, Value>,
+    params: &mut Vec<Value>,
+) -> Result<Vec<String>, String> {
+    values
+        .iter()
+        .map(|(column, value)| {
+            let cast = columns
+                .get(column)
+                .ok_or(format!("Unknown column: {}", column))?;
+            Ok(format!(
+                "{} = {}",
+                quote_ident(dialect, column),
+                bind(dialect, params, cast.as_deref(), value)
+            ))
+        })
+        .collect()
+}
+
+// The primary key values of `key`; only tables with a primary key can be
+// edited this way, so a statement never touches more than one row
+async fn primary_key_values(
+    client: &DbClient,
+    table: &TableRef,
+    key: &Map<String, Value>,
+) -> Result<Map<String, Value>, String> {
+    let primary_key = db::get_primary_key(client, table.schema.clone(), &table.table).await?;
+    if primary_key.is_empty() {
+        return Err(format!(
+            "Table {} has no primary key; stage the change instead",
+            table.table
+        ));
+    }
+    let mut pk = Map::new();
+    for column in &primary_key {
+        match key.get(column) {
+            Some(Value::Null) | None => {
+                return Err(format!("Missing primary key column: {}", column))
+            }
+            Some(value) => pk.insert(column.clone(), value.clone()),
+        };
+    }
+    Ok(pk)
+}
+
+// `selected` of the row matching `pk` as it is before an edit, for the undo
+// journal; None when no row matches
+async fn capture_row(
+    client: &DbClient,
+    dialect: Dialect,
+    table: &TableRef,
+    columns: &HashMap<String, Option<String>>,
+    selected: &str,
+    pk: &Map<String, Value>,
+) -> Result<Option<Map<String, Value>>, String> {
+    let mut params = Vec::new();
+    let filter = bound_terms(dialect, columns, pk, &mut params)?.join(" AND ");
+    let sql = format!(
+        "SELECT {} FROM {} WHERE {}",
+        selected,
+        table.qualified(dialect),
+        filter
+    );
+    let options = QueryOptions {
+        params,
+        ..QueryOptions::default()
+    };
+    let result = db::execute_query(client, sql, &options).await?;
+    let columns = result.columns;
+    result
+        .rows
+        .into_iter()
+        .next()
+        .map(|row| db::row_map(&columns, row))
+        .transpose()
+}
+
+async fn run_edit(
+    client: &DbClient,
+    sql: String,
+    params: Vec<Value>,
+    auditor: &Auditor,
+) -> Result<u64, String> {
+    let options = QueryOptions {
+        params,
+        ..QueryOptions::default()
+    };
+    let result = auditor
+        .run(&sql, db::execute_query(client, sql.clone(), &options))
+        .await?;
+    Ok(result.rows_affected.unwrap_or(0))
+}
+
+// Sets `values` on the row whose primary key is `key`. Like apply_changes,
+// returns the rows affected and the journal entries to undo the edit.
+pub async fn update_row(
+    client: &DbClient,
+    table: &TableRef,
+    values: &Map<String, Value>,
+    key: &Map<String, Value>,
+    auditor: &Auditor,
+) -> Result<(u64, Vec<RowChange>), String> {
+    if values.is_empty() {
+        return Err("No values to update".to_string());
+    }
+    let (dialect, columns) = edit_target(client, table).await?;
+    let pk = primary_key_values(client, table, key).await?;
+    let mut params = Vec::new();
+    let set = bound_terms(dialect, &columns, values, &mut params)?.join(", ");
+    let filter = bound_terms(dialect, &columns, &pk, &mut params)?.join(" AND ");
+    let selected = values
+        .keys()
+        .map(|c| quote_ident(dialect, c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let before = capture_row(client, dialect, table, &columns, &selected, &pk).await?;
+    let sql = format!(
+        "UPDATE {} SET {} WHERE {}",
+        table.qualified(dialect),
+        set,
+        filter
+    );
+    let affected = run_edit(client, sql, params, auditor).await?;
+    // The row is found again by its key as updated
+    let journal = before.filter(|_| affected == 1).map(|before| {
+        let mut key = pk;
+        for (column, value) in key.iter_mut() {
+            if let Some(new) = values.get(column) {
+                *value = new.clone();
+            }
+        }
+        RowChange::Update {
+            table: table.clone(),
+            key,
+            before,
+        }
+    });
+    Ok((affected, journal.into_iter().collect()))
+}
+
+// Columns left out take their defaults
+pub async fn insert_row(
+    client: &DbClient,
+    table: &TableRef,
+    values: &Map<String, Value>,
+    auditor: &Auditor,
+) -> Result<(u64, Vec<RowChange>), String> {
+    let (dialect, columns) = edit_target(client, table).await?;
+    let target = table.qualified(dialect);
+    // Values for identity and computed columns are left to the database
+    let generated: Vec<String> = db::get_column_info(client, table.schema.clone(), &table.table)
+        .await?
+        .into_iter()
+        .filter(|c| !c.is_writable())
+        .map(|c| c.name)
+        .collect();
+    let values: Map<String, Value> = values
+        .iter()
+        .filter(|(column, _)| !generated.contains(column))
+        .map(|(column, value)| (column.clone(), value.clone()))
+        .collect();
+    // A row of defaults has nothing to find it by, so it can't be undone
+    if values.is_empty() {
+        let sql = match dialect {
+            Dialect::Mysql => format!("INSERT INTO {} () VALUES ()", target),
+            _ => format!("INSERT INTO {} DEFAULT VALUES", target),
+        };
+        return Ok((
+            run_edit(client, sql, Vec::new(), auditor).await?,
+            Vec::new(),
+        ));
+    }
+    let mut params = Vec::new();
+    let mut names = Vec::new();
+    let mut placeholders = Vec::new();
+    for (column, value) in &values {
+        let cast = columns
+            .get(column)
+            .ok_or(format!("Unknown column: {}", column))?;
+        names.push(quote_ident(dialect, column));
+        placeholders.push(bind(dialect, &mut params, cast.as_deref(), value));
+    }
+    let sql = format!(
+        "INSERT INTO {} ({}) VALUES ({})",
+        target,
+        names.join(", "),
+        placeholders.join(", ")
+    );
+    let affected = run_edit(client, sql, params, auditor).await?;
+    let journal = (affected == 1).then(|| RowChange::Insert {
+        table: table.clone(),
+        key: values,
+    });
+    Ok((affected, journal.into_iter().collect()))
+}
+
+pub async fn delete_row(
+    client: &DbClient,
+    table: &TableRef,
+    key: &Map<String, Value>,
+    auditor: &Auditor,
+) -> Result<(u64, Vec<RowChange>), String> {
+    let (dialect, columns) = edit_target(client, table).await?;
+    let pk = primary_key_values(client, table, key).await?;
+    let row = capture_row(client, dialect, table, &columns, "*", &pk).await?;
+    let mut params = Vec::new();
+    let filter = bound_terms(dialect, &columns, &pk, &mut params)?.join(" AND ");
+    let sql = format!("DELETE FROM {} WHERE {}", table.qualified(dialect), filter);
+    let affected = run_edit(client, sql, params, auditor).await?;
+    let journal = row.filter(|_| affected == 1).map(|row| RowChange::Delete {
+        table: table.clone(),
+        row,
+    });
+    Ok((affected, journal.into_iter().collect()))
+}
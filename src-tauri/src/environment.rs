@@ -0,0 +1,110 @@
+// Environment labels for saved connections (development, staging, production)
+// and the context every window needs to show the same warning banner
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+
+use crate::db::DbClient;
+use crate::profiles::{Operation, Profile};
+use crate::SavedConnection;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Environment {
+    #[serde(alias = "dev")]
+    Development,
+    Staging,
+    #[serde(alias = "prod")]
+    Production,
+}
+
+impl Environment {
+    fn label(self) -> &'static str {
+        match self {
+            Environment::Development => "Development",
+            Environment::Staging => "Staging",
+            Environment::Production => "Production",
+        }
+    }
+
+    // Used when the connection has no color of its own
+    fn default_color(self) -> &'static str {
+        match self {
+            Environment::Development => "#16a34a",
+            Environment::Staging => "#d97706",
+            Environment::Production => "#dc2626",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConnectionContext {
+    pub connection: String,
+    pub environment: Option<Environment>,
+    pub label: Option<String>,
+    pub color: Option<String>,
+    pub read_only: bool, // the app profile doesn't allow data changes
+    pub connected: bool,
+    pub active_database: Option<String>, // None when not connected
+}
+
+// The database the connection is working in
+async fn active_database(client: &DbClient) -> Result<Option<String>, String> {
+    match client {
+        DbClient::Postgres(pool) => sqlx::query("SELECT current_database()::text")
+            .fetch_one(pool)
+            .await
+            .map(|row| row.get(0))
+            .map_err(|e| e.to_string()),
+        DbClient::Mysql(pool) => sqlx::query("SELECT DATABASE()")
+            .fetch_one(pool)
+            .await
+            .map(|row| row.get(0))
+            .map_err(|e| e.to_string()),
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
+            let row = client
+                .simple_query("SELECT DB_NAME()")
+                .await
+                .map_err(|e| e.to_string())?
+                .into_row()
+                .await
+                .map_err(|e| e.to_string())?;
+            Ok(row.and_then(|r| r.try_get::<&str, _>(0).ok().flatten().map(str::to_string)))
+        }
+        DbClient::Mongo(client) => Ok(client.default_database().map(|db| db.name().to_string())),
+        DbClient::Redis(client) => Ok(Some(client.get_connection_info().redis.db.to_string())),
+    }
+}
+
+// `client` is the open connection, if any. A server that doesn't answer leaves
+// the database unknown rather than failing, the banner matters more.
+pub async fn connection_context(
+    saved: &SavedConnection,
+    profile: Profile,
+    client: Option<&DbClient>,
+) -> ConnectionContext {
+    let environment = saved.environment;
+    let color = Some(saved.color.clone())
+        .filter(|c| !c.is_empty())
+        .or_else(|| environment.map(|e| e.default_color().to_string()));
+    let active_database = match client {
+        Some(client) => active_database(client).await.unwrap_or_else(|e| {
+            log::warn!(
+                "Failed to read the active database of {}: {}",
+                saved.name,
+                e
+            );
+            None
+        }),
+        None => None,
+    };
+    ConnectionContext {
+        connection: saved.name.clone(),
+        environment,
+        label: environment.map(|e| e.label().to_string()),
+        color,
+        read_only: !profile.allows(Operation::Write),
+        connected: client.is_some(),
+        active_database,
+    }
+}
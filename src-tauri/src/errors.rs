@@ -0,0 +1,228 @@
+// The error every command returns, sorted into the categories the frontend
+// renders differently. Driver errors are sorted where they are raised, by
+// their SQLSTATE or error number (see sql_error and mssql_error), and the
+// query paths in db.rs hand them on as they are. Other errors are plain
+// messages.
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DbError {
+    ConnectionFailed {
+        message: String,
+    },
+    Timeout {
+        message: String,
+    },
+    SyntaxError {
+        message: String,
+        // 1-based character offset into the statement (Postgres)
+        position: Option<usize>,
+        line: Option<u32>, // MySQL and SQL Server
+    },
+    PermissionDenied {
+        message: String,
+    },
+    Cancelled {
+        message: String,
+    },
+    DriverError {
+        message: String,
+        code: Option<String>, // SQLSTATE or server error number
+    },
+}
+
+// A sqlx error, sorted by its SQLSTATE (and on MySQL its error number)
+pub fn sql_error(error: sqlx::Error) -> DbError {
+    let database = match &error {
+        sqlx::Error::Database(database) => database,
+        sqlx::Error::PoolTimedOut => {
+            return DbError::Timeout {
+                message: error.to_string(),
+            }
+        }
+        sqlx::Error::Io(_)
+        | sqlx::Error::Tls(_)
+        | sqlx::Error::PoolClosed
+        | sqlx::Error::WorkerCrashed => {
+            return DbError::ConnectionFailed {
+                message: error.to_string(),
+            }
+        }
+        _ => {
+            return DbError::DriverError {
+                message: error.to_string(),
+                code: None,
+            }
+        }
+    };
+    let message = database.message().to_string();
+    let sqlstate = database.code().map(|c| c.to_string());
+    let mut position = None;
+    if let Some(pg) = database.try_downcast_ref::<sqlx::postgres::PgDatabaseError>() {
+        if let Some(sqlx::postgres::PgErrorPosition::Original(p)) = pg.position() {
+            position = Some(p);
+        }
+    }
+    let number = database
+        .try_downcast_ref::<sqlx::mysql::MySqlDatabaseError>()
+        .map(|mysql| mysql.number() as u32);
+    let kind = sqlstate
+        .as_deref()
+        .and_then(|s| from_sqlstate(s, &message))
+        .or_else(|| number.and_then(from_mysql_number));
+    // MySQL puts the line in the message: "... near 'x' at line 3"
+    let line = number.and_then(|_| {
+        let (_, rest) = message.rsplit_once(" at line ")?;
+        rest.trim().parse().ok()
+    });
+    let code = sqlstate.or_else(|| number.map(|n| n.to_string()));
+    sorted(kind, message, code, position, line)
+}
+
+// Same for SQL Server errors, which carry an error number and line
+pub fn mssql_error(error: tiberius::error::Error) -> DbError {
+    match &error {
+        tiberius::error::Error::Server(token) => sorted(
+            from_mssql_number(token.code()),
+            token.message().to_string(),
+            Some(token.code().to_string()),
+            None,
+            Some(token.line()),
+        ),
+        tiberius::error::Error::Io { .. }
+        | tiberius::error::Error::Tls(_)
+        | tiberius::error::Error::Routing { .. } => DbError::ConnectionFailed {
+            message: error.to_string(),
+        },
+        _ => DbError::DriverError {
+            message: error.to_string(),
+            code: None,
+        },
+    }
+}
+
+fn sorted(
+    kind: Option<Kind>,
+    message: String,
+    code: Option<String>,
+    position: Option<usize>,
+    line: Option<u32>,
+) -> DbError {
+    match kind {
+        Some(Kind::Connection) => DbError::ConnectionFailed { message },
+        Some(Kind::Timeout) => DbError::Timeout { message },
+        Some(Kind::Syntax) => DbError::SyntaxError {
+            message,
+            position,
+            line,
+        },
+        Some(Kind::Permission) => DbError::PermissionDenied { message },
+        Some(Kind::Cancelled) => DbError::Cancelled { message },
+        None => DbError::DriverError { message, code },
+    }
+}
+
+fn from_sqlstate(sqlstate: &str, message: &str) -> Option<Kind> {
+    match sqlstate {
+        "42601" => Some(Kind::Syntax),
+        "42501" => Some(Kind::Permission),
+        // Also raised by statement_timeout, which says so in the message
+        "57014" if message.contains("statement timeout") => Some(Kind::Timeout),
+        "57014" => Some(Kind::Cancelled),
+        "55P03" => Some(Kind::Timeout), // lock_timeout
+        s if s.starts_with("08") || s.starts_with("28") => Some(Kind::Connection),
+        _ => None,
+    }
+}
+
+fn from_mysql_number(number: u32) -> Option<Kind> {
+    match number {
+        1064 | 1149 => Some(Kind::Syntax),
+        1044 | 1142 | 1143 | 1227 | 1370 => Some(Kind::Permission),
+        1317 => Some(Kind::Cancelled),
+        1205 | 3024 => Some(Kind::Timeout),
+        1045 | 2002 | 2003 | 2006 | 2013 => Some(Kind::Connection),
+        _ => None,
+    }
+}
+
+fn from_mssql_number(number: u32) -> Option<Kind> {
+    match number {
+        102 | 105 | 156 | 170 => Some(Kind::Syntax),
+        229 | 230 | 262 | 297 | 300 | 916 => Some(Kind::Permission),
+        1222 => Some(Kind::Timeout),
+        3980 => Some(Kind::Cancelled),
+        18456 => Some(Kind::Connection),
+        _ => None,
+    }
+}
+
+enum Kind {
+    Connection,
+    Timeout,
+    Syntax,
+    Permission,
+    Cancelled,
+}
+
+// Errors raised as text carry no driver details to sort them by
+impl From<String> for DbError {
+    fn from(message: String) -> Self {
+        DbError::DriverError {
+            message,
+            code: None,
+        }
+    }
+}
+
+// For callers that keep their errors as text
+impl From<DbError> for String {
+    fn from(error: DbError) -> Self {
+        match error {
+            DbError::ConnectionFailed { message }
+            | DbError::Timeout { message }
+            | DbError::SyntaxError { message, .. }
+            | DbError::PermissionDenied { message }
+            | DbError::Cancelled { message }
+            | DbError::DriverError { message, .. } => message,
+        }
+    }
+}
+
+impl From<&str> for DbError {
+    fn from(text: &str) -> Self {
+        DbError::from(text.to_string())
+    }
+}
+
+impl DbError {
+    // A serialization failure or deadlock: the statement lost a conflict with
+    // another transaction and can simply be run again. MySQL reports its
+    // deadlocks (1213) as 40001 too; 1205 is a SQL Server deadlock victim.
+    pub fn is_conflict(&self) -> bool {
+        matches!(
+            self,
+            DbError::DriverError { code: Some(code), .. }
+                if matches!(code.as_str(), "40001" | "40P01" | "1205")
+        )
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            DbError::ConnectionFailed { message }
+            | DbError::Timeout { message }
+            | DbError::SyntaxError { message, .. }
+            | DbError::PermissionDenied { message }
+            | DbError::Cancelled { message }
+            | DbError::DriverError { message, .. } => message,
+        }
+    }
+}
+
+impl fmt::Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
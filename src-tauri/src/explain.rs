@@ -0,0 +1,330 @@
+// Execution plans, normalized into one tree shape for the plan visualizer
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::Row;
+
+use crate::db::{self, DbClient};
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PlanNode {
+    pub operation: String, // e.g. Seq Scan, table (ref), Clustered Index Seek
+    pub relation: Option<String>,
+    pub estimated_rows: Option<f64>,
+    pub estimated_cost: Option<f64>, // cumulative, in the server's own cost units
+    pub actual_rows: Option<f64>,    // only with analyze
+    pub actual_ms: Option<f64>,
+    pub details: Map<String, Value>, // everything else the server reported
+    pub children: Vec<PlanNode>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryPlan {
+    pub roots: Vec<PlanNode>, // one per statement
+    pub planning_ms: Option<f64>,
+    pub execution_ms: Option<f64>,
+    pub raw: String, // the plan as the server returned it, JSON or showplan XML
+}
+
+// The planner's row estimate for a query, without running it
+pub async fn estimate_rows(client: &DbClient, sql: &str) -> Result<Option<u64>, String> {
+    let plan = explain_query(client, sql, false).await?;
+    Ok(plan
+        .roots
+        .first()
+        .and_then(|root| root.estimated_rows)
+        .map(|rows| rows.max(0.0).round() as u64))
+}
+
+// With `analyze` the statement really runs, so its changes are made
+pub async fn explain_query(
+    client: &DbClient,
+    sql: &str,
+    analyze: bool,
+) -> Result<QueryPlan, String> {
+    let sql = sql.trim().trim_end_matches(';');
+    match client {
+        DbClient::Postgres(pool) => {
+            let options = if analyze {
+                "FORMAT JSON, ANALYZE"
+            } else {
+                "FORMAT JSON"
+            };
+            let raw: Value = sqlx::query_scalar(&format!("EXPLAIN ({}) {}", options, sql))
+                .fetch_one(pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            let top = raw.get(0).ok_or("The server returned an empty plan")?;
+            Ok(QueryPlan {
+                roots: top.get("Plan").map(pg_node).into_iter().collect(),
+                planning_ms: top.get("Planning Time").and_then(number),
+                execution_ms: top.get("Execution Time").and_then(number),
+                raw: raw.to_string(),
+            })
+        }
+        DbClient::Mysql(pool) => {
+            let sql = match analyze {
+                false => format!("EXPLAIN FORMAT=JSON {}", sql),
+                true => {
+                    if db::is_mariadb(pool).await? {
+                        format!("ANALYZE FORMAT=JSON {}", sql)
+                    } else {
+                        // MySQL 8.3 and later
+                        format!("EXPLAIN ANALYZE FORMAT=JSON {}", sql)
+                    }
+                }
+            };
+            let row = sqlx::query(&sql)
+                .fetch_one(pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            let raw: String = row.try_get(0).map_err(|e| e.to_string())?;
+            let plan: Value = serde_json::from_str(&raw).map_err(|e| e.to_string())?;
+            Ok(QueryPlan {
+                roots: vec![mysql_node("query", &plan)],
+                planning_ms: None,
+                execution_ms: None,
+                raw,
+            })
+        }
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
+            let option = if analyze {
+                "STATISTICS XML"
+            } else {
+                "SHOWPLAN_XML"
+            };
+            client
+                .simple_query(format!("SET {} ON", option))
+                .await
+                .map_err(|e| e.to_string())?
+                .into_results()
+                .await
+                .map_err(|e| e.to_string())?;
+            let result: Result<Vec<Vec<tiberius::Row>>, tiberius::error::Error> =
+                async { client.simple_query(sql).await?.into_results().await }.await;
+            // The option would otherwise stick to the shared connection
+            client
+                .simple_query(format!("SET {} OFF", option))
+                .await
+                .map_err(|e| e.to_string())?
+                .into_results()
+                .await
+                .map_err(|e| e.to_string())?;
+
+            // With STATISTICS XML the plans follow the statement's own results
+            let documents: Vec<String> = result
+                .map_err(|e| e.to_string())?
+                .iter()
+                .flatten()
+                .filter(|r| {
+                    r.columns()
+                        .first()
+                        .is_some_and(|c| c.name().contains("Showplan"))
+                })
+                .filter_map(|r| r.try_get::<&str, _>(0).ok().flatten().map(str::to_string))
+                .collect();
+            let mut plan = QueryPlan {
+                roots: Vec::new(),
+                planning_ms: None,
+                execution_ms: None,
+                raw: documents.join("\n"),
+            };
+            for document in &documents {
+                read_showplan(document, &mut plan)?;
+            }
+            Ok(plan)
+        }
+        _ => Err("Execution plans are supported for PostgreSQL, MySQL and SQL Server".to_string()),
+    }
+}
+
+// Costs and row counts come as strings in MySQL plans and showplan XML
+fn number(value: &Value) -> Option<f64> {
+    value.as_f64().or_else(|| value.as_str()?.parse().ok())
+}
+
+fn pg_node(plan: &Value) -> PlanNode {
+    let mut node = PlanNode::default();
+    let Some(fields) = plan.as_object() else {
+        return node;
+    };
+    for (key, value) in fields {
+        match key.as_str() {
+            "Plans" => {
+                node.children = value
+                    .as_array()
+                    .map(|plans| plans.iter().map(pg_node).collect())
+                    .unwrap_or_default()
+            }
+            _ => {
+                node.details.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    let text = |key| fields.get(key).and_then(Value::as_str).map(str::to_string);
+    let field = |key| fields.get(key).and_then(number);
+    node.operation = text("Node Type").unwrap_or_default();
+    node.relation = match (text("Schema"), text("Relation Name")) {
+        (Some(schema), Some(relation)) => Some(format!("{}.{}", schema, relation)),
+        (None, relation) => relation,
+        (Some(_), None) => None,
+    };
+    node.estimated_rows = field("Plan Rows");
+    node.estimated_cost = field("Total Cost");
+    // Actual figures are averages per loop
+    let loops = field("Actual Loops").unwrap_or(1.0);
+    node.actual_rows = field("Actual Rows").map(|rows| rows * loops);
+    node.actual_ms = field("Actual Total Time").map(|ms| ms * loops);
+    node
+}
+
+// MySQL nests operations as named objects (query_block, nested_loop, table,
+// ...); the newer format used by EXPLAIN ANALYZE names them in "operation"
+// and lists children under "inputs"
+fn mysql_node(operation: &str, plan: &Value) -> PlanNode {
+    let mut node = PlanNode {
+        operation: operation.to_string(),
+        ..Default::default()
+    };
+    let Some(fields) = plan.as_object() else {
+        return node;
+    };
+    for (key, value) in fields {
+        match value {
+            Value::Object(costs) if key == "cost_info" => {
+                node.details.extend(costs.clone());
+            }
+            Value::Object(_) => node.children.push(mysql_node(key, value)),
+            Value::Array(items) if !items.is_empty() && items.iter().all(Value::is_object) => {
+                for item in items {
+                    // nested_loop entries are wrapped as {"table": {...}}
+                    let child = match item.as_object() {
+                        Some(wrapper) if wrapper.len() == 1 => {
+                            let (name, inner) = wrapper.iter().next().unwrap();
+                            if inner.is_object() {
+                                mysql_node(name, inner)
+                            } else {
+                                mysql_node(key, item)
+                            }
+                        }
+                        _ => mysql_node(key, item),
+                    };
+                    node.children.push(child);
+                }
+            }
+            _ => {
+                node.details.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    let details = &node.details;
+    let first = |keys: &[&str]| keys.iter().find_map(|k| details.get(*k).and_then(number));
+    let estimated_rows = first(&[
+        "estimated_rows",
+        "rows_produced_per_join",
+        "rows_examined_per_scan",
+    ]);
+    let estimated_cost = first(&["estimated_total_cost", "prefix_cost", "query_cost"]);
+    let actual_rows = first(&["actual_rows", "r_rows"]);
+    let actual_ms = first(&["actual_last_row_ms", "r_total_time_ms"]);
+    let access_type = details.get("access_type").and_then(Value::as_str);
+    let explicit = details.get("operation").and_then(Value::as_str);
+    let table = details.get("table_name").and_then(Value::as_str);
+    node.operation = match (explicit, access_type) {
+        (Some(explicit), _) => explicit.to_string(),
+        (None, Some(access)) => format!("{} ({})", operation, access),
+        (None, None) => operation.to_string(),
+    };
+    node.relation = table.map(str::to_string);
+    node.estimated_rows = estimated_rows;
+    node.estimated_cost = estimated_cost;
+    node.actual_rows = actual_rows;
+    node.actual_ms = actual_ms;
+    node
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Option<String> {
+    element
+        .attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == name.as_bytes())
+        .and_then(|a| a.unescape_value().ok())
+        .map(|v| v.into_owned())
+}
+
+// Adds the statements of one showplan document to the plan
+fn read_showplan(xml: &str, plan: &mut QueryPlan) -> Result<(), String> {
+    let mut reader = Reader::from_str(xml);
+    let mut open: Vec<PlanNode> = Vec::new();
+    let add = |total: &mut Option<f64>, value: Option<f64>| {
+        if let Some(value) = value {
+            *total = Some(total.unwrap_or(0.0) + value);
+        }
+    };
+    loop {
+        match reader.read_event().map_err(|e| e.to_string())? {
+            Event::Start(e) if e.local_name().as_ref() == b"RelOp" => {
+                let mut node = PlanNode::default();
+                for a in e.attributes().flatten() {
+                    let key = String::from_utf8_lossy(a.key.as_ref()).into_owned();
+                    let value = a.unescape_value().map_err(|e| e.to_string())?;
+                    node.details.insert(key, Value::String(value.into_owned()));
+                }
+                let field = |key| node.details.get(key).and_then(number);
+                node.estimated_rows = field("EstimateRows");
+                node.estimated_cost = field("EstimatedTotalSubtreeCost");
+                node.operation = attribute(&e, "PhysicalOp").unwrap_or_default();
+                open.push(node);
+            }
+            Event::End(e) if e.local_name().as_ref() == b"RelOp" => {
+                let node = open.pop().ok_or("Malformed showplan XML")?;
+                match open.last_mut() {
+                    Some(parent) => parent.children.push(node),
+                    None => plan.roots.push(node),
+                }
+            }
+            Event::Start(e) | Event::Empty(e) => match e.local_name().as_ref() {
+                b"QueryPlan" => {
+                    let compile = attribute(&e, "CompileTime").and_then(|v| v.parse().ok());
+                    add(&mut plan.planning_ms, compile);
+                }
+                b"QueryTimeStats" => {
+                    let elapsed = attribute(&e, "ElapsedTime").and_then(|v| v.parse().ok());
+                    add(&mut plan.execution_ms, elapsed);
+                }
+                b"Object" => {
+                    let Some(node) = open.last_mut().filter(|n| n.relation.is_none()) else {
+                        continue;
+                    };
+                    let part =
+                        |name| attribute(&e, name).map(|v| v.trim_matches(['[', ']']).to_string());
+                    node.relation = match (part("Schema"), part("Table")) {
+                        (Some(schema), Some(table)) => Some(format!("{}.{}", schema, table)),
+                        (None, table) => table,
+                        (Some(_), None) => None,
+                    };
+                }
+                // One element per thread of a parallel operator
+                b"RunTimeCountersPerThread" => {
+                    let Some(node) = open.last_mut() else {
+                        continue;
+                    };
+                    let rows = attribute(&e, "ActualRows").and_then(|v| v.parse().ok());
+                    add(&mut node.actual_rows, rows);
+                    let elapsed: Option<f64> =
+                        attribute(&e, "ActualElapsedms").and_then(|v| v.parse().ok());
+                    if let Some(elapsed) = elapsed {
+                        node.actual_ms = Some(node.actual_ms.unwrap_or(0.0).max(elapsed));
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,245 @@
+// Column selection and simple per-column transforms for exports (rename, date
+// format, null placeholder, masking), applied to row batches on their way to
+// the file so exports come out in the shape downstream tools expect
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::Write;
+use std::sync::Mutex as StdMutex;
+
+use crate::db::RowBatch;
+use crate::values::Cell;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Mask {
+    Redact,                    // every character replaced
+    KeepLast { count: usize }, // e.g. the last digits of a card number
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportColumn {
+    pub name: String, // as named in the result
+    #[serde(default)]
+    pub rename: Option<String>,
+    // strftime pattern for date and time values, e.g. "%d/%m/%Y"
+    #[serde(default)]
+    pub date_format: Option<String>,
+    #[serde(default)]
+    pub null_as: Option<String>,
+    #[serde(default)]
+    pub mask: Option<Mask>,
+}
+
+pub enum Temporal {
+    Date(NaiveDate),
+    DateTime(NaiveDateTime), // wall-clock time as rendered, any offset dropped
+    Time(NaiveTime),
+}
+
+// Reads a date, timestamp or time as the value decoders render them
+pub fn parse_temporal(text: &str) -> Option<Temporal> {
+    const FORMATS: [&str; 2] = ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"];
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Some(Temporal::Date(date));
+    }
+    if let Ok(time) = NaiveTime::parse_from_str(text, "%H:%M:%S%.f") {
+        return Some(Temporal::Time(time));
+    }
+    DateTime::parse_from_rfc3339(text)
+        .or_else(|_| DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S%.f%#z"))
+        .map(|d| d.naive_local())
+        .ok()
+        .or_else(|| {
+            FORMATS
+                .iter()
+                .find_map(|f| NaiveDateTime::parse_from_str(text, f).ok())
+        })
+        .map(Temporal::DateTime)
+}
+
+// Values that aren't dates are left as they are, and so are values the
+// pattern asks more of than they have, e.g. %H for a date
+fn format_date(value: Value, pattern: &str) -> Value {
+    let mut formatted = String::new();
+    let written = match value.as_str().and_then(parse_temporal) {
+        Some(Temporal::Date(d)) => write!(formatted, "{}", d.format(pattern)),
+        Some(Temporal::DateTime(d)) => write!(formatted, "{}", d.format(pattern)),
+        Some(Temporal::Time(t)) => write!(formatted, "{}", t.format(pattern)),
+        None => return value,
+    };
+    match written {
+        Ok(()) => Value::String(formatted),
+        Err(_) => value,
+    }
+}
+
+fn mask(value: Value, mask: Mask) -> Value {
+    let text = match value {
+        Value::String(s) => s,
+        other => other.to_string(),
+    };
+    let keep = match mask {
+        Mask::Redact => 0,
+        Mask::KeepLast { count } => count,
+    };
+    let len = text.chars().count();
+    let masked = text
+        .chars()
+        .enumerate()
+        .map(|(i, c)| if i + keep < len { '*' } else { c })
+        .collect();
+    Value::String(masked)
+}
+
+fn transform(cell: Cell, column: &ExportColumn) -> Cell {
+    let Cell::Value(mut value) = cell else {
+        return match &column.null_as {
+            Some(placeholder) => Cell::Value(Value::String(placeholder.clone())),
+            None => cell,
+        };
+    };
+    if let Some(pattern) = &column.date_format {
+        value = format_date(value, pattern);
+    }
+    if let Some(m) = column.mask {
+        value = mask(value, m);
+    }
+    Cell::Value(value)
+}
+
+// Applies the export columns to each batch. Without any, batches pass through
+// untouched. Columns the result lacks come out empty, as a document
+// collection may only have them in later batches.
+pub struct ColumnShaper {
+    columns: Vec<ExportColumn>,
+    // For each export column, its position in the result
+    positions: StdMutex<Vec<Option<usize>>>,
+}
+
+impl ColumnShaper {
+    pub fn new(columns: Vec<ExportColumn>) -> Result<Self, String> {
+        for column in &columns {
+            // chrono panics on a bad pattern when formatting, so check it first
+            if let Some(pattern) = &column.date_format {
+                if StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error)) {
+                    return Err(format!(
+                        "Invalid date format for {}: {}",
+                        column.name, pattern
+                    ));
+                }
+            }
+        }
+        Ok(ColumnShaper {
+            columns,
+            positions: StdMutex::new(Vec::new()),
+        })
+    }
+
+    pub fn apply(&self, mut batch: RowBatch) -> RowBatch {
+        if self.columns.is_empty() {
+            return batch;
+        }
+        let mut positions = self.positions.lock().unwrap();
+        if let Some(names) = batch.columns.take() {
+            *positions = self
+                .columns
+                .iter()
+                .map(|c| names.iter().position(|n| *n == c.name))
+                .collect();
+            batch.columns = Some(
+                self.columns
+                    .iter()
+                    .map(|c| c.rename.clone().unwrap_or_else(|| c.name.clone()))
+                    .collect(),
+            );
+        }
+        if let Some(meta) = batch.column_meta.take() {
+            let shaped = positions
+                .iter()
+                .zip(&self.columns)
+                .filter_map(|(position, column)| {
+                    let mut meta = meta.get((*position)?)?.clone();
+                    meta.name = column.rename.clone().unwrap_or_else(|| column.name.clone());
+                    // Formatted and masked values are plain text
+                    if column.date_format.is_some() || column.mask.is_some() {
+                        meta.value_tag = None;
+                        meta.type_name = "TEXT".to_string();
+                    }
+                    Some(meta)
+                })
+                .collect::<Vec<_>>();
+            // Metadata only lines up when every column was found
+            if shaped.len() == self.columns.len() {
+                batch.column_meta = Some(shaped);
+            }
+        }
+        batch.rows = batch
+            .rows
+            .into_iter()
+            .map(|row| {
+                positions
+                    .iter()
+                    .zip(&self.columns)
+                    .map(|(position, column)| {
+                        let cell = position
+                            .and_then(|i| row.get(i).cloned())
+                            .unwrap_or(Cell::Null);
+                        transform(cell, column)
+                    })
+                    .collect()
+            })
+            .collect();
+        batch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(value: &str) -> Value {
+        Value::String(value.to_string())
+    }
+
+    #[test]
+    fn formats_dates_times_and_timestamps() {
+        assert_eq!(
+            format_date(text("2024-03-09"), "%d/%m/%Y"),
+            text("09/03/2024")
+        );
+        assert_eq!(
+            format_date(text("2024-03-09 14:05:00"), "%d/%m/%Y %H:%M"),
+            text("09/03/2024 14:05")
+        );
+        assert_eq!(format_date(text("14:05:00"), "%H.%M"), text("14.05"));
+    }
+
+    #[test]
+    fn keeps_values_the_pattern_does_not_fit() {
+        assert_eq!(
+            format_date(text("2024-03-09"), "%Y-%m-%d %H:%M:%S"),
+            text("2024-03-09")
+        );
+        assert_eq!(format_date(text("14:05:00"), "%Y"), text("14:05:00"));
+    }
+
+    #[test]
+    fn leaves_other_values_alone() {
+        assert_eq!(format_date(text("not a date"), "%Y"), text("not a date"));
+        assert_eq!(format_date(Value::from(42), "%Y"), Value::from(42));
+    }
+
+    #[test]
+    fn rejects_invalid_patterns() {
+        let column = ExportColumn {
+            name: "created".to_string(),
+            rename: None,
+            date_format: Some("%Q".to_string()),
+            null_as: None,
+            mask: None,
+        };
+        assert!(ColumnShaper::new(vec![column]).is_err());
+    }
+}
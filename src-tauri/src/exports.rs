@@ -0,0 +1,120 @@
+// Export templates: where exported files go and what they are called, so
+// repeated exports land in predictable places
+use chrono::Local;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+use crate::export_columns::ExportColumn;
+use crate::settings::ExportSettings;
+use crate::sqltext;
+
+#[derive(Serialize)]
+pub struct ExportedFile {
+    pub path: String,
+    pub rows: Option<u64>, // not known for COPY exports
+    pub bytes: u64,
+}
+
+impl ExportedFile {
+    pub fn written(path: String, rows: Option<u64>) -> Result<Self, String> {
+        let bytes = fs::metadata(&path).map_err(|e| e.to_string())?.len();
+        Ok(ExportedFile { path, rows, bytes })
+    }
+}
+
+// The file to write and the options to write it with
+pub struct Destination {
+    pub path: String,
+    pub format: String,
+    pub export: ExportSettings,
+    pub columns: Vec<ExportColumn>, // the template's; empty exports every column as is
+}
+
+fn extension(format: &str) -> &str {
+    match format {
+        "csv_semicolon" => "csv",
+        "excel" => "xlsx",
+        other => other,
+    }
+}
+
+// Placeholder values may come from identifiers, which can hold anything
+fn file_part(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            c if c.is_alphanumeric() || "-_.".contains(c) => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+// {table} is the first table the query reads, or "query" when there is none
+pub fn file_name(pattern: &str, connection: &str, sql: &str, format: &str) -> String {
+    let table = sqltext::table_refs(sql)
+        .into_iter()
+        .next()
+        .map_or_else(|| "query".to_string(), |(_, table)| table);
+    let now = Local::now();
+    let name = pattern
+        .replace("{connection}", &file_part(connection))
+        .replace("{table}", &file_part(&table))
+        .replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{time}", &now.format("%H%M%S").to_string());
+    match Path::new(&name).extension() {
+        Some(_) => name,
+        None => format!("{}.{}", name, extension(format)),
+    }
+}
+
+// An explicit path wins over the template's folder and pattern, but the
+// template's format options still apply
+pub fn resolve(
+    export: &ExportSettings,
+    template: Option<&str>,
+    path: Option<String>,
+    format: Option<String>,
+    connection: &str,
+    sql: &str,
+    default_folder: &Path,
+) -> Result<Destination, String> {
+    let template = template
+        .map(|name| {
+            export
+                .templates
+                .iter()
+                .find(|t| t.name == name)
+                .ok_or_else(|| format!("Export template '{}' not found", name))
+        })
+        .transpose()?;
+    let format = format
+        .or_else(|| template.and_then(|t| t.format.clone()))
+        .unwrap_or_else(|| export.default_format.clone());
+
+    let mut options = export.clone();
+    if let Some(t) = template {
+        options.csv_delimiter = t.csv_delimiter.clone().unwrap_or(options.csv_delimiter);
+        options.include_headers = t.include_headers.unwrap_or(options.include_headers);
+    }
+
+    let path = match (path, template) {
+        (Some(path), _) => path,
+        (None, Some(t)) => {
+            let folder = t.folder.as_deref().map_or(default_folder, Path::new);
+            fs::create_dir_all(folder)
+                .map_err(|e| format!("Failed to create {}: {}", folder.display(), e))?;
+            folder
+                .join(file_name(&t.filename_pattern, connection, sql, &format))
+                .to_string_lossy()
+                .into_owned()
+        }
+        (None, None) => return Err("Either a path or an export template is required".to_string()),
+    };
+    Ok(Destination {
+        path,
+        format,
+        export: options,
+        columns: template.map(|t| t.columns.clone()).unwrap_or_default(),
+    })
+}
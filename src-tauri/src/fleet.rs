@@ -0,0 +1,87 @@
+// Fleet overview: a quick health check of every saved connection, open or
+// not, run a few at a time so a long list doesn't open dozens of sockets at
+// once
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::db::{self, DbClient};
+use crate::diagnostics::{self, ConnectionStatus};
+use crate::environment::Environment;
+use crate::SavedConnection;
+
+pub const DEFAULT_FLEET_CONCURRENCY: usize = 8;
+const MAX_FLEET_CONCURRENCY: usize = 32;
+
+#[derive(Debug, Serialize)]
+pub struct FleetMember {
+    pub name: String,
+    pub conn_type: String,
+    pub environment: Option<Environment>,
+    pub open: bool, // checked over the app's open connection
+    pub reachable: bool,
+    pub latency_ms: Option<u64>, // round trip of the version query
+    pub server_version: Option<String>,
+    pub error: Option<String>,
+}
+
+// Connections that aren't open get a client of their own, dropped once
+// checked; `timeout` bounds connecting and the check each
+async fn check(
+    connection: SavedConnection,
+    open: Option<DbClient>,
+    timeout: Duration,
+) -> FleetMember {
+    let mut member = FleetMember {
+        name: connection.name.clone(),
+        conn_type: connection.conn_type.clone(),
+        environment: connection.environment,
+        open: open.is_some(),
+        reachable: false,
+        latency_ms: None,
+        server_version: None,
+        error: None,
+    };
+    let no_answer = || format!("No answer within {}s", timeout.as_secs());
+    let client = match open {
+        Some(client) => client,
+        None => {
+            let connect = db::create_client_with_tls(&connection.url, &connection.tls);
+            match tokio::time::timeout(timeout, connect).await {
+                Ok(Ok(client)) => client,
+                Ok(Err(e)) => {
+                    member.error = Some(e);
+                    return member;
+                }
+                Err(_) => {
+                    member.error = Some(no_answer());
+                    return member;
+                }
+            }
+        }
+    };
+    match tokio::time::timeout(timeout, diagnostics::connection_info(&client)).await {
+        Ok(info) => {
+            member.reachable = matches!(info.status, ConnectionStatus::Connected);
+            member.latency_ms = Some(info.latency_ms);
+            member.server_version = info.server.server_version;
+            member.error = info.error;
+        }
+        Err(_) => member.error = Some(no_answer()),
+    }
+    member
+}
+
+// In the order the connections were saved
+pub async fn fleet_status(
+    connections: Vec<(SavedConnection, Option<DbClient>)>,
+    concurrency: usize,
+    timeout: Duration,
+) -> Vec<FleetMember> {
+    let concurrency = concurrency.clamp(1, MAX_FLEET_CONCURRENCY);
+    stream::iter(connections)
+        .map(|(connection, open)| check(connection, open, timeout))
+        .buffered(concurrency)
+        .collect()
+        .await
+}
@@ -0,0 +1,99 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Mutex as StdMutex;
+
+use crate::storage;
+
+pub const HISTORY_FILE: &str = "query_history.json";
+
+// Oldest entries are dropped beyond this
+const MAX_HISTORY_ENTRIES: usize = 5000;
+
+pub const DEFAULT_HISTORY_LIMIT: usize = 200;
+
+// The file is read, changed and written back by one query at a time, so
+// concurrent queries don't drop each other's entries
+static WRITING: StdMutex<()> = StdMutex::new(());
+
+// One execution of a statement, successful or not
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: String,
+    pub connection: String,
+    pub sql: String,
+    pub executed_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub rows: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl HistoryEntry {
+    pub fn new(
+        connection: &str,
+        sql: &str,
+        executed_at: DateTime<Utc>,
+        duration_ms: u64,
+        outcome: Result<u64, &str>,
+    ) -> Self {
+        let (rows, error) = match outcome {
+            Ok(rows) => (Some(rows), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        HistoryEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            connection: connection.to_string(),
+            sql: sql.to_string(),
+            executed_at,
+            duration_ms,
+            rows,
+            error,
+        }
+    }
+}
+
+// Stored oldest first
+pub fn record(path: &Path, entry: HistoryEntry) -> Result<(), String> {
+    let _guard = WRITING.lock().unwrap();
+    let mut entries: Vec<HistoryEntry> = storage::read_json(path)?;
+    entries.push(entry);
+    let excess = entries.len().saturating_sub(MAX_HISTORY_ENTRIES);
+    entries.drain(..excess);
+    storage::write_json(path, &entries)
+}
+
+// Newest first, optionally for one connection and only entries whose SQL
+// contains `text` (case-insensitive)
+pub fn search(
+    path: &Path,
+    text: Option<&str>,
+    connection: Option<&str>,
+    limit: Option<usize>,
+) -> Result<Vec<HistoryEntry>, String> {
+    let entries: Vec<HistoryEntry> = storage::read_json(path)?;
+    let needle = text.map(str::to_lowercase);
+    Ok(entries
+        .into_iter()
+        .rev()
+        .filter(|e| connection.map_or(true, |c| e.connection == c))
+        .filter(|e| {
+            needle
+                .as_deref()
+                .map_or(true, |n| e.sql.to_lowercase().contains(n))
+        })
+        .take(limit.unwrap_or(DEFAULT_HISTORY_LIMIT))
+        .collect())
+}
+
+// Clears one connection's entries, or everything; returns how many went
+pub fn clear(path: &Path, connection: Option<&str>) -> Result<usize, String> {
+    let _guard = WRITING.lock().unwrap();
+    let mut entries: Vec<HistoryEntry> = storage::read_json(path)?;
+    let before = entries.len();
+    entries.retain(|e| connection.is_some_and(|c| e.connection != c));
+    let removed = before - entries.len();
+    if removed > 0 {
+        storage::write_json(path, &entries)?;
+    }
+    Ok(removed)
+}
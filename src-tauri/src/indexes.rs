@@ -0,0 +1,152 @@
+// Indexes of a table for the table detail panel. For MongoDB the table is a
+// collection and its schema the database.
+use serde::Serialize;
+use sqlx::Row;
+use std::collections::HashMap;
+
+use crate::db::{self, DbClient, Dialect};
+use crate::edits::TableRef;
+use crate::mongo;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TableIndex {
+    pub name: String,
+    pub columns: Vec<String>, // key columns in order; expressions as written
+    pub unique: bool,
+    pub primary: bool,
+    pub index_type: String, // e.g. btree, hash, gin, fulltext, clustered, text
+    pub size_bytes: Option<i64>, // None where the server doesn't tell
+}
+
+// Folds (index, column) rows, ordered by index, into indexes
+fn push_column(indexes: &mut Vec<TableIndex>, mut index: TableIndex, column: Option<String>) {
+    match indexes.last_mut() {
+        Some(last) if last.name == index.name => last.columns.extend(column),
+        _ => {
+            index.columns.extend(column);
+            indexes.push(index);
+        }
+    }
+}
+
+pub async fn get_indexes(client: &DbClient, table: &TableRef) -> Result<Vec<TableIndex>, String> {
+    match client {
+        DbClient::Postgres(pool) => {
+            let rows = sqlx::query(
+                "SELECT i.relname::text, ix.indisunique, ix.indisprimary, am.amname::text, \
+                 pg_relation_size(i.oid), \
+                 ARRAY(SELECT pg_get_indexdef(ix.indexrelid, k, true) \
+                       FROM generate_series(1, ix.indnkeyatts) k ORDER BY k) \
+                 FROM pg_index ix \
+                 JOIN pg_class i ON i.oid = ix.indexrelid \
+                 JOIN pg_am am ON am.oid = i.relam \
+                 WHERE ix.indrelid = $1::regclass \
+                 ORDER BY i.relname",
+            )
+            .bind(table.qualified(Dialect::Postgres))
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            Ok(rows
+                .iter()
+                .map(|r| TableIndex {
+                    name: r.get(0),
+                    unique: r.get(1),
+                    primary: r.get(2),
+                    index_type: r.get(3),
+                    size_bytes: r.get(4),
+                    columns: r.get(5),
+                })
+                .collect())
+        }
+        DbClient::Mysql(pool) => {
+            // Functional key parts have no column name and are left out
+            let rows = sqlx::query(
+                "SELECT INDEX_NAME, COLUMN_NAME, CAST(NON_UNIQUE AS SIGNED), INDEX_TYPE \
+                 FROM information_schema.STATISTICS \
+                 WHERE TABLE_SCHEMA = COALESCE(?, DATABASE()) AND TABLE_NAME = ? \
+                 ORDER BY INDEX_NAME, SEQ_IN_INDEX",
+            )
+            .bind(db::mysql_database(table.schema.clone()))
+            .bind(&table.table)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            // InnoDB's page counts need read access to the mysql schema; without
+            // it sizes are just unknown
+            let sizes: HashMap<String, i64> = sqlx::query(
+                "SELECT index_name, CAST(stat_value * @@innodb_page_size AS SIGNED) \
+                 FROM mysql.innodb_index_stats \
+                 WHERE database_name = COALESCE(?, DATABASE()) AND table_name = ? \
+                   AND stat_name = 'size'",
+            )
+            .bind(db::mysql_database(table.schema.clone()))
+            .bind(&table.table)
+            .fetch_all(pool)
+            .await
+            .map(|rows| rows.iter().map(|r| (r.get(0), r.get(1))).collect())
+            .unwrap_or_default();
+
+            let mut indexes = Vec::new();
+            for r in &rows {
+                let name: String = r.get(0);
+                let index = TableIndex {
+                    primary: name == "PRIMARY",
+                    size_bytes: sizes.get(&name).copied(),
+                    unique: r.get::<i64, _>(2) == 0,
+                    index_type: r.get::<String, _>(3).to_lowercase(),
+                    columns: Vec::new(),
+                    name,
+                };
+                push_column(&mut indexes, index, r.try_get(1).ok().flatten());
+            }
+            Ok(indexes)
+        }
+        DbClient::Mssql(pool) => {
+            let mut conn = pool.get().await?;
+            let schema = table.schema.clone().unwrap_or_else(|| "dbo".to_string());
+            // Included columns aren't part of the key; a heap (type 0) isn't an index
+            let query = "SELECT i.name, c.name, i.is_unique, i.is_primary_key, i.type_desc, \
+                 (SELECT CAST(SUM(a.used_pages) AS bigint) * 8192 FROM sys.partitions p \
+                  JOIN sys.allocation_units a ON a.container_id = p.partition_id \
+                  WHERE p.object_id = i.object_id AND p.index_id = i.index_id) \
+                 FROM sys.indexes i \
+                 LEFT JOIN sys.index_columns ic ON ic.object_id = i.object_id AND ic.index_id = i.index_id \
+                   AND ic.is_included_column = 0 \
+                 LEFT JOIN sys.columns c ON c.object_id = ic.object_id AND c.column_id = ic.column_id \
+                 WHERE i.object_id = OBJECT_ID(QUOTENAME(@P1) + '.' + QUOTENAME(@P2)) AND i.type > 0 \
+                 ORDER BY i.name, ic.key_ordinal";
+            let rows = conn
+                .query(query, &[&schema, &table.table])
+                .await
+                .map_err(|e| e.to_string())?
+                .into_first_result()
+                .await
+                .map_err(|e| e.to_string())?;
+            let text = |r: &tiberius::Row, i: usize| {
+                r.try_get::<&str, _>(i)
+                    .ok()
+                    .flatten()
+                    .map(|s| s.to_string())
+            };
+            let flag = |r: &tiberius::Row, i: usize| {
+                r.try_get::<bool, _>(i).ok().flatten().unwrap_or(false)
+            };
+            let mut indexes = Vec::new();
+            for r in &rows {
+                let index = TableIndex {
+                    name: text(r, 0).unwrap_or_default(),
+                    columns: Vec::new(),
+                    unique: flag(r, 2),
+                    primary: flag(r, 3),
+                    index_type: text(r, 4).unwrap_or_default().to_lowercase(),
+                    size_bytes: r.try_get::<i64, _>(5).ok().flatten(),
+                };
+                push_column(&mut indexes, index, text(r, 1));
+            }
+            Ok(indexes)
+        }
+        DbClient::Mongo(_) => mongo::list_indexes(client, table).await,
+        _ => Err("Index listing is not supported for this database type".to_string()),
+    }
+}
@@ -0,0 +1,344 @@
+// Long-running operations (exports, imports, queries) run in the background
+// as jobs: the command starting one returns the job id straight away, progress
+// and the outcome follow as "job-progress" and "job-finished" events, and jobs
+// can be listed and cancelled
+use chrono::{DateTime, Utc};
+use futures::future::{AbortHandle, Abortable};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use tauri::Emitter;
+
+use crate::db::{self, CsvSink, JsonSink, QueryOptions, RowBatch};
+use crate::export_columns::ColumnShaper;
+use crate::exports::{Destination, ExportedFile};
+use crate::running::{RunningQueries, TrackedQuery};
+use crate::xlsx::XlsxSink;
+
+// Finished jobs kept for list_jobs; older ones are dropped
+const MAX_FINISHED_JOBS: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Export,
+    Import,
+    Query,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct JobProgress {
+    pub done: u64,          // rows written or imported (committed) so far
+    pub total: Option<u64>, // when known up front
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobInfo {
+    pub id: String,
+    pub kind: JobKind,
+    pub connection: String,
+    pub description: String, // e.g. "Export to /home/me/orders.csv"
+    pub status: JobStatus,
+    pub progress: JobProgress,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+// Sent once per job; `result` is what the blocking command would have returned
+#[derive(Clone, Serialize)]
+pub struct JobFinishedEvent {
+    pub job: JobInfo,
+    pub result: Option<Value>,
+}
+
+struct Job {
+    info: JobInfo,
+    abort: AbortHandle,
+}
+
+#[derive(Default, Clone)]
+pub struct Jobs {
+    jobs: Arc<StdMutex<HashMap<String, Job>>>,
+}
+
+// Handed to a job's work to report how far it got
+#[derive(Clone)]
+pub struct JobReporter {
+    pub id: String,
+    jobs: Jobs,
+    app: tauri::AppHandle,
+}
+
+impl JobReporter {
+    pub fn progress(&self, progress: JobProgress) {
+        let info = {
+            let mut jobs = self.jobs.jobs.lock().unwrap();
+            let Some(job) = jobs.get_mut(&self.id) else {
+                return;
+            };
+            job.info.progress = progress;
+            job.info.clone()
+        };
+        if let Err(e) = self.app.emit("job-progress", info) {
+            log::warn!("Failed to emit progress of job {}: {}", self.id, e);
+        }
+    }
+}
+
+impl Jobs {
+    // Newest first
+    pub fn list(&self) -> Vec<JobInfo> {
+        let jobs = self.jobs.lock().unwrap();
+        let mut list: Vec<JobInfo> = jobs.values().map(|j| j.info.clone()).collect();
+        list.sort_by_key(|j| std::cmp::Reverse(j.started_at));
+        list
+    }
+
+    // Runs `work` in the background and returns the new job's id
+    pub fn start<W, F, T>(
+        &self,
+        app: &tauri::AppHandle,
+        kind: JobKind,
+        connection: &str,
+        description: String,
+        work: W,
+    ) -> String
+    where
+        W: FnOnce(JobReporter) -> F,
+        F: Future<Output = Result<T, String>> + Send + 'static,
+        T: Serialize,
+    {
+        let id = uuid::Uuid::new_v4().to_string();
+        let (abort, registration) = AbortHandle::new_pair();
+        let info = JobInfo {
+            id: id.clone(),
+            kind,
+            connection: connection.to_string(),
+            description,
+            status: JobStatus::Running,
+            progress: JobProgress::default(),
+            started_at: Utc::now(),
+            finished_at: None,
+            error: None,
+        };
+        self.jobs
+            .lock()
+            .unwrap()
+            .insert(id.clone(), Job { info, abort });
+
+        let reporter = JobReporter {
+            id: id.clone(),
+            jobs: self.clone(),
+            app: app.clone(),
+        };
+        let future = Abortable::new(work(reporter.clone()), registration);
+        tokio::spawn(async move {
+            let outcome = match future.await {
+                Ok(Ok(result)) => Ok(serde_json::to_value(result).ok()),
+                Ok(Err(e)) => Err(e),
+                Err(_) => Err("Job cancelled".to_string()),
+            };
+            reporter.jobs.finish(&reporter.app, &reporter.id, outcome);
+        });
+        id
+    }
+
+    fn finish(&self, app: &tauri::AppHandle, id: &str, outcome: Result<Option<Value>, String>) {
+        let info = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let Some(job) = jobs.get_mut(id) else {
+                return;
+            };
+            job.info.finished_at = Some(Utc::now());
+            // A cancelled job stays cancelled, however its work ended
+            if job.info.status == JobStatus::Running {
+                job.info.status = match &outcome {
+                    Ok(_) => JobStatus::Completed,
+                    Err(_) => JobStatus::Failed,
+                };
+                job.info.error = outcome.as_ref().err().cloned();
+            }
+            let info = job.info.clone();
+
+            let mut finished: Vec<(DateTime<Utc>, String)> = jobs
+                .values()
+                .filter_map(|j| j.info.finished_at.map(|at| (at, j.info.id.clone())))
+                .collect();
+            if finished.len() > MAX_FINISHED_JOBS {
+                finished.sort();
+                for (_, old) in &finished[..finished.len() - MAX_FINISHED_JOBS] {
+                    jobs.remove(old);
+                }
+            }
+            info
+        };
+        let event = JobFinishedEvent {
+            result: outcome
+                .ok()
+                .flatten()
+                .filter(|_| info.status == JobStatus::Completed),
+            job: info,
+        };
+        if let Err(e) = app.emit("job-finished", event) {
+            log::warn!("Failed to emit the end of job {}: {}", id, e);
+        }
+    }
+
+    // A job's query is stopped on the server where it can be, then its work
+    // is dropped
+    pub async fn cancel(&self, id: &str, running: &RunningQueries) -> Result<(), String> {
+        let abort = {
+            let mut jobs = self.jobs.lock().unwrap();
+            let job = jobs.get_mut(id).ok_or("Job not found")?;
+            if job.info.status != JobStatus::Running {
+                return Err("Job is not running".to_string());
+            }
+            job.info.status = JobStatus::Cancelled;
+            job.abort.clone()
+        };
+        // Export and query jobs run their query under the job's id
+        let _ = running.cancel(id).await;
+        abort.abort();
+        Ok(())
+    }
+}
+
+// Writer for the formats exported batch by batch as rows stream in
+pub enum ExportSink {
+    Csv(Box<CsvSink>),
+    Json(JsonSink),
+    Xlsx(Box<XlsxSink>),
+}
+
+impl ExportSink {
+    // None for formats without a streaming writer
+    pub fn create(destination: &Destination) -> Result<Option<Self>, String> {
+        let path = &destination.path;
+        Ok(Some(match destination.format.as_str() {
+            "csv" => ExportSink::Csv(Box::new(CsvSink::create(path, &destination.export)?)),
+            "json" => ExportSink::Json(JsonSink::create(path, false)?),
+            "jsonl" => ExportSink::Json(JsonSink::create(path, true)?),
+            "excel" | "xlsx" => ExportSink::Xlsx(Box::new(XlsxSink::create(
+                path,
+                destination.export.include_headers,
+            ))),
+            _ => return Ok(None),
+        }))
+    }
+
+    pub fn write(&self, batch: RowBatch) -> Result<(), String> {
+        match self {
+            ExportSink::Csv(sink) => sink.write(batch),
+            ExportSink::Json(sink) => sink.write(batch),
+            ExportSink::Xlsx(sink) => sink.write(batch),
+        }
+    }
+
+    pub fn finish(self) -> Result<(), String> {
+        match self {
+            ExportSink::Csv(sink) => sink.finish(),
+            ExportSink::Json(sink) => sink.finish(),
+            ExportSink::Xlsx(sink) => sink.finish(),
+        }
+    }
+}
+
+// An export file being written, removed when the export fails or is
+// cancelled (its work dropped) before it is kept. Declare it before the sink
+// so the file is closed before it is removed.
+pub struct PartialFile<'a> {
+    path: &'a str,
+    keep: bool,
+}
+
+impl<'a> PartialFile<'a> {
+    // Until `claim`, the file isn't ours to remove
+    pub fn new(path: &'a str) -> Self {
+        PartialFile { path, keep: true }
+    }
+
+    // Once the file has been created or truncated for this export
+    pub fn claim(&mut self) {
+        self.keep = false;
+    }
+
+    pub fn keep(mut self) {
+        self.keep = true;
+    }
+}
+
+impl Drop for PartialFile<'_> {
+    fn drop(&mut self) {
+        if self.keep {
+            return;
+        }
+        match std::fs::remove_file(self.path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Failed to remove the partial export {}: {}", self.path, e),
+        }
+    }
+}
+
+// Streams the query's result into the export destination, reporting the rows
+// written as progress. Formats without a streaming writer are written in one
+// go once the whole result is in.
+pub async fn export(
+    running: &RunningQueries,
+    query: TrackedQuery,
+    destination: Destination,
+    options: &QueryOptions,
+    job: &JobReporter,
+) -> Result<ExportedFile, String> {
+    let shaper = ColumnShaper::new(destination.columns.clone())?;
+    let mut partial = PartialFile::new(&destination.path);
+    let Some(sink) = ExportSink::create(&destination)? else {
+        // A file already there is only replaced once the result is in
+        if !std::path::Path::new(&destination.path).exists() {
+            partial.claim();
+        }
+        let rows = db::export_data(
+            &query.client,
+            query.sql,
+            destination.format.clone(),
+            destination.path.clone(),
+            &destination.export,
+            options,
+            &shaper,
+        )
+        .await?;
+        partial.keep();
+        return ExportedFile::written(destination.path, Some(rows));
+    };
+    partial.claim();
+    let written = AtomicU64::new(0);
+    let write = |batch: RowBatch| {
+        let rows = batch.rows.len() as u64;
+        sink.write(shaper.apply(batch))?;
+        job.progress(JobProgress {
+            done: written.fetch_add(rows, Ordering::Relaxed) + rows,
+            ..JobProgress::default()
+        });
+        Ok(())
+    };
+    let rows = running
+        .stream(query, options, db::DEFAULT_STREAM_BATCH, &write)
+        .await?;
+    sink.finish()?;
+    partial.keep();
+    ExportedFile::written(destination.path, Some(rows))
+}
@@ -0,0 +1,248 @@
+// JSON_TABLE queries for MySQL 8+ and MariaDB 10.6+, generated from a sample of
+// a JSON column, so its documents can be browsed as rows and columns
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::db::{self, DbClient, Dialect, QueryOptions};
+use crate::edits::TableRef;
+use crate::quoting::{quote_ident, quote_text};
+
+const DEFAULT_SAMPLE_SIZE: usize = 200;
+const MAX_SAMPLE_SIZE: usize = 10_000;
+// Longest VARCHAR a text field becomes; longer text is kept as JSON
+const MAX_VARCHAR: usize = 16_383;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct JsonTableOptions {
+    // JSON path of what to expand, e.g. "$.items"; an array there gives a row
+    // per element. Defaults to the whole document.
+    pub path: Option<String>,
+    pub sample_size: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonTableColumn {
+    pub name: String,
+    pub path: String,     // relative to the row path, e.g. $.name
+    pub sql_type: String, // e.g. BIGINT, VARCHAR(64) or JSON for nested values
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JsonTableQuery {
+    pub sql: String,
+    pub row_path: String,
+    pub columns: Vec<JsonTableColumn>,
+    pub sampled: usize, // documents the structure was taken from
+}
+
+// What the sample held under one key
+#[derive(Default)]
+struct FieldKinds {
+    integer: bool,
+    float: bool,
+    boolean: bool,
+    text: Option<usize>, // longest string, in characters
+    nested: bool,
+}
+
+impl FieldKinds {
+    fn add(&mut self, value: &Value) {
+        match value {
+            Value::Null => {}
+            Value::Bool(_) => self.boolean = true,
+            Value::Number(n) if n.is_i64() || n.is_u64() => self.integer = true,
+            Value::Number(_) => self.float = true,
+            Value::String(s) => {
+                let length = s.chars().count();
+                self.text = Some(self.text.map_or(length, |l| l.max(length)));
+            }
+            Value::Array(_) | Value::Object(_) => self.nested = true,
+        }
+    }
+
+    fn sql_type(&self) -> String {
+        let numeric = self.integer || self.float;
+        match (numeric, self.boolean, self.text, self.nested) {
+            (true, false, None, false) if self.float => "DOUBLE".to_string(),
+            (true, false, None, false) => "BIGINT".to_string(),
+            (false, true, None, false) => "BOOLEAN".to_string(),
+            (false, false, Some(length), false) if length <= MAX_VARCHAR => {
+                format!(
+                    "VARCHAR({})",
+                    length.max(1).next_power_of_two().clamp(64, MAX_VARCHAR)
+                )
+            }
+            // Nested, mixed, over-long or only ever null
+            _ => "JSON".to_string(),
+        }
+    }
+}
+
+// A key as a JSON path step; keys that aren't plain identifiers are quoted
+fn path_step(key: &str) -> String {
+    let plain = key
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        format!(".{}", key)
+    } else {
+        format!(".\"{}\"", key.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+// MySQL returns JSON_EXTRACT as JSON, MariaDB as text
+fn sampled_value(value: Value) -> Value {
+    match value {
+        Value::String(text) => serde_json::from_str(&text).unwrap_or(Value::String(text)),
+        other => other,
+    }
+}
+
+// Samples `column` of `table` and writes a JSON_TABLE query with a column per
+// key found, typed by the values seen. The table's primary key is selected
+// alongside so each row can be traced back.
+pub async fn json_table_query(
+    client: &DbClient,
+    table: &TableRef,
+    column: &str,
+    options: JsonTableOptions,
+) -> Result<JsonTableQuery, String> {
+    if client.dialect() != Some(Dialect::Mysql) {
+        return Err("JSON_TABLE is only available on MySQL and MariaDB".to_string());
+    }
+    let dialect = Dialect::Mysql;
+    let base_path = options
+        .path
+        .map(|p| p.trim().trim_end_matches("[*]").to_string())
+        .filter(|p| !p.is_empty())
+        .unwrap_or_else(|| "$".to_string());
+    let sample_size = options
+        .sample_size
+        .unwrap_or(DEFAULT_SAMPLE_SIZE)
+        .clamp(1, MAX_SAMPLE_SIZE);
+    let source = table.qualified(dialect);
+    let quoted_column = quote_ident(dialect, column);
+
+    let sample_sql = format!(
+        "SELECT JSON_EXTRACT({col}, ?) FROM {} WHERE {col} IS NOT NULL LIMIT {}",
+        source,
+        sample_size,
+        col = quoted_column
+    );
+    let sample_options = QueryOptions {
+        params: vec![Value::from(base_path.clone())],
+        ..QueryOptions::default()
+    };
+    let sample = db::execute_query(client, sample_sql, &sample_options).await?;
+    let values: Vec<Value> = sample
+        .rows
+        .into_iter()
+        .filter_map(|row| row.into_iter().next())
+        .map(|cell| sampled_value(cell.into_value()))
+        .filter(|v| !v.is_null())
+        .collect();
+    if values.is_empty() {
+        return Err(format!(
+            "No JSON values found at {} in {}",
+            base_path, column
+        ));
+    }
+
+    // Arrays give a row per element
+    let expand = values.iter().all(Value::is_array);
+    let row_path = if expand {
+        format!("{}[*]", base_path)
+    } else {
+        base_path.clone()
+    };
+    let rows: Vec<&Value> = if expand {
+        values
+            .iter()
+            .flat_map(|v| v.as_array().into_iter().flatten())
+            .collect()
+    } else {
+        values.iter().collect()
+    };
+
+    let mut keys: Vec<(String, FieldKinds)> = Vec::new();
+    let mut scalars = FieldKinds::default();
+    for row in &rows {
+        match row {
+            Value::Object(object) => {
+                for (key, value) in object {
+                    match keys.iter_mut().find(|(k, _)| k == key) {
+                        Some((_, kinds)) => kinds.add(value),
+                        None => {
+                            let mut kinds = FieldKinds::default();
+                            kinds.add(value);
+                            keys.push((key.clone(), kinds));
+                        }
+                    }
+                }
+            }
+            scalar => scalars.add(scalar),
+        }
+    }
+    let mut columns: Vec<JsonTableColumn> = keys
+        .iter()
+        .map(|(key, kinds)| JsonTableColumn {
+            name: key.clone(),
+            path: format!("${}", path_step(key)),
+            sql_type: kinds.sql_type(),
+        })
+        .collect();
+    // Rows that are plain values, e.g. an array of tags
+    if columns.is_empty() {
+        columns.push(JsonTableColumn {
+            name: "value".to_string(),
+            path: "$".to_string(),
+            sql_type: scalars.sql_type(),
+        });
+    }
+
+    let mut definitions = Vec::new();
+    if expand {
+        definitions.push(format!(
+            "{} FOR ORDINALITY",
+            quote_ident(dialect, "ordinal")
+        ));
+    }
+    for c in &columns {
+        // A value of another type than sampled reads as NULL instead of failing
+        let on_error = if c.sql_type == "JSON" {
+            ""
+        } else {
+            " NULL ON ERROR"
+        };
+        definitions.push(format!(
+            "{} {} PATH {}{}",
+            quote_ident(dialect, &c.name),
+            c.sql_type,
+            quote_text(Some(dialect), &c.path),
+            on_error
+        ));
+    }
+    let key = db::get_primary_key(client, table.schema.clone(), &table.table).await?;
+    let mut select: Vec<String> = key
+        .iter()
+        .map(|k| format!("t.{}", quote_ident(dialect, k)))
+        .collect();
+    select.push("jt.*".to_string());
+    let sql = format!(
+        "SELECT {}\nFROM {} AS t,\nJSON_TABLE(t.{}, {} COLUMNS (\n  {}\n)) AS jt",
+        select.join(", "),
+        source,
+        quoted_column,
+        quote_text(Some(dialect), &row_path),
+        definitions.join(",\n  ")
+    );
+    Ok(JsonTableQuery {
+        sql,
+        row_path,
+        columns,
+        sampled: values.len(),
+    })
+}
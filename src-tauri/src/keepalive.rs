@@ -0,0 +1,105 @@
+// Keep-alive pings for open connections, at the interval from the connection
+// settings. Servers and firewalls drop idle sessions less often, and a
+// connection that stops answering is reported as a "connection-health" event.
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex as StdMutex;
+use std::time::Duration;
+use tauri::{Emitter, Manager};
+use tokio::task::JoinHandle;
+
+use crate::db::{self, DatabaseState};
+use crate::notifications::{self, JobKind, Notification};
+use crate::settings::SettingsState;
+
+// How often a disabled keep-alive checks whether it was turned back on
+const DISABLED_RECHECK_SECS: u64 = 60;
+
+// Ping tasks by connection name
+#[derive(Default)]
+pub struct KeepAlives {
+    tasks: StdMutex<HashMap<String, JoinHandle<()>>>,
+}
+
+impl KeepAlives {
+    // Replaces any task already running for the connection
+    fn start(&self, name: &str, task: JoinHandle<()>) {
+        if let Some(old) = self.tasks.lock().unwrap().insert(name.to_string(), task) {
+            old.abort();
+        }
+    }
+
+    pub fn stop(&self, name: &str) {
+        if let Some(task) = self.tasks.lock().unwrap().remove(name) {
+            task.abort();
+        }
+    }
+}
+
+// Sent when a connection stops answering pings and again when it recovers
+#[derive(Clone, Serialize)]
+pub struct ConnectionHealthEvent {
+    pub connection: String,
+    pub healthy: bool,
+    pub error: Option<String>,
+}
+
+// Pings the connection until it is closed. The settings are read on every
+// round, so a changed interval applies without reconnecting; 0 disables.
+pub fn start(app: tauri::AppHandle, name: String) {
+    let task_app = app.clone();
+    let task_name = name.clone();
+    let task = tokio::spawn(async move {
+        let mut healthy = true;
+        loop {
+            let settings = task_app.state::<SettingsState>().get().connection;
+            let interval = settings.keep_alive_interval_seconds.max(0) as u64;
+            if interval == 0 {
+                tokio::time::sleep(Duration::from_secs(DISABLED_RECHECK_SECS)).await;
+                continue;
+            }
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+
+            // Doesn't count as a use, so pings don't keep the connection from eviction
+            let Ok(client) = task_app.state::<DatabaseState>().peek(&task_name) else {
+                break;
+            };
+            let timeout = Duration::from_secs(settings.connection_timeout_seconds.max(1) as u64);
+            let result = match tokio::time::timeout(timeout, db::ping(&client)).await {
+                Ok(result) => result,
+                Err(_) => Err(format!("No answer within {}s", timeout.as_secs())),
+            };
+            if result.is_ok() == healthy {
+                continue;
+            }
+            healthy = result.is_ok();
+            if let Err(e) = &result {
+                log::warn!("Connection {} is not responding: {}", task_name, e);
+            }
+            let notification = match &result {
+                Ok(_) => Notification {
+                    job: JobKind::HealthCheck,
+                    ok: true,
+                    title: format!("{} is responding again", task_name),
+                    message: format!("Keep-alive pings to {} are answered again.", task_name),
+                },
+                Err(e) => Notification {
+                    job: JobKind::HealthCheck,
+                    ok: false,
+                    title: format!("{} is not responding", task_name),
+                    message: format!("Keep-alive ping to {} failed: {}", task_name, e),
+                },
+            };
+            notifications::notify(&task_app, notification);
+            let event = ConnectionHealthEvent {
+                connection: task_name.clone(),
+                healthy,
+                error: result.err(),
+            };
+            if let Err(e) = task_app.emit("connection-health", event) {
+                log::warn!("Failed to emit connection health for {}: {}", task_name, e);
+            }
+        }
+    });
+    app.state::<DatabaseState>().keep_alives.start(&name, task);
+}
@@ -1,11 +1,82 @@
+pub mod audit;
+pub mod bookmarks;
+pub mod browse;
+pub mod capabilities;
+pub mod confirm;
+pub mod csv_import;
 pub mod db;
+pub mod ddl;
+pub mod ddl_history;
+pub mod diagnostics;
+pub mod edits;
+pub mod environment;
+pub mod errors;
+pub mod explain;
+pub mod export_columns;
+pub mod exports;
+pub mod fleet;
+pub mod history;
+pub mod indexes;
+pub mod jobs;
+pub mod json_table;
+pub mod keepalive;
+pub mod lint;
+pub mod metadata;
+pub mod mongo;
+pub mod mssql_pool;
+pub mod notifications;
+pub mod offline;
+pub mod paste_import;
+pub mod pinned_results;
+pub mod pins;
+pub mod profiles;
+pub mod quoting;
+pub mod recent;
+pub mod redis_tools;
+pub mod relations;
+pub mod running;
+pub mod schedule_windows;
+pub mod scheduled_exports;
+pub mod schema_snapshots;
+pub mod schemawatch;
+pub mod scripts;
+pub mod secrets;
 pub mod settings;
+pub mod snippets;
+pub mod sql_file;
+pub mod sql_projects;
+pub mod sqltext;
+pub mod storage;
+pub mod temporal;
+pub mod tls;
+pub mod transactions;
+pub mod trash;
+pub mod usage;
+pub mod values;
+pub mod vault;
+pub mod warmup;
+pub mod workspace;
+pub mod xlsx;
 
-use db::{DatabaseState, QueryResponse};
+use bookmarks::{Bookmark, BookmarkFilter};
+use chrono::{DateTime, Utc};
+use confirm::Guarded;
+use db::{DatabaseState, QueryOptions, QueryResponse, QueryRoute, ReplicaSet};
+use edits::{RowIdentity, StagedChange, TableRef};
+use errors::DbError;
+use export_columns::ColumnShaper;
+use exports::ExportedFile;
+use profiles::Operation;
+use running::TrackedQuery;
 use serde::{Deserialize, Serialize};
-use settings::Settings;
+use settings::{Settings, SettingsState};
 use std::fs;
-use tauri::{Manager, State};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+use tauri::{Emitter, Manager, State};
+use tls::TlsOptions;
+use trash::{TrashItem, TrashKind};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct SavedConnection {
@@ -13,228 +84,3066 @@ pub struct SavedConnection {
     pub url: String,
     pub conn_type: String,
     pub color: String,
+    #[serde(default)]
+    pub replicas: Vec<String>, // read replica URLs, same engine as `url`
+    #[serde(default)]
+    pub secret_ref: Option<String>, // keychain entry with the passwords, see vault.rs
+    #[serde(default)]
+    pub environment: Option<environment::Environment>,
+    #[serde(default)]
+    pub tls: TlsOptions,
+}
+
+// Saved connections with their passwords filled in from the keychain. Ones
+// still stored in plain text are moved to the keychain on the way.
+pub(crate) fn read_saved_connections(
+    app: &tauri::AppHandle,
+) -> Result<Vec<SavedConnection>, String> {
+    let path = storage::data_file(app, "connections.json")?;
+    let mut connections: Vec<SavedConnection> = storage::read_json(&path)?;
+
+    let mut migrated = false;
+    for connection in connections.iter_mut().filter(|c| vault::is_exposed(c)) {
+        let mut sealed = connection.clone();
+        match vault::seal(&mut sealed, None) {
+            Ok(()) => {
+                *connection = sealed;
+                migrated = true;
+            }
+            Err(e) => log::warn!(
+                "Leaving password of {} in connections.json: {}",
+                connection.name,
+                e
+            ),
+        }
+    }
+    if migrated {
+        storage::write_json(&path, &connections)?;
+    }
+
+    for connection in &mut connections {
+        if let Err(e) = vault::unseal(connection) {
+            log::warn!("No password for {}: {}", connection.name, e);
+        }
+    }
+    Ok(connections)
 }
 
 #[tauri::command]
 async fn connect_db(
+    app: tauri::AppHandle,
     state: State<'_, DatabaseState>,
+    settings: State<'_, SettingsState>,
     name: String,
     url: String,
-) -> Result<String, String> {
-    let client = db::create_client(&url).await.map_err(|e| e.to_string())?;
-    state
-        .connections
-        .lock()
-        .unwrap()
-        .insert(name.clone(), client);
-    Ok(format!("Connected to {}", name))
-}
-
-#[tauri::command]
-async fn disconnect_db(state: State<'_, DatabaseState>, name: String) -> Result<String, String> {
-    state
-        .connections
-        .lock()
-        .unwrap()
-        .remove(&name)
-        .ok_or("Connection not found")?;
+    tls: Option<TlsOptions>, // defaults to the saved connection's
+) -> Result<String, DbError> {
+    let saved = read_saved_connections(&app)?;
+    let saved = saved.iter().find(|c| c.name == name);
+    let tls = tls
+        .or_else(|| saved.map(|c| c.tls.clone()))
+        .unwrap_or_default();
+    let client = db::create_client_with_tls(&url, &tls)
+        .await
+        .map_err(|message| DbError::ConnectionFailed { message })?;
+    state.insert(&name, &url, client);
+    keepalive::start(app.clone(), name.clone());
+    if settings.get().connection.warm_up_on_connect {
+        warmup::start(app.clone(), name.clone());
+    }
+
+    // Replicas come from the saved connection; one that is down only loses its share of reads
+    let mut replicas = Vec::new();
+    for replica_url in saved.iter().flat_map(|c| &c.replicas) {
+        match db::create_client_with_tls(replica_url, &tls).await {
+            Ok(replica) => replicas.push(replica),
+            Err(e) => log::warn!("Skipping read replica of {}: {}", name, e),
+        }
+    }
+    let replica_count = replicas.len();
+    {
+        let mut replica_sets = state.replicas.lock().unwrap();
+        match ReplicaSet::new(replicas) {
+            Some(set) => replica_sets.insert(name.clone(), Arc::new(set)),
+            None => replica_sets.remove(&name),
+        };
+    }
+
+    // 0 means no limit
+    let max = settings.get().advanced.max_cached_connections;
+    if max > 0 {
+        for evicted in state.evict_lru(max as usize, &name) {
+            let _ = app.emit("connection-evicted", &evicted);
+        }
+    }
+
+    if replica_count > 0 {
+        Ok(format!(
+            "Connected to {} ({} read replicas)",
+            name, replica_count
+        ))
+    } else {
+        Ok(format!("Connected to {}", name))
+    }
+}
+
+#[tauri::command]
+async fn get_capabilities(
+    state: State<'_, DatabaseState>,
+    name: String,
+) -> Result<capabilities::Capabilities, DbError> {
+    Ok(capabilities::of(&state.peek(&name)?).await)
+}
+
+#[tauri::command]
+async fn disconnect_db(state: State<'_, DatabaseState>, name: String) -> Result<String, DbError> {
+    state.remove(&name).ok_or("Connection not found")?;
     Ok(format!("Disconnected {}", name))
 }
 
 #[tauri::command]
-async fn test_conn(url: String) -> Result<String, String> {
-    db::test_connection(&url).await
+async fn test_conn(url: String, tls: Option<TlsOptions>) -> Result<String, DbError> {
+    db::test_connection(&url, &tls.unwrap_or_default())
+        .await
+        .map_err(|message| DbError::ConnectionFailed { message })
 }
 
 #[tauri::command]
 async fn execute_query(
-    state: State<'_, DatabaseState>,
+    app: tauri::AppHandle,
+    name: String,
+    sql: String,
+    route: Option<QueryRoute>,
+    query_id: Option<String>, // makes the query cancellable through cancel_query
+    limit: Option<usize>,     // overrides query.auto_limit; 0 means no limit
+) -> Result<QueryResponse, DbError> {
+    let options = editor_query_options(&app, limit);
+    run_editor_query(&app, name, sql, route, query_id, options).await
+}
+
+// `params` are bound to the $1 / ? / @P1 placeholders in `sql`
+#[tauri::command]
+async fn execute_query_with_params(
+    app: tauri::AppHandle,
+    name: String,
+    sql: String,
+    params: Vec<serde_json::Value>,
+    route: Option<QueryRoute>,
+    query_id: Option<String>,
+    limit: Option<usize>,
+) -> Result<QueryResponse, DbError> {
+    let mut options = editor_query_options(&app, limit);
+    options.params = params;
+    run_editor_query(&app, name, sql, route, query_id, options).await
+}
+
+fn editor_query_options(app: &tauri::AppHandle, limit: Option<usize>) -> QueryOptions {
+    let settings = app.state::<SettingsState>().get();
+    let mut options = QueryOptions::from_settings(&settings);
+    let auto_limit = settings.query.auto_limit.max(0) as usize;
+    options.row_limit = Some(limit.unwrap_or(auto_limit)).filter(|l| *l > 0);
+    options
+}
+
+async fn run_editor_query(
+    app: &tauri::AppHandle,
     name: String,
     sql: String,
-) -> Result<QueryResponse, String> {
-    let client = {
-        let pools = state.connections.lock().unwrap();
-        pools.get(&name).cloned().ok_or("Connection not found")?
+    route: Option<QueryRoute>,
+    query_id: Option<String>,
+    mut options: QueryOptions,
+) -> Result<QueryResponse, DbError> {
+    let state = app.state::<DatabaseState>();
+    let client = state.routed_client(&name, &sql, route.unwrap_or_default())?;
+    require(app, Operation::of_statement(&client, &sql))?;
+    // A conflict aborts the whole open transaction, so the statement alone
+    // can't be run again
+    if state.transactions.client(&name).is_some() {
+        options.conflict_retries = 0;
+    }
+
+    let ddl = ddl_history::capture(&client, &sql).await;
+    let dialect = client.dialect();
+    let started = (Utc::now(), Instant::now());
+    let result = match query_id {
+        Some(id) => {
+            let query = TrackedQuery {
+                id,
+                connection: name.clone(),
+                client,
+                sql: sql.clone(),
+                max_concurrent: max_concurrent(app),
+            };
+            state.running.run(query, &options).await
+        }
+        None => db::execute_query(&client, sql.clone(), &options).await,
+    };
+    let outcome = match &result {
+        Ok(response) => Ok(response.rows.len() as u64),
+        Err(e) => Err(e.message()),
+    };
+    note_history(app, &name, &sql, started, outcome);
+    if let Ok(response) = &result {
+        note_used_objects(app, &name, &sql, dialect);
+        note_ddl(app, &name, ddl);
+        note_offline_result(app, &name, &sql, response);
+    }
+    result
+}
+
+// Kept for offline mode on a best effort basis, like history; the file is
+// written off the query's path
+fn note_offline_result(app: &tauri::AppHandle, name: &str, sql: &str, response: &QueryResponse) {
+    let keep = app
+        .state::<SettingsState>()
+        .get()
+        .connection
+        .offline_results
+        .max(0) as usize;
+    if keep == 0 {
+        return;
+    }
+    let prepared = offline::cached_response(response).and_then(|cached| {
+        let path = storage::data_file(app, offline::OFFLINE_FILE)?;
+        Ok(cached.map(|cached| (path, cached)))
+    });
+    let (path, cached) = match prepared {
+        Ok(Some(prepared)) => prepared,
+        Ok(None) => return,
+        Err(e) => {
+            log::warn!(
+                "Failed to keep the result for offline use on {}: {}",
+                name,
+                e
+            );
+            return;
+        }
     };
+    let (name, sql) = (name.to_string(), sql.to_string());
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = offline::remember_result(&path, &name, &sql, cached, keep) {
+            log::warn!(
+                "Failed to keep the result for offline use on {}: {}",
+                name,
+                e
+            );
+        }
+    });
+}
 
-    db::execute_query(&client, sql).await
+// Like history, DDL history is best effort
+fn note_ddl(app: &tauri::AppHandle, name: &str, captured: Vec<ddl_history::Captured>) {
+    let result = storage::data_file(app, ddl_history::DDL_HISTORY_FILE)
+        .and_then(|path| ddl_history::record(&path, name, captured));
+    if let Err(e) = result {
+        log::warn!("Failed to record DDL history for {}: {}", name, e);
+    }
 }
 
-#[tauri::command]
-async fn get_schemas(state: State<'_, DatabaseState>, name: String) -> Result<Vec<String>, String> {
-    let client = {
-        let pools = state.connections.lock().unwrap();
-        pools.get(&name).cloned().ok_or("Connection not found")?
+// Like the recent list, history is best effort
+fn note_history(
+    app: &tauri::AppHandle,
+    name: &str,
+    sql: &str,
+    (executed_at, started): (DateTime<Utc>, Instant),
+    outcome: Result<u64, &str>,
+) {
+    audit::Auditor::for_connection(app, name).record(
+        sql,
+        (executed_at, started),
+        outcome.map(|_| ()),
+    );
+    let duration_ms = started.elapsed().as_millis() as u64;
+    let entry = history::HistoryEntry::new(name, sql, executed_at, duration_ms, outcome);
+    let path = match storage::data_file(app, history::HISTORY_FILE) {
+        Ok(path) => path,
+        Err(e) => {
+            log::warn!("Failed to record query history for {}: {}", name, e);
+            return;
+        }
     };
+    // Written off the runtime, like the audit log
+    let name = name.to_string();
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = history::record(&path, entry) {
+            log::warn!("Failed to record query history for {}: {}", name, e);
+        }
+    });
+}
 
-    db::get_schemas(&client).await
+#[tauri::command]
+async fn get_query_history(
+    app: tauri::AppHandle,
+    name: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<history::HistoryEntry>, DbError> {
+    let path = storage::data_file(&app, history::HISTORY_FILE)?;
+    Ok(history::search(&path, None, name.as_deref(), limit)?)
 }
 
+// Audit log entries newest first
 #[tauri::command]
-async fn get_databases(
-    state: State<'_, DatabaseState>,
-    name: String,
-) -> Result<Vec<String>, String> {
-    let client = {
-        let pools = state.connections.lock().unwrap();
-        pools.get(&name).cloned().ok_or("Connection not found")?
-    };
+async fn search_audit_log(
+    app: tauri::AppHandle,
+    filter: Option<audit::AuditFilter>,
+) -> Result<Vec<audit::AuditEntry>, DbError> {
+    let dir = storage::data_file(&app, audit::AUDIT_DIR)?;
+    Ok(audit::search(&dir, &filter.unwrap_or_default())?)
+}
+
+// Writes the matching entries to `path` as CSV, or JSON lines for a .jsonl path
+#[tauri::command]
+async fn export_audit_log(
+    app: tauri::AppHandle,
+    path: String,
+    filter: Option<audit::AuditFilter>,
+) -> Result<usize, DbError> {
+    let dir = storage::data_file(&app, audit::AUDIT_DIR)?;
+    Ok(audit::export(&dir, &filter.unwrap_or_default(), &path)?)
+}
 
-    db::get_databases(&client).await
+#[tauri::command]
+async fn search_query_history(
+    app: tauri::AppHandle,
+    text: String,
+    name: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<history::HistoryEntry>, DbError> {
+    let path = storage::data_file(&app, history::HISTORY_FILE)?;
+    Ok(history::search(&path, Some(&text), name.as_deref(), limit)?)
 }
+
+// DDL run from the editor with rollback scripts, newest first
 #[tauri::command]
-async fn get_tables(
+async fn get_ddl_history(
+    app: tauri::AppHandle,
+    name: Option<String>,
+    limit: Option<usize>,
+) -> Result<Vec<ddl_history::DdlHistoryEntry>, DbError> {
+    let path = storage::data_file(&app, ddl_history::DDL_HISTORY_FILE)?;
+    Ok(ddl_history::list(&path, name.as_deref(), limit)?)
+}
+
+// CREATE TABLE script for a table, with its constraints and indexes
+#[tauri::command]
+async fn get_table_ddl(
     state: State<'_, DatabaseState>,
     name: String,
     schema: Option<String>,
-) -> Result<Vec<String>, String> {
-    let client = {
-        let pools = state.connections.lock().unwrap();
-        pools.get(&name).cloned().ok_or("Connection not found")?
-    };
+    table: String,
+) -> Result<String, DbError> {
+    let client = state.client(&name)?;
+    let statements = ddl_history::table_ddl(&client, &TableRef { schema, table }).await?;
+    Ok(format!("{};\n", statements.join(";\n\n")))
+}
+
+// Without a connection name the whole history is cleared
+#[tauri::command]
+async fn clear_query_history(
+    app: tauri::AppHandle,
+    name: Option<String>,
+) -> Result<usize, DbError> {
+    let path = storage::data_file(&app, history::HISTORY_FILE)?;
+    Ok(history::clear(&path, name.as_deref())?)
+}
 
-    db::get_tables(&client, schema).await
+// Rejects the command unless the active profile allows the operation
+fn require(app: &tauri::AppHandle, operation: Operation) -> Result<(), String> {
+    app.state::<SettingsState>()
+        .get()
+        .profile
+        .require(operation)
 }
 
+// A failure to update the recent list or usage counts shouldn't fail the query.
+// MongoDB and Redis commands aren't SQL, so they name no tables or columns.
+fn note_used_objects(app: &tauri::AppHandle, name: &str, sql: &str, dialect: Option<db::Dialect>) {
+    if dialect.is_none() {
+        return;
+    }
+    let result = storage::data_file(app, recent::RECENT_TABLES_FILE)
+        .and_then(|path| recent::record(&path, name, sql));
+    if let Err(e) = result {
+        log::warn!("Failed to record recent tables for {}: {}", name, e);
+    }
+    let result =
+        storage::data_file(app, usage::USAGE_FILE).and_then(|path| usage::record(&path, name, sql));
+    if let Err(e) = result {
+        log::warn!("Failed to record object usage for {}: {}", name, e);
+    }
+}
+
+// Reports definition changes on the server as "schema-changed" events until
+// unwatched or disconnected
 #[tauri::command]
-async fn get_views(
+async fn watch_schema(
+    app: tauri::AppHandle,
     state: State<'_, DatabaseState>,
     name: String,
-    schema: Option<String>,
-) -> Result<Vec<String>, String> {
-    let client = {
-        let pools = state.connections.lock().unwrap();
-        pools.get(&name).cloned().ok_or("Connection not found")?
-    };
+    interval_secs: Option<u64>,
+) -> Result<(), DbError> {
+    let client = state.client(&name)?;
+    let interval_secs = interval_secs.unwrap_or(schemawatch::DEFAULT_WATCH_INTERVAL_SECS);
+    Ok(schemawatch::watch(app, name, &client, interval_secs).await?)
+}
 
-    db::get_views(&client, schema).await
+#[tauri::command]
+async fn unwatch_schema(state: State<'_, DatabaseState>, name: String) -> Result<bool, DbError> {
+    Ok(state.watches.stop(&name))
 }
 
 #[tauri::command]
-async fn get_functions(
-    state: State<'_, DatabaseState>,
+async fn pin_object(
+    app: tauri::AppHandle,
     name: String,
-    schema: Option<String>,
-) -> Result<Vec<String>, String> {
-    let client = {
-        let pools = state.connections.lock().unwrap();
-        pools.get(&name).cloned().ok_or("Connection not found")?
+    object: pins::PinnedObject,
+) -> Result<(), DbError> {
+    let path = storage::data_file(&app, pins::PINS_FILE)?;
+    Ok(pins::pin(&path, &name, object)?)
+}
+
+#[tauri::command]
+async fn unpin_object(
+    app: tauri::AppHandle,
+    name: String,
+    object: pins::PinnedObject,
+) -> Result<(), DbError> {
+    let path = storage::data_file(&app, pins::PINS_FILE)?;
+    Ok(pins::unpin(&path, &name, &object)?)
+}
+
+#[tauri::command]
+async fn list_pinned(
+    app: tauri::AppHandle,
+    name: String,
+) -> Result<Vec<pins::PinnedObject>, DbError> {
+    let path = storage::data_file(&app, pins::PINS_FILE)?;
+    Ok(pins::list(&path, &name)?)
+}
+
+#[tauri::command]
+async fn pin_result(
+    app: tauri::AppHandle,
+    name: String,
+    result: pinned_results::NewPinnedResult,
+) -> Result<pinned_results::PinnedResult, DbError> {
+    let path = storage::data_file(&app, pinned_results::PINNED_RESULTS_FILE)?;
+    Ok(pinned_results::pin(&path, &name, result)?)
+}
+
+#[tauri::command]
+async fn list_pinned_results(
+    app: tauri::AppHandle,
+    name: Option<String>,
+) -> Result<Vec<pinned_results::PinnedResult>, DbError> {
+    let path = storage::data_file(&app, pinned_results::PINNED_RESULTS_FILE)?;
+    Ok(pinned_results::list(&path, name.as_deref())?)
+}
+
+#[tauri::command]
+async fn set_pinned_result_note(
+    app: tauri::AppHandle,
+    id: String,
+    note: String,
+) -> Result<(), DbError> {
+    let path = storage::data_file(&app, pinned_results::PINNED_RESULTS_FILE)?;
+    Ok(pinned_results::set_note(&path, &id, note)?)
+}
+
+#[tauri::command]
+async fn delete_pinned_result(app: tauri::AppHandle, id: String) -> Result<(), DbError> {
+    let path = storage::data_file(&app, pinned_results::PINNED_RESULTS_FILE)?;
+    Ok(pinned_results::delete(&path, &id)?)
+}
+
+#[tauri::command]
+async fn get_recent_tables(
+    app: tauri::AppHandle,
+    name: String,
+    limit: Option<usize>,
+) -> Result<Vec<recent::RecentTable>, DbError> {
+    let path = storage::data_file(&app, recent::RECENT_TABLES_FILE)?;
+    Ok(recent::list(&path, &name, limit)?)
+}
+
+#[tauri::command]
+async fn clear_recent_tables(app: tauri::AppHandle, name: String) -> Result<(), DbError> {
+    let path = storage::data_file(&app, recent::RECENT_TABLES_FILE)?;
+    Ok(recent::clear(&path, &name)?)
+}
+
+// Tables and columns by how much they're used, for ranking completions
+#[tauri::command]
+async fn get_completion_ranking(
+    app: tauri::AppHandle,
+    name: String,
+    kind: Option<usage::UsageKind>,
+    limit: Option<usize>,
+) -> Result<Vec<usage::ObjectUsage>, DbError> {
+    let path = storage::data_file(&app, usage::USAGE_FILE)?;
+    Ok(usage::ranking(&path, &name, kind, limit)?)
+}
+
+#[tauri::command]
+async fn clear_completion_ranking(app: tauri::AppHandle, name: String) -> Result<(), DbError> {
+    let path = storage::data_file(&app, usage::USAGE_FILE)?;
+    Ok(usage::clear(&path, &name)?)
+}
+
+#[derive(Serialize, Clone)]
+struct QueryRowsEvent {
+    query_id: String,
+    #[serde(flatten)]
+    batch: db::RowBatch,
+}
+
+#[derive(Serialize, Clone)]
+struct QueryDoneEvent {
+    query_id: String,
+    rows: Option<u64>,
+    error: Option<DbError>,
+}
+
+// Runs only the statement of `script` under `cursor` (a char offset) and
+// reports which one it was, so the editor can highlight it
+#[tauri::command]
+async fn execute_statement_at(
+    app: tauri::AppHandle,
+    name: String,
+    script: String,
+    cursor: usize,
+    route: Option<QueryRoute>,
+    query_id: Option<String>,
+    limit: Option<usize>,
+) -> Result<scripts::StatementResult, DbError> {
+    let state = app.state::<DatabaseState>();
+    let dialect = state
+        .client(&name)?
+        .dialect()
+        .ok_or("Statement execution is only supported for SQL databases")?;
+
+    let statement = scripts::statement_at(dialect, &script, cursor)?;
+    let options = editor_query_options(&app, limit);
+    let sql = statement.sql.clone();
+    let result = run_editor_query(&app, name, sql, route, query_id, options).await?;
+    Ok(scripts::StatementResult { statement, result })
+}
+
+// Where SQL files run on `name`: the open transaction's connection when there
+// is one, else a connection of their own, so the statements share a session
+async fn sql_file_session(
+    app: &tauri::AppHandle,
+    name: &str,
+) -> Result<(db::DbClient, db::Dialect), String> {
+    let state = app.state::<DatabaseState>();
+    let primary = state.client(name)?;
+    let dialect = primary
+        .dialect()
+        .ok_or("SQL files can only be run on SQL databases")?;
+    let client = match state.transactions.client(name) {
+        Some(tx) => tx,
+        None => transactions::dedicated_client(&primary).await?,
     };
+    Ok((client, dialect))
+}
+
+async fn run_file_on(
+    app: &tauri::AppHandle,
+    name: &str,
+    (client, dialect): &(db::DbClient, db::Dialect),
+    path: &str,
+    stop_on_error: bool,
+) -> Result<sql_file::SqlFileResult, String> {
+    let profile = app.state::<SettingsState>().get().profile;
+    sql_file::run_sql_file(
+        client,
+        *dialect,
+        path,
+        stop_on_error,
+        &audit::Auditor::for_connection(app, name),
+        |sql| profile.require(Operation::of_sql(sql)),
+        |progress| {
+            let _ = app.emit("sql-file-progress", progress);
+        },
+    )
+    .await
+}
 
-    db::get_functions(&client, schema).await
+// Runs `work` on the session of a SQL file run, under `query_id` when given
+// so that cancel_query stops it like any other query
+async fn tracked_file_run<T>(
+    app: &tauri::AppHandle,
+    name: &str,
+    (client, _): &(db::DbClient, db::Dialect),
+    query_id: Option<String>,
+    description: String,
+    work: impl std::future::Future<Output = Result<T, String>>,
+) -> Result<T, DbError> {
+    let Some(id) = query_id else {
+        return Ok(work.await?);
+    };
+    // Without its session the run can still be stopped between statements
+    let backend = match db::session_id(client).await {
+        Ok(backend) => Some(backend),
+        Err(e) => {
+            log::warn!("Failed to read the session id on {}: {}", name, e);
+            None
+        }
+    };
+    let query = TrackedQuery {
+        id,
+        connection: name.to_string(),
+        client: client.clone(),
+        sql: description,
+        max_concurrent: 0,
+    };
+    let running = &app.state::<DatabaseState>().running;
+    running.run_script(query, backend, work).await
 }
 
+// Runs a .sql file from disk statement by statement, reporting progress as
+// "sql-file-progress" events
 #[tauri::command]
-async fn save_connections(
+async fn run_sql_file(
     app: tauri::AppHandle,
-    connections: Vec<SavedConnection>,
-) -> Result<(), String> {
-    let path = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?
-        .join("connections.json");
-    println!("Saving connections to: {:?}", path);
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-    }
-    let json = serde_json::to_string_pretty(&connections).map_err(|e| e.to_string())?;
-    fs::write(&path, json).map_err(|e| format!("Failed to write to {:?}: {}", path, e))?;
-    println!("Successfully saved {} connections", connections.len());
-    Ok(())
+    name: String,
+    path: String,
+    stop_on_error: Option<bool>,
+    query_id: Option<String>, // makes the run cancellable through cancel_query
+) -> Result<sql_file::SqlFileResult, DbError> {
+    let session = sql_file_session(&app, &name).await?;
+    let work = run_file_on(&app, &name, &session, &path, stop_on_error.unwrap_or(true));
+    let description = format!("-- {}", path);
+    tracked_file_run(&app, &name, &session, query_id, description, work).await
 }
 
+fn workspace_root(app: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    let settings = app.state::<SettingsState>().get();
+    workspace::root(settings.advanced.workspace_dir.as_deref())
+}
+
+// Snippets and notebooks in the workspace folder
 #[tauri::command]
-async fn load_connections(app: tauri::AppHandle) -> Result<Vec<SavedConnection>, String> {
-    let path = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?
-        .join("connections.json");
-    println!("Loading connections from: {:?}", path);
-    if !path.exists() {
-        println!("File does not exist");
-        return Ok(Vec::new());
-    }
-    let json =
-        fs::read_to_string(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
-    let connections: Vec<SavedConnection> =
-        serde_json::from_str(&json).map_err(|e| e.to_string())?;
-    println!("Loaded {} connections", connections.len());
-    Ok(connections)
+async fn list_workspace_files(
+    app: tauri::AppHandle,
+) -> Result<Vec<workspace::WorkspaceFile>, DbError> {
+    Ok(workspace::list(&workspace_root(&app)?)?)
 }
 
 #[tauri::command]
-async fn debug_path(app: tauri::AppHandle) -> Result<String, String> {
-    let path = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?
-        .join("connections.json");
-    Ok(path.to_string_lossy().to_string())
+async fn read_workspace_file(app: tauri::AppHandle, path: String) -> Result<String, DbError> {
+    Ok(workspace::read(&workspace_root(&app)?, &path)?)
 }
 
 #[tauri::command]
-async fn load_settings(app: tauri::AppHandle) -> Result<Settings, String> {
-    let path = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?
-        .join("settings.json");
+async fn write_workspace_file(
+    app: tauri::AppHandle,
+    path: String,
+    content: String,
+) -> Result<(), DbError> {
+    Ok(workspace::write(&workspace_root(&app)?, &path, &content)?)
+}
 
-    if !path.exists() {
-        return Ok(Settings::default());
-    }
+#[tauri::command]
+async fn delete_workspace_file(app: tauri::AppHandle, path: String) -> Result<(), DbError> {
+    Ok(workspace::delete(&workspace_root(&app)?, &path)?)
+}
 
-    let json = fs::read_to_string(&path).map_err(|e| format!("Failed to read settings: {}", e))?;
-    let settings: Settings = serde_json::from_str(&json).unwrap_or_else(|_| Settings::default());
-    Ok(settings)
+// Git state of a file or of the files under a folder; `path` defaults to the
+// workspace folder
+#[tauri::command]
+async fn get_file_status(
+    app: tauri::AppHandle,
+    path: Option<String>,
+) -> Result<workspace::GitStatus, DbError> {
+    let path = match path {
+        Some(path) => path,
+        None => workspace_root(&app)?.to_string_lossy().to_string(),
+    };
+    Ok(workspace::file_status(&path).await?)
 }
 
 #[tauri::command]
-async fn save_settings(app: tauri::AppHandle, settings: Settings) -> Result<(), String> {
-    let path = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())?
-        .join("settings.json");
+async fn list_sql_projects(
+    app: tauri::AppHandle,
+) -> Result<Vec<sql_projects::SqlProject>, DbError> {
+    let path = storage::data_file(&app, sql_projects::SQL_PROJECTS_FILE)?;
+    Ok(sql_projects::list(&path)?)
+}
 
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
-    }
+#[tauri::command]
+async fn save_sql_project(
+    app: tauri::AppHandle,
+    project: sql_projects::SqlProject,
+) -> Result<sql_projects::SqlProject, DbError> {
+    let path = storage::data_file(&app, sql_projects::SQL_PROJECTS_FILE)?;
+    Ok(sql_projects::save(&path, project)?)
+}
 
-    let json = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
-    fs::write(&path, json).map_err(|e| format!("Failed to write settings: {}", e))?;
+#[tauri::command]
+async fn delete_sql_project(
+    app: tauri::AppHandle,
+    watches: State<'_, sql_projects::ProjectWatches>,
+    id: String,
+) -> Result<(), DbError> {
+    let path = storage::data_file(&app, sql_projects::SQL_PROJECTS_FILE)?;
+    sql_projects::delete(&path, &id)?;
+    watches.stop(&id);
     Ok(())
 }
 
 #[tauri::command]
-async fn export_data(
+async fn list_sql_project_files(
+    app: tauri::AppHandle,
+    id: String,
+) -> Result<Vec<sql_projects::ProjectFile>, DbError> {
+    let path = storage::data_file(&app, sql_projects::SQL_PROJECTS_FILE)?;
+    Ok(sql_projects::files(&sql_projects::get(&path, &id)?)?)
+}
+
+// The project and the connection to run it on: `name`, else the project's own
+fn project_target(
+    app: &tauri::AppHandle,
+    id: &str,
+    name: Option<String>,
+) -> Result<(sql_projects::SqlProject, String), String> {
+    let path = storage::data_file(app, sql_projects::SQL_PROJECTS_FILE)?;
+    let project = sql_projects::get(&path, id)?;
+    let name = name
+        .or_else(|| project.connection.clone())
+        .ok_or_else(|| format!("Choose a connection to run {} on", project.name))?;
+    Ok((project, name))
+}
+
+#[tauri::command]
+async fn run_sql_project_file(
+    app: tauri::AppHandle,
+    id: String,
+    file: String,
+    name: Option<String>,
+    stop_on_error: Option<bool>,
+    query_id: Option<String>,
+) -> Result<sql_file::SqlFileResult, DbError> {
+    let (project, name) = project_target(&app, &id, name)?;
+    let path = sql_projects::file_path(&project, &file)?;
+    let path = path.to_string_lossy();
+    let session = sql_file_session(&app, &name).await?;
+    let work = run_file_on(&app, &name, &session, &path, stop_on_error.unwrap_or(true));
+    let description = format!("-- {}", path);
+    tracked_file_run(&app, &name, &session, query_id, description, work).await
+}
+
+// Runs every file of the project in path order in one session. With
+// `stop_on_error` the first failing statement ends the whole run.
+#[tauri::command]
+async fn run_sql_project(
+    app: tauri::AppHandle,
+    id: String,
+    name: Option<String>,
+    stop_on_error: Option<bool>,
+    query_id: Option<String>,
+) -> Result<Vec<sql_projects::ProjectFileRun>, DbError> {
+    let (project, name) = project_target(&app, &id, name)?;
+    let stop_on_error = stop_on_error.unwrap_or(true);
+    let files = sql_projects::files(&project)?;
+    let session = sql_file_session(&app, &name).await?;
+    let work = async {
+        let mut runs = Vec::new();
+        for file in files {
+            let path = sql_projects::file_path(&project, &file.path)?;
+            let result = run_file_on(
+                &app,
+                &name,
+                &session,
+                &path.to_string_lossy(),
+                stop_on_error,
+            )
+            .await?;
+            let stopped = result.stopped;
+            runs.push(sql_projects::ProjectFileRun {
+                file: file.path,
+                result,
+            });
+            if stopped {
+                break;
+            }
+        }
+        Ok(runs)
+    };
+    let description = format!("-- project {}", project.name);
+    tracked_file_run(&app, &name, &session, query_id, description, work).await
+}
+
+// Reports changes to the project's files as "sql-project-changed" events
+// until unwatched
+#[tauri::command]
+async fn watch_sql_project(
+    app: tauri::AppHandle,
+    watches: State<'_, sql_projects::ProjectWatches>,
+    id: String,
+) -> Result<(), DbError> {
+    let path = storage::data_file(&app, sql_projects::SQL_PROJECTS_FILE)?;
+    let project = sql_projects::get(&path, &id)?;
+    Ok(sql_projects::watch(app.clone(), &watches, project)?)
+}
+
+#[tauri::command]
+async fn unwatch_sql_project(
+    watches: State<'_, sql_projects::ProjectWatches>,
+    id: String,
+) -> Result<bool, DbError> {
+    Ok(watches.stop(&id))
+}
+
+// Tables, views and their columns for editor completion, cached and loaded
+// ahead by the warm-up after connecting
+#[tauri::command]
+async fn get_completion_metadata(
+    state: State<'_, DatabaseState>,
+    name: String,
+) -> Result<metadata::CompletionMetadata, DbError> {
+    let client = state.client(&name)?;
+    let metadata = state.metadata.completion(&name, &client).await?;
+    Ok(metadata.as_ref().clone())
+}
+
+// Advisory checks to run before executing; an empty list means nothing was found
+#[tauri::command]
+async fn lint_query(
     state: State<'_, DatabaseState>,
     name: String,
     sql: String,
-    format: String,
-    path: String,
-) -> Result<(), String> {
-    let client = {
-        let pools = state.connections.lock().unwrap();
-        pools.get(&name).cloned().ok_or("Connection not found")?
-    };
+) -> Result<Vec<lint::LintIssue>, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(lint::lint_query(&client, &state.metadata, &name, &sql).await?)
+}
 
-    db::export_data(&client, sql, format, path).await
+// With `analyze` the statement is executed to collect actual row counts and timings
+#[tauri::command]
+async fn explain_query(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+    sql: String,
+    analyze: Option<bool>,
+) -> Result<explain::QueryPlan, DbError> {
+    let analyze = analyze.unwrap_or(false);
+    let client = state.routed_client(&name, &sql, QueryRoute::Primary)?;
+    if analyze {
+        require(&app, Operation::of_statement(&client, &sql))?;
+    }
+
+    Ok(explain::explain_query(&client, &sql, analyze).await?)
+}
+
+// Emits the result as "query://rows" batches followed by one "query://done";
+// cancel it with cancel_query like any tracked query
+#[tauri::command]
+async fn execute_query_stream(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+    sql: String,
+    query_id: String,
+    batch_size: Option<usize>,
+    route: Option<QueryRoute>,
+) -> Result<u64, DbError> {
+    let client = state.routed_client(&name, &sql, route.unwrap_or_default())?;
+    require(&app, Operation::of_statement(&client, &sql))?;
+
+    let options = QueryOptions::from_settings(&app.state::<SettingsState>().get());
+    let ddl = ddl_history::capture(&client, &sql).await;
+    let dialect = client.dialect();
+    let query = TrackedQuery {
+        id: query_id.clone(),
+        connection: name.clone(),
+        client,
+        sql: sql.clone(),
+        max_concurrent: max_concurrent(&app),
+    };
+    let emit = |batch| {
+        let event = QueryRowsEvent {
+            query_id: query_id.clone(),
+            batch,
+        };
+        let _ = app.emit("query://rows", event);
+        Ok(())
+    };
+    let batch_size = batch_size.unwrap_or(db::DEFAULT_STREAM_BATCH);
+    let started = (Utc::now(), Instant::now());
+    let result = state
+        .running
+        .stream(query, &options, batch_size, &emit)
+        .await;
+    let outcome = result.as_ref().copied().map_err(DbError::message);
+    note_history(&app, &name, &sql, started, outcome);
+    if result.is_ok() {
+        note_used_objects(&app, &name, &sql, dialect);
+        note_ddl(&app, &name, ddl);
+    }
+    let _ = app.emit(
+        "query://done",
+        QueryDoneEvent {
+            query_id,
+            rows: result.as_ref().ok().copied(),
+            error: result.as_ref().err().cloned(),
+        },
+    );
+    result
+}
+
+#[tauri::command]
+async fn execute_mongo_find(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+    db: Option<String>,
+    collection: String,
+    query: mongo::FindQuery,
+) -> Result<QueryResponse, DbError> {
+    let client = state.client(&name)?;
+
+    let command = format!(
+        "{}.find({})",
+        collection,
+        serde_json::to_string(&query).unwrap_or_default()
+    );
+    let row_limit = editor_query_options(&app, None).row_limit;
+    let work = mongo::find(&client, db, &collection, query, row_limit);
+    Ok(audit::Auditor::for_connection(&app, &name)
+        .run(&command, work)
+        .await?)
+}
+
+// Streams an aggregation as "query://rows" batches followed by "query://done",
+// like execute_query_stream, and can be cancelled by `query_id` the same way
+#[tauri::command]
+async fn execute_mongo_aggregate(
+    app: tauri::AppHandle,
+    name: String,
+    db: Option<String>,
+    collection: String,
+    pipeline_json: String,
+    query_id: String,
+    batch_size: Option<usize>,
+) -> Result<u64, DbError> {
+    let pipeline = mongo::parse_pipeline(&pipeline_json)?;
+    if mongo::pipeline_writes(&pipeline) {
+        require(&app, Operation::Write)?;
+    }
+    let client = app.state::<DatabaseState>().client(&name)?;
+
+    let emit = |batch| {
+        let event = QueryRowsEvent {
+            query_id: query_id.clone(),
+            batch,
+        };
+        let _ = app.emit("query://rows", event);
+        Ok(())
+    };
+    let batch_size = batch_size.unwrap_or(db::DEFAULT_STREAM_BATCH);
+    let command = format!("{}.aggregate({})", collection, pipeline_json);
+    let query = TrackedQuery {
+        id: query_id.clone(),
+        connection: name.clone(),
+        client: client.clone(),
+        sql: command.clone(),
+        max_concurrent: max_concurrent(&app),
+    };
+    let work = mongo::aggregate(&client, db, &collection, pipeline, batch_size, &emit);
+    let running = &app.state::<DatabaseState>().running;
+    let result = audit::Auditor::for_connection(&app, &name)
+        .run(&command, running.run_with(query, work))
+        .await;
+    let _ = app.emit(
+        "query://done",
+        QueryDoneEvent {
+            query_id,
+            rows: result.as_ref().ok().copied(),
+            error: result.as_ref().err().cloned(),
+        },
+    );
+    result
+}
+
+// Until commit or rollback, execute_query and execute_query_stream on this
+// connection run inside the transaction
+#[tauri::command]
+async fn begin_transaction(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+) -> Result<transactions::TransactionInfo, DbError> {
+    require(&app, Operation::Write)?;
+    let client = state.client(&name)?;
+    let work = state.transactions.begin(&name, &client);
+    Ok(audit::Auditor::for_connection(&app, &name)
+        .run("BEGIN", work)
+        .await?)
+}
+
+#[tauri::command]
+async fn commit_transaction(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+) -> Result<(), DbError> {
+    let work = state.transactions.commit(&name);
+    Ok(audit::Auditor::for_connection(&app, &name)
+        .run("COMMIT", work)
+        .await?)
+}
+
+#[tauri::command]
+async fn rollback_transaction(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+) -> Result<(), DbError> {
+    let work = state.transactions.rollback(&name);
+    audit::Auditor::for_connection(&app, &name)
+        .run("ROLLBACK", work)
+        .await?;
+    // Edits made in the transaction are gone, and undoing them would fail
+    state.undo.clear(&name);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_transaction_status(
+    state: State<'_, DatabaseState>,
+    name: String,
+) -> Result<Option<transactions::TransactionInfo>, DbError> {
+    Ok(state.transactions.info(&name))
+}
+
+#[tauri::command]
+async fn cancel_query(state: State<'_, DatabaseState>, query_id: String) -> Result<(), DbError> {
+    Ok(state.running.cancel(&query_id).await?)
+}
+
+#[tauri::command]
+async fn list_running_queries(
+    state: State<'_, DatabaseState>,
+    name: Option<String>,
+) -> Result<Vec<running::RunningQueryInfo>, DbError> {
+    Ok(state.running.list(name.as_deref()))
+}
+
+#[tauri::command]
+async fn list_jobs(state: State<'_, DatabaseState>) -> Result<Vec<jobs::JobInfo>, DbError> {
+    Ok(state.jobs.list())
+}
+
+#[tauri::command]
+async fn cancel_job(state: State<'_, DatabaseState>, id: String) -> Result<(), DbError> {
+    Ok(state.jobs.cancel(&id, &state.running).await?)
+}
+
+// Runs a query as a background job; the response comes with its
+// "job-finished" event
+#[tauri::command]
+async fn start_query_job(
+    app: tauri::AppHandle,
+    name: String,
+    sql: String,
+    route: Option<QueryRoute>,
+) -> Result<String, DbError> {
+    let state = app.state::<DatabaseState>();
+    let client = state.routed_client(&name, &sql, route.unwrap_or_default())?;
+    require(&app, Operation::of_statement(&client, &sql))?;
+    let options = QueryOptions::from_settings(&app.state::<SettingsState>().get());
+    let max_concurrent = max_concurrent(&app);
+    let description = format!("Query on {}", name);
+    let connection = name.clone();
+    let handle = app.clone();
+    Ok(state.jobs.start(
+        &app,
+        jobs::JobKind::Query,
+        &name,
+        description,
+        |job| async move {
+            let statement = sql.clone();
+            let query = TrackedQuery {
+                id: job.id.clone(),
+                connection: connection.clone(),
+                client,
+                sql,
+                max_concurrent,
+            };
+            let running = &handle.state::<DatabaseState>().running;
+            let work = running.run(query, &options);
+            audit::Auditor::for_connection(&handle, &connection)
+                .run(&statement, work)
+                .await
+                .map_err(String::from)
+        },
+    ))
+}
+
+fn max_concurrent(app: &tauri::AppHandle) -> usize {
+    let settings = app.state::<SettingsState>().get();
+    settings.query.max_concurrent_per_connection.max(0) as usize
+}
+
+// Queries waiting behind the per-connection limit, next to run first
+#[tauri::command]
+async fn get_query_queue(
+    state: State<'_, DatabaseState>,
+    name: Option<String>,
+) -> Result<Vec<running::QueuedQueryInfo>, DbError> {
+    Ok(state.running.queue(name.as_deref()))
+}
+
+// `position` 0 runs the query as soon as a slot frees up
+#[tauri::command]
+async fn reprioritize_query(
+    state: State<'_, DatabaseState>,
+    query_id: String,
+    position: usize,
+) -> Result<Vec<running::QueuedQueryInfo>, DbError> {
+    state.running.reprioritize(&query_id, position)?;
+    Ok(state.running.queue(None))
+}
+
+#[tauri::command]
+async fn get_schemas(
+    state: State<'_, DatabaseState>,
+    name: String,
+) -> Result<Vec<String>, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(db::get_schemas(&client).await?)
+}
+
+#[tauri::command]
+async fn get_databases(
+    state: State<'_, DatabaseState>,
+    name: String,
+) -> Result<Vec<String>, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(db::get_databases(&client).await?)
+}
+#[tauri::command]
+async fn get_tables(
+    state: State<'_, DatabaseState>,
+    name: String,
+    schema: Option<String>,
+    include_temp: Option<bool>,
+) -> Result<Vec<String>, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(db::get_tables(&client, schema, include_temp.unwrap_or(false)).await?)
+}
+
+#[tauri::command]
+async fn get_views(
+    state: State<'_, DatabaseState>,
+    name: String,
+    schema: Option<String>,
+) -> Result<Vec<String>, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(db::get_views(&client, schema).await?)
+}
+
+#[tauri::command]
+async fn get_functions(
+    state: State<'_, DatabaseState>,
+    name: String,
+    schema: Option<String>,
+) -> Result<Vec<String>, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(db::get_functions(&client, schema).await?)
+}
+
+#[tauri::command]
+async fn get_view_definition(
+    state: State<'_, DatabaseState>,
+    name: String,
+    schema: Option<String>,
+    view: String,
+) -> Result<String, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(db::get_view_definition(&client, schema, &view).await?)
+}
+
+#[tauri::command]
+async fn get_routine_definition(
+    state: State<'_, DatabaseState>,
+    name: String,
+    schema: Option<String>,
+    routine: String,
+) -> Result<String, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(db::get_routine_definition(&client, schema, &routine).await?)
+}
+
+// Passwords go to the OS keychain; connections.json only keeps a reference.
+// Keychain entries of connections dropped or renamed here are removed.
+#[tauri::command]
+async fn save_connections(
+    app: tauri::AppHandle,
+    connections: Vec<SavedConnection>,
+) -> Result<(), DbError> {
+    let path = storage::data_file(&app, "connections.json")?;
+    log::debug!("Saving connections to: {:?}", path);
+    let existing: Vec<SavedConnection> = storage::read_json(&path)?;
+    for connection in &connections {
+        vault::check_sealable(connection)?;
+    }
+    let mut connections = connections;
+    for connection in &mut connections {
+        let fallback_ref = existing
+            .iter()
+            .find(|c| c.name == connection.name)
+            .and_then(|c| c.secret_ref.clone());
+        let mut sealed = connection.clone();
+        match vault::seal(&mut sealed, fallback_ref.as_deref()) {
+            Ok(()) => *connection = sealed,
+            // Without a usable keychain the password stays where it always was
+            Err(e) => log::warn!(
+                "Keeping password of {} in connections.json: {}",
+                connection.name,
+                e
+            ),
+        }
+    }
+    storage::write_json(&path, &connections)?;
+    let orphaned = existing
+        .iter()
+        .filter_map(|c| c.secret_ref.as_deref())
+        .filter(|r| {
+            !connections
+                .iter()
+                .any(|c| c.secret_ref.as_deref() == Some(*r))
+        });
+    for secret_ref in orphaned {
+        if let Err(e) = vault::forget(secret_ref) {
+            log::warn!("Failed to remove an unused keychain entry: {}", e);
+        }
+    }
+    log::debug!("Successfully saved {} connections", connections.len());
+    Ok(())
+}
+
+#[tauri::command]
+async fn load_connections(app: tauri::AppHandle) -> Result<Vec<SavedConnection>, DbError> {
+    let connections = read_saved_connections(&app)?;
+    log::debug!("Loaded {} connections", connections.len());
+    Ok(connections)
+}
+
+#[tauri::command]
+async fn debug_path(app: tauri::AppHandle) -> Result<String, DbError> {
+    let path = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join("connections.json");
+    Ok(path.to_string_lossy().to_string())
+}
+
+fn read_settings(app: &tauri::AppHandle) -> Result<Settings, String> {
+    let path = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join("settings.json");
+
+    if !path.exists() {
+        return Ok(Settings::default());
+    }
+
+    let json = fs::read_to_string(&path).map_err(|e| format!("Failed to read settings: {}", e))?;
+    let settings: Settings = serde_json::from_str(&json).unwrap_or_else(|_| Settings::default());
+    Ok(settings)
+}
+
+#[tauri::command]
+async fn load_settings(
+    app: tauri::AppHandle,
+    state: State<'_, SettingsState>,
+) -> Result<Settings, DbError> {
+    let settings = read_settings(&app)?;
+    state.set(settings.clone());
+    Ok(settings)
+}
+
+#[tauri::command]
+async fn save_settings(
+    app: tauri::AppHandle,
+    state: State<'_, SettingsState>,
+    settings: Settings,
+) -> Result<(), DbError> {
+    let path = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join("settings.json");
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+
+    let json = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
+    fs::write(&path, json).map_err(|e| format!("Failed to write settings: {}", e))?;
+    state.set(settings);
+    Ok(())
+}
+
+// Where an export goes: `path` if given, otherwise named by the template
+fn export_destination(
+    app: &tauri::AppHandle,
+    name: &str,
+    sql: &str,
+    path: Option<String>,
+    template: Option<String>,
+    format: Option<String>,
+) -> Result<exports::Destination, String> {
+    let downloads = app.path().download_dir().map_err(|e| e.to_string())?;
+    let settings = app.state::<SettingsState>().get();
+    exports::resolve(
+        &settings.export,
+        template.as_deref(),
+        path,
+        format,
+        name,
+        sql,
+        &downloads,
+    )
+}
+
+#[tauri::command]
+async fn export_data(
+    app: tauri::AppHandle,
+    name: String,
+    sql: String,
+    format: Option<String>,
+    path: Option<String>,
+    template: Option<String>,
+    route: Option<QueryRoute>,
+) -> Result<ExportedFile, DbError> {
+    let state = app.state::<DatabaseState>();
+    let client = state.routed_client(&name, &sql, route.unwrap_or_default())?;
+    require(&app, Operation::of_statement(&client, &sql))?;
+
+    let destination = export_destination(&app, &name, &sql, path, template, format)?;
+    let options = QueryOptions::for_export(&app.state::<SettingsState>().get());
+    let shaper = ColumnShaper::new(destination.columns)?;
+    let statement = sql.clone();
+    let export = db::export_data(
+        &client,
+        sql,
+        destination.format,
+        destination.path.clone(),
+        &destination.export,
+        &options,
+        &shaper,
+    );
+    let rows = audit::Auditor::for_connection(&app, &name)
+        .run(&statement, export)
+        .await?;
+    Ok(ExportedFile::written(destination.path, Some(rows))?)
+}
+
+// Exports the editor's query in full rather than the previewed rows: the
+// auto-limit comes off, and since the result may be far larger, the export
+// only runs once the estimated row count is confirmed
+#[tauri::command]
+async fn export_full_result(
+    app: tauri::AppHandle,
+    name: String,
+    sql: String,
+    format: Option<String>,
+    path: Option<String>,
+    template: Option<String>,
+    confirm_token: Option<String>,
+) -> Result<Guarded<ExportedFile>, DbError> {
+    let state = app.state::<DatabaseState>();
+    let client = state.routed_client(&name, &sql, QueryRoute::default())?;
+    require(&app, Operation::of_statement(&client, &sql))?;
+    client
+        .dialect()
+        .ok_or("Full exports are only supported for SQL databases")?;
+
+    // The editor's auto-limit is applied as a row limit when it runs, never
+    // written into the SQL, and export options carry none
+    let statements = vec![sql.clone()];
+    // The estimate is only for the confirmation prompt
+    let action = match confirm_token {
+        Some(_) => String::new(),
+        None => match explain::estimate_rows(&client, &sql).await {
+            Ok(Some(rows)) => format!("Export about {} rows from {}", rows, name),
+            Ok(None) => format!("Export all rows from {}", name),
+            Err(e) => {
+                log::warn!("No row estimate for the export from {}: {}", name, e);
+                format!("Export all rows from {}", name)
+            }
+        },
+    };
+    if let Some(confirmation) =
+        state
+            .confirmations
+            .check(&name, action, &statements, confirm_token.as_deref())?
+    {
+        return Ok(Guarded::ConfirmationRequired(confirmation));
+    }
+
+    let destination = export_destination(&app, &name, &sql, path, template, format)?;
+    let options = QueryOptions::for_export(&app.state::<SettingsState>().get());
+    let shaper = ColumnShaper::new(destination.columns)?;
+    let statement = sql.clone();
+    let export = db::export_data(
+        &client,
+        sql,
+        destination.format,
+        destination.path.clone(),
+        &destination.export,
+        &options,
+        &shaper,
+    );
+    let rows = audit::Auditor::for_connection(&app, &name)
+        .run(&statement, export)
+        .await?;
+    Ok(Guarded::Done {
+        result: ExportedFile::written(destination.path, Some(rows))?,
+    })
+}
+
+// Streams the result to disk in `format` without passing rows through the
+// frontend, cancellable through cancel_query when given a query id. A failed
+// or cancelled export doesn't leave a partial file behind.
+async fn stream_export(
+    app: &tauri::AppHandle,
+    name: String,
+    sql: String,
+    (path, template, format): (Option<String>, Option<String>, &str),
+    include_headers: Option<bool>,
+    query_id: Option<String>,
+    route: Option<QueryRoute>,
+) -> Result<ExportedFile, DbError> {
+    let state = app.state::<DatabaseState>();
+    let client = state.routed_client(&name, &sql, route.unwrap_or_default())?;
+    require(app, Operation::of_statement(&client, &sql))?;
+
+    let mut destination =
+        export_destination(app, &name, &sql, path, template, Some(format.into()))?;
+    if let Some(include_headers) = include_headers {
+        destination.export.include_headers = include_headers;
+    }
+    let options = QueryOptions::for_export(&app.state::<SettingsState>().get());
+    let shaper = ColumnShaper::new(destination.columns.clone())?;
+    let mut partial = jobs::PartialFile::new(&destination.path);
+    let sink = jobs::ExportSink::create(&destination)?
+        .ok_or_else(|| format!("Unsupported format: {}", format))?;
+    partial.claim();
+    let write = |batch| sink.write(shaper.apply(batch));
+    let statement = sql.clone();
+    let export = async {
+        match query_id {
+            Some(id) => {
+                let query = TrackedQuery {
+                    id,
+                    connection: name.clone(),
+                    client,
+                    sql,
+                    max_concurrent: max_concurrent(app),
+                };
+                state
+                    .running
+                    .stream(query, &options, db::DEFAULT_STREAM_BATCH, &write)
+                    .await
+            }
+            None => {
+                db::stream_query(
+                    &client,
+                    sql,
+                    &options,
+                    db::DEFAULT_STREAM_BATCH,
+                    None,
+                    &write,
+                )
+                .await
+            }
+        }
+    };
+    let rows = audit::Auditor::for_connection(app, &name)
+        .run(&statement, export)
+        .await?;
+    sink.finish()?;
+    partial.keep();
+    Ok(ExportedFile::written(destination.path, Some(rows))?)
+}
+
+#[tauri::command]
+async fn export_query_to_csv(
+    app: tauri::AppHandle,
+    name: String,
+    sql: String,
+    path: Option<String>,
+    template: Option<String>,
+    query_id: Option<String>,
+    route: Option<QueryRoute>,
+) -> Result<ExportedFile, DbError> {
+    let destination = (path, template, "csv");
+    stream_export(&app, name, sql, destination, None, query_id, route).await
+}
+
+// A JSON array, or with `ndjson` one object per line, for piping into other
+// tools
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_query_to_json(
+    app: tauri::AppHandle,
+    name: String,
+    sql: String,
+    path: Option<String>,
+    ndjson: bool,
+    template: Option<String>,
+    query_id: Option<String>,
+    route: Option<QueryRoute>,
+) -> Result<ExportedFile, DbError> {
+    let destination = (path, template, if ndjson { "jsonl" } else { "json" });
+    stream_export(&app, name, sql, destination, None, query_id, route).await
+}
+
+// Typed Excel export; the header row follows the export settings (or
+// template) unless `include_headers` is given. Rows are fetched in batches but
+// the workbook is built in memory and written out at the end.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn export_query_to_xlsx(
+    app: tauri::AppHandle,
+    name: String,
+    sql: String,
+    path: Option<String>,
+    template: Option<String>,
+    include_headers: Option<bool>,
+    query_id: Option<String>,
+    route: Option<QueryRoute>,
+) -> Result<ExportedFile, DbError> {
+    let destination = (path, template, "excel");
+    stream_export(
+        &app,
+        name,
+        sql,
+        destination,
+        include_headers,
+        query_id,
+        route,
+    )
+    .await
+}
+
+// Background counterpart of export_data: returns the job id at once and
+// reports the rows written as "job-progress" events
+#[tauri::command]
+async fn start_export_job(
+    app: tauri::AppHandle,
+    name: String,
+    sql: String,
+    format: Option<String>,
+    path: Option<String>,
+    template: Option<String>,
+    route: Option<QueryRoute>,
+) -> Result<String, DbError> {
+    let state = app.state::<DatabaseState>();
+    let client = state.routed_client(&name, &sql, route.unwrap_or_default())?;
+    require(&app, Operation::of_statement(&client, &sql))?;
+
+    let destination = export_destination(&app, &name, &sql, path, template, format)?;
+    let options = QueryOptions::for_export(&app.state::<SettingsState>().get());
+    let max_concurrent = max_concurrent(&app);
+    let description = format!("Export to {}", destination.path);
+    let connection = name.clone();
+    let handle = app.clone();
+    Ok(state.jobs.start(
+        &app,
+        jobs::JobKind::Export,
+        &name,
+        description,
+        |job| async move {
+            let statement = sql.clone();
+            let query = TrackedQuery {
+                id: job.id.clone(),
+                connection: connection.clone(),
+                client,
+                sql,
+                max_concurrent,
+            };
+            let running = &handle.state::<DatabaseState>().running;
+            let work = jobs::export(running, query, destination, &options, &job);
+            audit::Auditor::for_connection(&handle, &connection)
+                .run(&statement, work)
+                .await
+        },
+    ))
+}
+
+// COPY options in `sql` decide the file format; a template only names the file
+#[tauri::command]
+async fn copy_to_file(
+    app: tauri::AppHandle,
+    name: String,
+    sql: String,
+    path: Option<String>,
+    template: Option<String>,
+) -> Result<ExportedFile, DbError> {
+    let client = app.state::<DatabaseState>().client(&name)?;
+    let operation = if sqltext::is_copy_out(&sql) {
+        Operation::Read
+    } else {
+        Operation::of_statement(&client, &sql)
+    };
+    require(&app, operation)?;
+
+    let destination = export_destination(&app, &name, &sql, path, template, Some("csv".into()))?;
+    db::copy_to_file(&client, sql, destination.path.clone()).await?;
+    Ok(ExportedFile::written(destination.path, None)?)
+}
+
+#[tauri::command]
+async fn list_scheduled_exports(
+    app: tauri::AppHandle,
+) -> Result<Vec<scheduled_exports::ScheduledExport>, DbError> {
+    let path = storage::data_file(&app, scheduled_exports::SCHEDULED_EXPORTS_FILE)?;
+    Ok(scheduled_exports::list(&path)?)
+}
+
+#[tauri::command]
+async fn save_scheduled_export(
+    app: tauri::AppHandle,
+    export: scheduled_exports::ScheduledExport,
+) -> Result<scheduled_exports::ScheduledExport, DbError> {
+    // Checked again against the connection each time the export runs
+    let operation = match app.state::<DatabaseState>().peek(&export.connection) {
+        Ok(client) => Operation::of_statement(&client, &export.sql),
+        Err(_) => Operation::of_sql(&export.sql),
+    };
+    require(&app, operation)?;
+    let path = storage::data_file(&app, scheduled_exports::SCHEDULED_EXPORTS_FILE)?;
+    Ok(scheduled_exports::save(&path, export)?)
+}
+
+#[tauri::command]
+async fn delete_scheduled_export(app: tauri::AppHandle, id: String) -> Result<(), DbError> {
+    let path = storage::data_file(&app, scheduled_exports::SCHEDULED_EXPORTS_FILE)?;
+    Ok(scheduled_exports::delete(&path, &id)?)
+}
+
+// Moves an incremental export's watermark; None exports everything next run
+#[tauri::command]
+async fn set_scheduled_export_watermark(
+    app: tauri::AppHandle,
+    id: String,
+    watermark: Option<serde_json::Value>,
+) -> Result<(), DbError> {
+    let path = storage::data_file(&app, scheduled_exports::SCHEDULED_EXPORTS_FILE)?;
+    Ok(scheduled_exports::set_watermark(&path, &id, watermark)?)
+}
+
+// Runs a scheduled export right away; the next run is due an interval later
+#[tauri::command]
+async fn run_scheduled_export(app: tauri::AppHandle, id: String) -> Result<ExportedFile, DbError> {
+    let path = storage::data_file(&app, scheduled_exports::SCHEDULED_EXPORTS_FILE)?;
+    let export = scheduled_exports::list(&path)?
+        .into_iter()
+        .find(|e| e.id == id)
+        .ok_or("Scheduled export not found")?;
+    Ok(scheduled_exports::run(&app, &export).await?)
+}
+
+#[tauri::command]
+async fn list_notification_channels(
+    app: tauri::AppHandle,
+) -> Result<Vec<notifications::NotificationChannel>, DbError> {
+    let path = storage::data_file(&app, notifications::NOTIFICATIONS_FILE)?;
+    Ok(notifications::list(&path)?)
+}
+
+#[tauri::command]
+async fn save_notification_channel(
+    app: tauri::AppHandle,
+    channel: notifications::NotificationChannel,
+) -> Result<notifications::NotificationChannel, DbError> {
+    let path = storage::data_file(&app, notifications::NOTIFICATIONS_FILE)?;
+    Ok(notifications::save(&path, channel)?)
+}
+
+#[tauri::command]
+async fn delete_notification_channel(app: tauri::AppHandle, id: String) -> Result<(), DbError> {
+    let path = storage::data_file(&app, notifications::NOTIFICATIONS_FILE)?;
+    Ok(notifications::delete(&path, &id)?)
+}
+
+#[tauri::command]
+async fn test_notification_channel(app: tauri::AppHandle, id: String) -> Result<(), DbError> {
+    let path = storage::data_file(&app, notifications::NOTIFICATIONS_FILE)?;
+    Ok(notifications::test(&path, &id).await?)
+}
+
+// Names and descriptions only; values stay in the keychain
+#[tauri::command]
+async fn list_secrets(app: tauri::AppHandle) -> Result<Vec<secrets::SecretInfo>, DbError> {
+    let path = storage::data_file(&app, secrets::SECRETS_FILE)?;
+    Ok(secrets::list(&path)?)
+}
+
+#[tauri::command]
+async fn set_secret(
+    app: tauri::AppHandle,
+    name: String,
+    value: String,
+    description: Option<String>,
+) -> Result<secrets::SecretInfo, DbError> {
+    let path = storage::data_file(&app, secrets::SECRETS_FILE)?;
+    Ok(secrets::set_secret(&path, &name, &value, description)?)
+}
+
+#[tauri::command]
+async fn delete_secret(app: tauri::AppHandle, name: String) -> Result<(), DbError> {
+    let path = storage::data_file(&app, secrets::SECRETS_FILE)?;
+    Ok(secrets::delete_secret(&path, &name)?)
+}
+
+#[tauri::command]
+async fn undo_last_change(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+) -> Result<Vec<String>, DbError> {
+    require(&app, Operation::Write)?;
+    let (client, nested) = state.edit_client(&name)?;
+
+    let changes = state.undo.take_last(&name).ok_or("Nothing to undo")?;
+    match edits::revert_changes(
+        &client,
+        nested,
+        &changes,
+        &audit::Auditor::for_connection(&app, &name),
+    )
+    .await
+    {
+        Ok(statements) => Ok(statements),
+        Err(e) => {
+            state.undo.restore(&name, changes);
+            Err(e.into())
+        }
+    }
+}
+
+#[tauri::command]
+async fn get_row_identity(
+    state: State<'_, DatabaseState>,
+    name: String,
+    schema: Option<String>,
+    table: String,
+) -> Result<RowIdentity, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(edits::resolve_row_identity(&client, &TableRef { schema, table }).await?)
+}
+
+#[tauri::command]
+async fn get_columns(
+    state: State<'_, DatabaseState>,
+    name: String,
+    schema: Option<String>,
+    table: String,
+) -> Result<Vec<db::TableColumn>, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(db::get_columns(&client, schema, &table).await?)
+}
+
+// A JSON_TABLE query expanding a JSON column into rows and columns, with the
+// structure taken from a sample of its values (MySQL and MariaDB)
+#[tauri::command]
+async fn get_json_table_query(
+    state: State<'_, DatabaseState>,
+    name: String,
+    schema: Option<String>,
+    table: String,
+    column: String,
+    options: Option<json_table::JsonTableOptions>,
+) -> Result<json_table::JsonTableQuery, DbError> {
+    let client = state.client(&name)?;
+    let table = TableRef { schema, table };
+
+    Ok(json_table::json_table_query(&client, &table, &column, options.unwrap_or_default()).await?)
+}
+
+#[tauri::command]
+async fn get_constraints(
+    state: State<'_, DatabaseState>,
+    name: String,
+    schema: Option<String>,
+    table: String,
+) -> Result<db::TableConstraints, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(db::get_constraints(&client, schema, &table).await?)
+}
+
+#[tauri::command]
+async fn get_indexes(
+    state: State<'_, DatabaseState>,
+    name: String,
+    schema: Option<String>,
+    table: String,
+) -> Result<Vec<indexes::TableIndex>, DbError> {
+    let client = state.client(&name)?;
+    let table = TableRef { schema, table };
+
+    Ok(indexes::get_indexes(&client, &table).await?)
+}
+
+#[tauri::command]
+async fn get_partitions(
+    state: State<'_, DatabaseState>,
+    name: String,
+    table: TableRef,
+) -> Result<Vec<browse::TablePartition>, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(browse::get_partitions(&client, &table).await?)
+}
+
+// `partition` narrows the read to one partition, as listed by get_partitions
+#[tauri::command]
+async fn get_table_data(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+    table: TableRef,
+    limit: usize,
+    offset: usize,
+    partition: Option<String>,
+) -> Result<QueryResponse, DbError> {
+    let client = state.client(&name)?;
+    let columns = state
+        .metadata
+        .columns(&name, &client, table.schema.as_deref(), &table.table)
+        .await?;
+
+    let options = QueryOptions::from_settings(&app.state::<SettingsState>().get());
+    Ok(browse::get_table_data(
+        &client,
+        &table,
+        &columns,
+        partition.as_deref(),
+        limit,
+        offset,
+        &options,
+    )
+    .await?)
+}
+
+// A page of rows sorted and filtered server-side, with the total match count;
+// `page` is 1-based
+#[tauri::command]
+async fn browse_table(
+    app: tauri::AppHandle,
+    name: String,
+    table: TableRef,
+    page: Option<usize>,
+    page_size: Option<usize>,
+    sort: Option<Vec<browse::SortKey>>,
+    filters: Option<Vec<browse::ColumnFilter>>,
+) -> Result<browse::TablePage, DbError> {
+    let state = app.state::<DatabaseState>();
+    let client = state.client(&name)?;
+    let columns = state
+        .metadata
+        .columns(&name, &client, table.schema.as_deref(), &table.table)
+        .await?;
+
+    let options = QueryOptions::from_settings(&app.state::<SettingsState>().get());
+    Ok(browse::browse_table(
+        &client,
+        &table,
+        &columns,
+        (
+            page.unwrap_or(1),
+            page_size.unwrap_or(browse::DEFAULT_PAGE_SIZE),
+        ),
+        &sort.unwrap_or_default(),
+        &filters.unwrap_or_default(),
+        &options,
+    )
+    .await?)
+}
+
+#[tauri::command]
+async fn get_temporal_tables(
+    state: State<'_, DatabaseState>,
+    name: String,
+    schema: Option<String>,
+) -> Result<Vec<temporal::TemporalTable>, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(temporal::get_temporal_tables(&client, schema).await?)
+}
+
+// SQL for a point-in-time read, to be run through execute_query
+#[tauri::command]
+async fn build_as_of_query(
+    state: State<'_, DatabaseState>,
+    name: String,
+    table: TableRef,
+    at: DateTime<Utc>,
+) -> Result<String, DbError> {
+    let client = state.client(&name)?;
+    let dialect = client
+        .dialect()
+        .ok_or("Temporal queries are not supported for this database type")?;
+    Ok(temporal::as_of_sql(dialect, &table, at)?)
+}
+
+#[tauri::command]
+async fn get_foreign_keys(
+    state: State<'_, DatabaseState>,
+    name: String,
+    schema: Option<String>,
+    table: String,
+) -> Result<Vec<db::ForeignKey>, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(db::get_foreign_keys(&client, schema, &table).await?)
+}
+
+#[tauri::command]
+async fn resolve_foreign_row(
+    state: State<'_, DatabaseState>,
+    settings: State<'_, SettingsState>,
+    name: String,
+    table: TableRef,
+    column: String,
+    row: serde_json::Map<String, serde_json::Value>,
+) -> Result<relations::RelatedRows, DbError> {
+    let client = state.client(&name)?;
+
+    let options = QueryOptions::from_settings(&settings.get());
+    Ok(relations::resolve_foreign_row(&client, &table, &column, &row, &options).await?)
+}
+
+#[tauri::command]
+async fn find_referencing_rows(
+    state: State<'_, DatabaseState>,
+    settings: State<'_, SettingsState>,
+    name: String,
+    table: TableRef,
+    row: serde_json::Map<String, serde_json::Value>,
+    limit: Option<usize>,
+) -> Result<Vec<relations::RelatedRows>, DbError> {
+    let client = state.client(&name)?;
+
+    let options = QueryOptions::from_settings(&settings.get());
+    Ok(relations::find_referencing_rows(&client, &table, &row, limit, &options).await?)
+}
+
+#[tauri::command]
+async fn get_full_row(
+    state: State<'_, DatabaseState>,
+    settings: State<'_, SettingsState>,
+    name: String,
+    table: TableRef,
+    key: serde_json::Map<String, serde_json::Value>,
+) -> Result<QueryResponse, DbError> {
+    let client = state.client(&name)?;
+
+    let options = QueryOptions::from_settings(&settings.get());
+    Ok(relations::get_full_row(&client, &table, &key, &options).await?)
+}
+
+#[tauri::command]
+async fn stage_change(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+    change: StagedChange,
+) -> Result<usize, DbError> {
+    require(&app, Operation::Write)?;
+    let client = state.client(&name)?;
+
+    let change = edits::prepare_change(&client, change).await?;
+    Ok(state.staged.stage(&name, change))
+}
+
+#[tauri::command]
+async fn list_staged_changes(
+    state: State<'_, DatabaseState>,
+    name: String,
+) -> Result<Vec<StagedChange>, DbError> {
+    Ok(state.staged.list(&name))
+}
+
+#[tauri::command]
+async fn preview_changes(
+    state: State<'_, DatabaseState>,
+    name: String,
+) -> Result<Vec<String>, DbError> {
+    let client = state.client(&name)?;
+    let dialect = client
+        .dialect()
+        .ok_or("Editing is not supported for this database type")?;
+
+    Ok(state
+        .staged
+        .list(&name)
+        .iter()
+        .map(|c| c.sql(dialect))
+        .collect())
+}
+
+#[tauri::command]
+async fn apply_changes(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+) -> Result<u64, DbError> {
+    require(&app, Operation::Write)?;
+    let (client, nested) = state.edit_client(&name)?;
+
+    let changes = state.staged.list(&name);
+    if changes.is_empty() {
+        return Ok(0);
+    }
+    let (affected, journal) = edits::apply_changes(
+        &client,
+        nested,
+        &changes,
+        &audit::Auditor::for_connection(&app, &name),
+    )
+    .await?;
+    // Only drop the staged set once it is committed, so a failed apply can be fixed and retried
+    state.staged.take(&name);
+    state.undo.record(&name, journal);
+    Ok(affected)
+}
+
+#[tauri::command]
+async fn update_row(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+    table: TableRef,
+    values: serde_json::Map<String, serde_json::Value>,
+    key: serde_json::Map<String, serde_json::Value>,
+) -> Result<u64, DbError> {
+    require(&app, Operation::Write)?;
+    let (client, _) = state.edit_client(&name)?;
+    let (affected, journal) = edits::update_row(
+        &client,
+        &table,
+        &values,
+        &key,
+        &audit::Auditor::for_connection(&app, &name),
+    )
+    .await?;
+    state.undo.record(&name, journal);
+    Ok(affected)
+}
+
+#[tauri::command]
+async fn insert_row(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+    table: TableRef,
+    values: serde_json::Map<String, serde_json::Value>,
+) -> Result<u64, DbError> {
+    require(&app, Operation::Write)?;
+    let (client, _) = state.edit_client(&name)?;
+    let (affected, journal) = edits::insert_row(
+        &client,
+        &table,
+        &values,
+        &audit::Auditor::for_connection(&app, &name),
+    )
+    .await?;
+    state.undo.record(&name, journal);
+    Ok(affected)
+}
+
+#[tauri::command]
+async fn delete_row(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+    table: TableRef,
+    key: serde_json::Map<String, serde_json::Value>,
+) -> Result<u64, DbError> {
+    require(&app, Operation::Write)?;
+    let (client, _) = state.edit_client(&name)?;
+    let (affected, journal) = edits::delete_row(
+        &client,
+        &table,
+        &key,
+        &audit::Auditor::for_connection(&app, &name),
+    )
+    .await?;
+    state.undo.record(&name, journal);
+    Ok(affected)
+}
+
+// Previews a grid pasted from the clipboard, or inserts it into `table`
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+async fn paste_import(
+    app: tauri::AppHandle,
+    name: String,
+    table: TableRef,
+    text: String,
+    delimiter: Option<String>,
+    has_header: Option<bool>,
+    conflict_columns: Option<Vec<String>>,
+    preview: bool,
+) -> Result<paste_import::PasteImportResult, DbError> {
+    if !preview {
+        require(&app, Operation::Write)?;
+    }
+    let client = app.state::<DatabaseState>().edit_client(&name)?;
+
+    let auditor = audit::Auditor::for_connection(&app, &name);
+    Ok(paste_import::paste_import(
+        &client,
+        &table,
+        &text,
+        (delimiter, has_header),
+        conflict_columns.as_deref(),
+        preview,
+        &auditor,
+    )
+    .await?)
+}
+
+// Imports a CSV file into `table`, reporting progress as "csv-import-progress"
+// events and returning the rows that were rejected
+#[tauri::command]
+async fn import_csv(
+    app: tauri::AppHandle,
+    name: String,
+    schema: Option<String>,
+    table: String,
+    path: String,
+    options: Option<csv_import::CsvImportOptions>,
+) -> Result<csv_import::CsvImportResult, DbError> {
+    require(&app, Operation::Write)?;
+    let client = app.state::<DatabaseState>().client(&name)?;
+    let table = TableRef { schema, table };
+
+    Ok(csv_import::import_csv(
+        &client,
+        &table,
+        &path,
+        options.unwrap_or_default(),
+        &audit::Auditor::for_connection(&app, &name),
+        |progress| {
+            let _ = app.emit("csv-import-progress", progress);
+        },
+    )
+    .await?)
+}
+
+// Background counterpart of import_csv, with the rows read so far as the
+// job's progress
+#[tauri::command]
+async fn start_import_job(
+    app: tauri::AppHandle,
+    name: String,
+    schema: Option<String>,
+    table: String,
+    path: String,
+    options: Option<csv_import::CsvImportOptions>,
+) -> Result<String, DbError> {
+    require(&app, Operation::Write)?;
+    let client = app.state::<DatabaseState>().client(&name)?;
+    let table = TableRef { schema, table };
+    let description = format!("Import {} into {}", path, table.table);
+    let auditor = audit::Auditor::for_connection(&app, &name);
+
+    Ok(app.state::<DatabaseState>().jobs.start(
+        &app,
+        jobs::JobKind::Import,
+        &name,
+        description,
+        |job| async move {
+            csv_import::import_csv(
+                &client,
+                &table,
+                &path,
+                options.unwrap_or_default(),
+                &auditor,
+                |progress| {
+                    // Batches are committed as they go, so a cancelled
+                    // import reports the rows that stay in the table
+                    job.progress(jobs::JobProgress {
+                        done: progress.inserted,
+                        total: None,
+                        message: Some(format!(
+                            "{} read, {} rejected",
+                            progress.read, progress.rejected
+                        )),
+                    })
+                },
+            )
+            .await
+        },
+    ))
+}
+
+#[tauri::command]
+async fn discard_changes(state: State<'_, DatabaseState>, name: String) -> Result<(), DbError> {
+    state.staged.clear(&name);
+    Ok(())
+}
+
+#[tauri::command]
+async fn generate_upsert(
+    state: State<'_, DatabaseState>,
+    name: String,
+    schema: Option<String>,
+    table: String,
+    conflict_columns: Vec<String>,
+    columns: Option<Vec<String>>,
+) -> Result<String, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(edits::generate_upsert(
+        &client,
+        &TableRef { schema, table },
+        &conflict_columns,
+        columns,
+    )
+    .await?)
+}
+
+// Returns the statements; with `dry_run` they are only generated for preview
+#[tauri::command]
+async fn create_database(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+    spec: ddl::DatabaseSpec,
+    dry_run: Option<bool>,
+) -> Result<Vec<String>, DbError> {
+    if !dry_run.unwrap_or(false) {
+        require(&app, Operation::Ddl)?;
+    }
+    let client = state.client(&name)?;
+
+    let statements = ddl::create_database_sql(ddl::dialect(&client)?, &spec)?;
+    if !dry_run.unwrap_or(false) {
+        ddl::run_statements(
+            &client,
+            &statements,
+            &audit::Auditor::for_connection(&app, &name),
+        )
+        .await?;
+    }
+    Ok(statements)
+}
+
+// Destructive commands run in two calls: without `confirm_token` they return
+// the statements and a token, and only the call repeating that token runs them.
+// Drops also take `confirmation`, the object name as typed by the user.
+#[tauri::command]
+async fn drop_database(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+    database: String,
+    force: Option<bool>,
+    confirmation: Option<String>,
+    confirm_token: Option<String>,
+) -> Result<Guarded<Vec<String>>, DbError> {
+    require(&app, Operation::Ddl)?;
+    let client = state.client(&name)?;
+
+    let statements =
+        ddl::drop_database_sql(ddl::dialect(&client)?, &database, force.unwrap_or(false));
+    // The typed name is checked before the token is spent on a typo
+    if confirm_token.is_some() {
+        ddl::check_confirmation(&database, confirmation.as_deref().unwrap_or(""))?;
+    }
+    let action = format!("Drop database {}", database);
+    if let Some(confirmation) =
+        state
+            .confirmations
+            .check(&name, action, &statements, confirm_token.as_deref())?
+    {
+        return Ok(Guarded::ConfirmationRequired(confirmation));
+    }
+    ddl::run_statements(
+        &client,
+        &statements,
+        &audit::Auditor::for_connection(&app, &name),
+    )
+    .await?;
+    Ok(Guarded::Done { result: statements })
+}
+
+#[tauri::command]
+async fn create_schema(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+    schema: String,
+    owner: Option<String>,
+    dry_run: Option<bool>,
+) -> Result<Vec<String>, DbError> {
+    if !dry_run.unwrap_or(false) {
+        require(&app, Operation::Ddl)?;
+    }
+    let client = state.client(&name)?;
+
+    let statements = ddl::create_schema_sql(ddl::dialect(&client)?, &schema, owner.as_deref())?;
+    if !dry_run.unwrap_or(false) {
+        ddl::run_statements(
+            &client,
+            &statements,
+            &audit::Auditor::for_connection(&app, &name),
+        )
+        .await?;
+    }
+    Ok(statements)
+}
+
+#[tauri::command]
+async fn drop_schema(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+    schema: String,
+    cascade: Option<bool>,
+    confirmation: Option<String>,
+    confirm_token: Option<String>,
+) -> Result<Guarded<Vec<String>>, DbError> {
+    require(&app, Operation::Ddl)?;
+    let client = state.client(&name)?;
+
+    let statements =
+        ddl::drop_schema_sql(ddl::dialect(&client)?, &schema, cascade.unwrap_or(false))?;
+    if confirm_token.is_some() {
+        ddl::check_confirmation(&schema, confirmation.as_deref().unwrap_or(""))?;
+    }
+    let action = format!("Drop schema {}", schema);
+    if let Some(confirmation) =
+        state
+            .confirmations
+            .check(&name, action, &statements, confirm_token.as_deref())?
+    {
+        return Ok(Guarded::ConfirmationRequired(confirmation));
+    }
+    ddl::run_statements(
+        &client,
+        &statements,
+        &audit::Auditor::for_connection(&app, &name),
+    )
+    .await?;
+    Ok(Guarded::Done { result: statements })
+}
+
+#[tauri::command]
+async fn truncate_table(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+    table: TableRef,
+    confirm_token: Option<String>,
+) -> Result<Guarded<Vec<String>>, DbError> {
+    require(&app, Operation::Ddl)?;
+    let client = state.client(&name)?;
+
+    let statements = ddl::truncate_table_sql(ddl::dialect(&client)?, &table);
+    let action = format!("Truncate table {}", table.table);
+    if let Some(confirmation) =
+        state
+            .confirmations
+            .check(&name, action, &statements, confirm_token.as_deref())?
+    {
+        return Ok(Guarded::ConfirmationRequired(confirmation));
+    }
+    ddl::run_statements(
+        &client,
+        &statements,
+        &audit::Auditor::for_connection(&app, &name),
+    )
+    .await?;
+    Ok(Guarded::Done { result: statements })
+}
+
+#[tauri::command]
+async fn kill_session(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+    session_id: i64,
+    confirm_token: Option<String>,
+) -> Result<Guarded<Vec<String>>, DbError> {
+    require(&app, Operation::Admin)?;
+    let client = state.client(&name)?;
+
+    let statements = diagnostics::kill_session_sql(ddl::dialect(&client)?, session_id);
+    let action = format!("Kill session {}", session_id);
+    if let Some(confirmation) =
+        state
+            .confirmations
+            .check(&name, action, &statements, confirm_token.as_deref())?
+    {
+        return Ok(Guarded::ConfirmationRequired(confirmation));
+    }
+    ddl::run_statements(
+        &client,
+        &statements,
+        &audit::Auditor::for_connection(&app, &name),
+    )
+    .await?;
+    Ok(Guarded::Done { result: statements })
+}
+
+#[tauri::command]
+async fn transfer_schema_object(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+    schema: String,
+    object: String,
+    target_schema: String,
+    dry_run: Option<bool>,
+) -> Result<Vec<String>, DbError> {
+    if !dry_run.unwrap_or(false) {
+        require(&app, Operation::Ddl)?;
+    }
+    let client = state.client(&name)?;
+
+    let statements =
+        ddl::transfer_schema_sql(ddl::dialect(&client)?, &schema, &object, &target_schema)?;
+    if !dry_run.unwrap_or(false) {
+        ddl::run_statements(
+            &client,
+            &statements,
+            &audit::Auditor::for_connection(&app, &name),
+        )
+        .await?;
+    }
+    Ok(statements)
+}
+
+#[tauri::command]
+async fn list_gridfs_buckets(
+    state: State<'_, DatabaseState>,
+    name: String,
+    database: Option<String>,
+) -> Result<Vec<mongo::GridFsBucketInfo>, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(mongo::list_gridfs_buckets(&client, database).await?)
+}
+
+#[tauri::command]
+async fn list_gridfs_files(
+    state: State<'_, DatabaseState>,
+    name: String,
+    database: Option<String>,
+    bucket: String,
+    filename: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<mongo::GridFsFile>, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(mongo::list_gridfs_files(&client, database, bucket, filename, limit).await?)
+}
+
+#[tauri::command]
+async fn download_gridfs_file(
+    state: State<'_, DatabaseState>,
+    name: String,
+    database: Option<String>,
+    bucket: String,
+    id: serde_json::Value,
+    path: String,
+) -> Result<u64, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(mongo::download_gridfs_file(&client, database, bucket, id, path).await?)
+}
+
+#[tauri::command]
+async fn upload_gridfs_file(
+    app: tauri::AppHandle,
+    name: String,
+    database: Option<String>,
+    bucket: String,
+    path: String,
+    filename: Option<String>,
+    metadata: Option<serde_json::Value>,
+) -> Result<serde_json::Value, DbError> {
+    require(&app, Operation::Write)?;
+    let state = app.state::<DatabaseState>();
+    let client = state.client(&name)?;
+
+    Ok(mongo::upload_gridfs_file(&client, database, bucket, path, filename, metadata).await?)
+}
+
+#[tauri::command]
+async fn delete_gridfs_file(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+    database: Option<String>,
+    bucket: String,
+    id: serde_json::Value,
+) -> Result<(), DbError> {
+    require(&app, Operation::Write)?;
+    let client = state.client(&name)?;
+
+    Ok(mongo::delete_gridfs_file(&client, database, bucket, id).await?)
+}
+
+#[tauri::command]
+async fn infer_collection_schema(
+    state: State<'_, DatabaseState>,
+    name: String,
+    database: Option<String>,
+    collection: String,
+    sample_size: Option<i64>,
+) -> Result<mongo::CollectionSchema, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(mongo::infer_collection_schema(&client, database, collection, sample_size).await?)
+}
+
+#[tauri::command]
+async fn snapshot_pg_stat_statements(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+    label: Option<String>,
+) -> Result<diagnostics::StatSnapshotSummary, DbError> {
+    let client = state.client(&name)?;
+    let path = storage::data_file(&app, diagnostics::STAT_SNAPSHOTS_FILE)?;
+    Ok(diagnostics::take_snapshot(&path, &client, &name, label).await?)
+}
+
+#[tauri::command]
+async fn list_stat_snapshots(
+    app: tauri::AppHandle,
+    name: Option<String>,
+) -> Result<Vec<diagnostics::StatSnapshotSummary>, DbError> {
+    let path = storage::data_file(&app, diagnostics::STAT_SNAPSHOTS_FILE)?;
+    Ok(diagnostics::list_snapshots(&path, name.as_deref())?)
+}
+
+#[tauri::command]
+async fn diff_stat_snapshots(
+    app: tauri::AppHandle,
+    from_id: String,
+    to_id: String,
+) -> Result<diagnostics::StatDiff, DbError> {
+    let path = storage::data_file(&app, diagnostics::STAT_SNAPSHOTS_FILE)?;
+    Ok(diagnostics::diff_snapshots(&path, &from_id, &to_id)?)
+}
+
+#[tauri::command]
+async fn delete_stat_snapshot(app: tauri::AppHandle, id: String) -> Result<(), DbError> {
+    let path = storage::data_file(&app, diagnostics::STAT_SNAPSHOTS_FILE)?;
+    Ok(diagnostics::delete_snapshot(&path, &id)?)
+}
+
+// Writes the connection's schema to a portable JSON file
+#[tauri::command]
+async fn export_schema_snapshot(
+    state: State<'_, DatabaseState>,
+    name: String,
+    path: String,
+) -> Result<schema_snapshots::SchemaSnapshotSummary, DbError> {
+    let client = state.client(&name)?;
+    Ok(schema_snapshots::export(&client, &name, Path::new(&path)).await?)
+}
+
+// Loads a snapshot file for browsing and diffing without a connection
+#[tauri::command]
+async fn import_schema_snapshot(
+    app: tauri::AppHandle,
+    path: String,
+    name: Option<String>,
+) -> Result<schema_snapshots::SchemaSnapshotSummary, DbError> {
+    let store = storage::data_file(&app, schema_snapshots::SCHEMA_SNAPSHOTS_FILE)?;
+    Ok(schema_snapshots::import(&store, Path::new(&path), name)?)
+}
+
+#[tauri::command]
+async fn list_schema_snapshots(
+    app: tauri::AppHandle,
+) -> Result<Vec<schema_snapshots::SchemaSnapshotSummary>, DbError> {
+    let store = storage::data_file(&app, schema_snapshots::SCHEMA_SNAPSHOTS_FILE)?;
+    Ok(schema_snapshots::list(&store)?)
+}
+
+#[tauri::command]
+async fn get_schema_snapshot(
+    app: tauri::AppHandle,
+    id: String,
+) -> Result<schema_snapshots::SchemaSnapshot, DbError> {
+    let store = storage::data_file(&app, schema_snapshots::SCHEMA_SNAPSHOTS_FILE)?;
+    Ok(schema_snapshots::get(&store, &id)?)
+}
+
+#[tauri::command]
+async fn delete_schema_snapshot(app: tauri::AppHandle, id: String) -> Result<(), DbError> {
+    let store = storage::data_file(&app, schema_snapshots::SCHEMA_SNAPSHOTS_FILE)?;
+    Ok(schema_snapshots::delete(&store, &id)?)
+}
+
+async fn load_schema_snapshot(
+    app: &tauri::AppHandle,
+    source: schema_snapshots::SnapshotSource,
+) -> Result<schema_snapshots::SchemaSnapshot, String> {
+    match source {
+        schema_snapshots::SnapshotSource::Snapshot { id } => {
+            let store = storage::data_file(app, schema_snapshots::SCHEMA_SNAPSHOTS_FILE)?;
+            schema_snapshots::get(&store, &id)
+        }
+        schema_snapshots::SnapshotSource::Connection { name } => {
+            let client = app.state::<DatabaseState>().client(&name)?;
+            schema_snapshots::take(&client, &name).await
+        }
+    }
+}
+
+#[tauri::command]
+async fn diff_schema_snapshots(
+    app: tauri::AppHandle,
+    from: schema_snapshots::SnapshotSource,
+    to: schema_snapshots::SnapshotSource,
+) -> Result<Vec<schema_snapshots::SchemaDifference>, DbError> {
+    let before = load_schema_snapshot(&app, from).await?;
+    let after = load_schema_snapshot(&app, to).await?;
+    Ok(schema_snapshots::diff(&before, &after))
+}
+
+// Keeps a copy of the connection's schema for offline mode
+#[tauri::command]
+async fn save_offline_copy(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+) -> Result<schema_snapshots::SchemaSnapshotSummary, DbError> {
+    let client = state.client(&name)?;
+    let snapshot = schema_snapshots::take(&client, &name).await?;
+    let summary = schema_snapshots::SchemaSnapshotSummary::of(&snapshot);
+    let path = storage::data_file(&app, offline::OFFLINE_FILE)?;
+    offline::save_schema(&path, snapshot)?;
+    Ok(summary)
+}
+
+// Opens a connection that can't be reached from what was kept of it
+#[tauri::command]
+async fn open_offline_connection(
+    app: tauri::AppHandle,
+    name: String,
+) -> Result<offline::OfflineConnection, DbError> {
+    let path = storage::data_file(&app, offline::OFFLINE_FILE)?;
+    let snapshots = storage::data_file(&app, schema_snapshots::SCHEMA_SNAPSHOTS_FILE)?;
+    Ok(offline::open(&path, &snapshots, &name)?)
+}
+
+#[tauri::command]
+async fn get_offline_result(
+    app: tauri::AppHandle,
+    name: String,
+    id: String,
+) -> Result<offline::CachedResult, DbError> {
+    let path = storage::data_file(&app, offline::OFFLINE_FILE)?;
+    Ok(offline::result(&path, &name, &id)?)
+}
+
+#[tauri::command]
+async fn clear_offline_copy(app: tauri::AppHandle, name: String) -> Result<(), DbError> {
+    let path = storage::data_file(&app, offline::OFFLINE_FILE)?;
+    Ok(offline::clear(&path, &name)?)
+}
+
+#[tauri::command]
+async fn reset_pg_stat_statements(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+) -> Result<(), DbError> {
+    require(&app, Operation::Admin)?;
+    let client = state.client(&name)?;
+    Ok(diagnostics::reset_statement_stats(&client).await?)
+}
+
+#[tauri::command]
+async fn get_bloat_report(
+    state: State<'_, DatabaseState>,
+    name: String,
+    schema: Option<String>,
+) -> Result<diagnostics::BloatReport, DbError> {
+    let client = state.client(&name)?;
+    Ok(diagnostics::bloat_report(&client, schema).await?)
+}
+
+#[tauri::command]
+async fn get_wait_stats(
+    state: State<'_, DatabaseState>,
+    name: String,
+    limit: Option<usize>,
+) -> Result<Vec<diagnostics::WaitStat>, DbError> {
+    let client = state.client(&name)?;
+    Ok(diagnostics::wait_stats(&client, limit).await?)
+}
+
+#[tauri::command]
+async fn get_blocking_tree(
+    state: State<'_, DatabaseState>,
+    name: String,
+) -> Result<Vec<diagnostics::BlockingSession>, DbError> {
+    let client = state.client(&name)?;
+    Ok(diagnostics::blocking_tree(&client).await?)
+}
+
+// Polled by the status bar; doesn't count as using the connection
+#[tauri::command]
+async fn get_connection_info(
+    state: State<'_, DatabaseState>,
+    name: String,
+) -> Result<diagnostics::ConnectionInfo, DbError> {
+    let client = state.peek(&name)?;
+    Ok(diagnostics::connection_info(&client).await)
+}
+
+// Health of every saved connection for the fleet overview. Open connections
+// are checked over their pools without counting as a use.
+#[tauri::command]
+async fn get_fleet_status(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    concurrency: Option<usize>,
+) -> Result<Vec<fleet::FleetMember>, DbError> {
+    let connections = read_saved_connections(&app)?
+        .into_iter()
+        .map(|c| {
+            let open = state.peek(&c.name).ok();
+            (c, open)
+        })
+        .collect();
+    let settings = app.state::<SettingsState>().get();
+    let timeout = settings.connection.connection_timeout_seconds.max(1) as u64;
+    Ok(fleet::fleet_status(
+        connections,
+        concurrency.unwrap_or(fleet::DEFAULT_FLEET_CONCURRENCY),
+        std::time::Duration::from_secs(timeout),
+    )
+    .await)
+}
+
+// Environment banner data, the same for every window showing the connection
+#[tauri::command]
+async fn get_connection_context(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+) -> Result<environment::ConnectionContext, DbError> {
+    let path = storage::data_file(&app, "connections.json")?;
+    let saved: Vec<SavedConnection> = storage::read_json(&path)?;
+    let connection = saved
+        .iter()
+        .find(|c| c.name == name)
+        .ok_or_else(|| format!("Connection '{}' not found", name))?;
+    let profile = app.state::<SettingsState>().get().profile;
+    let client = state.peek(&name).ok();
+    Ok(environment::connection_context(connection, profile, client.as_ref()).await)
+}
+
+#[tauri::command]
+async fn get_storage_breakdown(
+    state: State<'_, DatabaseState>,
+    name: String,
+    schema: Option<String>,
+) -> Result<diagnostics::StorageBreakdown, DbError> {
+    let client = state.client(&name)?;
+    Ok(diagnostics::storage_breakdown(&client, schema).await?)
+}
+
+#[tauri::command]
+async fn redis_scan_keys(
+    state: State<'_, DatabaseState>,
+    name: String,
+    pattern: Option<String>,
+    cursor: Option<u64>,
+    count: Option<usize>,
+) -> Result<redis_tools::KeyPage, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(redis_tools::scan_keys(&client, pattern, cursor, count).await?)
+}
+
+#[tauri::command]
+async fn redis_get_value(
+    state: State<'_, DatabaseState>,
+    name: String,
+    key: String,
+) -> Result<redis_tools::KeyValue, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(redis_tools::get_value(&client, &key).await?)
+}
+
+#[tauri::command]
+async fn redis_memory_report(
+    state: State<'_, DatabaseState>,
+    name: String,
+    pattern: Option<String>,
+    sample_size: Option<usize>,
+    separator: Option<String>,
+    depth: Option<usize>,
+) -> Result<redis_tools::MemoryReport, DbError> {
+    let client = state.client(&name)?;
+
+    Ok(redis_tools::memory_report(&client, pattern, sample_size, separator, depth).await?)
+}
+
+#[tauri::command]
+async fn redis_bulk_ttl(
+    app: tauri::AppHandle,
+    state: State<'_, DatabaseState>,
+    name: String,
+    pattern: String,
+    action: redis_tools::TtlAction,
+    dry_run: bool,
+    batch_size: Option<usize>,
+) -> Result<redis_tools::TtlBulkResult, DbError> {
+    if !dry_run {
+        require(&app, Operation::Write)?;
+    }
+    let client = state.client(&name)?;
+
+    Ok(
+        redis_tools::bulk_ttl(&client, pattern, action, dry_run, batch_size, |progress| {
+            let _ = app.emit("redis-ttl-progress", progress);
+        })
+        .await?,
+    )
+}
+
+#[tauri::command]
+async fn list_bookmarks(
+    app: tauri::AppHandle,
+    filter: Option<BookmarkFilter>,
+) -> Result<Vec<Bookmark>, DbError> {
+    let path = storage::data_file(&app, bookmarks::BOOKMARKS_FILE)?;
+    Ok(bookmarks::list(&path, &filter.unwrap_or_default())?)
+}
+
+#[tauri::command]
+async fn save_bookmark(app: tauri::AppHandle, bookmark: Bookmark) -> Result<Bookmark, DbError> {
+    let path = storage::data_file(&app, bookmarks::BOOKMARKS_FILE)?;
+    Ok(bookmarks::save(&path, bookmark)?)
+}
+
+// Deleted bookmarks go to the trash so they can be restored
+#[tauri::command]
+async fn delete_bookmark(app: tauri::AppHandle, id: String) -> Result<(), DbError> {
+    let path = storage::data_file(&app, bookmarks::BOOKMARKS_FILE)?;
+    let bookmark = bookmarks::get(&path, &id)?;
+    trash::put(
+        &storage::data_file(&app, trash::TRASH_FILE)?,
+        TrashKind::Bookmark,
+        &bookmark.name,
+        &bookmark,
+    )?;
+    bookmarks::delete(&path, &id)?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn list_snippets(
+    app: tauri::AppHandle,
+    filter: Option<snippets::SnippetFilter>,
+) -> Result<Vec<snippets::Snippet>, DbError> {
+    let path = storage::data_file(&app, snippets::SNIPPETS_FILE)?;
+    Ok(snippets::list(&path, &filter.unwrap_or_default())?)
+}
+
+#[tauri::command]
+async fn save_snippet(
+    app: tauri::AppHandle,
+    snippet: snippets::Snippet,
+) -> Result<snippets::Snippet, DbError> {
+    let path = storage::data_file(&app, snippets::SNIPPETS_FILE)?;
+    Ok(snippets::save(&path, snippet)?)
+}
+
+// Moves the snippet to the trash, where restore_item can bring it back
+#[tauri::command]
+async fn delete_snippet(app: tauri::AppHandle, id: String) -> Result<(), DbError> {
+    let path = storage::data_file(&app, snippets::SNIPPETS_FILE)?;
+    let snippet = snippets::get(&path, &id)?;
+    trash::put(
+        &storage::data_file(&app, trash::TRASH_FILE)?,
+        TrashKind::Snippet,
+        &snippet.name,
+        &snippet,
+    )?;
+    Ok(snippets::delete(&path, &id)?)
+}
+
+#[tauri::command]
+async fn search_bookmarks(app: tauri::AppHandle, query: String) -> Result<Vec<Bookmark>, DbError> {
+    let path = storage::data_file(&app, bookmarks::BOOKMARKS_FILE)?;
+    Ok(bookmarks::search(&path, &query)?)
+}
+
+// Moves the saved connection to the trash instead of dropping it for good
+#[tauri::command]
+async fn delete_connection(app: tauri::AppHandle, name: String) -> Result<(), DbError> {
+    let path = storage::data_file(&app, "connections.json")?;
+    let mut connections: Vec<SavedConnection> = storage::read_json(&path)?;
+    let index = connections
+        .iter()
+        .position(|c| c.name == name)
+        .ok_or("Connection not found")?;
+    trash::put(
+        &storage::data_file(&app, trash::TRASH_FILE)?,
+        TrashKind::Connection,
+        &name,
+        &connections[index],
+    )?;
+    connections.remove(index);
+    Ok(storage::write_json(&path, &connections)?)
+}
+
+#[tauri::command]
+async fn list_trash(app: tauri::AppHandle) -> Result<Vec<TrashItem>, DbError> {
+    Ok(trash::list(&storage::data_file(&app, trash::TRASH_FILE)?)?)
+}
+
+#[tauri::command]
+async fn restore_item(app: tauri::AppHandle, id: String) -> Result<TrashItem, DbError> {
+    let trash_path = storage::data_file(&app, trash::TRASH_FILE)?;
+    let entry = trash::get(&trash_path, &id)?;
+    match entry.kind {
+        TrashKind::Connection => {
+            let path = storage::data_file(&app, "connections.json")?;
+            let mut connections: Vec<SavedConnection> = storage::read_json(&path)?;
+            let connection: SavedConnection = entry.clone().into_item()?;
+            if connections.iter().any(|c| c.name == connection.name) {
+                return Err(
+                    format!("A connection named '{}' already exists", connection.name).into(),
+                );
+            }
+            connections.push(connection);
+            storage::write_json(&path, &connections)?;
+        }
+        TrashKind::Bookmark => {
+            let path = storage::data_file(&app, bookmarks::BOOKMARKS_FILE)?;
+            bookmarks::restore(&path, entry.clone().into_item()?)?;
+        }
+        TrashKind::Snippet => {
+            let path = storage::data_file(&app, snippets::SNIPPETS_FILE)?;
+            snippets::restore(&path, entry.clone().into_item()?)?;
+        }
+    }
+    trash::purge(&trash_path, Some(&[id]))?;
+    Ok(entry)
+}
+
+// Purged connections take their keychain entries with them
+#[tauri::command]
+async fn purge_trash(app: tauri::AppHandle, ids: Option<Vec<String>>) -> Result<usize, DbError> {
+    let path = storage::data_file(&app, trash::TRASH_FILE)?;
+    let purged_connections = trash::list(&path)?.into_iter().filter(|item| {
+        item.kind == TrashKind::Connection
+            && ids.as_ref().map_or(true, |ids| ids.contains(&item.id))
+    });
+    for item in purged_connections {
+        let connection: SavedConnection = item.into_item()?;
+        if let Some(secret_ref) = connection.secret_ref {
+            vault::forget(&secret_ref)?;
+        }
+    }
+    Ok(trash::purge(&path, ids.as_deref())?)
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .manage(DatabaseState::default())
+        .manage(SettingsState::default())
+        .manage(sql_projects::ProjectWatches::default())
         .invoke_handler(tauri::generate_handler![
             connect_db,
             disconnect_db,
+            get_capabilities,
             execute_query,
+            execute_query_with_params,
+            execute_query_stream,
+            execute_mongo_find,
+            execute_mongo_aggregate,
+            execute_statement_at,
+            run_sql_file,
+            list_workspace_files,
+            read_workspace_file,
+            write_workspace_file,
+            delete_workspace_file,
+            get_file_status,
+            list_sql_projects,
+            save_sql_project,
+            delete_sql_project,
+            list_sql_project_files,
+            run_sql_project_file,
+            run_sql_project,
+            watch_sql_project,
+            unwatch_sql_project,
+            explain_query,
+            lint_query,
+            cancel_query,
+            list_jobs,
+            cancel_job,
+            start_query_job,
+            list_running_queries,
+            get_query_queue,
+            reprioritize_query,
+            get_recent_tables,
+            clear_recent_tables,
+            get_completion_ranking,
+            clear_completion_ranking,
+            get_completion_metadata,
+            get_query_history,
+            search_query_history,
+            search_audit_log,
+            export_audit_log,
+            get_ddl_history,
+            get_table_ddl,
+            clear_query_history,
+            begin_transaction,
+            commit_transaction,
+            rollback_transaction,
+            get_transaction_status,
+            watch_schema,
+            unwatch_schema,
+            pin_object,
+            unpin_object,
+            list_pinned,
+            pin_result,
+            list_pinned_results,
+            set_pinned_result_note,
+            delete_pinned_result,
             get_tables,
             get_views,
             get_functions,
+            get_view_definition,
+            get_routine_definition,
             get_schemas,
             get_databases,
             test_conn,
@@ -244,7 +3153,102 @@ pub fn run() {
             load_settings,
             load_settings,
             save_settings,
-            export_data
+            export_data,
+            start_export_job,
+            export_full_result,
+            export_query_to_csv,
+            export_query_to_xlsx,
+            export_query_to_json,
+            copy_to_file,
+            list_scheduled_exports,
+            save_scheduled_export,
+            delete_scheduled_export,
+            run_scheduled_export,
+            set_scheduled_export_watermark,
+            list_notification_channels,
+            save_notification_channel,
+            delete_notification_channel,
+            list_secrets,
+            set_secret,
+            delete_secret,
+            test_notification_channel,
+            undo_last_change,
+            get_row_identity,
+            get_columns,
+            get_constraints,
+            get_indexes,
+            get_json_table_query,
+            get_partitions,
+            get_table_data,
+            browse_table,
+            get_temporal_tables,
+            build_as_of_query,
+            get_foreign_keys,
+            resolve_foreign_row,
+            find_referencing_rows,
+            get_full_row,
+            stage_change,
+            list_staged_changes,
+            preview_changes,
+            apply_changes,
+            update_row,
+            insert_row,
+            delete_row,
+            paste_import,
+            import_csv,
+            start_import_job,
+            discard_changes,
+            generate_upsert,
+            create_database,
+            drop_database,
+            create_schema,
+            drop_schema,
+            truncate_table,
+            kill_session,
+            transfer_schema_object,
+            list_gridfs_buckets,
+            list_gridfs_files,
+            download_gridfs_file,
+            upload_gridfs_file,
+            delete_gridfs_file,
+            infer_collection_schema,
+            snapshot_pg_stat_statements,
+            list_stat_snapshots,
+            diff_stat_snapshots,
+            delete_stat_snapshot,
+            export_schema_snapshot,
+            import_schema_snapshot,
+            list_schema_snapshots,
+            get_schema_snapshot,
+            delete_schema_snapshot,
+            diff_schema_snapshots,
+            save_offline_copy,
+            open_offline_connection,
+            get_offline_result,
+            clear_offline_copy,
+            reset_pg_stat_statements,
+            get_bloat_report,
+            get_wait_stats,
+            get_blocking_tree,
+            get_storage_breakdown,
+            get_connection_info,
+            get_fleet_status,
+            get_connection_context,
+            redis_scan_keys,
+            redis_get_value,
+            redis_memory_report,
+            redis_bulk_ttl,
+            list_bookmarks,
+            save_bookmark,
+            delete_bookmark,
+            search_bookmarks,
+            list_snippets,
+            save_snippet,
+            delete_snippet,
+            delete_connection,
+            list_trash,
+            restore_item,
+            purge_trash
         ])
         .setup(|app| {
             if cfg!(debug_assertions) {
@@ -255,6 +3259,9 @@ pub fn run() {
                 )?;
             }
             app.handle().plugin(tauri_plugin_dialog::init())?;
+            let settings = read_settings(app.handle())?;
+            app.state::<SettingsState>().set(settings);
+            scheduled_exports::start(app.handle().clone());
             Ok(())
         })
         .run(tauri::generate_context!())
@@ -0,0 +1,589 @@
+// Pre-execution checks for common query mistakes, on sqlparser's AST plus
+// cached column metadata. Findings are advice: the server has the final say.
+use serde::Serialize;
+use sqlparser::ast::{
+    BinaryOperator, Expr, GroupByExpr, Ident, JoinConstraint, JoinOperator, Query, Select,
+    SelectItem, SetExpr, TableFactor, Value as SqlValue, Visit, Visitor,
+};
+use sqlparser::dialect::{MsSqlDialect, MySqlDialect, PostgreSqlDialect};
+use sqlparser::parser::Parser;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+
+use crate::db::{ColumnInfo, DbClient, Dialect};
+use crate::metadata::MetadataCache;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LintIssue {
+    pub code: &'static str, // ungrouped_column, ambiguous_column, type_mismatch or unparsed
+    pub message: String,
+    // 1-based position of the offending identifier, when known
+    pub line: Option<u64>,
+    pub column: Option<u64>,
+}
+
+impl LintIssue {
+    fn at(code: &'static str, message: String, ident: Option<&Ident>) -> Self {
+        let start = ident.map(|i| i.span.start).filter(|s| s.line > 0);
+        LintIssue {
+            code,
+            message,
+            line: start.map(|s| s.line),
+            column: start.map(|s| s.column),
+        }
+    }
+}
+
+const AGGREGATES: [&str; 16] = [
+    "count",
+    "sum",
+    "avg",
+    "min",
+    "max",
+    "array_agg",
+    "string_agg",
+    "group_concat",
+    "json_agg",
+    "jsonb_agg",
+    "bool_and",
+    "bool_or",
+    "every",
+    "stddev",
+    "variance",
+    "listagg",
+];
+
+fn is_aggregate(expr: &Expr) -> bool {
+    match expr {
+        Expr::Function(f) if f.over.is_none() => f
+            .name
+            .0
+            .last()
+            .is_some_and(|n| AGGREGATES.contains(&n.value.to_lowercase().as_str())),
+        _ => false,
+    }
+}
+
+// A column reference: the table or alias qualifying it, if any, and its name
+#[derive(Debug, Clone)]
+struct ColumnRef {
+    qualifier: Option<String>,
+    ident: Ident,
+    aggregated: bool,
+}
+
+fn column_ref(expr: &Expr, aggregated: bool) -> Option<ColumnRef> {
+    match expr {
+        // Variables and placeholders such as @id
+        Expr::Identifier(ident) if ident.value.starts_with(['@', '$', ':']) => None,
+        Expr::Identifier(ident) => Some(ColumnRef {
+            qualifier: None,
+            ident: ident.clone(),
+            aggregated,
+        }),
+        Expr::CompoundIdentifier(parts) if parts.len() >= 2 => Some(ColumnRef {
+            qualifier: Some(parts[parts.len() - 2].value.clone()),
+            ident: parts[parts.len() - 1].clone(),
+            aggregated,
+        }),
+        _ => None,
+    }
+}
+
+// Collects the column references and comparisons of one query level; those
+// of subqueries belong to their own level and are skipped
+#[derive(Default)]
+struct LevelCollector {
+    depth: usize,
+    aggregates: usize, // aggregate calls currently open
+    any_aggregate: bool,
+    columns: Vec<ColumnRef>,
+    comparisons: Vec<(Expr, Expr)>,
+}
+
+impl Visitor for LevelCollector {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, _query: &Query) -> ControlFlow<()> {
+        self.depth += 1;
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_query(&mut self, _query: &Query) -> ControlFlow<()> {
+        self.depth -= 1;
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<()> {
+        if self.depth > 0 {
+            return ControlFlow::Continue(());
+        }
+        if is_aggregate(expr) {
+            self.aggregates += 1;
+            self.any_aggregate = true;
+        }
+        if let Some(column) = column_ref(expr, self.aggregates > 0) {
+            self.columns.push(column);
+        }
+        if let Expr::BinaryOp { left, op, right } = expr {
+            if matches!(
+                op,
+                BinaryOperator::Eq
+                    | BinaryOperator::NotEq
+                    | BinaryOperator::Lt
+                    | BinaryOperator::LtEq
+                    | BinaryOperator::Gt
+                    | BinaryOperator::GtEq
+            ) {
+                self.comparisons.push((*left.clone(), *right.clone()));
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn post_visit_expr(&mut self, expr: &Expr) -> ControlFlow<()> {
+        if self.depth == 0 && is_aggregate(expr) {
+            self.aggregates -= 1;
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+fn collect<'a>(exprs: impl IntoIterator<Item = &'a Expr>) -> LevelCollector {
+    let mut collector = LevelCollector::default();
+    for expr in exprs {
+        let _ = expr.visit(&mut collector);
+    }
+    collector
+}
+
+// Every SELECT in the statement, including those of subqueries and CTEs
+#[derive(Default)]
+struct SelectCollector {
+    selects: Vec<Select>,
+}
+
+impl SelectCollector {
+    fn add(&mut self, body: &SetExpr) {
+        match body {
+            SetExpr::Select(select) => self.selects.push(*select.clone()),
+            SetExpr::SetOperation { left, right, .. } => {
+                self.add(left);
+                self.add(right);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Visitor for SelectCollector {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &Query) -> ControlFlow<()> {
+        self.add(&query.body);
+        ControlFlow::Continue(())
+    }
+}
+
+// A table in FROM, with the name columns may be qualified by
+struct Source {
+    qualifier: String,
+    name: String,
+    columns: Arc<Vec<ColumnInfo>>,
+}
+
+impl Source {
+    fn column(&self, name: &str) -> Option<&ColumnInfo> {
+        self.columns
+            .iter()
+            .find(|c| c.name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Number,
+    Text,
+    Time,
+    Bool,
+}
+
+// Broad category of a column type as the catalogs spell it
+fn kind_of_type(data_type: &str) -> Option<Kind> {
+    let lower = data_type.to_lowercase();
+    let base = lower
+        .split('(')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(" unsigned")
+        .trim();
+    match base {
+        "smallint" | "integer" | "int" | "bigint" | "int2" | "int4" | "int8" | "tinyint"
+        | "mediumint" | "decimal" | "numeric" | "real" | "double precision" | "double"
+        | "float" | "float4" | "float8" | "money" | "smallmoney" | "serial" | "bigserial"
+        | "smallserial" => Some(Kind::Number),
+        "char" | "character" | "varchar" | "character varying" | "text" | "nchar" | "nvarchar"
+        | "ntext" | "tinytext" | "mediumtext" | "longtext" | "citext" | "enum" | "set" => {
+            Some(Kind::Text)
+        }
+        "date"
+        | "time"
+        | "timestamp"
+        | "timestamptz"
+        | "timetz"
+        | "datetime"
+        | "datetime2"
+        | "smalldatetime"
+        | "datetimeoffset"
+        | "timestamp with time zone"
+        | "timestamp without time zone"
+        | "time with time zone"
+        | "time without time zone" => Some(Kind::Time),
+        "boolean" | "bool" | "bit" => Some(Kind::Bool),
+        _ => None,
+    }
+}
+
+// What one side of a comparison is known to be
+enum Operand {
+    Column(String, Kind), // name and category
+    Literal(String),      // quoted string, which the server may convert
+    Number,
+    Bool,
+}
+
+fn literal_fits(text: &str, kind: Kind) -> bool {
+    let text = text.trim();
+    match kind {
+        Kind::Text => true,
+        Kind::Number => text.parse::<f64>().is_ok(),
+        Kind::Time => {
+            text.get(..10)
+                .is_some_and(|d| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").is_ok())
+                || chrono::NaiveTime::parse_from_str(text, "%H:%M:%S").is_ok()
+                || chrono::NaiveTime::parse_from_str(text, "%H:%M").is_ok()
+        }
+        Kind::Bool => matches!(
+            text.to_lowercase().as_str(),
+            "t" | "f" | "true" | "false" | "1" | "0" | "yes" | "no" | "on" | "off"
+        ),
+    }
+}
+
+fn compatible(a: &Operand, b: &Operand) -> bool {
+    match (a, b) {
+        (Operand::Column(_, x), Operand::Column(_, y)) => {
+            // Booleans are numbers on MySQL and SQL Server
+            x == y
+                || matches!(
+                    (x, y),
+                    (Kind::Number, Kind::Bool) | (Kind::Bool, Kind::Number)
+                )
+        }
+        (Operand::Column(_, kind), Operand::Literal(text))
+        | (Operand::Literal(text), Operand::Column(_, kind)) => literal_fits(text, *kind),
+        (Operand::Column(_, kind), Operand::Number)
+        | (Operand::Number, Operand::Column(_, kind)) => {
+            matches!(kind, Kind::Number | Kind::Bool)
+        }
+        (Operand::Column(_, kind), Operand::Bool) | (Operand::Bool, Operand::Column(_, kind)) => {
+            matches!(kind, Kind::Number | Kind::Bool)
+        }
+        _ => true,
+    }
+}
+
+fn describe(operand: &Operand) -> String {
+    match operand {
+        Operand::Column(name, kind) => format!("{} ({:?})", name, kind).to_lowercase(),
+        Operand::Literal(text) => format!("'{}'", text),
+        Operand::Number => "a number".to_string(),
+        Operand::Bool => "a boolean".to_string(),
+    }
+}
+
+struct SelectScope {
+    sources: Vec<Source>,
+    using: Vec<String>, // lower-cased USING columns, unambiguous by definition
+    natural: bool,
+    complete: bool, // every FROM item is a table whose columns are known
+}
+
+impl SelectScope {
+    // Sources having an unqualified column of this name
+    fn owners(&self, name: &str) -> Vec<&Source> {
+        self.sources
+            .iter()
+            .filter(|s| s.column(name).is_some())
+            .collect()
+    }
+
+    fn resolve(&self, column: &ColumnRef) -> Option<&ColumnInfo> {
+        match &column.qualifier {
+            Some(q) => self
+                .sources
+                .iter()
+                .find(|s| s.qualifier.eq_ignore_ascii_case(q))?
+                .column(&column.ident.value),
+            None => match self.owners(&column.ident.value).as_slice() {
+                [only] => only.column(&column.ident.value),
+                _ => None,
+            },
+        }
+    }
+
+    fn operand(&self, expr: &Expr) -> Option<Operand> {
+        match expr {
+            Expr::Nested(inner) => self.operand(inner),
+            Expr::Value(SqlValue::Number(..)) => Some(Operand::Number),
+            Expr::Value(SqlValue::Boolean(_)) => Some(Operand::Bool),
+            Expr::Value(SqlValue::SingleQuotedString(s)) => Some(Operand::Literal(s.clone())),
+            _ => {
+                let column = column_ref(expr, false)?;
+                let info = self.resolve(&column)?;
+                Some(Operand::Column(
+                    column.ident.value.clone(),
+                    kind_of_type(&info.data_type)?,
+                ))
+            }
+        }
+    }
+}
+
+async fn scope(
+    select: &Select,
+    cache: &MetadataCache,
+    connection: &str,
+    client: &DbClient,
+) -> Result<SelectScope, String> {
+    let mut scope = SelectScope {
+        sources: Vec::new(),
+        using: Vec::new(),
+        natural: false,
+        complete: true,
+    };
+    let mut factors = Vec::new();
+    for from in &select.from {
+        factors.push(&from.relation);
+        for join in &from.joins {
+            factors.push(&join.relation);
+            let constraint = match &join.join_operator {
+                JoinOperator::Inner(c)
+                | JoinOperator::LeftOuter(c)
+                | JoinOperator::RightOuter(c)
+                | JoinOperator::FullOuter(c) => Some(c),
+                _ => None,
+            };
+            match constraint {
+                Some(JoinConstraint::Using(columns)) => scope
+                    .using
+                    .extend(columns.iter().map(|c| c.value.to_lowercase())),
+                Some(JoinConstraint::Natural) => scope.natural = true,
+                _ => {}
+            }
+        }
+    }
+    for factor in factors {
+        let TableFactor::Table {
+            name, alias, args, ..
+        } = factor
+        else {
+            scope.complete = false;
+            continue;
+        };
+        let parts = &name.0;
+        let (Some(table), None) = (parts.last(), args) else {
+            scope.complete = false;
+            continue;
+        };
+        let schema = (parts.len() > 1).then(|| parts[parts.len() - 2].value.as_str());
+        let columns = cache
+            .columns(connection, client, schema, &table.value)
+            .await?;
+        // A CTE or a table we can't see
+        if columns.is_empty() {
+            scope.complete = false;
+        }
+        scope.sources.push(Source {
+            qualifier: alias
+                .as_ref()
+                .map_or_else(|| table.value.clone(), |a| a.name.value.clone()),
+            name: table.value.clone(),
+            columns,
+        });
+    }
+    Ok(scope)
+}
+
+fn check_grouping(select: &Select, issues: &mut Vec<LintIssue>) {
+    let group_exprs: &[Expr] = match &select.group_by {
+        GroupByExpr::Expressions(exprs, _) => exprs,
+        GroupByExpr::All(_) => return,
+    };
+    let items: Vec<(&Expr, Option<&Ident>)> = select
+        .projection
+        .iter()
+        .filter_map(|item| match item {
+            SelectItem::UnnamedExpr(expr) => Some((expr, None)),
+            SelectItem::ExprWithAlias { expr, alias } => Some((expr, Some(alias))),
+            _ => None,
+        })
+        .collect();
+    let aggregated = collect(items.iter().map(|(e, _)| *e)).any_aggregate;
+    if group_exprs.is_empty() && !aggregated {
+        return;
+    }
+
+    let grouped_text: Vec<String> = group_exprs.iter().map(|e| e.to_string()).collect();
+    let grouped_columns: Vec<String> = collect(group_exprs)
+        .columns
+        .iter()
+        .map(|c| c.ident.value.to_lowercase())
+        .collect();
+    let mut reported: Vec<String> = Vec::new();
+    for (i, (expr, alias)) in items.iter().enumerate() {
+        // GROUP BY 2, GROUP BY alias, or the same expression
+        let position = (i + 1).to_string();
+        let by_reference = grouped_text.iter().any(|g| {
+            *g == position
+                || alias.is_some_and(|a| g.eq_ignore_ascii_case(&a.value))
+                || *g == expr.to_string()
+        });
+        if by_reference {
+            continue;
+        }
+        for column in collect([*expr]).columns {
+            let name = column.ident.value.to_lowercase();
+            if column.aggregated || grouped_columns.contains(&name) || reported.contains(&name) {
+                continue;
+            }
+            let message = if group_exprs.is_empty() {
+                format!(
+                    "Column {} is not aggregated, but the query aggregates without GROUP BY",
+                    column.ident.value
+                )
+            } else {
+                format!(
+                    "Column {} must appear in GROUP BY or be used in an aggregate",
+                    column.ident.value
+                )
+            };
+            issues.push(LintIssue::at(
+                "ungrouped_column",
+                message,
+                Some(&column.ident),
+            ));
+            reported.push(name);
+        }
+    }
+}
+
+fn check_ambiguity(select: &Select, scope: &SelectScope, issues: &mut Vec<LintIssue>) {
+    if scope.natural || scope.sources.len() < 2 {
+        return;
+    }
+    let mut exprs: Vec<&Expr> = select
+        .projection
+        .iter()
+        .filter_map(|item| match item {
+            SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } => Some(expr),
+            _ => None,
+        })
+        .collect();
+    exprs.extend(&select.selection);
+    exprs.extend(&select.having);
+    if let GroupByExpr::Expressions(group, _) = &select.group_by {
+        exprs.extend(group);
+    }
+
+    let mut reported: Vec<String> = Vec::new();
+    for column in collect(exprs).columns {
+        let name = column.ident.value.to_lowercase();
+        if column.qualifier.is_some() || scope.using.contains(&name) || reported.contains(&name) {
+            continue;
+        }
+        let owners = scope.owners(&column.ident.value);
+        if owners.len() > 1 {
+            let tables: Vec<&str> = owners.iter().map(|s| s.name.as_str()).collect();
+            let message = format!(
+                "Column {} is ambiguous: it exists in {}",
+                column.ident.value,
+                tables.join(" and ")
+            );
+            issues.push(LintIssue::at(
+                "ambiguous_column",
+                message,
+                Some(&column.ident),
+            ));
+            reported.push(name);
+        }
+    }
+}
+
+fn check_comparisons(select: &Select, scope: &SelectScope, issues: &mut Vec<LintIssue>) {
+    let mut exprs: Vec<&Expr> = select.selection.iter().chain(&select.having).collect();
+    for from in &select.from {
+        for join in &from.joins {
+            if let JoinOperator::Inner(JoinConstraint::On(on))
+            | JoinOperator::LeftOuter(JoinConstraint::On(on))
+            | JoinOperator::RightOuter(JoinConstraint::On(on))
+            | JoinOperator::FullOuter(JoinConstraint::On(on)) = &join.join_operator
+            {
+                exprs.push(on);
+            }
+        }
+    }
+    for (left, right) in collect(exprs).comparisons {
+        let (Some(a), Some(b)) = (scope.operand(&left), scope.operand(&right)) else {
+            continue;
+        };
+        if compatible(&a, &b) {
+            continue;
+        }
+        let ident = [&left, &right]
+            .into_iter()
+            .find_map(|e| column_ref(e, false))
+            .map(|c| c.ident);
+        let message = format!("Comparing {} with {}", describe(&a), describe(&b));
+        issues.push(LintIssue::at("type_mismatch", message, ident.as_ref()));
+    }
+}
+
+pub async fn lint_query(
+    client: &DbClient,
+    cache: &MetadataCache,
+    connection: &str,
+    sql: &str,
+) -> Result<Vec<LintIssue>, String> {
+    let dialect = client
+        .dialect()
+        .ok_or("Query checks are only supported for SQL databases")?;
+    let parsed = match dialect {
+        Dialect::Postgres => Parser::parse_sql(&PostgreSqlDialect {}, sql),
+        Dialect::Mysql => Parser::parse_sql(&MySqlDialect {}, sql),
+        Dialect::Mssql => Parser::parse_sql(&MsSqlDialect {}, sql),
+    };
+    // The parser doesn't know every server extension, so this isn't an error
+    let statements = match parsed {
+        Ok(statements) => statements,
+        Err(e) => {
+            let message = format!("The query could not be checked: {}", e);
+            return Ok(vec![LintIssue::at("unparsed", message, None)]);
+        }
+    };
+
+    let mut selects = SelectCollector::default();
+    for statement in &statements {
+        let _ = statement.visit(&mut selects);
+    }
+    let mut issues = Vec::new();
+    for select in &selects.selects {
+        check_grouping(select, &mut issues);
+        let scope = scope(select, cache, connection, client).await?;
+        check_ambiguity(select, &scope, &mut issues);
+        if scope.complete {
+            check_comparisons(select, &scope, &mut issues);
+        }
+    }
+    Ok(issues)
+}
@@ -0,0 +1,132 @@
+// Column metadata cached per connection, for checks that run before every
+// query (lint_query) and shouldn't hit the catalog each time, and the tables
+// and columns editor completion offers
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+
+use crate::db::{self, ColumnInfo, DbClient, Dialect};
+
+const CACHE_TTL: Duration = Duration::from_secs(300);
+
+// Tables whose columns are loaded for completion; the rest are listed by name
+const MAX_COMPLETION_TABLES: usize = 1000;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CompletionTable {
+    pub schema: Option<String>, // None on MySQL, where it's the connected database
+    pub name: String,
+    pub is_view: bool,
+    pub columns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CompletionMetadata {
+    pub tables: Vec<CompletionTable>,
+    pub complete: bool, // false when some tables are listed without columns
+}
+
+// (connection, schema, table), lower-cased
+type TableKey = (String, Option<String>, String);
+
+type CachedColumns = (Instant, Arc<Vec<ColumnInfo>>);
+
+#[derive(Default)]
+pub struct MetadataCache {
+    columns: StdMutex<HashMap<TableKey, CachedColumns>>,
+    completion: StdMutex<HashMap<String, (Instant, Arc<CompletionMetadata>)>>,
+}
+
+impl MetadataCache {
+    // Columns of the table, empty when it doesn't exist
+    pub async fn columns(
+        &self,
+        connection: &str,
+        client: &DbClient,
+        schema: Option<&str>,
+        table: &str,
+    ) -> Result<Arc<Vec<ColumnInfo>>, String> {
+        let key = (
+            connection.to_string(),
+            schema.map(str::to_lowercase),
+            table.to_lowercase(),
+        );
+        if let Some((fetched, columns)) = self.columns.lock().unwrap().get(&key) {
+            if fetched.elapsed() < CACHE_TTL {
+                return Ok(columns.clone());
+            }
+        }
+        let columns =
+            Arc::new(db::get_column_info(client, schema.map(str::to_string), table).await?);
+        self.columns
+            .lock()
+            .unwrap()
+            .insert(key, (Instant::now(), columns.clone()));
+        Ok(columns)
+    }
+
+    // Every table and view with its columns, one catalog query per schema
+    pub async fn completion(
+        &self,
+        connection: &str,
+        client: &DbClient,
+    ) -> Result<Arc<CompletionMetadata>, String> {
+        if let Some((fetched, metadata)) = self.completion.lock().unwrap().get(connection) {
+            if fetched.elapsed() < CACHE_TTL {
+                return Ok(metadata.clone());
+            }
+        }
+        let dialect = client
+            .dialect()
+            .ok_or("Completion metadata is only available for SQL databases")?;
+        let schemas: Vec<Option<String>> = match dialect {
+            Dialect::Mysql => vec![None],
+            _ => db::get_schemas(client)
+                .await?
+                .into_iter()
+                .map(Some)
+                .collect(),
+        };
+        let mut tables = Vec::new();
+        for schema in schemas {
+            let first = tables.len();
+            for (names, is_view) in [
+                (db::get_tables(client, schema.clone(), false).await?, false),
+                (db::get_views(client, schema.clone()).await?, true),
+            ] {
+                tables.extend(names.into_iter().map(|name| CompletionTable {
+                    schema: schema.clone(),
+                    name,
+                    is_view,
+                    columns: Vec::new(),
+                }));
+            }
+            if first >= MAX_COMPLETION_TABLES {
+                continue;
+            }
+            let mut columns = db::get_schema_column_names(client, schema.clone()).await?;
+            let end = tables.len().min(MAX_COMPLETION_TABLES);
+            for table in &mut tables[first..end] {
+                table.columns = columns.remove(&table.name).unwrap_or_default();
+            }
+        }
+        let metadata = Arc::new(CompletionMetadata {
+            complete: tables.len() <= MAX_COMPLETION_TABLES,
+            tables,
+        });
+        self.completion
+            .lock()
+            .unwrap()
+            .insert(connection.to_string(), (Instant::now(), metadata.clone()));
+        Ok(metadata)
+    }
+
+    pub fn forget(&self, connection: &str) {
+        self.columns
+            .lock()
+            .unwrap()
+            .retain(|(c, _, _), _| c != connection);
+        self.completion.lock().unwrap().remove(connection);
+    }
+}
@@ -0,0 +1,856 @@
+use futures::{AsyncWriteExt, TryStreamExt};
+use mongodb::bson::{doc, Bson, Document};
+use mongodb::options::GridFsBucketOptions;
+use mongodb::IndexModel;
+use serde::de::{IgnoredAny, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use tokio_util::compat::{TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
+
+use crate::browse::{ColumnFilter, FilterOp, SortDirection, SortKey};
+use crate::db::{ColumnMeta, DbClient, QueryResponse, RowBatch};
+use crate::edits::TableRef;
+use crate::indexes::TableIndex;
+use crate::sqltext::StatementKind;
+use crate::values::{Cell, MAX_SAFE_INTEGER};
+
+fn mongo_client(client: &DbClient) -> Result<&mongodb::Client, String> {
+    match client {
+        DbClient::Mongo(client) => Ok(client),
+        _ => Err("This operation is only supported for MongoDB".to_string()),
+    }
+}
+
+// Falls back to the database named in the connection URL
+fn database(client: &DbClient, name: Option<String>) -> Result<mongodb::Database, String> {
+    let client = mongo_client(client)?;
+    match name {
+        Some(name) => Ok(client.database(&name)),
+        None => client
+            .default_database()
+            .ok_or_else(|| "No database selected".to_string()),
+    }
+}
+
+// Values go out as relaxed Extended JSON, e.g. {"$oid": "..."} and
+// {"$date": "..."}, so numbers show and export as plain numbers; the column's
+// type name tells an int64 or double apart. Only 64-bit integers JavaScript
+// can't hold exactly stay canonical ({"$numberLong": "..."}).
+pub fn extended_json(value: Bson) -> Value {
+    match value {
+        Bson::Int64(n) if !(-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&n) => {
+            value.into_canonical_extjson()
+        }
+        Bson::Document(doc) => Value::Object(
+            doc.into_iter()
+                .map(|(key, value)| (key, extended_json(value)))
+                .collect(),
+        ),
+        Bson::Array(items) => Value::Array(items.into_iter().map(extended_json).collect()),
+        other => other.into_relaxed_extjson(),
+    }
+}
+
+// Ids arrive from the frontend as Extended JSON, e.g. {"$oid": "..."}
+fn bson_id(id: Value) -> Result<Bson, String> {
+    Bson::try_from(id).map_err(|e| format!("Invalid file id: {}", e))
+}
+
+fn escape_regex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[derive(Serialize)]
+pub struct GridFsBucketInfo {
+    pub name: String,
+    pub files: u64,
+}
+
+#[derive(Serialize)]
+pub struct GridFsFile {
+    pub id: Value,
+    pub filename: Option<String>,
+    pub length: u64,
+    pub chunk_size: u32,
+    pub upload_date: String,
+    pub metadata: Option<Value>,
+}
+
+// A bucket is a `<name>.files` collection with a matching `<name>.chunks`
+pub async fn list_gridfs_buckets(
+    client: &DbClient,
+    database_name: Option<String>,
+) -> Result<Vec<GridFsBucketInfo>, String> {
+    let db = database(client, database_name)?;
+    let collections = db
+        .list_collection_names()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut buckets = Vec::new();
+    for files in &collections {
+        let Some(name) = files.strip_suffix(".files") else {
+            continue;
+        };
+        if !collections.contains(&format!("{}.chunks", name)) {
+            continue;
+        }
+        let count = db
+            .collection::<Document>(files)
+            .estimated_document_count()
+            .await
+            .map_err(|e| e.to_string())?;
+        buckets.push(GridFsBucketInfo {
+            name: name.to_string(),
+            files: count,
+        });
+    }
+    buckets.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(buckets)
+}
+
+fn bucket(
+    client: &DbClient,
+    database_name: Option<String>,
+    bucket: String,
+) -> Result<mongodb::gridfs::GridFsBucket, String> {
+    let db = database(client, database_name)?;
+    Ok(db.gridfs_bucket(GridFsBucketOptions::builder().bucket_name(bucket).build()))
+}
+
+// Newest first; `filename` is a case-insensitive substring match
+pub async fn list_gridfs_files(
+    client: &DbClient,
+    database_name: Option<String>,
+    bucket_name: String,
+    filename: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<GridFsFile>, String> {
+    let bucket = bucket(client, database_name, bucket_name)?;
+    let filter = match filename.filter(|f| !f.is_empty()) {
+        Some(f) => doc! { "filename": { "$regex": escape_regex(&f), "$options": "i" } },
+        None => doc! {},
+    };
+    let mut cursor = bucket
+        .find(filter)
+        .sort(doc! { "uploadDate": -1 })
+        .limit(limit.unwrap_or(1000))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut files = Vec::new();
+    while let Some(file) = cursor.try_next().await.map_err(|e| e.to_string())? {
+        files.push(GridFsFile {
+            id: extended_json(file.id),
+            filename: file.filename,
+            length: file.length,
+            chunk_size: file.chunk_size_bytes,
+            upload_date: file.upload_date.try_to_rfc3339_string().unwrap_or_default(),
+            metadata: file.metadata.map(|m| extended_json(Bson::Document(m))),
+        });
+    }
+    Ok(files)
+}
+
+// Returns the number of bytes written to `path`
+pub async fn download_gridfs_file(
+    client: &DbClient,
+    database_name: Option<String>,
+    bucket_name: String,
+    id: Value,
+    path: String,
+) -> Result<u64, String> {
+    let bucket = bucket(client, database_name, bucket_name)?;
+    let download = bucket
+        .open_download_stream(bson_id(id)?)
+        .await
+        .map_err(|e| e.to_string())?;
+    let file = tokio::fs::File::create(&path)
+        .await
+        .map_err(|e| format!("Failed to create {}: {}", path, e))?;
+    let mut writer = file.compat_write();
+    let written = futures::io::copy(download, &mut writer)
+        .await
+        .map_err(|e| e.to_string())?;
+    writer.flush().await.map_err(|e| e.to_string())?;
+    Ok(written)
+}
+
+// Uploads the file at `path` and returns the new file's id
+pub async fn upload_gridfs_file(
+    client: &DbClient,
+    database_name: Option<String>,
+    bucket_name: String,
+    path: String,
+    filename: Option<String>,
+    metadata: Option<Value>,
+) -> Result<Value, String> {
+    let bucket = bucket(client, database_name, bucket_name)?;
+    let filename = match filename {
+        Some(f) => f,
+        None => std::path::Path::new(&path)
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .ok_or("Missing file name")?,
+    };
+    let metadata = match metadata {
+        Some(m) => match Bson::try_from(m).map_err(|e| e.to_string())? {
+            Bson::Document(d) => Some(d),
+            _ => return Err("Metadata must be a JSON object".to_string()),
+        },
+        None => None,
+    };
+
+    let file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let mut action = bucket.open_upload_stream(filename);
+    if let Some(metadata) = metadata {
+        action = action.metadata(metadata);
+    }
+    let mut upload = action.await.map_err(|e| e.to_string())?;
+    let id = upload.id().clone();
+    if let Err(e) = futures::io::copy(file.compat(), &mut upload).await {
+        // Drop the chunks written so far
+        let _ = upload.abort().await;
+        return Err(e.to_string());
+    }
+    upload.close().await.map_err(|e| e.to_string())?;
+    Ok(extended_json(id))
+}
+
+pub async fn delete_gridfs_file(
+    client: &DbClient,
+    database_name: Option<String>,
+    bucket_name: String,
+    id: Value,
+) -> Result<(), String> {
+    let bucket = bucket(client, database_name, bucket_name)?;
+    bucket.delete(bson_id(id)?).await.map_err(|e| e.to_string())
+}
+
+// Names as used by the `$type` query operator
+fn bson_type_name(value: &Bson) -> &'static str {
+    match value {
+        Bson::Double(_) => "double",
+        Bson::String(_) => "string",
+        Bson::Document(_) => "object",
+        Bson::Array(_) => "array",
+        Bson::Binary(_) => "binData",
+        Bson::Undefined => "undefined",
+        Bson::ObjectId(_) => "objectId",
+        Bson::Boolean(_) => "bool",
+        Bson::DateTime(_) => "date",
+        Bson::Null => "null",
+        Bson::RegularExpression(_) => "regex",
+        Bson::DbPointer(_) => "dbPointer",
+        Bson::JavaScriptCode(_) => "javascript",
+        Bson::Symbol(_) => "symbol",
+        Bson::JavaScriptCodeWithScope(_) => "javascriptWithScope",
+        Bson::Int32(_) => "int",
+        Bson::Timestamp(_) => "timestamp",
+        Bson::Int64(_) => "long",
+        Bson::Decimal128(_) => "decimal",
+        Bson::MinKey => "minKey",
+        Bson::MaxKey => "maxKey",
+    }
+}
+
+#[derive(Serialize)]
+pub struct TypeCount {
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub count: u64,
+}
+
+#[derive(Serialize)]
+pub struct FieldSchema {
+    pub path: String, // dot notation, e.g. "address.city"
+    pub name: String,
+    pub depth: usize,
+    pub count: u64, // sampled documents containing the field
+    pub optional: bool,
+    pub types: Vec<TypeCount>, // most common first
+}
+
+#[derive(Serialize)]
+pub struct CollectionSchema {
+    pub sampled: u64,
+    pub fields: Vec<FieldSchema>,
+}
+
+#[derive(Default)]
+struct FieldStats {
+    documents: u64,
+    types: Vec<(&'static str, u64)>,
+}
+
+// Records every field of `doc` under `prefix`. Documents inside arrays are
+// walked under the array's own path, matching how queries address them.
+fn collect_fields(
+    doc: &Document,
+    prefix: &str,
+    order: &mut Vec<String>,
+    stats: &mut HashMap<String, FieldStats>,
+    seen: &mut HashSet<String>,
+) {
+    for (key, value) in doc {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        record_value(&path, value, order, stats, seen);
+    }
+}
+
+fn record_value(
+    path: &str,
+    value: &Bson,
+    order: &mut Vec<String>,
+    stats: &mut HashMap<String, FieldStats>,
+    seen: &mut HashSet<String>,
+) {
+    let entry = stats.entry(path.to_string()).or_insert_with(|| {
+        order.push(path.to_string());
+        FieldStats::default()
+    });
+    // Count each field once per document, even when it repeats inside an array
+    if seen.insert(path.to_string()) {
+        entry.documents += 1;
+    }
+    let name = bson_type_name(value);
+    match entry.types.iter_mut().find(|(t, _)| *t == name) {
+        Some((_, count)) => *count += 1,
+        None => entry.types.push((name, 1)),
+    }
+
+    match value {
+        Bson::Document(doc) => collect_fields(doc, path, order, stats, seen),
+        Bson::Array(items) => {
+            for item in items {
+                if let Bson::Document(doc) = item {
+                    collect_fields(doc, path, order, stats, seen);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+// Infers field names, types and optionality from a random sample of documents
+pub async fn infer_collection_schema(
+    client: &DbClient,
+    database_name: Option<String>,
+    collection: String,
+    sample_size: Option<i64>,
+) -> Result<CollectionSchema, String> {
+    let db = database(client, database_name)?;
+    let sample_size = sample_size.unwrap_or(100).max(1);
+    let mut cursor = db
+        .collection::<Document>(&collection)
+        .aggregate(vec![doc! { "$sample": { "size": sample_size } }])
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut order = Vec::new();
+    let mut stats = HashMap::new();
+    let mut sampled = 0u64;
+    while let Some(doc) = cursor.try_next().await.map_err(|e| e.to_string())? {
+        sampled += 1;
+        let mut seen = HashSet::new();
+        collect_fields(&doc, "", &mut order, &mut stats, &mut seen);
+    }
+
+    let fields = order
+        .into_iter()
+        .map(|path| {
+            let FieldStats {
+                documents,
+                mut types,
+            } = stats.remove(&path).unwrap_or_default();
+            types.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+            FieldSchema {
+                name: path.rsplit('.').next().unwrap_or(&path).to_string(),
+                depth: path.matches('.').count(),
+                count: documents,
+                optional: documents < sampled,
+                types: types
+                    .into_iter()
+                    .map(|(type_name, count)| TypeCount {
+                        type_name: type_name.to_string(),
+                        count,
+                    })
+                    .collect(),
+                path,
+            }
+        })
+        .collect();
+
+    Ok(CollectionSchema { sampled, fields })
+}
+
+struct FirstKey;
+
+impl<'de> Visitor<'de> for FirstKey {
+    type Value = Option<String>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a command document")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let first = map.next_key::<String>()?;
+        if first.is_some() {
+            map.next_value::<IgnoredAny>()?;
+        }
+        while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {}
+        Ok(first)
+    }
+}
+
+// The name of a command written as JSON: its first key, in the order written
+pub fn command_name(text: &str) -> Option<String> {
+    serde_json::Deserializer::from_str(text)
+        .deserialize_map(FirstKey)
+        .ok()
+        .flatten()
+}
+
+// Whether an aggregate command writes its output with $out or $merge
+pub fn command_writes(text: &str) -> bool {
+    let Ok(Value::Object(command)) = serde_json::from_str::<Value>(text) else {
+        return false;
+    };
+    command
+        .get("pipeline")
+        .and_then(Value::as_array)
+        .is_some_and(|stages| {
+            stages.iter().any(|stage| {
+                stage
+                    .as_object()
+                    .is_some_and(|s| s.contains_key("$out") || s.contains_key("$merge"))
+            })
+        })
+}
+
+// Runs a database command written as Extended JSON, e.g.
+// {"find": "users", "filter": {"active": true}}; "$db" picks the database,
+// otherwise the one in the connection URL is used
+pub async fn run_command(client: &DbClient, text: &str) -> Result<QueryResponse, String> {
+    let json: Value = serde_json::from_str(text).map_err(|e| format!("Invalid command: {}", e))?;
+    let Bson::Document(mut command) =
+        Bson::try_from(json).map_err(|e| format!("Invalid command: {}", e))?
+    else {
+        return Err("The command must be a JSON object".to_string());
+    };
+    let database_name = command
+        .remove("$db")
+        .and_then(|name| name.as_str().map(str::to_string));
+    let db = database(client, database_name)?;
+    let kind = match command_name(text).as_deref() {
+        Some("find" | "aggregate" | "count" | "distinct") => StatementKind::Query,
+        Some("insert") => StatementKind::Insert,
+        Some("update" | "findAndModify") => StatementKind::Update,
+        Some("delete") => StatementKind::Delete,
+        Some(
+            "create" | "drop" | "dropDatabase" | "createIndexes" | "dropIndexes" | "collMod"
+            | "renameCollection",
+        ) => StatementKind::Ddl,
+        _ => StatementKind::Other,
+    };
+    let reply = db.run_command(command).await.map_err(|e| e.to_string())?;
+
+    let affected = match kind {
+        StatementKind::Update => reply.get("nModified"),
+        _ => reply.get("n"),
+    }
+    .and_then(|n| match n {
+        Bson::Int32(n) => Some(*n as u64),
+        Bson::Int64(n) => Some(*n as u64),
+        _ => None,
+    });
+    // Cursor replies are shown as their first batch; the rest isn't fetched
+    let (documents, truncated) = match reply.get_document("cursor") {
+        Ok(cursor) => (
+            cursor
+                .get_array("firstBatch")
+                .map(|batch| {
+                    batch
+                        .iter()
+                        .filter_map(Bson::as_document)
+                        .cloned()
+                        .collect()
+                })
+                .unwrap_or_default(),
+            cursor.get_i64("id").unwrap_or(0) != 0,
+        ),
+        Err(_) => (vec![reply.clone()], false),
+    };
+
+    let mut response = documents_response(&documents).counted(kind, affected);
+    response.truncated = truncated;
+    Ok(response)
+}
+
+// Documents as grid rows: one column per top-level field, in first-seen order,
+// typed by its first value
+fn documents_response(documents: &[Document]) -> QueryResponse {
+    let mut column_meta: Vec<ColumnMeta> = Vec::new();
+    add_document_columns(&mut column_meta, documents);
+    let columns: Vec<String> = column_meta.iter().map(|c| c.name.clone()).collect();
+    let rows = documents
+        .iter()
+        .map(|doc| document_row(doc, &column_meta))
+        .collect();
+    QueryResponse::new(columns, rows, column_meta)
+}
+
+// Appends the fields of `documents` not in `column_meta` yet
+fn add_document_columns(column_meta: &mut Vec<ColumnMeta>, documents: &[Document]) {
+    for doc in documents {
+        for (key, value) in doc {
+            if !column_meta.iter().any(|c| &c.name == key) {
+                column_meta.push(ColumnMeta {
+                    name: key.clone(),
+                    type_name: bson_type_name(value).to_string(),
+                    timezone: None,
+                    value_tag: Some("extjson"),
+                });
+            }
+        }
+    }
+}
+
+fn document_row(doc: &Document, column_meta: &[ColumnMeta]) -> Vec<Cell> {
+    column_meta
+        .iter()
+        .map(|column| match doc.get(&column.name) {
+            None | Some(Bson::Null) => Cell::Null,
+            Some(value) => Cell::Value(extended_json(value.clone())),
+        })
+        .collect()
+}
+
+// A LIKE pattern as an anchored regex
+fn like_regex(pattern: &str) -> String {
+    let mut regex = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '%' => regex.push_str(".*"),
+            '_' => regex.push('.'),
+            c => regex.push_str(&escape_regex(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+fn filter_document(filters: &[ColumnFilter]) -> Result<Document, String> {
+    let mut filter = Document::new();
+    for f in filters {
+        let value = Bson::try_from(f.value.clone())
+            .map_err(|e| format!("Invalid value for {}: {}", f.column, e))?;
+        let condition = match f.op {
+            FilterOp::Eq => value,
+            FilterOp::Ne => Bson::Document(doc! { "$ne": value }),
+            FilterOp::Lt => Bson::Document(doc! { "$lt": value }),
+            FilterOp::Le => Bson::Document(doc! { "$lte": value }),
+            FilterOp::Gt => Bson::Document(doc! { "$gt": value }),
+            FilterOp::Ge => Bson::Document(doc! { "$gte": value }),
+            FilterOp::In => Bson::Document(doc! { "$in": value }),
+            FilterOp::IsNull => Bson::Null,
+            FilterOp::IsNotNull => Bson::Document(doc! { "$ne": Bson::Null }),
+            FilterOp::Like | FilterOp::NotLike => {
+                let Bson::String(pattern) = &value else {
+                    return Err(format!("Filter on {} needs a text pattern", f.column));
+                };
+                let regex = doc! { "$regex": like_regex(pattern) };
+                match f.op {
+                    FilterOp::Like => Bson::Document(regex),
+                    _ => Bson::Document(doc! { "$not": regex }),
+                }
+            }
+        };
+        // Several filters on one field must all hold
+        match filter.remove(&f.column) {
+            Some(existing) => {
+                let all = filter
+                    .entry("$and".to_string())
+                    .or_insert(Bson::Array(vec![]));
+                if let Bson::Array(all) = all {
+                    all.push(Bson::Document(doc! { f.column.clone(): existing }));
+                    all.push(Bson::Document(doc! { f.column.clone(): condition }));
+                }
+            }
+            None => {
+                filter.insert(f.column.clone(), condition);
+            }
+        }
+    }
+    Ok(filter)
+}
+
+// browse_table for a collection: `table.schema` is the database
+pub async fn browse_collection(
+    client: &DbClient,
+    table: &TableRef,
+    skip: usize,
+    limit: usize,
+    sort: &[SortKey],
+    filters: &[ColumnFilter],
+) -> Result<(QueryResponse, u64), String> {
+    let collection = database(client, table.schema.clone())?.collection::<Document>(&table.table);
+    let filter = filter_document(filters)?;
+    let mut order = Document::new();
+    for key in sort {
+        let direction = match key.direction {
+            SortDirection::Asc => 1,
+            SortDirection::Desc => -1,
+        };
+        order.insert(key.column.clone(), direction);
+    }
+    let documents: Vec<Document> = collection
+        .find(filter.clone())
+        .sort(order)
+        .skip(skip as u64)
+        .limit(limit as i64)
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())?;
+    let total = collection
+        .count_documents(filter)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok((documents_response(&documents), total))
+}
+
+// A find written by hand: filter, projection and sort are relaxed Extended
+// JSON objects, e.g. {"age": {"$gt": 30}}
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FindQuery {
+    pub filter: Option<Value>,
+    pub projection: Option<Value>,
+    pub sort: Option<Value>,
+    pub limit: Option<usize>,
+    pub skip: Option<usize>,
+}
+
+const MAX_FIND_LIMIT: usize = 10_000;
+
+fn json_document(value: Option<Value>, what: &str) -> Result<Option<Document>, String> {
+    let Some(value) = value.filter(|v| !v.is_null()) else {
+        return Ok(None);
+    };
+    match Bson::try_from(value) {
+        Ok(Bson::Document(document)) => Ok(Some(document)),
+        Ok(_) => Err(format!("The {} must be a JSON object", what)),
+        Err(e) => Err(format!("Invalid {}: {}", what, e)),
+    }
+}
+
+// Runs a find on `collection`; the response is truncated when more documents
+// match past the limit. The query's own limit wins over `row_limit`, the
+// editor's auto-limit; without either it is MAX_FIND_LIMIT.
+pub async fn find(
+    client: &DbClient,
+    database_name: Option<String>,
+    collection: &str,
+    query: FindQuery,
+    row_limit: Option<usize>,
+) -> Result<QueryResponse, String> {
+    let collection = database(client, database_name)?.collection::<Document>(collection);
+    let filter = json_document(query.filter, "filter")?.unwrap_or_default();
+    let limit = query
+        .limit
+        .or(row_limit)
+        .unwrap_or(MAX_FIND_LIMIT)
+        .clamp(1, MAX_FIND_LIMIT);
+    // One more than the limit tells whether anything was cut off
+    let mut find = collection
+        .find(filter)
+        .skip(query.skip.unwrap_or(0) as u64)
+        .limit(limit as i64 + 1);
+    if let Some(projection) = json_document(query.projection, "projection")? {
+        find = find.projection(projection);
+    }
+    if let Some(sort) = json_document(query.sort, "sort")? {
+        find = find.sort(sort);
+    }
+    let started = std::time::Instant::now();
+    let mut documents: Vec<Document> = find
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())?;
+    let truncated = documents.len() > limit;
+    documents.truncate(limit);
+
+    let mut response = documents_response(&documents).counted(StatementKind::Query, None);
+    response.truncated = truncated;
+    response.execution_ms = started.elapsed().as_millis() as u64;
+    Ok(response)
+}
+
+// listIndexes, with sizes from collStats where the server still answers it
+pub async fn list_indexes(client: &DbClient, table: &TableRef) -> Result<Vec<TableIndex>, String> {
+    let db = database(client, table.schema.clone())?;
+    let models: Vec<IndexModel> = db
+        .collection::<Document>(&table.table)
+        .list_indexes()
+        .await
+        .map_err(|e| e.to_string())?
+        .try_collect()
+        .await
+        .map_err(|e| e.to_string())?;
+    let sizes = db
+        .run_command(doc! { "collStats": &table.table })
+        .await
+        .ok()
+        .and_then(|stats| stats.get_document("indexSizes").ok().cloned())
+        .unwrap_or_default();
+    Ok(models
+        .into_iter()
+        .map(|model| {
+            let options = model.options.unwrap_or_default();
+            let name = options.name.unwrap_or_default();
+            let primary = name == "_id_";
+            TableIndex {
+                columns: model.keys.keys().cloned().collect(),
+                unique: primary || options.unique.unwrap_or(false),
+                primary,
+                // A direction for ordinary keys, a name like "text" or "hashed" otherwise
+                index_type: model
+                    .keys
+                    .values()
+                    .find_map(Bson::as_str)
+                    .unwrap_or("btree")
+                    .to_string(),
+                size_bytes: match sizes.get(&name) {
+                    Some(Bson::Int32(n)) => Some(*n as i64),
+                    Some(Bson::Int64(n)) => Some(*n),
+                    Some(Bson::Double(n)) => Some(*n as i64),
+                    _ => None,
+                },
+                name,
+            }
+        })
+        .collect())
+}
+
+// Pipeline stages from a JSON array, e.g. [{"$match": {...}}, {"$group": {...}}]
+pub fn parse_pipeline(text: &str) -> Result<Vec<Document>, String> {
+    let json: Value = serde_json::from_str(text).map_err(|e| format!("Invalid pipeline: {}", e))?;
+    let Value::Array(stages) = json else {
+        return Err("The pipeline must be a JSON array of stages".to_string());
+    };
+    stages
+        .into_iter()
+        .enumerate()
+        .map(|(i, stage)| match Bson::try_from(stage) {
+            Ok(Bson::Document(stage)) => Ok(stage),
+            Ok(_) => Err(format!("Stage {} is not an object", i + 1)),
+            Err(e) => Err(format!("Invalid stage {}: {}", i + 1, e)),
+        })
+        .collect()
+}
+
+// $out and $merge write the results to a collection
+pub fn pipeline_writes(pipeline: &[Document]) -> bool {
+    pipeline
+        .iter()
+        .any(|stage| stage.contains_key("$out") || stage.contains_key("$merge"))
+}
+
+// Runs an aggregation and hands the results to `emit` in batches as the cursor
+// yields them. Documents don't share a schema, so a batch carries the column
+// list again whenever it has grown; rows are in the order of the latest list.
+// Returns the number of documents.
+pub async fn aggregate(
+    client: &DbClient,
+    database_name: Option<String>,
+    collection: &str,
+    pipeline: Vec<Document>,
+    batch_size: usize,
+    emit: &(dyn Fn(RowBatch) -> Result<(), String> + Sync),
+) -> Result<u64, String> {
+    let collection = database(client, database_name)?.collection::<Document>(collection);
+    let mut cursor = collection
+        .aggregate(pipeline)
+        .batch_size(batch_size as u32)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let batch_size = batch_size.max(1);
+    let mut column_meta: Vec<ColumnMeta> = Vec::new();
+    let mut documents: Vec<Document> = Vec::new();
+    let mut index = 0;
+    let mut total = 0;
+    loop {
+        let next = cursor.try_next().await.map_err(|e| e.to_string())?;
+        let done = next.is_none();
+        documents.extend(next);
+        if documents.len() < batch_size && !done {
+            continue;
+        }
+        if !documents.is_empty() {
+            let known = column_meta.len();
+            add_document_columns(&mut column_meta, &documents);
+            let grown = column_meta.len() > known;
+            let rows = documents
+                .iter()
+                .map(|doc| document_row(doc, &column_meta))
+                .collect();
+            emit(RowBatch {
+                index,
+                columns: grown.then(|| column_meta.iter().map(|c| c.name.clone()).collect()),
+                column_meta: grown.then(|| column_meta.clone()),
+                rows,
+            })?;
+            index += 1;
+            total += documents.len() as u64;
+            documents.clear();
+        }
+        if done {
+            break;
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn numbers_are_rendered_relaxed() {
+        assert_eq!(extended_json(Bson::Int64(5)), json!(5));
+        assert_eq!(extended_json(Bson::Double(1.0)), json!(1.0));
+        assert_eq!(
+            extended_json(Bson::Document(doc! { "n": 7_i64 })),
+            json!({ "n": 7 })
+        );
+    }
+
+    #[test]
+    fn int64_past_the_safe_range_stays_canonical() {
+        assert_eq!(
+            extended_json(Bson::Int64(i64::MAX)),
+            json!({ "$numberLong": i64::MAX.to_string() })
+        );
+    }
+}
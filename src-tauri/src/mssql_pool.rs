@@ -0,0 +1,206 @@
+// A small pool of SQL Server connections. A tiberius client is a single
+// connection, so sharing one made every query on a connection wait its turn.
+use std::collections::HashSet;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex as StdMutex, OnceLock};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::db::{self, Dialect, MssqlClient};
+use crate::quoting::quote_ident;
+use crate::tls::TlsOptions;
+
+pub const MSSQL_POOL_SIZE: usize = 4;
+
+// A returned connection that doesn't answer the reset in time is dropped
+const RESET_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct MssqlPool {
+    url: String,
+    tls: TlsOptions,
+    idle: StdMutex<Vec<(MssqlClient, i64)>>, // with the connection's SPID
+    slots: Arc<Semaphore>,
+    // Sessions killed by a cancel; their connections are dropped, not reused
+    killed: StdMutex<HashSet<i64>>,
+    // Whether returned connections are reset; a transaction's own pool keeps
+    // its session between statements
+    reset: bool,
+    database: OnceLock<String>, // the login's database, restored by the reset
+}
+
+// A connection checked out of the pool, returned to it when dropped
+pub struct PooledMssql {
+    client: Option<MssqlClient>,
+    spid: i64,
+    pool: Arc<MssqlPool>,
+    slot: Option<OwnedSemaphorePermit>,
+}
+
+impl MssqlPool {
+    // Opens the first connection straight away so bad settings fail here
+    pub async fn open(url: &str, tls: &TlsOptions, size: usize) -> Result<Arc<Self>, String> {
+        Self::open_with(url, tls, size, true).await
+    }
+
+    async fn open_with(
+        url: &str,
+        tls: &TlsOptions,
+        size: usize,
+        reset: bool,
+    ) -> Result<Arc<Self>, String> {
+        let pool = Arc::new(MssqlPool {
+            url: url.to_string(),
+            tls: tls.clone(),
+            idle: StdMutex::new(Vec::new()),
+            slots: Arc::new(Semaphore::new(size.max(1))),
+            killed: StdMutex::new(HashSet::new()),
+            reset,
+            database: OnceLock::new(),
+        });
+        let first = pool.connect().await?;
+        pool.idle.lock().unwrap().push(first);
+        Ok(pool)
+    }
+
+    // A separate pool of one connection to the same server, for a transaction;
+    // it fails instead of reconnecting once that connection is lost
+    pub async fn dedicated(&self) -> Result<Arc<Self>, String> {
+        Self::open_with(&self.url, &self.tls, 1, false).await
+    }
+
+    // A new connection that doesn't count against the pool size, so a KILL
+    // can be sent even while every pooled connection is busy
+    pub async fn connect(&self) -> Result<(MssqlClient, i64), String> {
+        let mut client = db::connect_mssql(&self.url, &self.tls).await?;
+        let row = client
+            .simple_query("SELECT CAST(@@SPID AS bigint), DB_NAME()")
+            .await
+            .map_err(|e| e.to_string())?
+            .into_row()
+            .await
+            .map_err(|e| e.to_string())?
+            .ok_or("The server did not report a session id")?;
+        let spid = row
+            .get::<i64, _>(0)
+            .ok_or("The server did not report a session id")?;
+        if let Some(database) = row.get::<&str, _>(1) {
+            let _ = self.database.set(database.to_string());
+        }
+        Ok((client, spid))
+    }
+
+    // Undoes what a statement may have left on the session: an open
+    // transaction, SET options and a USE. A connection that errors here is
+    // broken, e.g. by a failed read, and isn't reused.
+    async fn reset_session(&self, client: &mut MssqlClient) -> Result<(), tiberius::error::Error> {
+        // SHOWPLAN_XML has to be turned off in a batch of its own
+        client
+            .simple_query("SET SHOWPLAN_XML OFF")
+            .await?
+            .into_results()
+            .await?;
+        let mut batch = "IF @@TRANCOUNT > 0 ROLLBACK TRANSACTION; \
+             SET STATISTICS XML OFF; SET NOCOUNT OFF; SET XACT_ABORT OFF; \
+             SET ROWCOUNT 0; SET LOCK_TIMEOUT -1; \
+             SET TRANSACTION ISOLATION LEVEL READ COMMITTED;"
+            .to_string();
+        if let Some(database) = self.database.get() {
+            batch.push_str(&format!(" USE {};", quote_ident(Dialect::Mssql, database)));
+        }
+        client.simple_query(batch).await?.into_results().await?;
+        Ok(())
+    }
+
+    // Whether every connection is in use, so get would wait
+    pub fn busy(&self) -> bool {
+        self.slots.available_permits() == 0
+    }
+
+    // Waits while all connections are in use
+    pub async fn get(self: &Arc<Self>) -> Result<PooledMssql, String> {
+        let slot = self
+            .slots
+            .clone()
+            .acquire_owned()
+            .await
+            .map_err(|e| e.to_string())?;
+        let idle = {
+            let mut idle = self.idle.lock().unwrap();
+            let mut killed = self.killed.lock().unwrap();
+            std::iter::from_fn(|| idle.pop()).find(|(_, spid)| !killed.remove(spid))
+        };
+        let (client, spid) = match idle {
+            Some(idle) => idle,
+            // A transaction's session is never replaced: once it is gone the
+            // server has rolled the transaction back, so later statements
+            // must fail rather than quietly run outside it
+            None if !self.reset => {
+                return Err(
+                    "The transaction's connection was lost and it was rolled back".to_string(),
+                )
+            }
+            None => self.connect().await?,
+        };
+        Ok(PooledMssql {
+            client: Some(client),
+            spid,
+            pool: self.clone(),
+            slot: Some(slot),
+        })
+    }
+
+    // Call before killing a session, so its connection isn't handed out again
+    pub fn forget_session(&self, spid: i64) {
+        self.killed.lock().unwrap().insert(spid);
+    }
+}
+
+impl PooledMssql {
+    pub fn spid(&self) -> i64 {
+        self.spid
+    }
+}
+
+impl Deref for PooledMssql {
+    type Target = MssqlClient;
+
+    fn deref(&self) -> &MssqlClient {
+        self.client.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledMssql {
+    fn deref_mut(&mut self) -> &mut MssqlClient {
+        self.client.as_mut().unwrap()
+    }
+}
+
+// The reset runs on a task of its own, holding the connection's slot until the
+// connection is back in the pool or dropped
+impl Drop for PooledMssql {
+    fn drop(&mut self) {
+        let Some(mut client) = self.client.take() else {
+            return;
+        };
+        if self.pool.killed.lock().unwrap().remove(&self.spid) {
+            return;
+        }
+        if !self.pool.reset {
+            self.pool.idle.lock().unwrap().push((client, self.spid));
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let pool = self.pool.clone();
+        let spid = self.spid;
+        let slot = self.slot.take();
+        runtime.spawn(async move {
+            let reset = tokio::time::timeout(RESET_TIMEOUT, pool.reset_session(&mut client)).await;
+            if matches!(reset, Ok(Ok(()))) && !pool.killed.lock().unwrap().remove(&spid) {
+                pool.idle.lock().unwrap().push((client, spid));
+            }
+            drop(slot);
+        });
+    }
+}
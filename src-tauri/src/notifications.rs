@@ -0,0 +1,272 @@
+// Job outcome notifications: background jobs report how they went to
+// Slack-compatible webhooks or by email over SMTP. Webhook URLs and SMTP
+// passwords are kept in the keychain, see vault.rs, or taken from a workspace
+// secret, see secrets.rs.
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::secrets;
+use crate::storage;
+use crate::vault;
+
+pub const NOTIFICATIONS_FILE: &str = "notifications.json";
+
+// How long one delivery may take before it is given up on
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(20);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    ScheduledExport,
+    HealthCheck, // a connection's keep-alive stopped or resumed answering
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    #[default]
+    StartTls,
+    Tls, // implicit TLS, usually port 465
+    None,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Destination {
+    Webhook, // the URL is the channel's secret
+    Email {
+        host: String,
+        port: Option<u16>, // the default for `security` when unset
+        #[serde(default)]
+        security: SmtpSecurity,
+        username: Option<String>, // the password is the channel's secret
+        from: String,
+        to: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationChannel {
+    #[serde(default)]
+    pub id: String, // empty when creating
+    pub name: String,
+    pub destination: Destination,
+    // Jobs reported through this channel; all of them when empty
+    #[serde(default)]
+    pub jobs: Vec<JobKind>,
+    #[serde(default)]
+    pub failures_only: bool,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    // Webhook URL or SMTP password; goes to the keychain, never to the file.
+    // Left unset when saving, the stored one is kept.
+    #[serde(default, skip_serializing)]
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub secret_ref: Option<String>,
+    // A workspace secret used instead of the channel's own, so several
+    // channels can share one webhook or SMTP account
+    #[serde(default)]
+    pub secret_name: Option<String>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub job: JobKind,
+    pub ok: bool,
+    pub title: String,
+    pub message: String,
+}
+
+impl NotificationChannel {
+    fn wants(&self, notification: &Notification) -> bool {
+        self.enabled
+            && (self.jobs.is_empty() || self.jobs.contains(&notification.job))
+            && !(self.failures_only && notification.ok)
+    }
+}
+
+pub fn list(path: &Path) -> Result<Vec<NotificationChannel>, String> {
+    storage::read_json(path)
+}
+
+// Creates the channel when its id is empty or unknown, otherwise replaces it
+pub fn save(path: &Path, mut channel: NotificationChannel) -> Result<NotificationChannel, String> {
+    if channel.name.trim().is_empty() {
+        return Err("Channel name is required".to_string());
+    }
+    if let Destination::Email { to, .. } = &channel.destination {
+        if to.is_empty() {
+            return Err("At least one recipient is required".to_string());
+        }
+    }
+    let mut channels = list(path)?;
+    let existing = channels
+        .iter()
+        .position(|c| !channel.id.is_empty() && c.id == channel.id);
+    channel.secret_ref = existing.and_then(|i| channels[i].secret_ref.clone());
+    if let Some(secret) = channel.secret.take().filter(|s| !s.is_empty()) {
+        let secret_ref = channel
+            .secret_ref
+            .clone()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        vault::store(&secret_ref, &secret)?;
+        channel.secret_ref = Some(secret_ref);
+    }
+    channel.secret_name = channel.secret_name.filter(|n| !n.trim().is_empty());
+    let has_secret = channel.secret_ref.is_some() || channel.secret_name.is_some();
+    if matches!(channel.destination, Destination::Webhook) && !has_secret {
+        return Err("A webhook URL is required".to_string());
+    }
+
+    match existing {
+        Some(i) => channels[i] = channel.clone(),
+        None => {
+            if channel.id.is_empty() {
+                channel.id = uuid::Uuid::new_v4().to_string();
+            }
+            channels.push(channel.clone());
+        }
+    }
+    storage::write_json(path, &channels)?;
+    Ok(channel)
+}
+
+pub fn delete(path: &Path, id: &str) -> Result<(), String> {
+    let mut channels = list(path)?;
+    let index = channels
+        .iter()
+        .position(|c| c.id == id)
+        .ok_or("Notification channel not found")?;
+    let removed = channels.remove(index);
+    storage::write_json(path, &channels)?;
+    match removed.secret_ref {
+        Some(secret_ref) => vault::forget(&secret_ref),
+        None => Ok(()),
+    }
+}
+
+fn mailbox(address: &str) -> Result<Mailbox, String> {
+    address
+        .parse()
+        .map_err(|e| format!("Invalid email address {}: {}", address, e))
+}
+
+pub async fn deliver(
+    channel: &NotificationChannel,
+    notification: &Notification,
+) -> Result<(), String> {
+    let secret = match (&channel.secret_name, &channel.secret_ref) {
+        (Some(name), _) => Some(
+            secrets::get_secret(name)?
+                .ok_or_else(|| format!("The workspace secret {} is not set", name))?,
+        ),
+        (None, Some(secret_ref)) => vault::load(secret_ref)?,
+        (None, None) => None,
+    };
+    match &channel.destination {
+        Destination::Webhook => {
+            let url = secret.ok_or("The webhook URL is missing from the keychain")?;
+            // Slack and compatible services (Mattermost, Discord's /slack
+            // endpoint, ...) all take a "text" field
+            let text = format!("*{}*\n{}", notification.title, notification.message);
+            reqwest::Client::builder()
+                .timeout(DELIVERY_TIMEOUT)
+                .build()
+                .map_err(|e| e.to_string())?
+                .post(&url)
+                .json(&json!({ "text": text }))
+                .send()
+                .await
+                .map_err(|e| e.to_string())?
+                .error_for_status()
+                .map_err(|e| e.to_string())?;
+        }
+        Destination::Email {
+            host,
+            port,
+            security,
+            username,
+            from,
+            to,
+        } => {
+            let mut message = Message::builder()
+                .from(mailbox(from)?)
+                .subject(&notification.title);
+            for recipient in to {
+                message = message.to(mailbox(recipient)?);
+            }
+            let message = message
+                .body(notification.message.clone())
+                .map_err(|e| e.to_string())?;
+
+            let mut transport = match security {
+                SmtpSecurity::StartTls => {
+                    AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                }
+                SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+                SmtpSecurity::None => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(
+                    host,
+                )),
+            }
+            .map_err(|e| e.to_string())?;
+            if let Some(port) = port {
+                transport = transport.port(*port);
+            }
+            transport = transport.timeout(Some(DELIVERY_TIMEOUT));
+            if let Some(username) = username {
+                let password = secret.unwrap_or_default();
+                transport = transport.credentials(Credentials::new(username.clone(), password));
+            }
+            let mailer: AsyncSmtpTransport<Tokio1Executor> = transport.build();
+            mailer.send(message).await.map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+// Sends the notification to every channel that wants it, in the background
+// so a slow channel doesn't hold up the job being reported. Delivery problems
+// are logged; they shouldn't fail the job either.
+pub fn notify(app: &tauri::AppHandle, notification: Notification) {
+    let channels = storage::data_file(app, NOTIFICATIONS_FILE).and_then(|path| list(&path));
+    let channels = match channels {
+        Ok(channels) => channels,
+        Err(e) => {
+            log::warn!("Failed to read notification channels: {}", e);
+            return;
+        }
+    };
+    for channel in channels.into_iter().filter(|c| c.wants(&notification)) {
+        let notification = notification.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Err(e) = deliver(&channel, &notification).await {
+                log::warn!("Failed to notify {}: {}", channel.name, e);
+            }
+        });
+    }
+}
+
+// Sends a sample message through one channel so its settings can be checked
+pub async fn test(path: &Path, id: &str) -> Result<(), String> {
+    let channel = list(path)?
+        .into_iter()
+        .find(|c| c.id == id)
+        .ok_or("Notification channel not found")?;
+    let notification = Notification {
+        job: JobKind::HealthCheck,
+        ok: true,
+        title: "Test notification".to_string(),
+        message: format!("Notifications from DBMS reach {}.", channel.name),
+    };
+    deliver(&channel, &notification).await
+}
@@ -0,0 +1,174 @@
+// Offline mode: a connection that can't be reached can still be opened from
+// what was kept of it, a copy of its schema and the results of its last
+// queries. Everything served from here is flagged stale.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex as StdMutex;
+
+use crate::db::QueryResponse;
+use crate::schema_snapshots::{self, SchemaSnapshot};
+use crate::sqltext::StatementKind;
+use crate::storage;
+
+pub const OFFLINE_FILE: &str = "offline_cache.json";
+
+// Rows kept of each result
+const MAX_CACHED_ROWS: usize = 1000;
+
+static WRITING: StdMutex<()> = StdMutex::new(());
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResult {
+    pub id: String,
+    pub sql: String,
+    pub fetched_at: DateTime<Utc>,
+    pub rows: usize,     // rows kept
+    pub truncated: bool, // the query matched more rows than were kept
+    pub response: Value, // the QueryResponse as it was returned
+}
+
+#[derive(Debug, Serialize)]
+pub struct CachedResultSummary {
+    pub id: String,
+    pub sql: String,
+    pub fetched_at: DateTime<Utc>,
+    pub rows: usize,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct OfflineCopy {
+    #[serde(default)]
+    schema: Option<SchemaSnapshot>,
+    #[serde(default)]
+    results: Vec<CachedResult>, // newest first
+}
+
+// Offline copies by connection name
+type OfflineCopies = HashMap<String, OfflineCopy>;
+
+#[derive(Debug, Serialize)]
+pub struct OfflineConnection {
+    pub connection: String,
+    pub stale: bool, // always set, nothing here comes from the server
+    pub schema_taken_at: Option<DateTime<Utc>>,
+    pub schema: Option<SchemaSnapshot>,
+    pub results: Vec<CachedResultSummary>,
+}
+
+// The result of a query as kept for offline use; None for statements that
+// return no result set
+pub fn cached_response(response: &QueryResponse) -> Result<Option<Value>, String> {
+    if response.statement_kind != StatementKind::Query {
+        return Ok(None);
+    }
+    serde_json::to_value(response)
+        .map(Some)
+        .map_err(|e| e.to_string())
+}
+
+// Keeps a cached_response, replacing an earlier result of the same SQL; `keep`
+// is how many results the connection holds on to
+pub fn remember_result(
+    path: &Path,
+    connection: &str,
+    sql: &str,
+    mut response: Value,
+    keep: usize,
+) -> Result<(), String> {
+    if keep == 0 {
+        return Ok(());
+    }
+    let mut truncated = response["truncated"].as_bool().unwrap_or(false);
+    let mut rows = 0;
+    if let Some(all) = response.get_mut("rows").and_then(Value::as_array_mut) {
+        if all.len() > MAX_CACHED_ROWS {
+            all.truncate(MAX_CACHED_ROWS);
+            truncated = true;
+        }
+        rows = all.len();
+    }
+    response["truncated"] = Value::Bool(truncated);
+
+    // Results are written from background tasks; one at a time, so none is lost
+    let _writing = WRITING.lock().unwrap();
+    let mut copies: OfflineCopies = storage::read_json(path)?;
+    let results = &mut copies.entry(connection.to_string()).or_default().results;
+    results.retain(|r| r.sql != sql);
+    results.insert(
+        0,
+        CachedResult {
+            id: uuid::Uuid::new_v4().to_string(),
+            sql: sql.to_string(),
+            fetched_at: Utc::now(),
+            rows,
+            truncated,
+            response,
+        },
+    );
+    results.truncate(keep);
+    storage::write_json(path, &copies)
+}
+
+// Replaces the schema kept for the connection
+pub fn save_schema(path: &Path, snapshot: SchemaSnapshot) -> Result<(), String> {
+    let mut copies: OfflineCopies = storage::read_json(path)?;
+    let copy = copies.entry(snapshot.connection.clone()).or_default();
+    copy.schema = Some(snapshot);
+    storage::write_json(path, &copies)
+}
+
+// Everything kept for the connection. The schema is the newest of the offline
+// copy and the connection's stored schema snapshots.
+pub fn open(path: &Path, snapshots: &Path, connection: &str) -> Result<OfflineConnection, String> {
+    let mut copies: OfflineCopies = storage::read_json(path)?;
+    let copy = copies.remove(connection).unwrap_or_default();
+    let stored = schema_snapshots::latest(snapshots, connection)?;
+    let schema = match (copy.schema, stored) {
+        (Some(a), Some(b)) => Some(if a.taken_at >= b.taken_at { a } else { b }),
+        (a, b) => a.or(b),
+    };
+    if schema.is_none() && copy.results.is_empty() {
+        return Err(format!(
+            "Nothing of {} is available offline; save an offline copy while connected",
+            connection
+        ));
+    }
+    Ok(OfflineConnection {
+        connection: connection.to_string(),
+        stale: true,
+        schema_taken_at: schema.as_ref().map(|s| s.taken_at),
+        schema,
+        results: copy
+            .results
+            .into_iter()
+            .map(|r| CachedResultSummary {
+                id: r.id,
+                sql: r.sql,
+                fetched_at: r.fetched_at,
+                rows: r.rows,
+                truncated: r.truncated,
+            })
+            .collect(),
+    })
+}
+
+pub fn result(path: &Path, connection: &str, id: &str) -> Result<CachedResult, String> {
+    let mut copies: OfflineCopies = storage::read_json(path)?;
+    copies
+        .remove(connection)
+        .and_then(|copy| copy.results.into_iter().find(|r| r.id == id))
+        .ok_or_else(|| "Cached result not found".to_string())
+}
+
+// Drops everything kept for the connection
+pub fn clear(path: &Path, connection: &str) -> Result<(), String> {
+    let mut copies: OfflineCopies = storage::read_json(path)?;
+    if copies.remove(connection).is_some() {
+        storage::write_json(path, &copies)?;
+    }
+    Ok(())
+}
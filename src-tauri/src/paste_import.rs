@@ -0,0 +1,390 @@
+// Quick import of a grid pasted from the clipboard, e.g. cells copied out of a
+// spreadsheet (tab-separated) or a CSV snippet
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+use crate::audit::Auditor;
+use crate::db::{self, ColumnInfo, DbClient, TxStep};
+use crate::edits::{self, TableRef};
+use crate::quoting;
+
+// Rows returned in the preview; the import itself takes them all
+const PREVIEW_ROWS: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InferredType {
+    Integer,
+    Decimal,
+    Boolean,
+    Date,
+    Timestamp,
+    Text,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PastePreview {
+    pub delimiter: String,
+    pub has_header: bool,
+    pub columns: Vec<String>,     // the table's own column names
+    pub types: Vec<InferredType>, // declared by the column, or inferred from the cells
+    pub rows: Vec<Vec<Value>>,
+    pub total_rows: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PasteImportResult {
+    pub preview: PastePreview,
+    pub inserted: Option<u64>, // None for a preview
+}
+
+pub fn cell_type(cell: &str) -> InferredType {
+    const TIMESTAMP_FORMATS: [&str; 2] = ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S%.f"];
+    if cell.parse::<i64>().is_ok() {
+        InferredType::Integer
+    } else if cell.parse::<f64>().is_ok_and(f64::is_finite) {
+        InferredType::Decimal
+    } else if cell.eq_ignore_ascii_case("true") || cell.eq_ignore_ascii_case("false") {
+        InferredType::Boolean
+    } else if NaiveDate::parse_from_str(cell, "%Y-%m-%d").is_ok() {
+        InferredType::Date
+    } else if TIMESTAMP_FORMATS
+        .iter()
+        .any(|f| NaiveDateTime::parse_from_str(cell, f).is_ok())
+        || DateTime::parse_from_rfc3339(cell).is_ok()
+    {
+        InferredType::Timestamp
+    } else {
+        InferredType::Text
+    }
+}
+
+// The narrowest type every non-empty cell of the column fits
+pub fn column_type<'a>(cells: impl Iterator<Item = &'a str>) -> InferredType {
+    use InferredType::*;
+    let mut inferred: Option<InferredType> = None;
+    for cell in cells.filter(|c| !c.is_empty()) {
+        let cell = cell_type(cell);
+        inferred = Some(match (inferred, cell) {
+            (None, t) => t,
+            (Some(a), b) if a == b => a,
+            (Some(Integer | Decimal), Integer | Decimal) => Decimal,
+            (Some(Date | Timestamp), Date | Timestamp) => Timestamp,
+            _ => return Text,
+        });
+    }
+    inferred.unwrap_or(Text)
+}
+
+// The type a column's cells are converted to, from the column's declared
+// type; None for types the cells are better inferred for
+pub fn declared_type(data_type: &str) -> Option<InferredType> {
+    let data_type = data_type.to_ascii_lowercase();
+    let base = data_type.split('(').next().unwrap_or_default().trim();
+    let base = base.trim_end_matches(" unsigned");
+    // MySQL's boolean is a tinyint(1)
+    if data_type.starts_with("tinyint(1)") {
+        return Some(InferredType::Boolean);
+    }
+    Some(match base {
+        "smallint" | "integer" | "int" | "bigint" | "tinyint" | "mediumint" | "int2" | "int4"
+        | "int8" => InferredType::Integer,
+        "numeric" | "decimal" | "real" | "double precision" | "double" | "float" | "money"
+        | "smallmoney" => InferredType::Decimal,
+        "boolean" | "bool" | "bit" => InferredType::Boolean,
+        "date" => InferredType::Date,
+        "datetime" | "datetime2" | "smalldatetime" | "datetimeoffset" => InferredType::Timestamp,
+        _ if base.starts_with("timestamp") => InferredType::Timestamp,
+        "character varying" | "varchar" | "character" | "char" | "text" | "nvarchar" | "nchar"
+        | "ntext" | "citext" | "tinytext" | "mediumtext" | "longtext" | "uuid"
+        | "uniqueidentifier" => InferredType::Text,
+        _ => return None,
+    })
+}
+
+// Types of the table's columns that are known from their declaration
+pub fn declared_types(columns: &[ColumnInfo]) -> HashMap<String, InferredType> {
+    columns
+        .iter()
+        .filter_map(|c| Some((c.name.clone(), declared_type(&c.data_type)?)))
+        .collect()
+}
+
+// Integers and booleans become typed values; everything else stays text (so
+// decimals keep their exact digits, and "007" in a text column its zeros) and
+// is converted by the database on insert
+pub fn cell_value(cell: &str, data_type: InferredType) -> Value {
+    if cell.is_empty() {
+        return Value::Null;
+    }
+    match data_type {
+        InferredType::Integer => cell
+            .parse::<i64>()
+            .map(Value::from)
+            .unwrap_or_else(|_| Value::String(cell.to_string())),
+        InferredType::Boolean => match cell.to_ascii_lowercase().as_str() {
+            "true" | "1" => Value::Bool(true),
+            "false" | "0" => Value::Bool(false),
+            _ => Value::String(cell.to_string()),
+        },
+        _ => Value::String(cell.to_string()),
+    }
+}
+
+// Spreadsheets put tabs between cells; anything else is taken as CSV
+fn detect_delimiter(text: &str) -> u8 {
+    let first_line = text.lines().next().unwrap_or_default();
+    if first_line.contains('\t') {
+        b'\t'
+    } else {
+        b','
+    }
+}
+
+fn parse_grid(text: &str, delimiter: u8) -> Result<Vec<Vec<String>>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let mut grid = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        let cells: Vec<String> = record.iter().map(|c| c.trim().to_string()).collect();
+        if cells.iter().any(|c| !c.is_empty()) {
+            grid.push(cells);
+        }
+    }
+    Ok(grid)
+}
+
+// Matches pasted header names to the table's columns, ignoring case
+pub fn header_columns(header: &[String], table_columns: &[String]) -> Option<Vec<String>> {
+    header
+        .iter()
+        .map(|name| {
+            table_columns
+                .iter()
+                .find(|c| c.eq_ignore_ascii_case(name))
+                .cloned()
+        })
+        .collect()
+}
+
+// Parses `text` for `table`. Without an explicit `has_header`, the first row is
+// a header when every cell in it names a column of the table. Without a header
+// the cells map to the table's columns in order.
+async fn prepare(
+    client: &DbClient,
+    table: &TableRef,
+    text: &str,
+    delimiter: Option<String>,
+    has_header: Option<bool>,
+) -> Result<(PastePreview, Vec<Vec<Value>>), String> {
+    let delimiter = match delimiter.as_deref() {
+        Some("\\t") | Some("tab") => b'\t',
+        Some(d) if d.len() == 1 => d.as_bytes()[0],
+        Some(d) => return Err(format!("Invalid delimiter: {}", d)),
+        None => detect_delimiter(text),
+    };
+    let mut grid = parse_grid(text, delimiter)?;
+    if grid.is_empty() {
+        return Err("Nothing to import".to_string());
+    }
+    let info = db::get_column_info(client, table.schema.clone(), &table.table).await?;
+    if info.is_empty() {
+        return Err(format!("Table {} not found", table.table));
+    }
+    let table_columns: Vec<String> = info.iter().map(|c| c.name.clone()).collect();
+    let declared = declared_types(&info);
+    let writable: Vec<String> = info
+        .into_iter()
+        .filter(ColumnInfo::is_writable)
+        .map(|c| c.name)
+        .collect();
+
+    let from_header = header_columns(&grid[0], &table_columns);
+    let has_header = has_header.unwrap_or(from_header.is_some());
+    let mut columns = if has_header {
+        let header = grid.remove(0);
+        match from_header {
+            Some(columns) => columns,
+            None => {
+                let unknown: Vec<&String> = header
+                    .iter()
+                    .filter(|h| !table_columns.iter().any(|c| c.eq_ignore_ascii_case(h)))
+                    .collect();
+                return Err(format!(
+                    "Columns not found in {}: {:?}",
+                    table.table, unknown
+                ));
+            }
+        }
+    } else {
+        let width = grid.iter().map(Vec::len).max().unwrap_or(0);
+        if width > writable.len() {
+            return Err(format!(
+                "The pasted rows have {} columns but {} takes values for only {}",
+                width,
+                table.table,
+                writable.len()
+            ));
+        }
+        writable[..width].to_vec()
+    };
+
+    for (i, row) in grid.iter_mut().enumerate() {
+        if row.len() > columns.len() {
+            return Err(format!(
+                "Row {} has {} cells, expected {}",
+                i + 1,
+                row.len(),
+                columns.len()
+            ));
+        }
+        row.resize(columns.len(), String::new());
+    }
+    // A header may name identity or computed columns, e.g. rows copied out of
+    // the same table; their cells are left to the database
+    let keep: Vec<bool> = columns.iter().map(|c| writable.contains(c)).collect();
+    if keep.contains(&false) {
+        let retain = |cells: Vec<String>| -> Vec<String> {
+            cells
+                .into_iter()
+                .zip(&keep)
+                .filter_map(|(cell, keep)| keep.then_some(cell))
+                .collect()
+        };
+        columns = retain(columns);
+        for row in grid.iter_mut() {
+            *row = retain(std::mem::take(row));
+        }
+        if columns.is_empty() {
+            return Err(format!(
+                "None of the pasted columns of {} takes values",
+                table.table
+            ));
+        }
+    }
+    let types: Vec<InferredType> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| match declared.get(column) {
+            Some(declared) => *declared,
+            None => column_type(grid.iter().map(|row| row[i].as_str())),
+        })
+        .collect();
+    let rows: Vec<Vec<Value>> = grid
+        .iter()
+        .map(|row| {
+            row.iter()
+                .zip(&types)
+                .map(|(cell, t)| cell_value(cell, *t))
+                .collect()
+        })
+        .collect();
+
+    let preview = PastePreview {
+        delimiter: (delimiter as char).to_string(),
+        has_header,
+        columns,
+        types,
+        rows: rows.iter().take(PREVIEW_ROWS).cloned().collect(),
+        total_rows: rows.len(),
+    };
+    Ok((preview, rows))
+}
+
+// Previews the parsed grid, or with `preview` off inserts every row in one
+// transaction (with `nested`, within the one open on `client`), so a row the
+// table rejects leaves nothing behind. With `conflict_columns` rows matching
+// an existing one on them update it instead.
+pub async fn paste_import(
+    (client, nested): &(DbClient, bool),
+    table: &TableRef,
+    text: &str,
+    (delimiter, has_header): (Option<String>, Option<bool>),
+    conflict_columns: Option<&[String]>,
+    preview: bool,
+    auditor: &Auditor,
+) -> Result<PasteImportResult, String> {
+    let (parsed, rows) = prepare(client, table, text, delimiter, has_header).await?;
+    if preview {
+        return Ok(PasteImportResult {
+            preview: parsed,
+            inserted: None,
+        });
+    }
+    let dialect = client
+        .dialect()
+        .ok_or("Import is not supported for this database type")?;
+    let steps = rows
+        .into_iter()
+        .map(|row| match conflict_columns {
+            // An upsert may skip or replace a row, so its count isn't checked
+            Some(conflict_columns) => {
+                let values: Vec<String> = row
+                    .iter()
+                    .map(|v| quoting::sql_literal(Some(dialect), v))
+                    .collect();
+                Ok(TxStep {
+                    capture: None,
+                    statement: edits::upsert_values(
+                        dialect,
+                        table,
+                        &parsed.columns,
+                        conflict_columns,
+                        &values,
+                    )?,
+                    expect_rows: None,
+                })
+            }
+            None => {
+                let row: Map<String, Value> = parsed.columns.iter().cloned().zip(row).collect();
+                Ok(TxStep {
+                    capture: None,
+                    statement: edits::insert_statement(dialect, table, &row),
+                    expect_rows: Some(1),
+                })
+            }
+        })
+        .collect::<Result<Vec<TxStep>, String>>()?;
+    let work = db::execute_steps_in_transaction(client, &steps, *nested);
+    let results = auditor.run_steps(&steps, work).await?;
+    // MySQL counts a replaced row twice
+    Ok(PasteImportResult {
+        preview: parsed,
+        inserted: Some(results.iter().map(|r| r.rows_affected.min(1)).sum()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn declared_types_come_from_the_column() {
+        assert_eq!(declared_type("character varying"), Some(InferredType::Text));
+        assert_eq!(declared_type("varchar(20)"), Some(InferredType::Text));
+        assert_eq!(
+            declared_type("int(10) unsigned"),
+            Some(InferredType::Integer)
+        );
+        assert_eq!(declared_type("tinyint(1)"), Some(InferredType::Boolean));
+        assert_eq!(declared_type("interval"), None);
+        assert_eq!(
+            declared_type("timestamp with time zone"),
+            Some(InferredType::Timestamp)
+        );
+    }
+
+    #[test]
+    fn text_columns_keep_leading_zeros() {
+        assert_eq!(cell_value("007", InferredType::Text), json!("007"));
+        assert_eq!(cell_value("007", InferredType::Integer), json!(7));
+        assert_eq!(cell_value("1", InferredType::Boolean), json!(true));
+    }
+}
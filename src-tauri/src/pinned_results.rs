@@ -0,0 +1,95 @@
+// Result snapshots pinned with a note, kept as evidence of an investigation
+// across restarts. Rows are stored as the grid received them.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+use crate::storage;
+
+pub const PINNED_RESULTS_FILE: &str = "pinned_results.json";
+
+// Rows kept per pin; the rest are dropped and the pin marked truncated
+const MAX_PINNED_ROWS: usize = 10_000;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewPinnedResult {
+    pub sql: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+    #[serde(default)]
+    pub note: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PinnedResult {
+    pub id: String,
+    pub connection: String,
+    pub sql: String,
+    pub note: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+    pub truncated: bool,
+    pub pinned_at: DateTime<Utc>,
+}
+
+fn load(path: &Path) -> Result<Vec<PinnedResult>, String> {
+    storage::read_json(path)
+}
+
+pub fn pin(path: &Path, connection: &str, result: NewPinnedResult) -> Result<PinnedResult, String> {
+    if let Some(row) = result.rows.iter().find(|r| r.len() != result.columns.len()) {
+        return Err(format!(
+            "A row has {} values for {} columns",
+            row.len(),
+            result.columns.len()
+        ));
+    }
+    let mut rows = result.rows;
+    let truncated = rows.len() > MAX_PINNED_ROWS;
+    rows.truncate(MAX_PINNED_ROWS);
+    let pinned = PinnedResult {
+        id: uuid::Uuid::new_v4().to_string(),
+        connection: connection.to_string(),
+        sql: result.sql,
+        note: result.note,
+        columns: result.columns,
+        rows,
+        truncated,
+        pinned_at: Utc::now(),
+    };
+    let mut pins = load(path)?;
+    pins.push(pinned.clone());
+    storage::write_json(path, &pins)?;
+    Ok(pinned)
+}
+
+// Newest first; all connections when `connection` is None
+pub fn list(path: &Path, connection: Option<&str>) -> Result<Vec<PinnedResult>, String> {
+    let mut pins: Vec<PinnedResult> = load(path)?
+        .into_iter()
+        .filter(|p| connection.map_or(true, |c| p.connection == c))
+        .collect();
+    pins.sort_by_key(|p| std::cmp::Reverse(p.pinned_at));
+    Ok(pins)
+}
+
+pub fn set_note(path: &Path, id: &str, note: String) -> Result<(), String> {
+    let mut pins = load(path)?;
+    let pin = pins
+        .iter_mut()
+        .find(|p| p.id == id)
+        .ok_or("Pinned result not found")?;
+    pin.note = note;
+    storage::write_json(path, &pins)
+}
+
+pub fn delete(path: &Path, id: &str) -> Result<(), String> {
+    let mut pins = load(path)?;
+    let before = pins.len();
+    pins.retain(|p| p.id != id);
+    if pins.len() == before {
+        return Err("Pinned result not found".to_string());
+    }
+    storage::write_json(path, &pins)
+}
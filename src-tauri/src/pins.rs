@@ -0,0 +1,65 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::storage;
+
+pub const PINS_FILE: &str = "pins.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PinKind {
+    Table,
+    View,
+    Collection,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PinnedObject {
+    pub schema: Option<String>, // database for Mongo collections
+    pub name: String,
+    pub kind: PinKind,
+    #[serde(default = "Utc::now")]
+    pub pinned_at: DateTime<Utc>,
+}
+
+impl PinnedObject {
+    fn same_object(&self, other: &PinnedObject) -> bool {
+        self.kind == other.kind && self.schema == other.schema && self.name == other.name
+    }
+}
+
+// Pinned objects by connection name, in the order they were pinned
+type Pins = HashMap<String, Vec<PinnedObject>>;
+
+// Pinning an object that's already pinned leaves it where it is
+pub fn pin(path: &Path, connection: &str, object: PinnedObject) -> Result<(), String> {
+    let mut all: Pins = storage::read_json(path)?;
+    let pins = all.entry(connection.to_string()).or_default();
+    if pins.iter().any(|p| p.same_object(&object)) {
+        return Ok(());
+    }
+    pins.push(PinnedObject {
+        pinned_at: Utc::now(),
+        ..object
+    });
+    storage::write_json(path, &all)
+}
+
+pub fn unpin(path: &Path, connection: &str, object: &PinnedObject) -> Result<(), String> {
+    let mut all: Pins = storage::read_json(path)?;
+    let Some(pins) = all.get_mut(connection) else {
+        return Ok(());
+    };
+    pins.retain(|p| !p.same_object(object));
+    if pins.is_empty() {
+        all.remove(connection);
+    }
+    storage::write_json(path, &all)
+}
+
+pub fn list(path: &Path, connection: &str) -> Result<Vec<PinnedObject>, String> {
+    let mut all: Pins = storage::read_json(path)?;
+    Ok(all.remove(connection).unwrap_or_default())
+}
@@ -0,0 +1,271 @@
+// App profiles: which kinds of operations the backend accepts, checked in the
+// command handlers. A guard rail against accidents, not an access control
+// system; the profile is switched in settings like everything else.
+use serde::{Deserialize, Serialize};
+
+use crate::db::DbClient;
+use crate::mongo;
+use crate::redis_tools;
+use crate::sqltext;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Profile {
+    #[default]
+    Dba,
+    Analyst,
+    ReadOnly,
+}
+
+const MONGO_READS: &[&str] = &[
+    "aggregate",
+    "buildInfo",
+    "collStats",
+    "connectionStatus",
+    "count",
+    "dataSize",
+    "dbStats",
+    "distinct",
+    "explain",
+    "find",
+    "getMore",
+    "hello",
+    "isMaster",
+    "listCollections",
+    "listDatabases",
+    "listIndexes",
+    "ping",
+    "serverStatus",
+];
+const MONGO_WRITES: &[&str] = &["delete", "findAndModify", "insert", "update"];
+const MONGO_DDL: &[&str] = &[
+    "collMod",
+    "create",
+    "createIndexes",
+    "createRole",
+    "createUser",
+    "drop",
+    "dropDatabase",
+    "dropIndexes",
+    "dropRole",
+    "dropUser",
+    "grantRolesToUser",
+    "renameCollection",
+    "revokeRolesFromUser",
+    "updateUser",
+];
+
+const REDIS_READS: &[&str] = &[
+    "BITCOUNT",
+    "DBSIZE",
+    "DUMP",
+    "ECHO",
+    "EXISTS",
+    "GEODIST",
+    "GEOPOS",
+    "GEOSEARCH",
+    "GET",
+    "GETBIT",
+    "GETRANGE",
+    "HEXISTS",
+    "HGET",
+    "HGETALL",
+    "HKEYS",
+    "HLEN",
+    "HMGET",
+    "HSCAN",
+    "HSTRLEN",
+    "HVALS",
+    "INFO",
+    "KEYS",
+    "LINDEX",
+    "LLEN",
+    "LPOS",
+    "LRANGE",
+    "MGET",
+    "PFCOUNT",
+    "PING",
+    "PTTL",
+    "RANDOMKEY",
+    "SCAN",
+    "SCARD",
+    "SDIFF",
+    "SINTER",
+    "SISMEMBER",
+    "SMEMBERS",
+    "SMISMEMBER",
+    "SRANDMEMBER",
+    "SSCAN",
+    "STRLEN",
+    "SUNION",
+    "TIME",
+    "TTL",
+    "TYPE",
+    "XINFO",
+    "XLEN",
+    "XRANGE",
+    "XREVRANGE",
+    "ZCARD",
+    "ZCOUNT",
+    "ZRANGE",
+    "ZRANGEBYSCORE",
+    "ZRANK",
+    "ZREVRANGE",
+    "ZREVRANK",
+    "ZSCAN",
+    "ZSCORE",
+];
+const REDIS_WRITES: &[&str] = &[
+    "APPEND",
+    "COPY",
+    "DECR",
+    "DECRBY",
+    "DEL",
+    "EXPIRE",
+    "EXPIREAT",
+    "GEOADD",
+    "GETDEL",
+    "GETEX",
+    "GETSET",
+    "HDEL",
+    "HINCRBY",
+    "HINCRBYFLOAT",
+    "HMSET",
+    "HSET",
+    "HSETNX",
+    "INCR",
+    "INCRBY",
+    "INCRBYFLOAT",
+    "LINSERT",
+    "LMOVE",
+    "LPOP",
+    "LPUSH",
+    "LREM",
+    "LSET",
+    "LTRIM",
+    "MSET",
+    "MSETNX",
+    "PERSIST",
+    "PEXPIRE",
+    "PEXPIREAT",
+    "PFADD",
+    "PSETEX",
+    "RENAME",
+    "RENAMENX",
+    "RESTORE",
+    "RPOP",
+    "RPUSH",
+    "SADD",
+    "SDIFFSTORE",
+    "SET",
+    "SETBIT",
+    "SETEX",
+    "SETNX",
+    "SETRANGE",
+    "SINTERSTORE",
+    "SMOVE",
+    "SPOP",
+    "SREM",
+    "SUNIONSTORE",
+    "UNLINK",
+    "XADD",
+    "XDEL",
+    "XTRIM",
+    "ZADD",
+    "ZINCRBY",
+    "ZPOPMAX",
+    "ZPOPMIN",
+    "ZREM",
+    "ZREMRANGEBYRANK",
+    "ZREMRANGEBYSCORE",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Read,
+    Write, // data changes
+    Ddl,   // schema and permission changes
+    Admin, // server-wide actions such as resetting statistics
+}
+
+impl Operation {
+    pub fn of_sql(sql: &str) -> Operation {
+        if sqltext::is_ddl(sql) {
+            Operation::Ddl
+        } else if sqltext::is_read_query(sql) {
+            Operation::Read
+        } else {
+            Operation::Write
+        }
+    }
+
+    // SQL by its statement kind; MongoDB and Redis commands by name. Commands
+    // not known to read or write data count as administration.
+    pub fn of_statement(client: &DbClient, text: &str) -> Operation {
+        match client {
+            DbClient::Mongo(_) => Operation::of_mongo_command(text),
+            DbClient::Redis(_) => Operation::of_redis_command(text),
+            _ => Operation::of_sql(text),
+        }
+    }
+
+    fn of_mongo_command(text: &str) -> Operation {
+        let Some(name) = mongo::command_name(text) else {
+            return Operation::Admin;
+        };
+        match name.as_str() {
+            "aggregate" if mongo::command_writes(text) => Operation::Write,
+            name if MONGO_READS.contains(&name) => Operation::Read,
+            name if MONGO_WRITES.contains(&name) => Operation::Write,
+            name if MONGO_DDL.contains(&name) => Operation::Ddl,
+            _ => Operation::Admin,
+        }
+    }
+
+    fn of_redis_command(text: &str) -> Operation {
+        let name = redis_tools::command_args(text)
+            .ok()
+            .and_then(|args| args.into_iter().next())
+            .map(|name| name.to_uppercase());
+        match name.as_deref() {
+            Some(name) if REDIS_READS.contains(&name) => Operation::Read,
+            Some(name) if REDIS_WRITES.contains(&name) => Operation::Write,
+            _ => Operation::Admin,
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Operation::Read => "reading data",
+            Operation::Write => "changing data",
+            Operation::Ddl => "schema changes",
+            Operation::Admin => "server administration",
+        }
+    }
+}
+
+impl Profile {
+    pub fn allows(self, operation: Operation) -> bool {
+        match self {
+            Profile::Dba => true,
+            Profile::Analyst => matches!(operation, Operation::Read | Operation::Write),
+            Profile::ReadOnly => operation == Operation::Read,
+        }
+    }
+
+    pub fn require(self, operation: Operation) -> Result<(), String> {
+        if self.allows(operation) {
+            return Ok(());
+        }
+        let name = match self {
+            Profile::Dba => "DBA",
+            Profile::Analyst => "Analyst",
+            Profile::ReadOnly => "Read-only",
+        };
+        Err(format!(
+            "The {} profile does not allow {}",
+            name,
+            operation.describe()
+        ))
+    }
+}
@@ -0,0 +1,115 @@
+// Identifier and literal quoting shared by every piece of generated SQL.
+// Anything user-supplied that ends up in SQL text goes through here.
+use serde_json::Value;
+
+use crate::db::Dialect;
+
+// Quote an identifier for the given dialect, escaping embedded quote characters
+pub fn quote_ident(dialect: Dialect, ident: &str) -> String {
+    match dialect {
+        Dialect::Postgres => quote_ansi_ident(ident),
+        Dialect::Mysql => format!("`{}`", ident.replace('`', "``")),
+        Dialect::Mssql => format!("[{}]", ident.replace(']', "]]")),
+    }
+}
+
+// Standard SQL double-quoted identifier, for output not tied to one database
+pub fn quote_ansi_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+// `schema.name`, or just `name` when there is no schema
+pub fn qualified(dialect: Dialect, schema: Option<&str>, name: &str) -> String {
+    match schema {
+        Some(schema) if !schema.is_empty() => format!(
+            "{}.{}",
+            quote_ident(dialect, schema),
+            quote_ident(dialect, name)
+        ),
+        _ => quote_ident(dialect, name),
+    }
+}
+
+// String literal. MySQL treats backslashes as escapes by default, so they are
+// doubled there; MSSQL gets an N'' literal so non-Latin text survives.
+pub fn quote_text(dialect: Option<Dialect>, text: &str) -> String {
+    let escaped = text.replace('\'', "''");
+    match dialect {
+        Some(Dialect::Mysql) => format!("'{}'", escaped.replace('\\', "\\\\").replace('\0', "\\0")),
+        Some(Dialect::Mssql) => format!("N'{}'", escaped),
+        _ => format!("'{}'", escaped),
+    }
+}
+
+// Render a JSON value as an SQL literal; `None` produces portable ANSI literals
+pub fn sql_literal(dialect: Option<Dialect>, value: &Value) -> String {
+    match value {
+        Value::Null => "NULL".to_string(),
+        Value::String(s) => quote_text(dialect, s),
+        Value::Bool(b) => match (dialect, b) {
+            (Some(Dialect::Mssql), true) => "1".to_string(),
+            (Some(Dialect::Mssql), false) => "0".to_string(),
+            (_, true) => "TRUE".to_string(),
+            (_, false) => "FALSE".to_string(),
+        },
+        Value::Number(n) => n.to_string(),
+        _ => quote_text(dialect, &value.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn quotes_identifiers_per_dialect() {
+        assert_eq!(quote_ident(Dialect::Postgres, "order"), "\"order\"");
+        assert_eq!(quote_ident(Dialect::Mysql, "order"), "`order`");
+        assert_eq!(quote_ident(Dialect::Mssql, "order"), "[order]");
+    }
+
+    #[test]
+    fn escapes_embedded_quote_characters() {
+        assert_eq!(quote_ident(Dialect::Postgres, "a\"b"), "\"a\"\"b\"");
+        assert_eq!(quote_ident(Dialect::Mysql, "a`b"), "`a``b`");
+        assert_eq!(quote_ident(Dialect::Mssql, "a]b"), "[a]]b]");
+        // Only the closing character needs escaping
+        assert_eq!(quote_ident(Dialect::Postgres, "a`[]b"), "\"a`[]b\"");
+        assert_eq!(quote_ident(Dialect::Mysql, "a\"[]b"), "`a\"[]b`");
+        assert_eq!(quote_ident(Dialect::Mssql, "a[\"`b"), "[a[\"`b]");
+    }
+
+    #[test]
+    fn qualifies_with_a_schema() {
+        assert_eq!(
+            qualified(Dialect::Mssql, Some("dbo"), "t]x"),
+            "[dbo].[t]]x]"
+        );
+        assert_eq!(qualified(Dialect::Postgres, Some(""), "t"), "\"t\"");
+        assert_eq!(qualified(Dialect::Mysql, None, "t"), "`t`");
+    }
+
+    #[test]
+    fn escapes_string_literals_per_dialect() {
+        let text = json!("it's a\\b");
+        assert_eq!(sql_literal(Some(Dialect::Postgres), &text), "'it''s a\\b'");
+        assert_eq!(sql_literal(Some(Dialect::Mysql), &text), "'it''s a\\\\b'");
+        assert_eq!(sql_literal(Some(Dialect::Mssql), &text), "N'it''s a\\b'");
+        assert_eq!(sql_literal(None, &text), "'it''s a\\b'");
+        assert_eq!(sql_literal(Some(Dialect::Mysql), &json!("a\0b")), "'a\\0b'");
+    }
+
+    #[test]
+    fn renders_other_values_as_literals() {
+        assert_eq!(sql_literal(Some(Dialect::Postgres), &Value::Null), "NULL");
+        assert_eq!(sql_literal(Some(Dialect::Mysql), &json!(true)), "TRUE");
+        assert_eq!(sql_literal(Some(Dialect::Mssql), &json!(true)), "1");
+        assert_eq!(sql_literal(Some(Dialect::Mssql), &json!(false)), "0");
+        assert_eq!(sql_literal(None, &json!(-1.5)), "-1.5");
+        assert_eq!(
+            sql_literal(Some(Dialect::Postgres), &json!({"a": "b'c"})),
+            "'{\"a\":\"b''c\"}'"
+        );
+    }
+}
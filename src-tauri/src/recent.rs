@@ -0,0 +1,78 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::sqltext;
+use crate::storage;
+
+pub const RECENT_TABLES_FILE: &str = "recent_tables.json";
+
+// Per connection; the least recently used entries are dropped beyond this
+const MAX_RECENT_TABLES: usize = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentTable {
+    pub schema: Option<String>, // as written in the query; None when unqualified
+    pub table: String,
+    pub last_used: DateTime<Utc>,
+    pub uses: u64,
+}
+
+type RecentTables = HashMap<String, Vec<RecentTable>>;
+
+// Notes the tables referenced by `sql` as used just now
+pub fn record(path: &Path, connection: &str, sql: &str) -> Result<(), String> {
+    let refs = sqltext::table_refs(sql);
+    if refs.is_empty() {
+        return Ok(());
+    }
+    let mut all: RecentTables = storage::read_json(path)?;
+    let tables = all.entry(connection.to_string()).or_default();
+    let now = Utc::now();
+    for (schema, table) in refs {
+        // Identifiers compare case-insensitively here, which is right for the
+        // unquoted names most queries use
+        let existing = tables.iter_mut().find(|t| {
+            t.table.eq_ignore_ascii_case(&table)
+                && t.schema.as_deref().map(str::to_lowercase)
+                    == schema.as_deref().map(str::to_lowercase)
+        });
+        match existing {
+            Some(entry) => {
+                entry.last_used = now;
+                entry.uses += 1;
+            }
+            None => tables.push(RecentTable {
+                schema,
+                table,
+                last_used: now,
+                uses: 1,
+            }),
+        }
+    }
+    tables.sort_by_key(|t| std::cmp::Reverse(t.last_used));
+    tables.truncate(MAX_RECENT_TABLES);
+    storage::write_json(path, &all)
+}
+
+// Most recently used first
+pub fn list(
+    path: &Path,
+    connection: &str,
+    limit: Option<usize>,
+) -> Result<Vec<RecentTable>, String> {
+    let mut all: RecentTables = storage::read_json(path)?;
+    let mut tables = all.remove(connection).unwrap_or_default();
+    tables.sort_by_key(|t| std::cmp::Reverse(t.last_used));
+    tables.truncate(limit.unwrap_or(MAX_RECENT_TABLES));
+    Ok(tables)
+}
+
+pub fn clear(path: &Path, connection: &str) -> Result<(), String> {
+    let mut all: RecentTables = storage::read_json(path)?;
+    if all.remove(connection).is_some() {
+        storage::write_json(path, &all)?;
+    }
+    Ok(())
+}
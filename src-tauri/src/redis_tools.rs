@@ -0,0 +1,670 @@
+use redis::aio::MultiplexedConnection;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::db::{ColumnMeta, DbClient, QueryResponse};
+use crate::sqltext::StatementKind;
+use crate::values::Cell;
+
+const SCAN_COUNT: usize = 1000;
+const TOP_KEYS: usize = 20;
+
+pub async fn connection(client: &DbClient) -> Result<MultiplexedConnection, String> {
+    match client {
+        DbClient::Redis(client) => client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|e| e.to_string()),
+        _ => Err("This operation is only supported for Redis".to_string()),
+    }
+}
+
+// One SCAN step: the next cursor (0 when done) and the keys it returned
+pub async fn scan_page(
+    con: &mut MultiplexedConnection,
+    cursor: u64,
+    pattern: &str,
+    count: usize,
+) -> Result<(u64, Vec<String>), String> {
+    redis::cmd("SCAN")
+        .arg(cursor)
+        .arg("MATCH")
+        .arg(pattern)
+        .arg("COUNT")
+        .arg(count)
+        .query_async(con)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[derive(Serialize)]
+pub struct KeyInfo {
+    pub key: String,
+    #[serde(rename = "type")]
+    pub key_type: String, // string, hash, list, set, zset, stream, ...
+    pub ttl: Option<i64>, // seconds; None when the key doesn't expire
+}
+
+#[derive(Serialize)]
+pub struct KeyPage {
+    pub cursor: u64, // pass back for the next page; 0 when the scan is complete
+    pub keys: Vec<KeyInfo>,
+}
+
+// One page of the key browser. SCAN's COUNT is a hint, so a page can hold more
+// or fewer keys than asked for, or none at all while the cursor isn't 0 yet.
+pub async fn scan_keys(
+    client: &DbClient,
+    pattern: Option<String>,
+    cursor: Option<u64>,
+    count: Option<usize>,
+) -> Result<KeyPage, String> {
+    let mut con = connection(client).await?;
+    let pattern = pattern.filter(|p| !p.is_empty()).unwrap_or("*".to_string());
+    let count = count.unwrap_or(100).clamp(1, SCAN_COUNT);
+    let (next, keys) = scan_page(&mut con, cursor.unwrap_or(0), &pattern, count).await?;
+    if keys.is_empty() {
+        return Ok(KeyPage {
+            cursor: next,
+            keys: vec![],
+        });
+    }
+
+    let mut pipe = redis::pipe();
+    for key in &keys {
+        pipe.cmd("TYPE").arg(key).cmd("TTL").arg(key);
+    }
+    let details: Vec<(String, i64)> = pipe
+        .query_async(&mut con)
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut page: Vec<KeyInfo> = keys
+        .into_iter()
+        .zip(details)
+        // Keys that expired since the SCAN report type "none" and TTL -2
+        .filter(|(_, (key_type, _))| key_type != "none")
+        .map(|(key, (key_type, ttl))| KeyInfo {
+            key,
+            key_type,
+            ttl: (ttl >= 0).then_some(ttl),
+        })
+        .collect();
+    page.sort_by(|a, b| a.key.cmp(&b.key));
+    Ok(KeyPage {
+        cursor: next,
+        keys: page,
+    })
+}
+
+#[derive(Serialize, Clone)]
+pub struct KeyUsage {
+    pub key: String,
+    pub bytes: u64,
+}
+
+#[derive(Serialize)]
+pub struct PrefixUsage {
+    pub pattern: String, // e.g. "session:*"
+    pub keys: u64,
+    pub bytes: u64,
+    pub largest: KeyUsage,
+}
+
+#[derive(Serialize)]
+pub struct MemoryReport {
+    pub sampled_keys: u64,
+    pub sampled_bytes: u64,
+    pub total_keys: u64,
+    // Sampled bytes scaled up to the whole keyspace; only meaningful for an unfiltered scan
+    pub estimated_total_bytes: Option<u64>,
+    pub prefixes: Vec<PrefixUsage>,
+    pub top_keys: Vec<KeyUsage>,
+}
+
+// Group name for a key: its first `depth` segments followed by `*`
+fn key_pattern(key: &str, separator: &str, depth: usize) -> String {
+    let segments: Vec<&str> = key.split(separator).collect();
+    if segments.len() <= depth {
+        return key.to_string();
+    }
+    format!("{}{}*", segments[..depth].join(separator), separator)
+}
+
+// Samples keys with SCAN and sizes each with MEMORY USAGE, aggregated by key prefix
+pub async fn memory_report(
+    client: &DbClient,
+    pattern: Option<String>,
+    sample_size: Option<usize>,
+    separator: Option<String>,
+    depth: Option<usize>,
+) -> Result<MemoryReport, String> {
+    let mut con = connection(client).await?;
+    let pattern = pattern.filter(|p| !p.is_empty()).unwrap_or("*".to_string());
+    let sample_size = sample_size.unwrap_or(10_000);
+    let separator = separator
+        .filter(|s| !s.is_empty())
+        .unwrap_or(":".to_string());
+    let depth = depth.unwrap_or(1).max(1);
+
+    let total_keys: u64 = redis::cmd("DBSIZE")
+        .query_async(&mut con)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut usages: Vec<KeyUsage> = Vec::new();
+    let mut cursor = 0;
+    loop {
+        let (next, keys) = scan_page(&mut con, cursor, &pattern, SCAN_COUNT).await?;
+        let keys: Vec<String> = keys.into_iter().take(sample_size - usages.len()).collect();
+        if !keys.is_empty() {
+            let mut pipe = redis::pipe();
+            for key in &keys {
+                pipe.cmd("MEMORY").arg("USAGE").arg(key);
+            }
+            // Keys that expired between SCAN and MEMORY USAGE come back as nil
+            let sizes: Vec<Option<u64>> = pipe
+                .query_async(&mut con)
+                .await
+                .map_err(|e| e.to_string())?;
+            usages.extend(
+                keys.into_iter()
+                    .zip(sizes)
+                    .filter_map(|(key, bytes)| bytes.map(|bytes| KeyUsage { key, bytes })),
+            );
+        }
+        cursor = next;
+        if cursor == 0 || usages.len() >= sample_size {
+            break;
+        }
+    }
+
+    let mut groups: HashMap<String, PrefixUsage> = HashMap::new();
+    for usage in &usages {
+        let group = groups
+            .entry(key_pattern(&usage.key, &separator, depth))
+            .or_insert_with_key(|pattern| PrefixUsage {
+                pattern: pattern.clone(),
+                keys: 0,
+                bytes: 0,
+                largest: usage.clone(),
+            });
+        group.keys += 1;
+        group.bytes += usage.bytes;
+        if usage.bytes > group.largest.bytes {
+            group.largest = usage.clone();
+        }
+    }
+    let mut prefixes: Vec<PrefixUsage> = groups.into_values().collect();
+    prefixes.sort_by_key(|p| std::cmp::Reverse(p.bytes));
+
+    let sampled_keys = usages.len() as u64;
+    let sampled_bytes: u64 = usages.iter().map(|u| u.bytes).sum();
+    let estimated_total_bytes = (pattern == "*" && sampled_keys > 0)
+        .then(|| (sampled_bytes as f64 / sampled_keys as f64 * total_keys as f64) as u64);
+
+    usages.sort_by_key(|u| std::cmp::Reverse(u.bytes));
+    usages.truncate(TOP_KEYS);
+
+    Ok(MemoryReport {
+        sampled_keys,
+        sampled_bytes,
+        total_keys,
+        estimated_total_bytes,
+        prefixes,
+        top_keys: usages,
+    })
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TtlAction {
+    Set { seconds: u64 },
+    // Remove the expiry so keys persist
+    Clear,
+    // Add to the current TTL; keys without an expiry are left alone
+    Extend { seconds: u64 },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TtlProgress {
+    pub pattern: String,
+    pub scanned: u64,
+    pub updated: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TtlBulkResult {
+    pub matched: u64,
+    pub updated: u64,
+    pub dry_run: bool,
+}
+
+// Applies `action` to every key matching `pattern`, one pipeline per SCAN batch.
+// With `dry_run` nothing is changed and only the matching keys are counted.
+pub async fn bulk_ttl(
+    client: &DbClient,
+    pattern: String,
+    action: TtlAction,
+    dry_run: bool,
+    batch_size: Option<usize>,
+    on_progress: impl Fn(&TtlProgress),
+) -> Result<TtlBulkResult, String> {
+    if pattern.is_empty() {
+        return Err("A key pattern is required".to_string());
+    }
+    let mut con = connection(client).await?;
+    let batch_size = batch_size.unwrap_or(SCAN_COUNT).max(1);
+    let mut progress = TtlProgress {
+        pattern: pattern.clone(),
+        scanned: 0,
+        updated: 0,
+    };
+
+    let mut cursor = 0;
+    loop {
+        let (next, keys) = scan_page(&mut con, cursor, &pattern, batch_size).await?;
+        progress.scanned += keys.len() as u64;
+        if !dry_run && !keys.is_empty() {
+            progress.updated += apply_ttl(&mut con, &keys, &action).await?;
+        }
+        on_progress(&progress);
+        cursor = next;
+        if cursor == 0 {
+            break;
+        }
+    }
+
+    Ok(TtlBulkResult {
+        matched: progress.scanned,
+        updated: progress.updated,
+        dry_run,
+    })
+}
+
+// Returns how many keys were changed
+async fn apply_ttl(
+    con: &mut MultiplexedConnection,
+    keys: &[String],
+    action: &TtlAction,
+) -> Result<u64, String> {
+    let mut pipe = redis::pipe();
+    match action {
+        TtlAction::Set { seconds } => {
+            for key in keys {
+                pipe.cmd("EXPIRE").arg(key).arg(*seconds);
+            }
+        }
+        TtlAction::Clear => {
+            for key in keys {
+                pipe.cmd("PERSIST").arg(key);
+            }
+        }
+        TtlAction::Extend { seconds } => {
+            let mut ttl_pipe = redis::pipe();
+            for key in keys {
+                ttl_pipe.cmd("PTTL").arg(key);
+            }
+            // -1 means no expiry, -2 that the key is already gone
+            let ttls: Vec<i64> = ttl_pipe.query_async(con).await.map_err(|e| e.to_string())?;
+            for (key, ttl) in keys.iter().zip(ttls) {
+                if ttl >= 0 {
+                    pipe.cmd("PEXPIRE")
+                        .arg(key)
+                        .arg(ttl as u64 + seconds * 1000);
+                }
+            }
+        }
+    }
+    let results: Vec<i64> = pipe.query_async(con).await.map_err(|e| e.to_string())?;
+    Ok(results.into_iter().filter(|r| *r == 1).count() as u64)
+}
+
+// Splits a command line like redis-cli does: whitespace separated, with
+// single or double quotes around arguments containing spaces
+pub fn command_args(text: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut chars = text.trim().chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut arg = String::new();
+        while let Some(&c) = chars.peek() {
+            match c {
+                '"' | '\'' => {
+                    chars.next();
+                    loop {
+                        match chars.next() {
+                            Some(q) if q == c => break,
+                            Some('\\') if c == '"' => arg.extend(chars.next()),
+                            Some(other) => arg.push(other),
+                            None => return Err("Unterminated quote in command".to_string()),
+                        }
+                    }
+                }
+                c if c.is_whitespace() => break,
+                c => {
+                    chars.next();
+                    arg.push(c);
+                }
+            }
+        }
+        args.push(arg);
+    }
+    Ok(args)
+}
+
+fn reply_text(value: &redis::Value) -> Value {
+    match value {
+        redis::Value::Nil => Value::Null,
+        redis::Value::Int(n) => Value::from(*n),
+        redis::Value::Double(n) => Value::from(*n),
+        redis::Value::Boolean(b) => Value::Bool(*b),
+        redis::Value::Okay => Value::from("OK"),
+        redis::Value::SimpleString(s) => Value::from(s.as_str()),
+        redis::Value::BulkString(bytes) => Value::from(String::from_utf8_lossy(bytes)),
+        redis::Value::Array(items) | redis::Value::Set(items) => {
+            Value::Array(items.iter().map(reply_text).collect())
+        }
+        redis::Value::Map(pairs) => Value::Array(
+            pairs
+                .iter()
+                .map(|(k, v)| Value::Array(vec![reply_text(k), reply_text(v)]))
+                .collect(),
+        ),
+        other => Value::from(format!("{:?}", other)),
+    }
+}
+
+// Elements loaded from a hash, list, set, sorted set or stream; the rest are
+// only counted
+const MAX_VALUE_ITEMS: usize = 1000;
+
+#[derive(Serialize)]
+pub struct HashField {
+    pub field: String,
+    pub value: String,
+}
+
+#[derive(Serialize)]
+pub struct ScoredMember {
+    pub member: String,
+    pub score: f64,
+}
+
+#[derive(Serialize)]
+pub struct StreamEntry {
+    pub id: String,
+    pub fields: Vec<HashField>,
+}
+
+// Non-UTF-8 bytes are shown lossily, as in the key browser
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RedisValue {
+    String { value: String },
+    Hash { fields: Vec<HashField> },
+    List { items: Vec<String> },
+    Set { members: Vec<String> },
+    Zset { members: Vec<ScoredMember> },
+    Stream { entries: Vec<StreamEntry> },
+}
+
+#[derive(Serialize)]
+pub struct KeyValue {
+    pub key: String,
+    pub ttl: Option<i64>,         // seconds; None when the key doesn't expire
+    pub encoding: Option<String>, // OBJECT ENCODING, e.g. listpack or hashtable
+    pub length: u64,              // bytes of a string, elements of anything else
+    pub truncated: bool,          // more elements than MAX_VALUE_ITEMS
+    #[serde(flatten)]
+    pub value: RedisValue,
+}
+
+fn text(value: &redis::Value) -> String {
+    match reply_text(value) {
+        Value::String(s) => s,
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+// Field/value pairs from a map reply (RESP3), an array of two-element arrays
+// or a flat array alternating between the two (RESP2)
+fn pairs(value: redis::Value) -> Vec<(redis::Value, redis::Value)> {
+    match value {
+        redis::Value::Map(pairs) => pairs,
+        redis::Value::Array(items) => {
+            let nested = !items.is_empty()
+                && items
+                    .iter()
+                    .all(|i| matches!(i, redis::Value::Array(a) if a.len() == 2));
+            if nested {
+                items
+                    .into_iter()
+                    .filter_map(|item| match item {
+                        redis::Value::Array(mut pair) => {
+                            let value = pair.pop()?;
+                            Some((pair.pop()?, value))
+                        }
+                        _ => None,
+                    })
+                    .collect()
+            } else {
+                let mut items = items.into_iter();
+                let mut pairs = Vec::new();
+                while let (Some(k), Some(v)) = (items.next(), items.next()) {
+                    pairs.push((k, v));
+                }
+                pairs
+            }
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn items(value: redis::Value) -> Vec<redis::Value> {
+    match value {
+        redis::Value::Array(items) | redis::Value::Set(items) => items,
+        _ => Vec::new(),
+    }
+}
+
+fn hash_fields(value: redis::Value) -> Vec<HashField> {
+    pairs(value)
+        .iter()
+        .map(|(field, value)| HashField {
+            field: text(field),
+            value: text(value),
+        })
+        .collect()
+}
+
+async fn query<T: redis::FromRedisValue>(
+    con: &mut MultiplexedConnection,
+    cmd: &redis::Cmd,
+) -> Result<T, String> {
+    cmd.query_async(con).await.map_err(|e| e.to_string())
+}
+
+// HSCAN or SSCAN until `limit` elements (field/value pairs counting as two)
+// were returned, for keys too big to read whole
+async fn scan_elements(
+    con: &mut MultiplexedConnection,
+    command: &str,
+    key: &str,
+    limit: usize,
+) -> Result<Vec<redis::Value>, String> {
+    let mut elements = Vec::new();
+    let mut cursor = 0u64;
+    loop {
+        let mut cmd = redis::cmd(command);
+        cmd.arg(key).arg(cursor).arg("COUNT").arg(SCAN_COUNT);
+        let (next, page): (u64, redis::Value) = query(con, &cmd).await?;
+        elements.extend(items(page));
+        cursor = next;
+        if cursor == 0 || elements.len() >= limit {
+            break;
+        }
+    }
+    elements.truncate(limit);
+    Ok(elements)
+}
+
+// A key's value read with the command for its type, with its TTL and encoding
+pub async fn get_value(client: &DbClient, key: &str) -> Result<KeyValue, String> {
+    let mut con = connection(client).await?;
+    let (key_type, ttl, encoding): (String, i64, Option<String>) = redis::pipe()
+        .cmd("TYPE")
+        .arg(key)
+        .cmd("TTL")
+        .arg(key)
+        .cmd("OBJECT")
+        .arg("ENCODING")
+        .arg(key)
+        .query_async(&mut con)
+        .await
+        .map_err(|e| e.to_string())?;
+    let limit = MAX_VALUE_ITEMS;
+    let (length, value): (u64, RedisValue) = match key_type.as_str() {
+        "none" => return Err(format!("Key {} does not exist", key)),
+        "string" => {
+            let value: redis::Value = query(&mut con, redis::cmd("GET").arg(key)).await?;
+            let value = text(&value);
+            (value.len() as u64, RedisValue::String { value })
+        }
+        "hash" => {
+            let length: u64 = query(&mut con, redis::cmd("HLEN").arg(key)).await?;
+            let reply = if length as usize <= limit {
+                query(&mut con, redis::cmd("HGETALL").arg(key)).await?
+            } else {
+                redis::Value::Array(scan_elements(&mut con, "HSCAN", key, limit * 2).await?)
+            };
+            let fields = hash_fields(reply);
+            (length, RedisValue::Hash { fields })
+        }
+        "list" => {
+            let length: u64 = query(&mut con, redis::cmd("LLEN").arg(key)).await?;
+            let reply = query(
+                &mut con,
+                redis::cmd("LRANGE").arg(key).arg(0).arg(limit - 1),
+            )
+            .await?;
+            let items = items(reply).iter().map(text).collect();
+            (length, RedisValue::List { items })
+        }
+        "set" => {
+            let length: u64 = query(&mut con, redis::cmd("SCARD").arg(key)).await?;
+            let members = if length as usize <= limit {
+                items(query(&mut con, redis::cmd("SMEMBERS").arg(key)).await?)
+            } else {
+                scan_elements(&mut con, "SSCAN", key, limit).await?
+            };
+            let mut members: Vec<String> = members.iter().map(text).collect();
+            members.sort();
+            (length, RedisValue::Set { members })
+        }
+        "zset" => {
+            let length: u64 = query(&mut con, redis::cmd("ZCARD").arg(key)).await?;
+            let mut cmd = redis::cmd("ZRANGE");
+            cmd.arg(key).arg(0).arg(limit - 1).arg("WITHSCORES");
+            let reply = query(&mut con, &cmd).await?;
+            let members = pairs(reply)
+                .iter()
+                .map(|(member, score)| ScoredMember {
+                    member: text(member),
+                    score: match score {
+                        redis::Value::Double(n) => *n,
+                        other => text(other).parse().unwrap_or(f64::NAN),
+                    },
+                })
+                .collect();
+            (length, RedisValue::Zset { members })
+        }
+        "stream" => {
+            let length: u64 = query(&mut con, redis::cmd("XLEN").arg(key)).await?;
+            let mut cmd = redis::cmd("XRANGE");
+            cmd.arg(key).arg("-").arg("+").arg("COUNT").arg(limit);
+            let reply = query(&mut con, &cmd).await?;
+            let entries = items(reply)
+                .into_iter()
+                .filter_map(|entry| {
+                    let mut parts = items(entry).into_iter();
+                    let id = text(&parts.next()?);
+                    Some(StreamEntry {
+                        id,
+                        fields: hash_fields(parts.next()?),
+                    })
+                })
+                .collect();
+            (length, RedisValue::Stream { entries })
+        }
+        other => return Err(format!("Values of type {} can't be shown", other)),
+    };
+    let shown = match &value {
+        RedisValue::String { .. } => 0,
+        RedisValue::Hash { fields } => fields.len(),
+        RedisValue::List { items } => items.len(),
+        RedisValue::Set { members } => members.len(),
+        RedisValue::Zset { members } => members.len(),
+        RedisValue::Stream { entries } => entries.len(),
+    };
+    Ok(KeyValue {
+        key: key.to_string(),
+        ttl: (ttl >= 0).then_some(ttl),
+        encoding,
+        length,
+        truncated: !matches!(value, RedisValue::String { .. }) && (shown as u64) < length,
+        value,
+    })
+}
+
+const DELETE_COMMANDS: [&str; 5] = ["DEL", "UNLINK", "HDEL", "SREM", "ZREM"];
+
+// Runs one command typed in the editor; arrays come back one element per
+// row, maps one field per row, anything else as a single row
+pub async fn run_command(client: &DbClient, text: &str) -> Result<QueryResponse, String> {
+    let args = command_args(text)?;
+    let Some((name, rest)) = args.split_first() else {
+        return Err("No command given".to_string());
+    };
+    let mut con = connection(client).await?;
+    let mut cmd = redis::cmd(name);
+    for arg in rest {
+        cmd.arg(arg);
+    }
+    let reply: redis::Value = cmd.query_async(&mut con).await.map_err(|e| e.to_string())?;
+
+    let meta = |name: &str| ColumnMeta {
+        name: name.to_string(),
+        type_name: "string".to_string(),
+        timezone: None,
+        value_tag: None,
+    };
+    let cell = |value: &redis::Value| match reply_text(value) {
+        Value::Null => Cell::Null,
+        value => Cell::Value(value),
+    };
+    let (column_meta, rows): (Vec<ColumnMeta>, Vec<Vec<Cell>>) = match &reply {
+        redis::Value::Array(items) | redis::Value::Set(items) => (
+            vec![meta("value")],
+            items.iter().map(|item| vec![cell(item)]).collect(),
+        ),
+        redis::Value::Map(pairs) => (
+            vec![meta("field"), meta("value")],
+            pairs.iter().map(|(k, v)| vec![cell(k), cell(v)]).collect(),
+        ),
+        single => (vec![meta("value")], vec![vec![cell(single)]]),
+    };
+    let columns = column_meta.iter().map(|c| c.name.clone()).collect();
+
+    let (kind, affected) = match (name.to_uppercase().as_str(), &reply) {
+        (command, redis::Value::Int(n)) if DELETE_COMMANDS.contains(&command) => {
+            (StatementKind::Delete, Some(*n as u64))
+        }
+        _ => (StatementKind::Other, None),
+    };
+    Ok(QueryResponse::new(columns, rows, column_meta).counted(kind, affected))
+}
@@ -0,0 +1,166 @@
+// Single-row lookups for the grid: full rows for the detail panel and
+// click-through navigation along foreign keys
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::db::{self, DbClient, Dialect, ForeignKey, QueryOptions, QueryResponse};
+use crate::edits::{self, where_clause, RowIdentity, TableRef, CTID};
+
+// Rows reached by following one foreign key
+#[derive(Serialize)]
+pub struct RelatedRows {
+    pub constraint: String,
+    pub table: TableRef,
+    pub key: Map<String, Value>, // the WHERE used, column -> value
+    pub result: QueryResponse,
+}
+
+pub const DEFAULT_REFERENCING_LIMIT: usize = 100;
+
+fn is_table(table: &TableRef, schema: &str, name: &str) -> bool {
+    table.table == name && table.schema.as_deref().map_or(true, |s| s == schema)
+}
+
+fn dialect(client: &DbClient) -> Result<Dialect, String> {
+    client
+        .dialect()
+        .ok_or_else(|| "Foreign key navigation is not supported for this database type".to_string())
+}
+
+fn select_sql(
+    dialect: Dialect,
+    table: &TableRef,
+    key: &Map<String, Value>,
+    limit: Option<usize>,
+) -> String {
+    let table = table.qualified(dialect);
+    let filter = where_clause(dialect, key);
+    match (dialect, limit) {
+        (Dialect::Mssql, Some(limit)) => {
+            format!("SELECT TOP {} * FROM {} WHERE {}", limit, table, filter)
+        }
+        (_, Some(limit)) => format!("SELECT * FROM {} WHERE {} LIMIT {}", table, filter, limit),
+        (_, None) => format!("SELECT * FROM {} WHERE {}", table, filter),
+    }
+}
+
+// Maps `from` columns to the row's values under the `to` column names. NULL
+// in any of them means the row doesn't reference anything through this key.
+fn key_values(
+    row: &Map<String, Value>,
+    from: &[String],
+    to: &[String],
+) -> Result<Option<Map<String, Value>>, String> {
+    let mut key = Map::new();
+    for (from, to) in from.iter().zip(to) {
+        match row.get(from) {
+            None => return Err(format!("Row is missing column {}", from)),
+            Some(Value::Null) => return Ok(None),
+            Some(value) => {
+                key.insert(to.clone(), value.clone());
+            }
+        }
+    }
+    Ok(Some(key))
+}
+
+// The row referenced by `column` of `row` in `table`
+pub async fn resolve_foreign_row(
+    client: &DbClient,
+    table: &TableRef,
+    column: &str,
+    row: &Map<String, Value>,
+    options: &QueryOptions,
+) -> Result<RelatedRows, String> {
+    let dialect = dialect(client)?;
+    let foreign_keys = db::get_foreign_keys(client, table.schema.clone(), &table.table).await?;
+    let fk: &ForeignKey = foreign_keys
+        .iter()
+        .find(|fk| is_table(table, &fk.schema, &fk.table) && fk.columns.iter().any(|c| c == column))
+        .ok_or_else(|| format!("Column {} is not part of a foreign key", column))?;
+
+    let key = key_values(row, &fk.columns, &fk.referenced_columns)?
+        .ok_or("The foreign key is NULL, so no row is referenced")?;
+    let target = TableRef {
+        schema: Some(fk.referenced_schema.clone()),
+        table: fk.referenced_table.clone(),
+    };
+    let result =
+        db::execute_query(client, select_sql(dialect, &target, &key, None), options).await?;
+    Ok(RelatedRows {
+        constraint: fk.name.clone(),
+        table: target,
+        key,
+        result,
+    })
+}
+
+// Rows in other tables (or this one) whose foreign keys point at `row`, one
+// entry per referencing constraint, each capped at `limit` rows
+pub async fn find_referencing_rows(
+    client: &DbClient,
+    table: &TableRef,
+    row: &Map<String, Value>,
+    limit: Option<usize>,
+    options: &QueryOptions,
+) -> Result<Vec<RelatedRows>, String> {
+    let dialect = dialect(client)?;
+    let limit = limit.unwrap_or(DEFAULT_REFERENCING_LIMIT);
+    let foreign_keys = db::get_foreign_keys(client, table.schema.clone(), &table.table).await?;
+
+    let mut related = Vec::new();
+    for fk in foreign_keys
+        .iter()
+        .filter(|fk| is_table(table, &fk.referenced_schema, &fk.referenced_table))
+    {
+        let Some(key) = key_values(row, &fk.referenced_columns, &fk.columns)? else {
+            continue;
+        };
+        let source = TableRef {
+            schema: Some(fk.schema.clone()),
+            table: fk.table.clone(),
+        };
+        let sql = select_sql(dialect, &source, &key, Some(limit));
+        related.push(RelatedRows {
+            constraint: fk.name.clone(),
+            table: source,
+            key,
+            result: db::execute_query(client, sql, options).await?,
+        });
+    }
+    Ok(related)
+}
+
+// The complete row addressed by `key` (primary key columns, or the ctid on
+// Postgres tables without one), whatever the grid's projection left out
+pub async fn get_full_row(
+    client: &DbClient,
+    table: &TableRef,
+    key: &Map<String, Value>,
+    options: &QueryOptions,
+) -> Result<QueryResponse, String> {
+    let dialect = dialect(client)?;
+    let columns = match edits::resolve_row_identity(client, table).await? {
+        RowIdentity::PrimaryKey { columns } => columns,
+        RowIdentity::Ctid => vec![CTID.to_string()],
+        RowIdentity::AllColumns => {
+            return Err("Table has no primary key to look the row up by".to_string())
+        }
+    };
+    let mut row_key = Map::new();
+    for col in columns {
+        let value = key
+            .get(&col)
+            .cloned()
+            .ok_or(format!("Missing key column: {}", col))?;
+        row_key.insert(col, value);
+    }
+
+    let result =
+        db::execute_query(client, select_sql(dialect, table, &row_key, None), options).await?;
+    match result.rows.len() {
+        0 => Err("Row not found; it may have been changed or deleted".to_string()),
+        1 => Ok(result),
+        _ => Err("Key matches more than one row".to_string()),
+    }
+}
@@ -0,0 +1,355 @@
+use chrono::{DateTime, Utc};
+use futures::future::{AbortHandle, AbortRegistration, Abortable, Aborted};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::sync::oneshot;
+
+use crate::db::{self, DbClient, QueryOptions, QueryResponse, RowBatch};
+use crate::errors::DbError;
+
+struct RunningQuery {
+    connection: String,
+    sql: String,
+    started_at: DateTime<Utc>,
+    client: DbClient,
+    backend: Option<i64>, // known once the statement has a session
+    script: bool,         // several statements; cancelling stops them all
+    abort: AbortHandle,
+}
+
+fn cancelled() -> DbError {
+    DbError::Cancelled {
+        message: "Query cancelled".to_string(),
+    }
+}
+
+// A query to run under an id the frontend chose, so it can be cancelled
+pub struct TrackedQuery {
+    pub id: String,
+    pub connection: String,
+    pub client: DbClient,
+    pub sql: String,
+    pub max_concurrent: usize, // queries running at once on the connection; 0 = no limit
+}
+
+// A query waiting for one of its connection's slots
+struct QueuedQuery {
+    id: String,
+    sql: String,
+    queued_at: DateTime<Utc>,
+    go: oneshot::Sender<QueueSlot>,
+}
+
+#[derive(Default)]
+struct ConnectionQueue {
+    running: usize,
+    waiting: Vec<QueuedQuery>, // next to run first
+}
+
+type Queues = Arc<StdMutex<HashMap<String, ConnectionQueue>>>;
+
+// A running query's place on its connection, handed to the next query in
+// line when dropped
+struct QueueSlot {
+    queues: Queues,
+    connection: String,
+}
+
+impl Drop for QueueSlot {
+    fn drop(&mut self) {
+        let next = {
+            let mut queues = self.queues.lock().unwrap();
+            let Some(queue) = queues.get_mut(&self.connection) else {
+                return;
+            };
+            if queue.waiting.is_empty() {
+                queue.running -= 1;
+                if queue.running == 0 {
+                    queues.remove(&self.connection);
+                }
+                return;
+            }
+            queue.waiting.remove(0)
+        };
+        let slot = QueueSlot {
+            queues: self.queues.clone(),
+            connection: self.connection.clone(),
+        };
+        // When the waiting query has gone away the slot comes straight back
+        // and, dropped in turn, moves on to the one after it
+        let _ = next.go.send(slot);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueuedQueryInfo {
+    pub id: String,
+    pub connection: String,
+    pub sql: String,
+    pub queued_at: DateTime<Utc>,
+    pub position: usize, // 0 runs next
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RunningQueryInfo {
+    pub id: String,
+    pub connection: String,
+    pub sql: String,
+    pub started_at: DateTime<Utc>,
+    pub backend: Option<i64>,
+}
+
+// In-flight queries by the id the frontend gave them, and those queued
+// behind the per-connection limit
+#[derive(Default)]
+pub struct RunningQueries {
+    queries: StdMutex<HashMap<String, RunningQuery>>,
+    queues: Queues,
+}
+
+impl RunningQueries {
+    pub fn list(&self, connection: Option<&str>) -> Vec<RunningQueryInfo> {
+        let queries = self.queries.lock().unwrap();
+        let mut list: Vec<RunningQueryInfo> = queries
+            .iter()
+            .filter(|(_, q)| connection.map_or(true, |c| q.connection == c))
+            .map(|(id, q)| RunningQueryInfo {
+                id: id.clone(),
+                connection: q.connection.clone(),
+                sql: q.sql.clone(),
+                started_at: q.started_at,
+                backend: q.backend,
+            })
+            .collect();
+        list.sort_by_key(|q| q.started_at);
+        list
+    }
+
+    pub fn queue(&self, connection: Option<&str>) -> Vec<QueuedQueryInfo> {
+        let queues = self.queues.lock().unwrap();
+        let mut list: Vec<QueuedQueryInfo> = queues
+            .iter()
+            .filter(|(name, _)| connection.map_or(true, |c| *name == c))
+            .flat_map(|(name, queue)| {
+                queue
+                    .waiting
+                    .iter()
+                    .enumerate()
+                    .map(move |(position, q)| QueuedQueryInfo {
+                        id: q.id.clone(),
+                        connection: name.clone(),
+                        sql: q.sql.clone(),
+                        queued_at: q.queued_at,
+                        position,
+                    })
+            })
+            .collect();
+        list.sort_by(|a, b| (&a.connection, a.position).cmp(&(&b.connection, b.position)));
+        list
+    }
+
+    // Moves a queued query to `position` in its connection's line, 0 being next
+    pub fn reprioritize(&self, id: &str, position: usize) -> Result<(), String> {
+        let mut queues = self.queues.lock().unwrap();
+        let waiting = queues
+            .values_mut()
+            .map(|q| &mut q.waiting)
+            .find(|w| w.iter().any(|q| q.id == id))
+            .ok_or("Query is not queued")?;
+        let from = waiting.iter().position(|q| q.id == id).unwrap();
+        let query = waiting.remove(from);
+        waiting.insert(position.min(waiting.len()), query);
+        Ok(())
+    }
+
+    // Drops a queued query; its run returns as cancelled
+    fn dequeue(&self, id: &str) -> bool {
+        let mut queues = self.queues.lock().unwrap();
+        for queue in queues.values_mut() {
+            if let Some(i) = queue.waiting.iter().position(|q| q.id == id) {
+                queue.waiting.remove(i);
+                return true;
+            }
+        }
+        false
+    }
+
+    // Waits while the connection already runs as many queries as allowed
+    async fn acquire(&self, query: &TrackedQuery) -> Result<QueueSlot, DbError> {
+        let waiting = {
+            let mut queues = self.queues.lock().unwrap();
+            let queued = queues
+                .values()
+                .any(|q| q.waiting.iter().any(|w| w.id == query.id));
+            if queued || self.queries.lock().unwrap().contains_key(&query.id) {
+                return Err(format!("A query with id {} is already running", query.id).into());
+            }
+            let queue = queues.entry(query.connection.clone()).or_default();
+            if query.max_concurrent == 0 || queue.running < query.max_concurrent {
+                queue.running += 1;
+                None
+            } else {
+                let (go, slot) = oneshot::channel();
+                queue.waiting.push(QueuedQuery {
+                    id: query.id.clone(),
+                    sql: query.sql.clone(),
+                    queued_at: Utc::now(),
+                    go,
+                });
+                Some(slot)
+            }
+        };
+        match waiting {
+            Some(slot) => slot.await.map_err(|_| cancelled()),
+            None => Ok(QueueSlot {
+                queues: self.queues.clone(),
+                connection: query.connection.clone(),
+            }),
+        }
+    }
+
+    fn set_backend(&self, id: &str, backend: i64) {
+        if let Some(query) = self.queries.lock().unwrap().get_mut(id) {
+            query.backend = Some(backend);
+        }
+    }
+
+    fn start(&self, query: &TrackedQuery, script: bool) -> Result<AbortRegistration, String> {
+        let (abort, registration) = AbortHandle::new_pair();
+        let mut queries = self.queries.lock().unwrap();
+        if queries.contains_key(&query.id) {
+            return Err(format!("A query with id {} is already running", query.id));
+        }
+        queries.insert(
+            query.id.clone(),
+            RunningQuery {
+                connection: query.connection.clone(),
+                sql: query.sql.clone(),
+                started_at: Utc::now(),
+                client: query.client.clone(),
+                backend: None,
+                script,
+                abort,
+            },
+        );
+        Ok(registration)
+    }
+
+    fn finish<T>(
+        &self,
+        id: &str,
+        result: Result<Result<T, DbError>, Aborted>,
+    ) -> Result<T, DbError> {
+        self.queries.lock().unwrap().remove(id);
+        result.unwrap_or_else(|_| Err(cancelled()))
+    }
+
+    // Runs the query until it finishes or is cancelled
+    pub async fn run(
+        &self,
+        query: TrackedQuery,
+        options: &QueryOptions,
+    ) -> Result<QueryResponse, DbError> {
+        let _slot = self.acquire(&query).await?;
+        let registration = self.start(&query, false)?;
+        let report = |backend| self.set_backend(&query.id, backend);
+        let result = Abortable::new(
+            db::execute_query_reporting(&query.client, query.sql.clone(), options, Some(&report)),
+            registration,
+        )
+        .await;
+        self.finish(&query.id, result)
+    }
+
+    // Streaming counterpart of `run`; returns the number of rows emitted
+    pub async fn stream(
+        &self,
+        query: TrackedQuery,
+        options: &QueryOptions,
+        batch_size: usize,
+        emit: &(dyn Fn(RowBatch) -> Result<(), String> + Sync),
+    ) -> Result<u64, DbError> {
+        let _slot = self.acquire(&query).await?;
+        let registration = self.start(&query, false)?;
+        let report = |backend| self.set_backend(&query.id, backend);
+        let result = Abortable::new(
+            db::stream_query(
+                &query.client,
+                query.sql.clone(),
+                options,
+                batch_size,
+                Some(&report),
+                emit,
+            ),
+            registration,
+        )
+        .await;
+        self.finish(&query.id, result)
+    }
+
+    // Runs other work under the query's id, e.g. a MongoDB aggregation, once
+    // it has a slot. Cancelling drops the work.
+    pub async fn run_with<T>(
+        &self,
+        query: TrackedQuery,
+        work: impl Future<Output = Result<T, String>>,
+    ) -> Result<T, DbError> {
+        let _slot = self.acquire(&query).await?;
+        let registration = self.start(&query, false)?;
+        let result =
+            Abortable::new(async { work.await.map_err(DbError::from) }, registration).await;
+        self.finish(&query.id, result)
+    }
+
+    // Runs several statements, e.g. a SQL file, on the one session `backend`
+    // of the query's client, until they finish or are cancelled. Unlike
+    // single queries they don't wait for a slot, having a session of their own.
+    pub async fn run_script<T>(
+        &self,
+        query: TrackedQuery,
+        backend: Option<i64>,
+        work: impl Future<Output = Result<T, String>>,
+    ) -> Result<T, DbError> {
+        let registration = self.start(&query, true)?;
+        if let Some(backend) = backend {
+            self.set_backend(&query.id, backend);
+        }
+        let result =
+            Abortable::new(async { work.await.map_err(DbError::from) }, registration).await;
+        self.finish(&query.id, result)
+    }
+
+    // A queued query just leaves the queue. Otherwise asks the server to stop
+    // the statement where it can, else the local future is dropped. Either way
+    // `run` returns an error for the query.
+    pub async fn cancel(&self, id: &str) -> Result<(), String> {
+        if self.dequeue(id) {
+            return Ok(());
+        }
+        let (client, backend, script, abort) = {
+            let queries = self.queries.lock().unwrap();
+            let query = queries.get(id).ok_or("Query is not running")?;
+            let abort = query.abort.clone();
+            (query.client.clone(), query.backend, query.script, abort)
+        };
+
+        match backend.filter(|_| client.dialect().is_some()) {
+            Some(backend) => {
+                // The session has to be forgotten before the future lets go of it
+                if let DbClient::Mssql(pool) = &client {
+                    pool.forget_session(backend);
+                    abort.abort();
+                }
+                db::cancel_backend(&client, backend).await?;
+                // The statement fails; the ones after it mustn't run
+                if script {
+                    abort.abort();
+                }
+            }
+            None => abort.abort(),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+// Execution windows for automated work: a scheduled export, the app's only
+// scheduled job, only starts inside one of its windows, e.g. 00:00-06:00
+// server time, so it never runs during business hours
+use chrono::{Datelike, Duration as ChronoDuration, NaiveDateTime, NaiveTime, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+
+use crate::values::RenderZone;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExecutionWindow {
+    pub start: String, // "HH:MM"
+    pub end: String,   // "HH:MM"; at or before `start` the window runs past midnight
+    // Days the window starts on, e.g. ["Sat", "Sun"]; empty means every day
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+}
+
+fn time_of(text: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(text.trim(), "%H:%M")
+        .map_err(|_| format!("Invalid window time {:?}, expected HH:MM", text))
+}
+
+impl ExecutionWindow {
+    fn contains(&self, at: NaiveDateTime) -> Result<bool, String> {
+        let (start, end) = (time_of(&self.start)?, time_of(&self.end)?);
+        let starts_on = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        let time = at.time();
+        Ok(if start < end {
+            starts_on(at.weekday()) && time >= start && time < end
+        } else {
+            // Past midnight the window belongs to the day it started on
+            let yesterday = (at - ChronoDuration::days(1)).weekday();
+            (starts_on(at.weekday()) && time >= start) || (starts_on(yesterday) && time < end)
+        })
+    }
+}
+
+// Clocks windows can be read on
+const CLOCKS: [&str; 3] = ["server", "client", "utc"];
+
+pub fn validate(windows: &[ExecutionWindow], clock: &str) -> Result<(), String> {
+    if !CLOCKS.contains(&clock) {
+        return Err(format!(
+            "Unknown window time zone {:?}, expected \"server\", \"client\" or \"utc\"",
+            clock
+        ));
+    }
+    for window in windows {
+        time_of(&window.start)?;
+        time_of(&window.end)?;
+    }
+    Ok(())
+}
+
+// The wall clock time in `zone` right now
+pub fn now_in(zone: RenderZone) -> NaiveDateTime {
+    match zone {
+        RenderZone::Fixed(offset) => Utc::now().with_timezone(&offset).naive_local(),
+        RenderZone::Local => chrono::Local::now().naive_local(),
+    }
+}
+
+// Whether `at` falls in one of the windows; no windows means any time
+pub fn is_open(windows: &[ExecutionWindow], at: NaiveDateTime) -> Result<bool, String> {
+    if windows.is_empty() {
+        return Ok(true);
+    }
+    for window in windows {
+        if window.contains(at)? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
@@ -0,0 +1,432 @@
+// Recurring exports: a saved query, format and destination, run in the
+// background and reported to the frontend as "scheduled-export" events
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Mutex as StdMutex, OnceLock};
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager};
+
+use crate::audit::Auditor;
+use crate::db::{self, DatabaseState, DbClient, Dialect, QueryOptions, TimezoneMode};
+use crate::export_columns::ColumnShaper;
+use crate::exports::{self, ExportedFile};
+use crate::notifications::{self, JobKind, Notification};
+use crate::profiles::Operation;
+use crate::quoting::{quote_ident, sql_literal};
+use crate::schedule_windows::{self, ExecutionWindow};
+use crate::settings::SettingsState;
+use crate::storage;
+use crate::values::RenderZone;
+
+pub const SCHEDULED_EXPORTS_FILE: &str = "scheduled_exports.json";
+
+// How often due exports are looked for
+const CHECK_INTERVAL_SECS: u64 = 30;
+// How long a server's clock offset is trusted before it is read again, so a
+// closed connection isn't opened on every check just for its clock
+const SERVER_ZONE_TTL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduledExport {
+    #[serde(default)]
+    pub id: String, // empty when creating
+    pub name: String,
+    pub connection: String, // saved connection name
+    pub sql: String,
+    #[serde(default)]
+    pub format: Option<String>,
+    // Where the file goes, as for export_data: a fixed path or a template
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub template: Option<String>,
+    pub interval_minutes: u32,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+    // Incremental mode: each run only exports rows whose value in this result
+    // column is past the watermark, which then moves to the highest one seen
+    #[serde(default)]
+    pub watermark_column: Option<String>,
+    #[serde(default)]
+    pub watermark: Option<Value>, // a starting point when creating
+    // Times the export may start in; empty means any time
+    #[serde(default)]
+    pub windows: Vec<ExecutionWindow>,
+    // Clock the windows are read on: "server" (the default), "client" or "utc"
+    #[serde(default = "server_clock")]
+    pub window_timezone: String,
+    #[serde(default)]
+    pub last_run: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_path: Option<String>,
+    #[serde(default)]
+    pub last_error: Option<String>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn server_clock() -> String {
+    "server".to_string()
+}
+
+impl ScheduledExport {
+    fn is_due(&self, now: DateTime<Utc>) -> bool {
+        let interval = ChronoDuration::minutes(self.interval_minutes.max(1) as i64);
+        self.enabled && self.last_run.map_or(true, |last| now - last >= interval)
+    }
+}
+
+#[derive(Clone, Serialize)]
+pub struct ScheduledExportEvent {
+    pub id: String,
+    pub name: String,
+    pub connection: String,
+    pub path: Option<String>,
+    pub rows: Option<u64>,
+    pub error: Option<String>,
+}
+
+pub fn list(path: &Path) -> Result<Vec<ScheduledExport>, String> {
+    storage::read_json(path)
+}
+
+// Creates the export when its id is empty or unknown, otherwise replaces its
+// definition; the run history is kept either way
+pub fn save(path: &Path, mut export: ScheduledExport) -> Result<ScheduledExport, String> {
+    if export.name.trim().is_empty() {
+        return Err("Scheduled export name is required".to_string());
+    }
+    if export.interval_minutes == 0 {
+        return Err("The interval must be at least one minute".to_string());
+    }
+    if export.path.is_none() && export.template.is_none() {
+        return Err("Either a path or an export template is required".to_string());
+    }
+    if export
+        .watermark_column
+        .as_ref()
+        .is_some_and(|c| c.trim().is_empty())
+    {
+        return Err("The watermark column name is empty".to_string());
+    }
+    schedule_windows::validate(&export.windows, &export.window_timezone)?;
+    let mut exports = list(path)?;
+    match exports
+        .iter_mut()
+        .find(|e| !export.id.is_empty() && e.id == export.id)
+    {
+        Some(existing) => {
+            export.last_run = existing.last_run;
+            export.last_path = existing.last_path.clone();
+            export.last_error = existing.last_error.clone();
+            if existing.watermark_column == export.watermark_column {
+                export.watermark = existing.watermark.clone();
+            }
+            *existing = export.clone();
+        }
+        None => {
+            if export.id.is_empty() {
+                export.id = uuid::Uuid::new_v4().to_string();
+            }
+            export.last_run = None;
+            export.last_path = None;
+            export.last_error = None;
+            exports.push(export.clone());
+        }
+    }
+    storage::write_json(path, &exports)?;
+    Ok(export)
+}
+
+pub fn delete(path: &Path, id: &str) -> Result<(), String> {
+    let mut exports = list(path)?;
+    let before = exports.len();
+    exports.retain(|e| e.id != id);
+    if exports.len() == before {
+        return Err("Scheduled export not found".to_string());
+    }
+    storage::write_json(path, &exports)
+}
+
+// Moves the watermark of an incremental export, e.g. back to None to export
+// everything again on the next run
+pub fn set_watermark(path: &Path, id: &str, watermark: Option<Value>) -> Result<(), String> {
+    let mut exports = list(path)?;
+    let export = exports
+        .iter_mut()
+        .find(|e| e.id == id)
+        .ok_or("Scheduled export not found")?;
+    if export.watermark_column.is_none() {
+        return Err(format!("{} is not an incremental export", export.name));
+    }
+    export.watermark = watermark;
+    storage::write_json(path, &exports)
+}
+
+fn record(
+    path: &Path,
+    id: &str,
+    at: DateTime<Utc>,
+    outcome: &Result<(ExportedFile, Option<Value>), String>,
+) -> Result<(), String> {
+    let mut exports = list(path)?;
+    let Some(export) = exports.iter_mut().find(|e| e.id == id) else {
+        return Ok(()); // deleted while it ran
+    };
+    export.last_run = Some(at);
+    match outcome {
+        Ok((file, watermark)) => {
+            export.last_path = Some(file.path.clone());
+            export.last_error = None;
+            if watermark.is_some() {
+                export.watermark = watermark.clone();
+            }
+        }
+        Err(e) => export.last_error = Some(e.clone()),
+    }
+    storage::write_json(path, &exports)
+}
+
+// The open connection when there is one (without counting as a use), else a
+// temporary one from the saved connection
+async fn client_for(app: &tauri::AppHandle, name: &str) -> Result<DbClient, String> {
+    if let Ok(client) = app.state::<DatabaseState>().peek(name) {
+        return Ok(client);
+    }
+    let saved = crate::read_saved_connections(app)?;
+    let connection = saved
+        .iter()
+        .find(|c| c.name == name)
+        .ok_or_else(|| format!("Connection '{}' not found", name))?;
+    db::create_client_with_tls(&connection.url, &connection.tls).await
+}
+
+// Past `low` and up to `high`, so rows added while the export runs are left
+// for the next run rather than skipped
+fn watermark_filter(
+    dialect: Dialect,
+    column: &str,
+    low: Option<&Value>,
+    high: Option<&Value>,
+) -> String {
+    let Some(high) = high else {
+        return "1 = 0".to_string(); // nothing new
+    };
+    let column = quote_ident(dialect, column);
+    let upto = format!("{} <= {}", column, sql_literal(Some(dialect), high));
+    match low {
+        Some(low) => format!(
+            "{} > {} AND {}",
+            column,
+            sql_literal(Some(dialect), low),
+            upto
+        ),
+        None => upto,
+    }
+}
+
+// The export's query narrowed to the rows past the watermark, with the
+// watermark those rows move it to. SQL Server rejects an ORDER BY inside the
+// wrapped query; the rows come out in watermark order regardless.
+async fn incremental(
+    client: &DbClient,
+    export: &ScheduledExport,
+    column: &str,
+) -> Result<(String, Option<Value>), String> {
+    let dialect = client
+        .dialect()
+        .ok_or("Incremental exports are only supported for SQL databases")?;
+    let sql = export.sql.trim().trim_end_matches(';');
+    let low = export.watermark.as_ref().filter(|v| !v.is_null());
+    let quoted = quote_ident(dialect, column);
+    let mut max_sql = format!("SELECT MAX({}) FROM ({}) watermarked", quoted, sql);
+    if let Some(low) = low {
+        max_sql.push_str(&format!(
+            " WHERE {} > {}",
+            quoted,
+            sql_literal(Some(dialect), low)
+        ));
+    }
+    let response = db::execute_query(client, max_sql, &QueryOptions::default()).await?;
+    let high = response
+        .rows
+        .into_iter()
+        .next()
+        .and_then(|row| row.into_iter().next())
+        .map(|cell| cell.try_into_value())
+        .transpose()?
+        .filter(|v| !v.is_null());
+    let sql = format!(
+        "SELECT * FROM ({}) watermarked WHERE {} ORDER BY {}",
+        sql,
+        watermark_filter(dialect, column, low, high.as_ref()),
+        quoted
+    );
+    Ok((sql, high.or_else(|| low.cloned())))
+}
+
+async fn export(
+    app: &tauri::AppHandle,
+    export: &ScheduledExport,
+) -> Result<(ExportedFile, Option<Value>), String> {
+    let settings = app.state::<SettingsState>().get();
+    let client = client_for(app, &export.connection).await?;
+    // The profile may have changed since the export was scheduled
+    settings
+        .profile
+        .require(Operation::of_statement(&client, &export.sql))?;
+    let downloads = app.path().download_dir().map_err(|e| e.to_string())?;
+    let destination = exports::resolve(
+        &settings.export,
+        export.template.as_deref(),
+        export.path.clone(),
+        export.format.clone(),
+        &export.connection,
+        &export.sql,
+        &downloads,
+    )?;
+    let (sql, watermark) = match &export.watermark_column {
+        Some(column) => incremental(&client, export, column).await?,
+        None => (export.sql.clone(), None),
+    };
+    let options = QueryOptions::for_export(&settings);
+    let shaper = ColumnShaper::new(destination.columns)?;
+    let statement = sql.clone();
+    let work = db::export_data(
+        &client,
+        sql,
+        destination.format,
+        destination.path.clone(),
+        &destination.export,
+        &options,
+        &shaper,
+    );
+    let rows = Auditor::for_connection(app, &export.connection)
+        .run(&statement, work)
+        .await?;
+    Ok((
+        ExportedFile::written(destination.path, Some(rows))?,
+        watermark,
+    ))
+}
+
+// Runs one export now, records the outcome and reports it as an event and
+// to the notification channels
+pub async fn run(
+    app: &tauri::AppHandle,
+    scheduled: &ScheduledExport,
+) -> Result<ExportedFile, String> {
+    let started = Utc::now();
+    let outcome = export(app, scheduled).await;
+    let path = storage::data_file(app, SCHEDULED_EXPORTS_FILE)?;
+    if let Err(e) = record(&path, &scheduled.id, started, &outcome) {
+        log::warn!("Failed to record run of {}: {}", scheduled.name, e);
+    }
+    let (file, error) = match &outcome {
+        Ok((file, _)) => (Some(file), None),
+        Err(e) => (None, Some(e.clone())),
+    };
+    let event = ScheduledExportEvent {
+        id: scheduled.id.clone(),
+        name: scheduled.name.clone(),
+        connection: scheduled.connection.clone(),
+        path: file.map(|f| f.path.clone()),
+        rows: file.and_then(|f| f.rows),
+        error,
+    };
+    if let Err(e) = app.emit("scheduled-export", event) {
+        log::warn!("Failed to emit scheduled export result: {}", e);
+    }
+    let notification = match &outcome {
+        Ok((file, _)) => Notification {
+            job: JobKind::ScheduledExport,
+            ok: true,
+            title: format!("Export {} finished", scheduled.name),
+            message: match file.rows {
+                Some(rows) => format!("{} rows written to {}", rows, file.path),
+                None => format!("Written to {}", file.path),
+            },
+        },
+        Err(e) => Notification {
+            job: JobKind::ScheduledExport,
+            ok: false,
+            title: format!("Export {} failed", scheduled.name),
+            message: format!("{} on {}: {}", scheduled.name, scheduled.connection, e),
+        },
+    };
+    notifications::notify(app, notification);
+    outcome.map(|(file, _)| file)
+}
+
+// The connection's server time zone, as last read within SERVER_ZONE_TTL
+async fn server_zone(app: &tauri::AppHandle, name: &str) -> Result<RenderZone, String> {
+    static ZONES: OnceLock<StdMutex<HashMap<String, (Instant, RenderZone)>>> = OnceLock::new();
+    let zones = ZONES.get_or_init(Default::default);
+    if let Some((read_at, zone)) = zones.lock().unwrap().get(name) {
+        if read_at.elapsed() < SERVER_ZONE_TTL {
+            return Ok(*zone);
+        }
+    }
+    let client = client_for(app, name).await?;
+    let zone = db::resolve_render_zone(&client, TimezoneMode::Server).await?;
+    zones
+        .lock()
+        .unwrap()
+        .insert(name.to_string(), (Instant::now(), zone));
+    Ok(zone)
+}
+
+// Whether the export may start now. Server time is asked of the connection;
+// exports run by hand aren't held to their windows.
+async fn in_window(app: &tauri::AppHandle, export: &ScheduledExport) -> Result<bool, String> {
+    if export.windows.is_empty() {
+        return Ok(true);
+    }
+    let zone = match TimezoneMode::parse(&export.window_timezone) {
+        TimezoneMode::Server => server_zone(app, &export.connection).await?,
+        TimezoneMode::Client => RenderZone::Local,
+        TimezoneMode::Utc => RenderZone::utc(),
+    };
+    schedule_windows::is_open(&export.windows, schedule_windows::now_in(zone))
+}
+
+// Checks for due exports for as long as the app runs; they run one at a time
+pub fn start(app: tauri::AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(CHECK_INTERVAL_SECS));
+        loop {
+            ticker.tick().await;
+            let due = storage::data_file(&app, SCHEDULED_EXPORTS_FILE)
+                .and_then(|path| list(&path))
+                .map(|exports| {
+                    let now = Utc::now();
+                    exports.into_iter().filter(|e| e.is_due(now)).collect()
+                });
+            let due: Vec<ScheduledExport> = match due {
+                Ok(due) => due,
+                Err(e) => {
+                    log::warn!("Failed to read scheduled exports: {}", e);
+                    continue;
+                }
+            };
+            for scheduled in &due {
+                // Outside its windows an export waits for the next check
+                match in_window(&app, scheduled).await {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(e) => {
+                        log::warn!("Failed to check the windows of {}: {}", scheduled.name, e);
+                        continue;
+                    }
+                }
+                // Failures are recorded and reported by run
+                let _ = run(&app, scheduled).await;
+            }
+        }
+    });
+}
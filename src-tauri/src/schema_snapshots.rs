@@ -0,0 +1,424 @@
+// Portable snapshots of a connection's schema: tables, views, columns, foreign
+// keys and comments. Exported to a file, and imported ones can be browsed and
+// diffed like an offline connection.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::db::{self, ColumnInfo, DbClient, Dialect, ForeignKey};
+use crate::storage;
+
+pub const SCHEMA_SNAPSHOTS_FILE: &str = "schema_snapshots.json";
+
+// Bumped when the file layout changes incompatibly
+const SNAPSHOT_FORMAT: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TableKind {
+    Table,
+    View,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotColumn {
+    #[serde(flatten)]
+    pub info: ColumnInfo,
+    pub is_primary_key: bool,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotTable {
+    pub schema: Option<String>, // None on MySQL, where it's the connected database
+    pub name: String,
+    pub kind: TableKind,
+    pub comment: Option<String>,
+    pub columns: Vec<SnapshotColumn>,
+    pub foreign_keys: Vec<ForeignKey>, // only those declared on this table
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaSnapshot {
+    pub format: u32,
+    pub id: String,
+    pub connection: String,
+    pub dialect: Dialect,
+    pub taken_at: DateTime<Utc>,
+    pub tables: Vec<SnapshotTable>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SchemaSnapshotSummary {
+    pub id: String,
+    pub connection: String,
+    pub dialect: Dialect,
+    pub taken_at: DateTime<Utc>,
+    pub tables: usize,
+}
+
+// What to compare: a stored snapshot or a live connection as it is now
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SnapshotSource {
+    Snapshot { id: String },
+    Connection { name: String },
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DifferenceKind {
+    TableAdded,
+    TableRemoved,
+    ColumnAdded,
+    ColumnRemoved,
+    ColumnChanged,
+    ForeignKeyAdded,
+    ForeignKeyRemoved,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SchemaDifference {
+    pub kind: DifferenceKind,
+    pub schema: Option<String>,
+    pub table: String,
+    pub name: Option<String>, // the column or foreign key
+    pub detail: Option<String>,
+}
+
+// (table, column) -> comment; the column is None for the table's own comment
+type Comments = HashMap<(String, Option<String>), String>;
+
+async fn comments(client: &DbClient, schema: Option<&str>) -> Result<Comments, String> {
+    let rows: Vec<(String, Option<String>, Option<String>)> = match client {
+        DbClient::Postgres(pool) => sqlx::query(
+            "SELECT c.relname::text, NULL::text, obj_description(c.oid, 'pg_class') \
+             FROM pg_class c JOIN pg_namespace n ON n.oid = c.relnamespace \
+             WHERE n.nspname = $1 AND c.relkind IN ('r', 'p', 'v', 'm') \
+             UNION ALL \
+             SELECT c.relname::text, a.attname::text, col_description(c.oid, a.attnum) \
+             FROM pg_class c JOIN pg_namespace n ON n.oid = c.relnamespace \
+             JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped \
+             WHERE n.nspname = $1 AND c.relkind IN ('r', 'p', 'v', 'm')",
+        )
+        .bind(schema)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|r| (r.get(0), r.get(1), r.get(2)))
+        .collect(),
+        DbClient::Mysql(pool) => sqlx::query(
+            "SELECT table_name, CAST(NULL AS CHAR), table_comment FROM information_schema.tables \
+             WHERE table_schema = DATABASE() \
+             UNION ALL \
+             SELECT table_name, column_name, column_comment FROM information_schema.columns \
+             WHERE table_schema = DATABASE()",
+        )
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|r| (r.get(0), r.get(1), r.get(2)))
+        .collect(),
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
+            let query = "SELECT o.name, c.name, CAST(ep.value AS nvarchar(4000)) \
+                 FROM sys.extended_properties ep \
+                 JOIN sys.objects o ON o.object_id = ep.major_id \
+                 LEFT JOIN sys.columns c ON c.object_id = ep.major_id AND c.column_id = ep.minor_id \
+                 WHERE ep.class = 1 AND ep.name = 'MS_Description' AND SCHEMA_NAME(o.schema_id) = @P1";
+            let rows = client
+                .query(query, &[&schema.unwrap_or("dbo")])
+                .await
+                .map_err(|e| e.to_string())?
+                .into_first_result()
+                .await
+                .map_err(|e| e.to_string())?;
+            let text = |r: &tiberius::Row, i: usize| {
+                r.try_get::<&str, _>(i).ok().flatten().map(str::to_string)
+            };
+            rows.iter()
+                .map(|r| (text(r, 0).unwrap_or_default(), text(r, 1), text(r, 2)))
+                .collect()
+        }
+        _ => vec![],
+    };
+    Ok(rows
+        .into_iter()
+        .filter_map(|(table, column, comment)| {
+            Some((table, column)).zip(comment.filter(|c| !c.is_empty()))
+        })
+        .collect())
+}
+
+async fn snapshot_table(
+    client: &DbClient,
+    schema: Option<&str>,
+    name: String,
+    kind: TableKind,
+    comments: &Comments,
+) -> Result<SnapshotTable, String> {
+    let schema_arg = schema.map(str::to_string);
+    let columns = db::get_columns(client, schema_arg.clone(), &name)
+        .await?
+        .into_iter()
+        .map(|c| SnapshotColumn {
+            comment: comments
+                .get(&(name.clone(), Some(c.info.name.clone())))
+                .cloned(),
+            info: c.info,
+            is_primary_key: c.is_primary_key,
+        })
+        .collect();
+    let foreign_keys = match kind {
+        TableKind::Table => db::get_foreign_keys(client, schema_arg, &name)
+            .await?
+            .into_iter()
+            .filter(|fk| fk.table == name && schema.map_or(true, |s| fk.schema == s))
+            .collect(),
+        TableKind::View => vec![],
+    };
+    Ok(SnapshotTable {
+        schema: schema.map(str::to_string),
+        comment: comments.get(&(name.clone(), None)).cloned(),
+        name,
+        kind,
+        columns,
+        foreign_keys,
+    })
+}
+
+// Reads the whole schema of a live connection
+pub async fn take(client: &DbClient, connection: &str) -> Result<SchemaSnapshot, String> {
+    let dialect = client
+        .dialect()
+        .ok_or("Schema snapshots are only supported for SQL databases")?;
+    let schemas: Vec<Option<String>> = match dialect {
+        Dialect::Mysql => vec![None],
+        _ => db::get_schemas(client)
+            .await?
+            .into_iter()
+            .map(Some)
+            .collect(),
+    };
+    let mut tables = Vec::new();
+    for schema in &schemas {
+        let comments = comments(client, schema.as_deref()).await?;
+        for name in db::get_tables(client, schema.clone(), false).await? {
+            tables.push(
+                snapshot_table(client, schema.as_deref(), name, TableKind::Table, &comments)
+                    .await?,
+            );
+        }
+        for name in db::get_views(client, schema.clone()).await? {
+            tables.push(
+                snapshot_table(client, schema.as_deref(), name, TableKind::View, &comments).await?,
+            );
+        }
+    }
+    Ok(SchemaSnapshot {
+        format: SNAPSHOT_FORMAT,
+        id: uuid::Uuid::new_v4().to_string(),
+        connection: connection.to_string(),
+        dialect,
+        taken_at: Utc::now(),
+        tables,
+    })
+}
+
+impl SchemaSnapshotSummary {
+    pub fn of(snapshot: &SchemaSnapshot) -> Self {
+        SchemaSnapshotSummary {
+            id: snapshot.id.clone(),
+            connection: snapshot.connection.clone(),
+            dialect: snapshot.dialect,
+            taken_at: snapshot.taken_at,
+            tables: snapshot.tables.len(),
+        }
+    }
+}
+
+fn load_snapshots(store: &Path) -> Result<Vec<SchemaSnapshot>, String> {
+    storage::read_json(store)
+}
+
+// Writes a snapshot of the connection to `file`
+pub async fn export(
+    client: &DbClient,
+    connection: &str,
+    file: &Path,
+) -> Result<SchemaSnapshotSummary, String> {
+    let snapshot = take(client, connection).await?;
+    storage::write_json(file, &snapshot)?;
+    Ok(SchemaSnapshotSummary::of(&snapshot))
+}
+
+// Stores a snapshot file for offline browsing, optionally under another
+// connection name
+pub fn import(
+    store: &Path,
+    file: &Path,
+    name: Option<String>,
+) -> Result<SchemaSnapshotSummary, String> {
+    let json = fs::read_to_string(file).map_err(|e| format!("Failed to read {:?}: {}", file, e))?;
+    let mut snapshot: SchemaSnapshot =
+        serde_json::from_str(&json).map_err(|e| format!("Not a schema snapshot: {}", e))?;
+    if snapshot.format > SNAPSHOT_FORMAT {
+        return Err("The snapshot was written by a newer version of the app".to_string());
+    }
+    snapshot.id = uuid::Uuid::new_v4().to_string();
+    if let Some(name) = name.filter(|n| !n.trim().is_empty()) {
+        snapshot.connection = name;
+    }
+    let summary = SchemaSnapshotSummary::of(&snapshot);
+    let mut snapshots = load_snapshots(store)?;
+    snapshots.push(snapshot);
+    storage::write_json(store, &snapshots)?;
+    Ok(summary)
+}
+
+pub fn list(store: &Path) -> Result<Vec<SchemaSnapshotSummary>, String> {
+    let mut summaries: Vec<SchemaSnapshotSummary> = load_snapshots(store)?
+        .iter()
+        .map(SchemaSnapshotSummary::of)
+        .collect();
+    summaries.sort_by_key(|s| std::cmp::Reverse(s.taken_at));
+    Ok(summaries)
+}
+
+pub fn get(store: &Path, id: &str) -> Result<SchemaSnapshot, String> {
+    load_snapshots(store)?
+        .into_iter()
+        .find(|s| s.id == id)
+        .ok_or_else(|| "Snapshot not found".to_string())
+}
+
+// The most recent stored snapshot of the connection
+pub fn latest(store: &Path, connection: &str) -> Result<Option<SchemaSnapshot>, String> {
+    Ok(load_snapshots(store)?
+        .into_iter()
+        .filter(|s| s.connection == connection)
+        .max_by_key(|s| s.taken_at))
+}
+
+pub fn delete(store: &Path, id: &str) -> Result<(), String> {
+    let mut snapshots = load_snapshots(store)?;
+    let before = snapshots.len();
+    snapshots.retain(|s| s.id != id);
+    if snapshots.len() == before {
+        return Err("Snapshot not found".to_string());
+    }
+    storage::write_json(store, &snapshots)
+}
+
+fn column_signature(column: &SnapshotColumn) -> String {
+    let info = &column.info;
+    let mut signature = info.data_type.clone();
+    signature += if info.nullable { " NULL" } else { " NOT NULL" };
+    if let Some(default) = &info.default {
+        signature += &format!(" DEFAULT {}", default);
+    }
+    if column.is_primary_key {
+        signature += " PRIMARY KEY";
+    }
+    signature
+}
+
+// What changed from `before` to `after`, table by table
+pub fn diff(before: &SchemaSnapshot, after: &SchemaSnapshot) -> Vec<SchemaDifference> {
+    let key = |t: &SnapshotTable| (t.schema.clone(), t.name.clone());
+    let old: HashMap<_, &SnapshotTable> = before.tables.iter().map(|t| (key(t), t)).collect();
+    let new: HashMap<_, &SnapshotTable> = after.tables.iter().map(|t| (key(t), t)).collect();
+    let difference = |kind, table: &SnapshotTable, name: Option<&str>, detail: Option<String>| {
+        SchemaDifference {
+            kind,
+            schema: table.schema.clone(),
+            table: table.name.clone(),
+            name: name.map(str::to_string),
+            detail,
+        }
+    };
+
+    let mut differences = Vec::new();
+    for table in &before.tables {
+        if !new.contains_key(&key(table)) {
+            differences.push(difference(DifferenceKind::TableRemoved, table, None, None));
+        }
+    }
+    for table in &after.tables {
+        let Some(previous) = old.get(&key(table)) else {
+            differences.push(difference(DifferenceKind::TableAdded, table, None, None));
+            continue;
+        };
+        for column in &previous.columns {
+            if !table
+                .columns
+                .iter()
+                .any(|c| c.info.name == column.info.name)
+            {
+                differences.push(difference(
+                    DifferenceKind::ColumnRemoved,
+                    table,
+                    Some(&column.info.name),
+                    None,
+                ));
+            }
+        }
+        for column in &table.columns {
+            match previous
+                .columns
+                .iter()
+                .find(|c| c.info.name == column.info.name)
+            {
+                None => differences.push(difference(
+                    DifferenceKind::ColumnAdded,
+                    table,
+                    Some(&column.info.name),
+                    Some(column_signature(column)),
+                )),
+                Some(old_column) => {
+                    let (was, now) = (column_signature(old_column), column_signature(column));
+                    if was != now {
+                        differences.push(difference(
+                            DifferenceKind::ColumnChanged,
+                            table,
+                            Some(&column.info.name),
+                            Some(format!("{} -> {}", was, now)),
+                        ));
+                    }
+                }
+            }
+        }
+        for fk in &previous.foreign_keys {
+            if !table.foreign_keys.iter().any(|f| f.name == fk.name) {
+                differences.push(difference(
+                    DifferenceKind::ForeignKeyRemoved,
+                    table,
+                    Some(&fk.name),
+                    None,
+                ));
+            }
+        }
+        for fk in &table.foreign_keys {
+            if !previous.foreign_keys.iter().any(|f| f.name == fk.name) {
+                differences.push(difference(
+                    DifferenceKind::ForeignKeyAdded,
+                    table,
+                    Some(&fk.name),
+                    Some(format!(
+                        "({}) -> {}.{} ({})",
+                        fk.columns.join(", "),
+                        fk.referenced_schema,
+                        fk.referenced_table,
+                        fk.referenced_columns.join(", ")
+                    )),
+                ));
+            }
+        }
+    }
+    differences
+}
@@ -0,0 +1,268 @@
+// Background drift detection: object definitions are hashed on an interval and
+// differences are reported to the frontend as "schema-changed" events
+use serde::Serialize;
+use sqlx::Row;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex as StdMutex;
+use std::time::Duration;
+use tauri::{Emitter, Manager};
+use tokio::task::JoinHandle;
+
+use crate::db::{DatabaseState, DbClient};
+
+pub const DEFAULT_WATCH_INTERVAL_SECS: u64 = 60;
+const MIN_WATCH_INTERVAL_SECS: u64 = 5;
+
+// Rows of (object, kind, ord, part); an object's parts in order make up its definition
+const POSTGRES_DEFINITIONS_SQL: &str = "
+SELECT n.nspname || '.' || c.relname AS object,
+       CASE WHEN c.relkind IN ('v', 'm') THEN 'view' ELSE 'table' END AS kind,
+       a.attnum::int AS ord,
+       a.attname || ' ' || format_type(a.atttypid, a.atttypmod)
+           || CASE WHEN a.attnotnull THEN ' NOT NULL' ELSE '' END
+           || COALESCE(' DEFAULT ' || pg_get_expr(d.adbin, d.adrelid), '') AS part
+FROM pg_class c
+JOIN pg_namespace n ON n.oid = c.relnamespace
+JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped
+LEFT JOIN pg_attrdef d ON d.adrelid = c.oid AND d.adnum = a.attnum
+WHERE c.relkind IN ('r', 'p', 'v', 'm', 'f')
+  AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+  AND n.nspname NOT LIKE 'pg_toast%'
+UNION ALL
+SELECT n.nspname || '.' || c.relname, 'view', 0, pg_get_viewdef(c.oid)
+FROM pg_class c
+JOIN pg_namespace n ON n.oid = c.relnamespace
+WHERE c.relkind IN ('v', 'm')
+  AND n.nspname NOT IN ('pg_catalog', 'information_schema')
+UNION ALL
+SELECT n.nspname || '.' || p.proname || '(' || pg_get_function_identity_arguments(p.oid) || ')',
+       'routine', 0, p.prosrc
+FROM pg_proc p
+JOIN pg_namespace n ON n.oid = p.pronamespace
+WHERE n.nspname NOT IN ('pg_catalog', 'information_schema')
+ORDER BY 1, 3";
+
+const MYSQL_DEFINITIONS_SQL: &str = "
+SELECT CONCAT(c.TABLE_SCHEMA, '.', c.TABLE_NAME) AS object,
+       IF(t.TABLE_TYPE = 'VIEW', 'view', 'table') AS kind,
+       c.ORDINAL_POSITION AS ord,
+       CONCAT(c.COLUMN_NAME, ' ', c.COLUMN_TYPE,
+              IF(c.IS_NULLABLE = 'NO', ' NOT NULL', ''),
+              COALESCE(CONCAT(' DEFAULT ', c.COLUMN_DEFAULT), '')) AS part
+FROM information_schema.COLUMNS c
+JOIN information_schema.TABLES t
+  ON t.TABLE_SCHEMA = c.TABLE_SCHEMA AND t.TABLE_NAME = c.TABLE_NAME
+WHERE c.TABLE_SCHEMA = DATABASE()
+UNION ALL
+SELECT CONCAT(TABLE_SCHEMA, '.', TABLE_NAME), 'view', 0, VIEW_DEFINITION
+FROM information_schema.VIEWS
+WHERE TABLE_SCHEMA = DATABASE()
+UNION ALL
+SELECT CONCAT(ROUTINE_SCHEMA, '.', ROUTINE_NAME), 'routine', 0, ROUTINE_DEFINITION
+FROM information_schema.ROUTINES
+WHERE ROUTINE_SCHEMA = DATABASE()
+ORDER BY 1, 3";
+
+const MSSQL_DEFINITIONS_SQL: &str = "
+SELECT s.name + '.' + o.name AS object,
+       CASE o.type WHEN 'V' THEN 'view' ELSE 'table' END AS kind,
+       c.column_id AS ord,
+       CAST(c.name + ' ' + TYPE_NAME(c.user_type_id)
+            + '(' + CAST(c.max_length AS varchar(10)) + ',' + CAST(c.precision AS varchar(10))
+            + ',' + CAST(c.scale AS varchar(10)) + ')'
+            + CASE WHEN c.is_nullable = 1 THEN '' ELSE ' NOT NULL' END
+            + COALESCE(' DEFAULT ' + dc.definition, '') AS nvarchar(max)) AS part
+FROM sys.columns c
+JOIN sys.objects o ON o.object_id = c.object_id
+JOIN sys.schemas s ON s.schema_id = o.schema_id
+LEFT JOIN sys.default_constraints dc ON dc.object_id = c.default_object_id
+WHERE o.type IN ('U', 'V') AND o.is_ms_shipped = 0
+UNION ALL
+SELECT s.name + '.' + o.name, CASE o.type WHEN 'V' THEN 'view' ELSE 'routine' END, 0,
+       CAST(m.definition AS nvarchar(max))
+FROM sys.sql_modules m
+JOIN sys.objects o ON o.object_id = m.object_id
+JOIN sys.schemas s ON s.schema_id = o.schema_id
+WHERE o.is_ms_shipped = 0
+ORDER BY 1, 3";
+
+// Definition hash per (kind, object)
+type Fingerprint = HashMap<(String, String), u64>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Altered,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SchemaChange {
+    pub kind: String, // table, view or routine
+    pub object: String,
+    pub change: ChangeKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SchemaChangedEvent {
+    pub connection: String,
+    pub changes: Vec<SchemaChange>,
+}
+
+fn hash_parts(parts: Vec<(String, String, Option<String>)>) -> Fingerprint {
+    let mut hashers: HashMap<(String, String), DefaultHasher> = HashMap::new();
+    for (object, kind, part) in parts {
+        part.hash(hashers.entry((kind, object)).or_default());
+    }
+    hashers
+        .into_iter()
+        .map(|(key, hasher)| (key, hasher.finish()))
+        .collect()
+}
+
+pub async fn fingerprint(client: &DbClient) -> Result<Fingerprint, String> {
+    let parts: Vec<(String, String, Option<String>)> = match client {
+        DbClient::Postgres(pool) => sqlx::query(POSTGRES_DEFINITIONS_SQL)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?
+            .iter()
+            .map(|r| (r.get(0), r.get(1), r.try_get(3).ok().flatten()))
+            .collect(),
+        DbClient::Mysql(pool) => sqlx::query(MYSQL_DEFINITIONS_SQL)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?
+            .iter()
+            .map(|r| (r.get(0), r.get(1), r.try_get(3).ok().flatten()))
+            .collect(),
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
+            let rows = client
+                .query(MSSQL_DEFINITIONS_SQL, &[])
+                .await
+                .map_err(|e| e.to_string())?
+                .into_first_result()
+                .await
+                .map_err(|e| e.to_string())?;
+            let text =
+                |r: &tiberius::Row, i| r.try_get::<&str, _>(i).ok().flatten().map(str::to_string);
+            rows.iter()
+                .map(|r| {
+                    (
+                        text(r, 0).unwrap_or_default(),
+                        text(r, 1).unwrap_or_default(),
+                        text(r, 3),
+                    )
+                })
+                .collect()
+        }
+        _ => return Err("Schema watching is not supported for this database type".to_string()),
+    };
+    Ok(hash_parts(parts))
+}
+
+pub fn diff(before: &Fingerprint, after: &Fingerprint) -> Vec<SchemaChange> {
+    let change = |(kind, object): &(String, String), change| SchemaChange {
+        kind: kind.clone(),
+        object: object.clone(),
+        change,
+    };
+    let mut changes: Vec<SchemaChange> = after
+        .iter()
+        .filter_map(|(key, hash)| match before.get(key) {
+            None => Some(change(key, ChangeKind::Added)),
+            Some(old) if old != hash => Some(change(key, ChangeKind::Altered)),
+            Some(_) => None,
+        })
+        .chain(
+            before
+                .keys()
+                .filter(|key| !after.contains_key(*key))
+                .map(|key| change(key, ChangeKind::Removed)),
+        )
+        .collect();
+    changes.sort_by(|a, b| (&a.object, &a.kind).cmp(&(&b.object, &b.kind)));
+    changes
+}
+
+// Watch tasks by connection name
+#[derive(Default)]
+pub struct SchemaWatches {
+    tasks: StdMutex<HashMap<String, JoinHandle<()>>>,
+}
+
+impl SchemaWatches {
+    // Replaces any watch already running for the connection
+    fn start(&self, name: &str, task: JoinHandle<()>) {
+        if let Some(old) = self.tasks.lock().unwrap().insert(name.to_string(), task) {
+            old.abort();
+        }
+    }
+
+    pub fn stop(&self, name: &str) -> bool {
+        match self.tasks.lock().unwrap().remove(name) {
+            Some(task) => {
+                task.abort();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+// Looked up on every tick without counting as a use, so a watch doesn't keep
+// an idle connection from being evicted
+fn current_client(app: &tauri::AppHandle, name: &str) -> Option<DbClient> {
+    let state = app.state::<DatabaseState>();
+    let connections = state.connections.lock().unwrap();
+    connections.get(name).cloned()
+}
+
+// Takes a baseline right away, so an unsupported or failing connection is
+// reported to the caller instead of from the background
+pub async fn watch(
+    app: tauri::AppHandle,
+    name: String,
+    client: &DbClient,
+    interval_secs: u64,
+) -> Result<(), String> {
+    let mut baseline = fingerprint(client).await?;
+    let period = Duration::from_secs(interval_secs.max(MIN_WATCH_INTERVAL_SECS));
+    let task_app = app.clone();
+    let task_name = name.clone();
+    let task = tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(period);
+        ticker.tick().await; // the first tick completes immediately
+        loop {
+            ticker.tick().await;
+            let Some(client) = current_client(&task_app, &task_name) else {
+                break;
+            };
+            let current = match fingerprint(&client).await {
+                Ok(current) => current,
+                Err(e) => {
+                    log::warn!("Schema watch of {} failed: {}", task_name, e);
+                    continue;
+                }
+            };
+            let changes = diff(&baseline, &current);
+            baseline = current;
+            if changes.is_empty() {
+                continue;
+            }
+            let event = SchemaChangedEvent {
+                connection: task_name.clone(),
+                changes,
+            };
+            if let Err(e) = task_app.emit("schema-changed", event) {
+                log::warn!("Failed to emit schema change for {}: {}", task_name, e);
+            }
+        }
+    });
+    app.state::<DatabaseState>().watches.start(&name, task);
+    Ok(())
+}
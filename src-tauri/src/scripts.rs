@@ -0,0 +1,183 @@
+// Splitting multi-statement scripts, using sqlparser's tokenizer for the
+// dialect so strings, comments and dollar quoting never split a statement
+use serde::Serialize;
+use sqlparser::dialect::{MsSqlDialect, MySqlDialect, PostgreSqlDialect};
+use sqlparser::tokenizer::{Location, Token, TokenWithSpan, Tokenizer};
+
+use crate::db::{Dialect, QueryResponse};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptStatement {
+    pub sql: String,
+    // Char offsets into the script, for highlighting; the terminating
+    // semicolon is not included
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Serialize)]
+pub struct StatementResult {
+    pub statement: ScriptStatement,
+    pub result: QueryResponse,
+}
+
+// Words after BEGIN that make it a transaction start rather than a block
+const TRANSACTION_WORDS: [&str; 6] = [
+    "TRANSACTION",
+    "TRAN",
+    "WORK",
+    "ISOLATION",
+    "READ",
+    "DISTRIBUTED",
+];
+
+// Words after END that close a block opened by something other than BEGIN
+// or CASE (MySQL stored program bodies)
+const UNCOUNTED_ENDS: [&str; 4] = ["IF", "LOOP", "WHILE", "REPEAT"];
+
+fn word(token: Option<&&TokenWithSpan>) -> Option<String> {
+    match &token?.token {
+        Token::Word(w) if w.quote_style.is_none() => Some(w.value.to_uppercase()),
+        _ => None,
+    }
+}
+
+// Statements end at semicolons outside BEGIN ... END and CASE ... END, and on
+// SQL Server also at GO lines
+pub fn split_statements(dialect: Dialect, script: &str) -> Result<Vec<ScriptStatement>, String> {
+    let tokens = match dialect {
+        Dialect::Postgres => Tokenizer::new(&PostgreSqlDialect {}, script).tokenize_with_location(),
+        Dialect::Mysql => Tokenizer::new(&MySqlDialect {}, script).tokenize_with_location(),
+        Dialect::Mssql => Tokenizer::new(&MsSqlDialect {}, script).tokenize_with_location(),
+    }
+    .map_err(|e| e.to_string())?;
+    let significant: Vec<&TokenWithSpan> = tokens
+        .iter()
+        .filter(|t| !matches!(t.token, Token::Whitespace(_) | Token::EOF))
+        .collect();
+
+    let chars: Vec<char> = script.chars().collect();
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(
+            chars
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| **c == '\n')
+                .map(|(i, _)| i + 1),
+        )
+        .collect();
+    let offset = |location: Location| {
+        let line = line_starts
+            .get(location.line.saturating_sub(1) as usize)
+            .copied();
+        (line.unwrap_or(chars.len()) + location.column.saturating_sub(1) as usize).min(chars.len())
+    };
+
+    let mut statements = Vec::new();
+    let mut current: Option<(usize, usize)> = None;
+    let mut flush = |current: &mut Option<(usize, usize)>| {
+        if let Some((start, end)) = current.take() {
+            statements.push(ScriptStatement {
+                sql: chars[start..end].iter().collect(),
+                start,
+                end,
+            });
+        }
+    };
+    let mut depth = 0usize;
+    for (i, token) in significant.iter().enumerate() {
+        let previous = i.checked_sub(1).and_then(|p| significant.get(p));
+        let next = significant.get(i + 1);
+        match word(Some(token)).as_deref() {
+            // GO is a client-side batch separator and must be alone on its line
+            Some("GO")
+                if dialect == Dialect::Mssql
+                    && previous.map_or(true, |p| p.span.end.line < token.span.start.line)
+                    && next.map_or(true, |n| n.span.start.line > token.span.end.line) =>
+            {
+                flush(&mut current);
+                depth = 0;
+                continue;
+            }
+            Some("BEGIN") => {
+                let transaction = match next.map(|n| &n.token) {
+                    None | Some(Token::SemiColon) => true,
+                    _ => word(next).is_some_and(|w| TRANSACTION_WORDS.contains(&w.as_str())),
+                };
+                if !transaction {
+                    depth += 1;
+                }
+            }
+            // END CASE closes a MySQL CASE statement, already counted at END
+            Some("CASE") if word(previous).as_deref() != Some("END") => depth += 1,
+            Some("END") if !word(next).is_some_and(|w| UNCOUNTED_ENDS.contains(&w.as_str())) => {
+                depth = depth.saturating_sub(1)
+            }
+            _ => {}
+        }
+        if token.token == Token::SemiColon && depth == 0 {
+            flush(&mut current);
+            continue;
+        }
+        let (start, end) = (offset(token.span.start), offset(token.span.end));
+        current = Some(current.map_or((start, end), |(s, _)| (s, end)));
+    }
+    flush(&mut current);
+    Ok(statements)
+}
+
+// The statement under `cursor` (a char offset): the last one starting at or
+// before it, so a cursor just past a semicolon still picks that statement
+pub fn statement_at(
+    dialect: Dialect,
+    script: &str,
+    cursor: usize,
+) -> Result<ScriptStatement, String> {
+    let statements = split_statements(dialect, script)?;
+    let index = statements
+        .iter()
+        .rposition(|s| s.start <= cursor)
+        .unwrap_or(0);
+    statements
+        .into_iter()
+        .nth(index)
+        .ok_or_else(|| "The script contains no statements".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(dialect: Dialect, script: &str) -> Vec<String> {
+        split_statements(dialect, script)
+            .unwrap()
+            .into_iter()
+            .map(|s| s.sql)
+            .collect()
+    }
+
+    #[test]
+    fn end_case_closes_a_case_statement() {
+        let script = "CREATE PROCEDURE grade(IN n INT)
+BEGIN
+  CASE WHEN n > 5 THEN SELECT 'high'; ELSE SELECT 'low'; END CASE;
+END;
+SELECT 1;";
+        let statements = split(Dialect::Mysql, script);
+        assert_eq!(statements.len(), 2);
+        assert!(statements[0].ends_with("END CASE;\nEND"));
+        assert_eq!(statements[1], "SELECT 1");
+    }
+
+    #[test]
+    fn case_expressions_stay_in_their_statement() {
+        let statements = split(
+            Dialect::Postgres,
+            "SELECT CASE WHEN x THEN 1 ELSE 0 END FROM t; SELECT 2;",
+        );
+        assert_eq!(
+            statements,
+            ["SELECT CASE WHEN x THEN 1 ELSE 0 END FROM t", "SELECT 2"]
+        );
+    }
+}
@@ -0,0 +1,92 @@
+// Workspace secrets: API keys and tokens for integrations (webhooks, LLM
+// providers, cloud auth), kept apart from connection credentials. Values are
+// encrypted at rest in the OS keychain, see vault.rs; secrets.json only lists
+// their names. Subsystems read a secret by name with get_secret, and values
+// are never handed back to the frontend.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::storage;
+use crate::vault;
+
+pub const SECRETS_FILE: &str = "secrets.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SecretInfo {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>, // e.g. what the key is for
+    pub updated_at: DateTime<Utc>,
+}
+
+// Keychain entries of workspace secrets, distinct from the uuids used for
+// connection and channel secrets
+fn secret_ref(name: &str) -> String {
+    format!("secret:{}", name)
+}
+
+fn valid_name(name: &str) -> Result<&str, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Secret name is required".to_string());
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+    {
+        return Err(format!(
+            "Invalid secret name {:?}: use letters, digits, '_', '-' and '.'",
+            name
+        ));
+    }
+    Ok(name)
+}
+
+pub fn list(path: &Path) -> Result<Vec<SecretInfo>, String> {
+    storage::read_json(path)
+}
+
+// Creates or replaces the secret `name`
+pub fn set_secret(
+    path: &Path,
+    name: &str,
+    value: &str,
+    description: Option<String>,
+) -> Result<SecretInfo, String> {
+    let name = valid_name(name)?;
+    if value.is_empty() {
+        return Err("Secret value is required".to_string());
+    }
+    vault::store(&secret_ref(name), value)?;
+    let mut secrets = list(path)?;
+    let info = SecretInfo {
+        name: name.to_string(),
+        description: description.filter(|d| !d.trim().is_empty()),
+        updated_at: Utc::now(),
+    };
+    match secrets.iter_mut().find(|s| s.name == name) {
+        Some(existing) => *existing = info.clone(),
+        None => secrets.push(info.clone()),
+    }
+    secrets.sort_by(|a, b| a.name.cmp(&b.name));
+    storage::write_json(path, &secrets)?;
+    Ok(info)
+}
+
+// None when no secret goes by that name
+pub fn get_secret(name: &str) -> Result<Option<String>, String> {
+    vault::load(&secret_ref(valid_name(name)?))
+}
+
+pub fn delete_secret(path: &Path, name: &str) -> Result<(), String> {
+    let name = valid_name(name)?;
+    let mut secrets = list(path)?;
+    let count = secrets.len();
+    secrets.retain(|s| s.name != name);
+    if secrets.len() == count {
+        return Err("Secret not found".to_string());
+    }
+    storage::write_json(path, &secrets)?;
+    vault::forget(&secret_ref(name))
+}
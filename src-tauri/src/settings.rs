@@ -1,4 +1,8 @@
 use serde::{Deserialize, Serialize};
+use std::sync::Mutex as StdMutex;
+
+use crate::export_columns::ExportColumn;
+use crate::profiles::Profile;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Settings {
@@ -12,6 +16,10 @@ pub struct Settings {
     pub export: ExportSettings,
     #[serde(default)]
     pub advanced: AdvancedSettings,
+    #[serde(default)]
+    pub profile: Profile,
+    #[serde(default)]
+    pub audit: AuditSettings,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,10 +40,16 @@ impl Default for AppearanceSettings {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct QuerySettings {
     pub auto_limit: i32, // 0 = no limit
     pub timeout_seconds: i32,
     pub auto_format: bool,
+    pub timezone: String, // "utc", "server", "client"
+    // Tracked queries beyond this wait in a queue per connection; 0 = no limit
+    pub max_concurrent_per_connection: i32,
+    // Runs repeated after a serialization failure or deadlock; 0 = off
+    pub conflict_retries: i32,
 }
 
 impl Default for QuerySettings {
@@ -44,15 +58,22 @@ impl Default for QuerySettings {
             auto_limit: 100,
             timeout_seconds: 30,
             auto_format: false,
+            timezone: "utc".to_string(),
+            max_concurrent_per_connection: 4,
+            conflict_retries: 0,
         }
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ConnectionSettings {
     pub auto_connect_on_startup: bool,
     pub connection_timeout_seconds: i32,
     pub keep_alive_interval_seconds: i32,
+    pub offline_results: i32, // query results kept per connection for offline mode; 0 = none
+    // Open pool connections and load completion metadata right after connecting
+    pub warm_up_on_connect: bool,
 }
 
 impl Default for ConnectionSettings {
@@ -61,6 +82,8 @@ impl Default for ConnectionSettings {
             auto_connect_on_startup: false,
             connection_timeout_seconds: 10,
             keep_alive_interval_seconds: 60,
+            offline_results: 0,
+            warm_up_on_connect: false,
         }
     }
 }
@@ -70,6 +93,8 @@ pub struct ExportSettings {
     pub default_format: String, // "csv", "json", "excel"
     pub csv_delimiter: String,  // ",", ";", "\t"
     pub include_headers: bool,
+    #[serde(default)]
+    pub templates: Vec<ExportTemplate>,
 }
 
 impl Default for ExportSettings {
@@ -78,15 +103,35 @@ impl Default for ExportSettings {
             default_format: "csv".to_string(),
             csv_delimiter: ",".to_string(),
             include_headers: true,
+            templates: Vec::new(),
         }
     }
 }
 
+// A named export preset; unset options fall back to the settings above
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportTemplate {
+    pub name: String,
+    // e.g. "{connection}_{table}_{date}.csv"; {time} is also filled in
+    pub filename_pattern: String,
+    pub folder: Option<String>, // the downloads folder when unset
+    pub format: Option<String>,
+    pub csv_delimiter: Option<String>,
+    pub include_headers: Option<bool>,
+    // Column subset and per-column transforms; every column as is when empty
+    #[serde(default)]
+    pub columns: Vec<ExportColumn>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AdvancedSettings {
     pub enable_debug_logs: bool,
     pub cache_table_list: bool,
     pub max_cached_connections: i32,
+    // Folder snippets and notebooks are kept in as plain files, e.g. inside a
+    // git repository; see workspace.rs
+    #[serde(default)]
+    pub workspace_dir: Option<String>,
 }
 
 impl Default for AdvancedSettings {
@@ -95,6 +140,44 @@ impl Default for AdvancedSettings {
             enable_debug_logs: false,
             cache_table_list: true,
             max_cached_connections: 5,
+            workspace_dir: None,
         }
     }
 }
+
+// The audit log of executed statements; see audit.rs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditSettings {
+    pub enabled: bool,
+    pub user_label: String, // who entries are attributed to; the OS user when empty
+    pub max_file_mb: i32,   // the log rotates past this size
+    pub max_files: i32,     // log files kept, the current one included
+}
+
+impl Default for AuditSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            user_label: String::new(),
+            max_file_mb: 10,
+            max_files: 5,
+        }
+    }
+}
+
+// Settings currently in effect, kept in sync with settings.json by the settings commands
+#[derive(Default)]
+pub struct SettingsState {
+    pub current: StdMutex<Settings>,
+}
+
+impl SettingsState {
+    pub fn get(&self) -> Settings {
+        self.current.lock().unwrap().clone()
+    }
+
+    pub fn set(&self, settings: Settings) {
+        *self.current.lock().unwrap() = settings;
+    }
+}
@@ -0,0 +1,131 @@
+// A library of named, reusable SQL snippets kept in app data, apart from
+// history and bookmarks. Snippets in a workspace folder live there as files.
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::bookmarks::normalize_tags;
+use crate::storage;
+
+pub const SNIPPETS_FILE: &str = "snippets.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snippet {
+    #[serde(default)]
+    pub id: String, // empty when creating
+    pub name: String, // unique, ignoring case
+    pub sql: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // Saved connection the snippet is meant for; None for any
+    #[serde(default)]
+    pub connection: Option<String>,
+    #[serde(default = "Utc::now")]
+    pub created_at: DateTime<Utc>,
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SnippetFilter {
+    pub tag: Option<String>,
+    // Snippets for this connection and those for any
+    pub connection: Option<String>,
+}
+
+impl SnippetFilter {
+    fn matches(&self, snippet: &Snippet) -> bool {
+        let tag_ok = self.tag.as_deref().map_or(true, |tag| {
+            snippet.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+        });
+        let connection_ok = match (&self.connection, &snippet.connection) {
+            (Some(wanted), Some(target)) => wanted == target,
+            _ => true,
+        };
+        tag_ok && connection_ok
+    }
+}
+
+fn load(path: &Path) -> Result<Vec<Snippet>, String> {
+    storage::read_json(path)
+}
+
+pub fn list(path: &Path, filter: &SnippetFilter) -> Result<Vec<Snippet>, String> {
+    let mut snippets: Vec<Snippet> = load(path)?
+        .into_iter()
+        .filter(|s| filter.matches(s))
+        .collect();
+    snippets.sort_by_key(|s| s.name.to_lowercase());
+    Ok(snippets)
+}
+
+// Creates the snippet when its id is empty or unknown, otherwise replaces it
+pub fn save(path: &Path, mut snippet: Snippet) -> Result<Snippet, String> {
+    snippet.name = snippet.name.trim().to_string();
+    if snippet.name.is_empty() {
+        return Err("Snippet name is required".to_string());
+    }
+    if snippet.sql.trim().is_empty() {
+        return Err("Snippet SQL is required".to_string());
+    }
+    let mut snippets = load(path)?;
+    if snippets
+        .iter()
+        .any(|s| s.id != snippet.id && s.name.eq_ignore_ascii_case(&snippet.name))
+    {
+        return Err(format!("A snippet named '{}' already exists", snippet.name));
+    }
+    let now = Utc::now();
+    snippet.tags = normalize_tags(snippet.tags);
+    snippet.connection = snippet.connection.filter(|c| !c.trim().is_empty());
+    snippet.updated_at = now;
+
+    match snippets
+        .iter_mut()
+        .find(|s| !snippet.id.is_empty() && s.id == snippet.id)
+    {
+        Some(existing) => {
+            snippet.created_at = existing.created_at;
+            *existing = snippet.clone();
+        }
+        None => {
+            if snippet.id.is_empty() {
+                snippet.id = uuid::Uuid::new_v4().to_string();
+            }
+            snippet.created_at = now;
+            snippets.push(snippet.clone());
+        }
+    }
+    storage::write_json(path, &snippets)?;
+    Ok(snippet)
+}
+
+pub fn get(path: &Path, id: &str) -> Result<Snippet, String> {
+    load(path)?
+        .into_iter()
+        .find(|s| s.id == id)
+        .ok_or_else(|| "Snippet not found".to_string())
+}
+
+// Put a previously deleted snippet back unchanged; names stay unique
+pub fn restore(path: &Path, snippet: Snippet) -> Result<(), String> {
+    let mut snippets = load(path)?;
+    if snippets
+        .iter()
+        .any(|s| s.id == snippet.id || s.name.eq_ignore_ascii_case(&snippet.name))
+    {
+        return Err(format!("A snippet named '{}' already exists", snippet.name));
+    }
+    snippets.push(snippet);
+    storage::write_json(path, &snippets)
+}
+
+pub fn delete(path: &Path, id: &str) -> Result<(), String> {
+    let mut snippets = load(path)?;
+    let before = snippets.len();
+    snippets.retain(|s| s.id != id);
+    if snippets.len() == before {
+        return Err("Snippet not found".to_string());
+    }
+    storage::write_json(path, &snippets)
+}
@@ -0,0 +1,307 @@
+// Running a .sql file from disk one statement at a time, so scripts too big
+// to open in the editor (dumps, migrations) can still be applied. The file is
+// read line by line and only the statement in progress is held in memory.
+// MySQL DELIMITER lines, as mysqldump writes around routines and triggers,
+// are followed the way the mysql client does.
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::audit::Auditor;
+use crate::db::{self, DbClient, Dialect, QueryOptions};
+use crate::scripts::{self, ScriptStatement};
+
+// A statement that grows past this is taken as a runaway string or comment
+const MAX_STATEMENT_BYTES: usize = 64 * 1024 * 1024;
+// Errors listed in the result; all of them are counted
+const MAX_REPORTED: usize = 1000;
+// Characters of a failed statement kept in its error report
+const SQL_PREVIEW_CHARS: usize = 200;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SqlFileProgress {
+    pub path: String,
+    pub bytes_read: u64,
+    pub total_bytes: u64,
+    pub executed: u64,
+    pub failed: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StatementError {
+    pub line: usize, // 1-based line the statement starts on
+    pub sql: String, // the start of the statement
+    pub error: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SqlFileResult {
+    pub executed: u64,
+    pub failed: u64,
+    pub stopped: bool, // an error ended the run early
+    pub errors: Vec<StatementError>,
+    pub elapsed_ms: u64,
+}
+
+// The statements in `text` when it ends on a statement boundary; None while
+// the last one is still open, e.g. inside a string or a BEGIN ... END body
+fn complete(dialect: Dialect, text: &str) -> Option<Vec<ScriptStatement>> {
+    let statements = scripts::split_statements(dialect, text).ok()?;
+    let length = text.trim_end().chars().count();
+    match statements.last() {
+        // A terminated statement ends before its semicolon or GO
+        Some(last) if last.end >= length => None,
+        _ => Some(statements),
+    }
+}
+
+// The new delimiter when `line` is a MySQL "DELIMITER ;;" command
+fn delimiter_command(line: &str) -> Option<&str> {
+    let (word, rest) = line.trim().split_once(char::is_whitespace)?;
+    let delimiter = rest.trim();
+    (word.eq_ignore_ascii_case("DELIMITER") && !delimiter.is_empty()).then_some(delimiter)
+}
+
+// `text` as a single statement, ending before `sql_end` bytes
+fn whole(text: &str, sql_end: usize) -> ScriptStatement {
+    let body = &text[..sql_end];
+    let start = body.chars().take_while(|c| c.is_whitespace()).count();
+    let sql = body.trim().to_string();
+    ScriptStatement {
+        start,
+        end: start + sql.chars().count(),
+        sql,
+    }
+}
+
+// Under a delimiter other than ";" the statement runs whole, semicolons and
+// all, once a line ends on the delimiter
+fn delimited(text: &str, delimiter: &str) -> Option<ScriptStatement> {
+    let trimmed = text.trim_end();
+    let body = trimmed.strip_suffix(delimiter)?;
+    Some(whole(text, body.len()))
+}
+
+fn is_boundary(dialect: Dialect, line: &str) -> bool {
+    let line = line.trim();
+    line.ends_with(';') || (dialect == Dialect::Mssql && line.eq_ignore_ascii_case("GO"))
+}
+
+struct Runner<'a, C, P> {
+    client: &'a DbClient,
+    auditor: &'a Auditor,
+    check: C,
+    on_progress: P,
+    stop_on_error: bool,
+    progress: SqlFileProgress,
+    errors: Vec<StatementError>,
+    reported_at: Instant,
+}
+
+impl<C, P> Runner<'_, C, P>
+where
+    C: Fn(&str) -> Result<(), String>,
+    P: Fn(&SqlFileProgress),
+{
+    fn fail(&mut self, line: usize, sql: &str, error: String) {
+        self.progress.failed += 1;
+        if self.errors.len() < MAX_REPORTED {
+            self.errors.push(StatementError {
+                line,
+                sql: sql.chars().take(SQL_PREVIEW_CHARS).collect(),
+                error,
+            });
+        }
+    }
+
+    fn report(&mut self, force: bool) {
+        if force || self.reported_at.elapsed() >= PROGRESS_INTERVAL {
+            (self.on_progress)(&self.progress);
+            self.reported_at = Instant::now();
+        }
+    }
+
+    // Runs the statements of `text`, which starts on `first_line`. False once
+    // a statement fails and the run should stop.
+    async fn run(
+        &mut self,
+        text: &str,
+        first_line: usize,
+        statements: Vec<ScriptStatement>,
+    ) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        for statement in statements {
+            let line = first_line
+                + chars[..statement.start]
+                    .iter()
+                    .filter(|c| **c == '\n')
+                    .count();
+            let outcome = match (self.check)(&statement.sql) {
+                Ok(()) => {
+                    let options = QueryOptions::default();
+                    let work = db::execute_query(self.client, statement.sql.clone(), &options);
+                    self.auditor
+                        .run(&statement.sql, work)
+                        .await
+                        .map_err(String::from)
+                }
+                Err(e) => Err(e),
+            };
+            match outcome {
+                Ok(_) => self.progress.executed += 1,
+                Err(e) => {
+                    self.fail(line, &statement.sql, e);
+                    if self.stop_on_error {
+                        return false;
+                    }
+                }
+            }
+            self.report(false);
+        }
+        true
+    }
+}
+
+// Runs every statement of the file at `path` in order on `client`. `check`
+// vets each statement before it runs, e.g. against the connection profile;
+// a statement it refuses counts as failed.
+pub async fn run_sql_file(
+    client: &DbClient,
+    dialect: Dialect,
+    path: &str,
+    stop_on_error: bool,
+    auditor: &Auditor,
+    check: impl Fn(&str) -> Result<(), String>,
+    on_progress: impl Fn(&SqlFileProgress),
+) -> Result<SqlFileResult, String> {
+    let started = Instant::now();
+    let file = tokio::fs::File::open(path)
+        .await
+        .map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    let total_bytes = file.metadata().await.map_err(|e| e.to_string())?.len();
+    let mut reader = BufReader::new(file);
+    let mut runner = Runner {
+        client,
+        auditor,
+        check,
+        on_progress,
+        stop_on_error,
+        progress: SqlFileProgress {
+            path: path.to_string(),
+            bytes_read: 0,
+            total_bytes,
+            executed: 0,
+            failed: 0,
+        },
+        errors: Vec::new(),
+        reported_at: Instant::now(),
+    };
+
+    let mut pending = String::new();
+    let mut pending_line = 1; // where `pending` starts
+    let mut line_number = 0;
+    let mut line = String::new();
+    let mut delimiter = ";".to_string();
+    let mut stopped = false;
+    loop {
+        line.clear();
+        let read = reader
+            .read_line(&mut line)
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        if read == 0 {
+            break;
+        }
+        line_number += 1;
+        runner.progress.bytes_read += read as u64;
+        if pending.trim().is_empty() {
+            pending.clear();
+            pending_line = line_number;
+            if dialect == Dialect::Mysql {
+                if let Some(next) = delimiter_command(&line) {
+                    delimiter = next.to_string();
+                    continue;
+                }
+            }
+        }
+        pending.push_str(&line);
+        if pending.len() > MAX_STATEMENT_BYTES {
+            return Err(format!(
+                "The statement starting on line {} is over {} MB; is a quote or comment left open?",
+                pending_line,
+                MAX_STATEMENT_BYTES / (1024 * 1024)
+            ));
+        }
+        if delimiter != ";" {
+            if let Some(statement) = delimited(&pending, &delimiter) {
+                if !runner.run(&pending, pending_line, vec![statement]).await {
+                    stopped = true;
+                    break;
+                }
+                pending.clear();
+            }
+            continue;
+        }
+        if !is_boundary(dialect, &line) {
+            continue;
+        }
+        if let Some(statements) = complete(dialect, &pending) {
+            if !runner.run(&pending, pending_line, statements).await {
+                stopped = true;
+                break;
+            }
+            pending.clear();
+        }
+    }
+    // Whatever is left has no terminator; it runs as is
+    if !stopped && !pending.trim().is_empty() {
+        let statements = if delimiter == ";" {
+            scripts::split_statements(dialect, &pending)
+        } else {
+            Ok(vec![whole(&pending, pending.len())])
+        };
+        match statements {
+            Ok(statements) => stopped = !runner.run(&pending, pending_line, statements).await,
+            Err(e) => {
+                runner.fail(pending_line, &pending, e);
+                stopped = stop_on_error;
+            }
+        }
+    }
+    runner.report(true);
+
+    Ok(SqlFileResult {
+        executed: runner.progress.executed,
+        failed: runner.progress.failed,
+        stopped,
+        errors: runner.errors,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_delimiter_commands() {
+        assert_eq!(delimiter_command("DELIMITER ;;\n"), Some(";;"));
+        assert_eq!(delimiter_command("delimiter $$"), Some("$$"));
+        assert_eq!(delimiter_command("DELIMITER\n"), None);
+        assert_eq!(delimiter_command("SELECT 1;"), None);
+    }
+
+    #[test]
+    fn runs_a_delimited_body_whole() {
+        let text =
+            "\nCREATE TRIGGER t BEFORE INSERT ON a FOR EACH ROW BEGIN\n  SET NEW.x = 1;\nEND ;;\n";
+        let statement = delimited(text, ";;").unwrap();
+        assert_eq!(
+            statement.sql,
+            "CREATE TRIGGER t BEFORE INSERT ON a FOR EACH ROW BEGIN\n  SET NEW.x = 1;\nEND"
+        );
+        assert_eq!(statement.start, 1);
+        assert!(delimited("BEGIN\n  SET NEW.x = 1;\n", ";;").is_none());
+    }
+}
@@ -0,0 +1,241 @@
+// SQL projects: local folders of .sql files registered in the app, listed and
+// run in path order, and watched for changes made outside the app
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex as StdMutex;
+use std::time::Duration;
+use tauri::Emitter;
+use tokio::task::JoinHandle;
+
+use crate::sql_file::SqlFileResult;
+use crate::storage;
+
+pub const SQL_PROJECTS_FILE: &str = "sql_projects.json";
+
+const WATCH_INTERVAL_SECS: u64 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SqlProject {
+    #[serde(default)]
+    pub id: String, // empty when creating
+    pub name: String,
+    pub path: String, // the folder
+    // Connection files run against unless another is given
+    #[serde(default)]
+    pub connection: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProjectFile {
+    pub path: String, // relative to the project folder, with forward slashes
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct ProjectFileRun {
+    pub file: String,
+    pub result: SqlFileResult,
+}
+
+#[derive(Clone, Serialize)]
+pub struct ProjectChangedEvent {
+    pub project: String, // id
+    pub added: Vec<String>,
+    pub modified: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+pub fn list(path: &Path) -> Result<Vec<SqlProject>, String> {
+    storage::read_json(path)
+}
+
+pub fn get(path: &Path, id: &str) -> Result<SqlProject, String> {
+    list(path)?
+        .into_iter()
+        .find(|p| p.id == id)
+        .ok_or_else(|| "SQL project not found".to_string())
+}
+
+// Creates the project when its id is empty or unknown, otherwise replaces it
+pub fn save(path: &Path, mut project: SqlProject) -> Result<SqlProject, String> {
+    if project.name.trim().is_empty() {
+        return Err("Project name is required".to_string());
+    }
+    if !Path::new(&project.path).is_dir() {
+        return Err(format!("{} is not a folder", project.path));
+    }
+    let mut projects = list(path)?;
+    match projects
+        .iter_mut()
+        .find(|p| !project.id.is_empty() && p.id == project.id)
+    {
+        Some(existing) => *existing = project.clone(),
+        None => {
+            if project.id.is_empty() {
+                project.id = uuid::Uuid::new_v4().to_string();
+            }
+            projects.push(project.clone());
+        }
+    }
+    storage::write_json(path, &projects)?;
+    Ok(project)
+}
+
+pub fn delete(path: &Path, id: &str) -> Result<(), String> {
+    let mut projects = list(path)?;
+    let before = projects.len();
+    projects.retain(|p| p.id != id);
+    if projects.len() == before {
+        return Err("SQL project not found".to_string());
+    }
+    storage::write_json(path, &projects)
+}
+
+fn is_sql(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("sql"))
+}
+
+fn walk(root: &Path, dir: &Path, files: &mut Vec<ProjectFile>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read {:?}: {}", dir, e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let path = entry.path();
+        // Hidden folders such as .git are skipped
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let metadata = entry.metadata().map_err(|e| e.to_string())?;
+        if metadata.is_dir() {
+            walk(root, &path, files)?;
+        } else if is_sql(&path) {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            files.push(ProjectFile {
+                path: relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+                size: metadata.len(),
+                modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+            });
+        }
+    }
+    Ok(())
+}
+
+// The project's .sql files, subfolders included, in the order they run: by
+// path, so numbered names such as 001_schema.sql run first
+pub fn files(project: &SqlProject) -> Result<Vec<ProjectFile>, String> {
+    let root = Path::new(&project.path);
+    let mut files = Vec::new();
+    walk(root, root, &mut files)?;
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+// Full path of one of the project's files; paths leading out of the folder
+// are refused
+pub fn file_path(project: &SqlProject, file: &str) -> Result<PathBuf, String> {
+    let root = fs::canonicalize(&project.path).map_err(|e| e.to_string())?;
+    let path =
+        fs::canonicalize(root.join(file)).map_err(|e| format!("Failed to open {}: {}", file, e))?;
+    if !path.starts_with(&root) || !is_sql(&path) {
+        return Err(format!("{} is not a SQL file of {}", file, project.name));
+    }
+    Ok(path)
+}
+
+// Watch tasks by project id
+#[derive(Default)]
+pub struct ProjectWatches {
+    tasks: StdMutex<HashMap<String, JoinHandle<()>>>,
+}
+
+impl ProjectWatches {
+    pub fn stop(&self, id: &str) -> bool {
+        match self.tasks.lock().unwrap().remove(id) {
+            Some(task) => {
+                task.abort();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+type Snapshot = HashMap<String, (u64, Option<DateTime<Utc>>)>;
+
+fn snapshot(project: &SqlProject) -> Result<Snapshot, String> {
+    Ok(files(project)?
+        .into_iter()
+        .map(|f| (f.path, (f.size, f.modified)))
+        .collect())
+}
+
+fn changes(project: &str, before: &Snapshot, after: &Snapshot) -> Option<ProjectChangedEvent> {
+    let mut event = ProjectChangedEvent {
+        project: project.to_string(),
+        added: Vec::new(),
+        modified: Vec::new(),
+        removed: Vec::new(),
+    };
+    for (path, state) in after {
+        match before.get(path) {
+            None => event.added.push(path.clone()),
+            Some(old) if old != state => event.modified.push(path.clone()),
+            _ => {}
+        }
+    }
+    event.removed = before
+        .keys()
+        .filter(|path| !after.contains_key(*path))
+        .cloned()
+        .collect();
+    if event.added.is_empty() && event.modified.is_empty() && event.removed.is_empty() {
+        return None;
+    }
+    event.added.sort();
+    event.modified.sort();
+    event.removed.sort();
+    Some(event)
+}
+
+// Polls the folder and reports added, changed and removed .sql files as
+// "sql-project-changed" events. Replaces any watch already running for it.
+pub fn watch(
+    app: tauri::AppHandle,
+    watches: &ProjectWatches,
+    project: SqlProject,
+) -> Result<(), String> {
+    let mut baseline = snapshot(&project)?;
+    let id = project.id.clone();
+    let task = tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(WATCH_INTERVAL_SECS));
+        ticker.tick().await; // the first tick completes immediately
+        loop {
+            ticker.tick().await;
+            let current = match snapshot(&project) {
+                Ok(current) => current,
+                Err(e) => {
+                    log::warn!("Watch of SQL project {} failed: {}", project.name, e);
+                    continue;
+                }
+            };
+            if let Some(event) = changes(&project.id, &baseline, &current) {
+                if let Err(e) = app.emit("sql-project-changed", event) {
+                    log::warn!("Failed to emit change of {}: {}", project.name, e);
+                }
+            }
+            baseline = current;
+        }
+    });
+    if let Some(old) = watches.tasks.lock().unwrap().insert(id, task) {
+        old.abort();
+    }
+    Ok(())
+}
@@ -0,0 +1,749 @@
+// Lightweight lexical helpers for classifying SQL text without a full parser
+use serde::Serialize;
+use std::ops::Range;
+
+use crate::db::Dialect;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Word(String),   // bare keyword, identifier or number, as written
+    Quoted(String), // quoted identifier with the quoting removed
+    Symbol(char),
+}
+
+// Splits SQL into tokens, dropping whitespace, comments and string literals
+pub fn tokens(sql: &str) -> Vec<Token> {
+    let chars: Vec<char> = sql.chars().collect();
+    spanned_tokens(&chars).into_iter().map(|(t, _)| t).collect()
+}
+
+// Tokens with the char range each one covers
+fn spanned_tokens(chars: &[char]) -> Vec<(Token, Range<usize>)> {
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        match (c, next) {
+            ('-', Some('-')) => i = find_from(chars, i + 2, |c| c == '\n') + 1,
+            ('/', Some('*')) => i = find_pair(chars, i + 2, '*', '/') + 1,
+            ('\'', _) => i = find_from(chars, i + 1, |x| x == '\'') + 1,
+            ('"', _) | ('`', _) | ('[', _) => {
+                let close = if c == '[' { ']' } else { c };
+                let end = find_from(chars, i + 1, |x| x == close);
+                let text = chars[i + 1..end].iter().collect();
+                tokens.push((Token::Quoted(text), i..(end + 1).min(chars.len())));
+                i = end + 1;
+            }
+            _ if c.is_alphanumeric() || c == '_' => {
+                let end = find_from(chars, i, |x| !(x.is_alphanumeric() || x == '_'));
+                tokens.push((Token::Word(chars[i..end].iter().collect()), i..end));
+                i = end;
+            }
+            _ if c.is_whitespace() => i += 1,
+            _ => {
+                tokens.push((Token::Symbol(c), i..i + 1));
+                i += 1;
+            }
+        }
+    }
+    tokens
+}
+
+// Upper-cased keywords and bare identifiers, with comments, string literals and
+// quoted identifiers removed
+pub fn words(sql: &str) -> Vec<String> {
+    tokens(sql)
+        .into_iter()
+        .filter_map(|t| match t {
+            Token::Word(w) => Some(w.to_uppercase()),
+            _ => None,
+        })
+        .collect()
+}
+
+// Index of the first matching char at or after `from`, or the end of input
+fn find_from(chars: &[char], from: usize, pred: impl Fn(char) -> bool) -> usize {
+    (from..chars.len())
+        .find(|&i| pred(chars[i]))
+        .unwrap_or(chars.len())
+}
+
+// Index of the second char of the first `a b` pair at or after `from`
+fn find_pair(chars: &[char], from: usize, a: char, b: char) -> usize {
+    (from..chars.len().saturating_sub(1))
+        .find(|&i| chars[i] == a && chars[i + 1] == b)
+        .map_or(chars.len(), |i| i + 1)
+}
+
+const READ_STARTS: [&str; 8] = [
+    "SELECT", "WITH", "SHOW", "DESCRIBE", "DESC", "EXPLAIN", "VALUES", "TABLE",
+];
+
+const WRITE_WORDS: [&str; 18] = [
+    "INSERT", "UPDATE", "DELETE", "MERGE", "INTO", "CREATE", "ALTER", "DROP", "TRUNCATE", "GRANT",
+    "REVOKE", "CALL", "EXEC", "EXECUTE", "SET", "LOCK", "COPY", "VACUUM",
+];
+
+// Conservative check for statements that only read: anything that might write
+// (including SELECT ... INTO and FOR UPDATE) counts as a write
+pub fn is_read_query(sql: &str) -> bool {
+    let words = words(sql);
+    match words.first() {
+        Some(first) if READ_STARTS.contains(&first.as_str()) => {
+            !words.iter().any(|w| WRITE_WORDS.contains(&w.as_str()))
+        }
+        _ => false,
+    }
+}
+
+// PostgreSQL COPY ... TO STDOUT of a table or of a query that only reads
+pub fn is_copy_out(sql: &str) -> bool {
+    let words = words(sql);
+    words.first().map(String::as_str) == Some("COPY")
+        && words.windows(2).any(|w| w[0] == "TO" && w[1] == "STDOUT")
+        && !words[1..].iter().any(|w| WRITE_WORDS.contains(&w.as_str()))
+}
+
+const DDL_STARTS: [&str; 8] = [
+    "CREATE", "ALTER", "DROP", "TRUNCATE", "RENAME", "GRANT", "REVOKE", "COMMENT",
+];
+
+// Whether any statement changes schema or permissions
+pub fn is_ddl(sql: &str) -> bool {
+    let mut at_start = true;
+    for token in tokens(sql) {
+        match token {
+            Token::Word(w) if at_start && DDL_STARTS.contains(&w.to_uppercase().as_str()) => {
+                return true
+            }
+            Token::Symbol(';') => at_start = true,
+            _ => at_start = false,
+        }
+    }
+    false
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatementKind {
+    Query, // returns rows
+    Insert,
+    Update,
+    Delete,
+    Merge,
+    Ddl,
+    Other, // SET, CALL, and Mongo or Redis commands that fit none of the above
+}
+
+const DML_WORDS: [&str; 5] = ["INSERT", "REPLACE", "UPDATE", "DELETE", "MERGE"];
+
+// What the first statement does; a WITH prefix counts as the statement it leads to
+pub fn statement_kind(sql: &str) -> StatementKind {
+    if is_ddl(sql) {
+        return StatementKind::Ddl;
+    }
+    let words = words(sql);
+    let verb = match words.first().map(String::as_str) {
+        Some("WITH") => words
+            .iter()
+            .find(|w| DML_WORDS.contains(&w.as_str()))
+            .or(words.first()),
+        _ => words.first(),
+    };
+    match verb.map(String::as_str) {
+        Some("INSERT" | "REPLACE") => StatementKind::Insert,
+        Some("UPDATE") => StatementKind::Update,
+        Some("DELETE") => StatementKind::Delete,
+        Some("MERGE") => StatementKind::Merge,
+        // including SELECT ... FOR UPDATE, which is_read_query counts as a write
+        Some(first) if READ_STARTS.contains(&first) => StatementKind::Query,
+        _ => StatementKind::Other,
+    }
+}
+
+// Words that can't be a table name or alias where one is expected
+const NOT_NAMES: [&str; 24] = [
+    "SELECT",
+    "WHERE",
+    "ON",
+    "USING",
+    "JOIN",
+    "INNER",
+    "LEFT",
+    "RIGHT",
+    "FULL",
+    "CROSS",
+    "OUTER",
+    "GROUP",
+    "ORDER",
+    "HAVING",
+    "LIMIT",
+    "OFFSET",
+    "UNION",
+    "EXCEPT",
+    "INTERSECT",
+    "SET",
+    "VALUES",
+    "LATERAL",
+    "ONLY",
+    "WINDOW",
+];
+
+fn is_keyword(token: Option<&Token>, keyword: &str) -> bool {
+    matches!(token, Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+}
+
+fn name_part(token: Option<&Token>) -> Option<String> {
+    match token? {
+        Token::Word(w) if !NOT_NAMES.contains(&w.to_uppercase().as_str()) => Some(w.clone()),
+        Token::Quoted(q) => Some(q.clone()),
+        _ => None,
+    }
+}
+
+// A possibly qualified name starting at `i`: its parts and the index after it
+fn qualified_name(tokens: &[Token], mut i: usize) -> Option<(Vec<String>, usize)> {
+    let mut parts = vec![name_part(tokens.get(i))?];
+    i += 1;
+    while tokens.get(i) == Some(&Token::Symbol('.')) {
+        parts.push(name_part(tokens.get(i + 1))?);
+        i += 2;
+    }
+    Some((parts, i))
+}
+
+// Best-effort list of the tables a statement reads or writes, as (schema, table)
+// in order of first appearance. CTE names and table functions are left out.
+pub fn table_refs(sql: &str) -> Vec<(Option<String>, String)> {
+    let tokens = tokens(sql);
+    let in_call = in_call(&tokens);
+    let ctes: Vec<String> = tokens
+        .windows(3)
+        .filter(|w| is_keyword(Some(&w[1]), "AS") && w[2] == Token::Symbol('('))
+        .filter_map(|w| name_part(Some(&w[0])))
+        .map(|n| n.to_lowercase())
+        .collect();
+
+    let mut refs: Vec<(Option<String>, String)> = Vec::new();
+    let mut add = |parts: Vec<String>| {
+        let table = parts[parts.len() - 1].clone();
+        let schema = (parts.len() > 1).then(|| parts[parts.len() - 2].clone());
+        if schema.is_none() && ctes.contains(&table.to_lowercase()) {
+            return;
+        }
+        if !refs.contains(&(schema.clone(), table.clone())) {
+            refs.push((schema, table));
+        }
+    };
+
+    for (i, token) in tokens.iter().enumerate() {
+        let Token::Word(word) = token else { continue };
+        let keyword = word.to_uppercase();
+        match keyword.as_str() {
+            "INTO" | "UPDATE" | "TABLE" => {
+                // CREATE TABLE IF NOT EXISTS, DROP TABLE IF EXISTS
+                let mut j = i + 1;
+                if is_keyword(tokens.get(j), "IF") {
+                    j += 1;
+                    if is_keyword(tokens.get(j), "NOT") {
+                        j += 1;
+                    }
+                    if is_keyword(tokens.get(j), "EXISTS") {
+                        j += 1;
+                    }
+                }
+                if let Some((parts, _)) = qualified_name(&tokens, j) {
+                    add(parts);
+                }
+            }
+            // The FROM of EXTRACT(YEAR FROM ts) and the like
+            "FROM" if in_call[i] => {}
+            // FROM and JOIN may list several tables separated by commas, each
+            // with an optional alias
+            "FROM" | "JOIN" => {
+                let mut j = i + 1;
+                while let Some((parts, next)) = qualified_name(&tokens, j) {
+                    j = next;
+                    if tokens.get(j) == Some(&Token::Symbol('(')) {
+                        break; // a table function
+                    }
+                    add(parts);
+                    if is_keyword(tokens.get(j), "AS") {
+                        j += 1;
+                    }
+                    if name_part(tokens.get(j)).is_some() {
+                        j += 1;
+                    }
+                    if tokens.get(j) != Some(&Token::Symbol(',')) || keyword == "JOIN" {
+                        break;
+                    }
+                    j += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    refs
+}
+
+// Words that can't be a column where an expression is expected
+const RESERVED: [&str; 64] = [
+    "SELECT",
+    "INSERT",
+    "DELETE",
+    "MERGE",
+    "RETURNING",
+    "OVER",
+    "FROM",
+    "WHERE",
+    "AND",
+    "OR",
+    "NOT",
+    "NULL",
+    "IS",
+    "IN",
+    "LIKE",
+    "ILIKE",
+    "BETWEEN",
+    "AS",
+    "ON",
+    "JOIN",
+    "INNER",
+    "LEFT",
+    "RIGHT",
+    "FULL",
+    "CROSS",
+    "OUTER",
+    "CASE",
+    "WHEN",
+    "THEN",
+    "ELSE",
+    "END",
+    "DISTINCT",
+    "ALL",
+    "ANY",
+    "SOME",
+    "EXISTS",
+    "TRUE",
+    "FALSE",
+    "ASC",
+    "DESC",
+    "BY",
+    "GROUP",
+    "ORDER",
+    "HAVING",
+    "LIMIT",
+    "OFFSET",
+    "TOP",
+    "UNION",
+    "EXCEPT",
+    "INTERSECT",
+    "WITH",
+    "SET",
+    "VALUES",
+    "DEFAULT",
+    "ESCAPE",
+    "NULLS",
+    "INTERVAL",
+    "CURRENT_DATE",
+    "CURRENT_TIME",
+    "CURRENT_TIMESTAMP",
+    "CURRENT_USER",
+    "SESSION_USER",
+    "LOCALTIME",
+    "LOCALTIMESTAMP",
+];
+
+// Statements whose names are worth counting as columns; DDL and commands
+// like SHOW or SET are left alone
+const COLUMN_STATEMENTS: [&str; 8] = [
+    "SELECT", "WITH", "INSERT", "UPDATE", "DELETE", "MERGE", "REPLACE", "VALUES",
+];
+
+// Keywords after which an expression, and so possibly a column, starts
+const EXPRESSION_STARTS: [&str; 15] = [
+    "SELECT",
+    "DISTINCT",
+    "WHERE",
+    "AND",
+    "OR",
+    "NOT",
+    "ON",
+    "BY",
+    "SET",
+    "HAVING",
+    "WHEN",
+    "THEN",
+    "ELSE",
+    "CASE",
+    "RETURNING",
+];
+
+// Keywords inside the arguments of EXTRACT, TRIM and the like
+const ARGUMENT_KEYWORDS: [&str; 14] = [
+    "YEAR", "MONTH", "DAY", "HOUR", "MINUTE", "SECOND", "EPOCH", "WEEK", "QUARTER", "DOW", "DOY",
+    "BOTH", "LEADING", "TRAILING",
+];
+
+// For each token, whether its innermost parentheses hold the arguments of a
+// function call rather than a subquery, a column list or a grouping
+fn in_call(tokens: &[Token]) -> Vec<bool> {
+    let mut calls = Vec::new();
+    let mut inside = Vec::with_capacity(tokens.len());
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Symbol('(') => {
+                let function = matches!(
+                    i.checked_sub(1).and_then(|p| tokens.get(p)),
+                    Some(Token::Word(w)) if !RESERVED.contains(&w.to_uppercase().as_str())
+                        && !NOT_NAMES.contains(&w.to_uppercase().as_str())
+                );
+                let subquery = is_keyword(tokens.get(i + 1), "SELECT")
+                    || is_keyword(tokens.get(i + 1), "WITH");
+                calls.push(function && !subquery);
+            }
+            Token::Symbol(')') => {
+                calls.pop();
+            }
+            _ => {}
+        }
+        inside.push(calls.last().copied().unwrap_or(false));
+    }
+    inside
+}
+
+// Whether a name after `previous` sits where an expression starts
+fn starts_expression(previous: Option<&Token>) -> bool {
+    match previous {
+        Some(Token::Word(w)) => EXPRESSION_STARTS.contains(&w.to_uppercase().as_str()),
+        Some(Token::Symbol(c)) => ",(=<>+-*/%|!.".contains(*c),
+        _ => false,
+    }
+}
+
+// Keywords after which names are tables or aliases rather than columns
+const TABLE_CONTEXT: [&str; 6] = ["FROM", "JOIN", "INTO", "UPDATE", "TABLE", "USING"];
+
+// Best-effort list of the column names a statement mentions, without their
+// qualifiers, in order of first appearance. Only names where an expression or
+// a column list starts count; function names, aliases, placeholders and
+// anything in DDL are left out. Select-list aliases used later may slip in.
+pub fn column_refs(sql: &str) -> Vec<String> {
+    let tokens = tokens(sql);
+    let in_call = in_call(&tokens);
+    let mut columns: Vec<String> = Vec::new();
+    let mut in_tables = false;
+    let mut counted = None; // whether the current statement is one to look at
+    for (i, token) in tokens.iter().enumerate() {
+        if token == &Token::Symbol(';') {
+            counted = None;
+            continue;
+        }
+        let counted = *counted.get_or_insert_with(|| {
+            matches!(token, Token::Word(w) if COLUMN_STATEMENTS.contains(&w.to_uppercase().as_str()))
+        });
+        if !counted {
+            continue;
+        }
+        let name = match token {
+            Token::Word(w) => {
+                let upper = w.to_uppercase();
+                // EXTRACT(YEAR FROM ts) and TRIM(BOTH ' ' FROM name)
+                if in_call[i] && (upper == "FROM" || ARGUMENT_KEYWORDS.contains(&upper.as_str())) {
+                    continue;
+                }
+                if TABLE_CONTEXT.contains(&upper.as_str()) {
+                    in_tables = true;
+                    continue;
+                }
+                if RESERVED.contains(&upper.as_str()) || NOT_NAMES.contains(&upper.as_str()) {
+                    in_tables = false;
+                    continue;
+                }
+                w
+            }
+            Token::Quoted(q) => q,
+            // A column list follows INSERT INTO t, and subqueries follow FROM
+            Token::Symbol('(') | Token::Symbol(')') => {
+                in_tables = false;
+                continue;
+            }
+            Token::Symbol(_) => continue,
+        };
+        let previous = i.checked_sub(1).and_then(|p| tokens.get(p));
+        let next = tokens.get(i + 1);
+        let skip = in_tables
+            || !(starts_expression(previous) || (in_call[i] && is_keyword(previous, "FROM")))
+            || name.starts_with(|c: char| c.is_ascii_digit())
+            || matches!(previous, Some(Token::Symbol('@' | ':' | '$' | '?')))
+            || is_keyword(previous, "AS")
+            || matches!(next, Some(Token::Symbol('(' | '.')))
+            // a CTE name
+            || (is_keyword(next, "AS") && tokens.get(i + 2) == Some(&Token::Symbol('(')));
+        if !skip && !columns.iter().any(|c| c.eq_ignore_ascii_case(name)) {
+            columns.push(name.clone());
+        }
+    }
+    columns
+}
+
+// Rewrites a single SELECT so the server returns at most `limit` rows: LIMIT
+// is appended on Postgres and MySQL, TOP inserted on SQL Server. Returns None
+// when the statement already limits itself or isn't a plain query, so callers
+// run it unchanged.
+pub fn limit_rows(sql: &str, dialect: Dialect, limit: usize) -> Option<String> {
+    if !is_read_query(sql) {
+        return None;
+    }
+    let chars: Vec<char> = sql.chars().collect();
+    let mut tokens = spanned_tokens(&chars);
+    while matches!(tokens.last(), Some((Token::Symbol(';'), _))) {
+        tokens.pop();
+    }
+
+    // Only look at the outer query; subqueries may limit themselves freely
+    let mut depth = 0i32;
+    let mut top_level = Vec::new();
+    for (token, _) in &tokens {
+        match token {
+            Token::Symbol('(') => depth += 1,
+            Token::Symbol(')') => depth -= 1,
+            Token::Symbol(';') if depth == 0 => return None, // several statements
+            Token::Word(w) if depth == 0 => top_level.push(w.to_uppercase()),
+            _ => {}
+        }
+    }
+    let has = |word: &str| top_level.iter().any(|w| w == word);
+
+    match dialect {
+        Dialect::Postgres | Dialect::Mysql => {
+            // FOR SHARE and friends must stay last
+            let first = top_level.first()?.as_str();
+            if !matches!(first, "SELECT" | "WITH" | "VALUES" | "TABLE")
+                || has("LIMIT")
+                || has("FETCH")
+                || has("FOR")
+            {
+                return None;
+            }
+            let end = tokens.last()?.1.end;
+            let body: String = chars[..end].iter().collect();
+            // On its own line, so a trailing comment can't swallow it
+            Some(format!("{}\nLIMIT {}", body, limit))
+        }
+        Dialect::Mssql => {
+            // TOP on the first SELECT of a UNION would only cut that branch
+            let compound = has("UNION") || has("EXCEPT") || has("INTERSECT");
+            if top_level.first()? != "SELECT" || has("TOP") || has("OFFSET") || compound {
+                return None;
+            }
+            let mut insert_at = tokens[0].1.end;
+            if matches!(&tokens.get(1), Some((Token::Word(w), _)) if w.eq_ignore_ascii_case("DISTINCT") || w.eq_ignore_ascii_case("ALL"))
+            {
+                insert_at = tokens[1].1.end;
+            }
+            let head: String = chars[..insert_at].iter().collect();
+            let tail: String = chars[insert_at..].iter().collect();
+            Some(format!("{} TOP ({}){}", head, limit, tail))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DdlAction {
+    Create,
+    CreateOrReplace, // also SQL Server's CREATE OR ALTER
+    Alter,
+    Drop,
+    Truncate,
+}
+
+// The first change of an ALTER TABLE
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TableChange {
+    AddColumn { column: String },
+    DropColumn { column: String },
+    AlterColumn { column: String }, // ALTER COLUMN, MySQL's MODIFY
+    RenameTo { name: String },
+    RenameColumn { from: String, to: String },
+}
+
+// What a DDL statement acts on, e.g. TABLE sales.orders
+#[derive(Debug, Clone, Serialize)]
+pub struct DdlTarget {
+    pub action: DdlAction,
+    pub object: String, // upper-case, e.g. "TABLE", "MATERIALIZED VIEW"
+    pub schema: Option<String>,
+    pub name: String,
+    // The table of an index, when the statement names it
+    pub table: Option<(Option<String>, String)>,
+    pub change: Option<TableChange>,
+}
+
+// Words between CREATE and the object type
+const CREATE_MODIFIERS: [&str; 9] = [
+    "TEMP",
+    "TEMPORARY",
+    "UNIQUE",
+    "CLUSTERED",
+    "NONCLUSTERED",
+    "UNLOGGED",
+    "GLOBAL",
+    "LOCAL",
+    "RECURSIVE",
+];
+
+// Words after ALTER TABLE ... ADD / DROP that mean a constraint or index, not a column
+const NOT_COLUMNS: [&str; 8] = [
+    "CONSTRAINT",
+    "PRIMARY",
+    "FOREIGN",
+    "UNIQUE",
+    "CHECK",
+    "INDEX",
+    "KEY",
+    "PERIOD",
+];
+
+fn split_name(mut parts: Vec<String>) -> (Option<String>, String) {
+    let name = parts.pop().unwrap_or_default();
+    (parts.pop(), name)
+}
+
+// Skips IF EXISTS / IF NOT EXISTS at `i`
+fn skip_if_exists(tokens: &[Token], mut i: usize) -> usize {
+    if is_keyword(tokens.get(i), "IF") {
+        i += 1;
+        if is_keyword(tokens.get(i), "NOT") {
+            i += 1;
+        }
+        if is_keyword(tokens.get(i), "EXISTS") {
+            i += 1;
+        }
+    }
+    i
+}
+
+fn table_change(tokens: &[Token], mut i: usize) -> Option<TableChange> {
+    let word = |i: usize| match tokens.get(i) {
+        Some(Token::Word(w)) => w.to_uppercase(),
+        _ => String::new(),
+    };
+    let action = word(i);
+    i += 1;
+    match action.as_str() {
+        "ADD" | "DROP" | "ALTER" | "MODIFY" => {
+            if NOT_COLUMNS.contains(&word(i).as_str()) {
+                return None;
+            }
+            if word(i) == "COLUMN" {
+                i += 1;
+            }
+            let column = name_part(tokens.get(skip_if_exists(tokens, i)))?;
+            Some(match action.as_str() {
+                "ADD" => TableChange::AddColumn { column },
+                "DROP" => TableChange::DropColumn { column },
+                _ => TableChange::AlterColumn { column },
+            })
+        }
+        "RENAME" => {
+            if is_keyword(tokens.get(i), "TO") {
+                let (_, name) = split_name(qualified_name(tokens, i + 1)?.0);
+                return Some(TableChange::RenameTo { name });
+            }
+            if word(i) == "COLUMN" {
+                i += 1;
+            }
+            let from = name_part(tokens.get(i))?;
+            if !is_keyword(tokens.get(i + 1), "TO") {
+                return None;
+            }
+            let to = name_part(tokens.get(i + 2))?;
+            Some(TableChange::RenameColumn { from, to })
+        }
+        _ => None,
+    }
+}
+
+// Parses the head of a CREATE, ALTER, DROP or TRUNCATE statement; None for
+// anything else or when the object can't be made out. Names come back as the
+// server stores them, so unquoted ones are folded to lower case on Postgres.
+pub fn ddl_target(sql: &str, dialect: Dialect) -> Option<DdlTarget> {
+    let tokens: Vec<Token> = tokens(sql)
+        .into_iter()
+        .map(|t| match t {
+            Token::Word(w) if dialect == Dialect::Postgres => Token::Word(w.to_lowercase()),
+            t => t,
+        })
+        .collect();
+    let word = |i: usize| match tokens.get(i) {
+        Some(Token::Word(w)) => w.to_uppercase(),
+        _ => String::new(),
+    };
+    let mut action = match word(0).as_str() {
+        "CREATE" => DdlAction::Create,
+        "ALTER" => DdlAction::Alter,
+        "DROP" => DdlAction::Drop,
+        "TRUNCATE" => DdlAction::Truncate,
+        _ => return None,
+    };
+    let mut i = 1;
+    if action == DdlAction::Create && word(1) == "OR" {
+        action = DdlAction::CreateOrReplace;
+        i = 3;
+    }
+    while CREATE_MODIFIERS.contains(&word(i).as_str()) {
+        i += 1;
+    }
+    let mut object = match (action, word(i).as_str()) {
+        (DdlAction::Truncate, "TABLE") => {
+            i += 1;
+            "TABLE".to_string()
+        }
+        (DdlAction::Truncate, _) => "TABLE".to_string(),
+        (_, "PROC") => {
+            i += 1;
+            "PROCEDURE".to_string()
+        }
+        (_, "") => return None,
+        (_, object) => {
+            i += 1;
+            object.to_string()
+        }
+    };
+    if object == "MATERIALIZED" && word(i) == "VIEW" {
+        object = "MATERIALIZED VIEW".to_string();
+        i += 1;
+    }
+    i = skip_if_exists(&tokens, i);
+    if word(i) == "CONCURRENTLY" || word(i) == "ONLY" {
+        i += 1;
+    }
+    i = skip_if_exists(&tokens, i);
+    let (parts, mut next) = qualified_name(&tokens, i)?;
+    let (schema, name) = split_name(parts);
+
+    let mut table = None;
+    let mut change = None;
+    if object == "INDEX" && is_keyword(tokens.get(next), "ON") {
+        next += 1;
+        if word(next) == "ONLY" {
+            next += 1;
+        }
+        table = qualified_name(&tokens, next).map(|(parts, _)| split_name(parts));
+    }
+    if object == "TABLE" && action == DdlAction::Alter {
+        change = table_change(&tokens, next);
+    }
+    Some(DdlTarget {
+        action,
+        object,
+        schema,
+        name,
+        table,
+        change,
+    })
+}
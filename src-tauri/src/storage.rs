@@ -0,0 +1,40 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::Manager;
+
+// Path of a JSON store inside the app data directory
+pub fn data_file(app: &tauri::AppHandle, file: &str) -> Result<PathBuf, String> {
+    Ok(app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join(file))
+}
+
+// A store that hasn't been written yet reads as empty
+pub fn read_json<T: DeserializeOwned + Default>(path: &Path) -> Result<T, String> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let json = fs::read_to_string(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Failed to parse {:?}: {}", path, e))
+}
+
+// Written to a temporary file beside the store and renamed over it, so a
+// crash mid-write leaves the previous contents rather than a truncated file
+pub fn write_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string_pretty(value).map_err(|e| e.to_string())?;
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(format!(".{}.tmp", uuid::Uuid::new_v4()));
+    let temp = PathBuf::from(temp);
+    let written = fs::write(&temp, json).and_then(|_| fs::rename(&temp, path));
+    written.map_err(|e| {
+        let _ = fs::remove_file(&temp);
+        format!("Failed to write to {:?}: {}", path, e)
+    })
+}
@@ -0,0 +1,115 @@
+// System-versioned tables: SQL Server temporal tables and MariaDB system
+// versioning, and point-in-time queries against them
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::Row;
+
+use crate::db::{DbClient, Dialect};
+use crate::edits::TableRef;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TemporalTable {
+    pub schema: String,
+    pub table: String,
+    // SQL Server keeps old row versions in a separate table; MariaDB keeps
+    // them in the table itself, so these are None there
+    pub history_schema: Option<String>,
+    pub history_table: Option<String>,
+    pub period_start: Option<String>,
+    pub period_end: Option<String>,
+}
+
+const MSSQL_TEMPORAL_SQL: &str = "
+SELECT s.name, t.name, hs.name, h.name, cs.name, ce.name
+FROM sys.tables t
+JOIN sys.schemas s ON s.schema_id = t.schema_id
+LEFT JOIN sys.tables h ON h.object_id = t.history_table_id
+LEFT JOIN sys.schemas hs ON hs.schema_id = h.schema_id
+LEFT JOIN sys.periods p ON p.object_id = t.object_id
+LEFT JOIN sys.columns cs ON cs.object_id = p.object_id AND cs.column_id = p.start_column_id
+LEFT JOIN sys.columns ce ON ce.object_id = p.object_id AND ce.column_id = p.end_column_id
+WHERE t.temporal_type = 2
+ORDER BY s.name, t.name";
+
+// Plain MySQL has no system versioning and simply returns nothing here
+const MARIADB_VERSIONED_SQL: &str = "
+SELECT TABLE_SCHEMA, TABLE_NAME
+FROM information_schema.TABLES
+WHERE TABLE_TYPE = 'SYSTEM VERSIONED' AND TABLE_SCHEMA = DATABASE()
+ORDER BY TABLE_NAME";
+
+pub async fn get_temporal_tables(
+    client: &DbClient,
+    schema: Option<String>,
+) -> Result<Vec<TemporalTable>, String> {
+    let tables = match client {
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
+            let rows = client
+                .query(MSSQL_TEMPORAL_SQL, &[])
+                .await
+                .map_err(|e| e.to_string())?
+                .into_first_result()
+                .await
+                .map_err(|e| e.to_string())?;
+            let text =
+                |r: &tiberius::Row, i| r.try_get::<&str, _>(i).ok().flatten().map(str::to_string);
+            rows.iter()
+                .map(|r| TemporalTable {
+                    schema: text(r, 0).unwrap_or_default(),
+                    table: text(r, 1).unwrap_or_default(),
+                    history_schema: text(r, 2),
+                    history_table: text(r, 3),
+                    period_start: text(r, 4),
+                    period_end: text(r, 5),
+                })
+                .collect()
+        }
+        DbClient::Mysql(pool) => sqlx::query(MARIADB_VERSIONED_SQL)
+            .fetch_all(pool)
+            .await
+            .map_err(|e| e.to_string())?
+            .iter()
+            .map(|r| TemporalTable {
+                schema: r.get(0),
+                table: r.get(1),
+                history_schema: None,
+                history_table: None,
+                // Implicit period columns are hidden from information_schema
+                period_start: None,
+                period_end: None,
+            })
+            .collect(),
+        // No built-in system versioning
+        DbClient::Postgres(_) => Vec::new(),
+        _ => return Err("Temporal tables are not supported for this database type".to_string()),
+    };
+    Ok(tables
+        .into_iter()
+        .filter(|t: &TemporalTable| schema.as_deref().map_or(true, |s| t.schema == s))
+        .collect())
+}
+
+// SELECT of `table` as it was at `at`
+pub fn as_of_sql(dialect: Dialect, table: &TableRef, at: DateTime<Utc>) -> Result<String, String> {
+    let name = table.qualified(dialect);
+    match dialect {
+        // Period columns are UTC datetime2
+        Dialect::Mssql => Ok(format!(
+            "SELECT * FROM {} FOR SYSTEM_TIME AS OF '{}'",
+            name,
+            at.format("%Y-%m-%dT%H:%M:%S%.6f")
+        )),
+        // Row timestamps are in the session time zone, which FROM_UNIXTIME
+        // converts to for us
+        Dialect::Mysql => Ok(format!(
+            "SELECT * FROM {} FOR SYSTEM_TIME AS OF TIMESTAMP FROM_UNIXTIME({}.{:06})",
+            name,
+            at.timestamp(),
+            at.timestamp_subsec_micros()
+        )),
+        Dialect::Postgres => {
+            Err("Postgres has no system-versioned tables to query AS OF".to_string())
+        }
+    }
+}
@@ -0,0 +1,132 @@
+// Structured TLS settings for saved connections, applied on top of whatever
+// the URL itself says (sslmode=..., ssl-mode=...). SQL Server has its own
+// encryption levels, the certificate files are shared.
+use serde::{Deserialize, Serialize};
+use sqlx::mysql::{MySqlConnectOptions, MySqlSslMode};
+use sqlx::postgres::{PgConnectOptions, PgSslMode};
+use std::path::Path;
+use std::str::FromStr;
+use tiberius::{Config, EncryptionLevel};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SslMode {
+    Disable,
+    Prefer,
+    Require,
+    VerifyCa,   // the server certificate must chain to the root CA
+    VerifyFull, // as VerifyCa, and its name must match the host
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MssqlEncryption {
+    Required,               // encrypted, certificate validated
+    TrustServerCertificate, // encrypted, any certificate accepted
+    Off,                    // only the login is encrypted
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsOptions {
+    pub mode: Option<SslMode>, // None leaves the URL's mode alone
+    // SQL Server; None trusts any certificate, as connections always did
+    pub encryption: Option<MssqlEncryption>,
+    pub root_cert: Option<String>,
+    pub client_cert: Option<String>,
+    pub client_key: Option<String>,
+}
+
+impl TlsOptions {
+    // Catches typos in certificate paths before the driver turns them into
+    // a vague handshake error
+    fn check(&self) -> Result<(), String> {
+        if self.client_cert.is_some() != self.client_key.is_some() {
+            return Err("A client certificate needs its key, and the other way round".to_string());
+        }
+        let files = [
+            ("Root certificate", &self.root_cert),
+            ("Client certificate", &self.client_cert),
+            ("Client key", &self.client_key),
+        ];
+        for (what, file) in files {
+            if let Some(file) = file {
+                if !Path::new(file).is_file() {
+                    return Err(format!("{} not found: {}", what, file));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn postgres_options(url: &str, tls: &TlsOptions) -> Result<PgConnectOptions, String> {
+    tls.check()?;
+    let mut options = PgConnectOptions::from_str(url).map_err(|e| e.to_string())?;
+    if let Some(mode) = tls.mode {
+        options = options.ssl_mode(match mode {
+            SslMode::Disable => PgSslMode::Disable,
+            SslMode::Prefer => PgSslMode::Prefer,
+            SslMode::Require => PgSslMode::Require,
+            SslMode::VerifyCa => PgSslMode::VerifyCa,
+            SslMode::VerifyFull => PgSslMode::VerifyFull,
+        });
+    }
+    if let Some(cert) = &tls.root_cert {
+        options = options.ssl_root_cert(cert);
+    }
+    if let Some(cert) = &tls.client_cert {
+        options = options.ssl_client_cert(cert);
+    }
+    if let Some(key) = &tls.client_key {
+        options = options.ssl_client_key(key);
+    }
+    Ok(options)
+}
+
+pub fn mysql_options(url: &str, tls: &TlsOptions) -> Result<MySqlConnectOptions, String> {
+    tls.check()?;
+    let mut options = MySqlConnectOptions::from_str(url).map_err(|e| e.to_string())?;
+    if let Some(mode) = tls.mode {
+        options = options.ssl_mode(match mode {
+            SslMode::Disable => MySqlSslMode::Disabled,
+            SslMode::Prefer => MySqlSslMode::Preferred,
+            SslMode::Require => MySqlSslMode::Required,
+            SslMode::VerifyCa => MySqlSslMode::VerifyCa,
+            SslMode::VerifyFull => MySqlSslMode::VerifyIdentity,
+        });
+    }
+    if let Some(cert) = &tls.root_cert {
+        options = options.ssl_ca(cert);
+    }
+    if let Some(cert) = &tls.client_cert {
+        options = options.ssl_client_cert(cert);
+    }
+    if let Some(key) = &tls.client_key {
+        options = options.ssl_client_key(key);
+    }
+    Ok(options)
+}
+
+// The root certificate, when set, is the CA bundle the server certificate is
+// validated against in addition to the system store
+pub fn apply_mssql(config: &mut Config, tls: &TlsOptions) -> Result<(), String> {
+    tls.check()?;
+    match tls.encryption {
+        Some(MssqlEncryption::Required) => {
+            config.encryption(EncryptionLevel::Required);
+            if let Some(ca) = &tls.root_cert {
+                config.trust_cert_ca(ca);
+            }
+        }
+        Some(MssqlEncryption::TrustServerCertificate) | None => {
+            config.encryption(EncryptionLevel::Required);
+            config.trust_cert();
+        }
+        Some(MssqlEncryption::Off) => {
+            config.encryption(EncryptionLevel::Off);
+            config.trust_cert();
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,194 @@
+// Interactive transactions: BEGIN pins a dedicated connection to the connection
+// name and queries run there until COMMIT or ROLLBACK releases it
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::pool::PoolOptions;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+
+use crate::db::DbClient;
+
+struct OpenTransaction {
+    // A single-connection pool, so every existing query path works on it unchanged
+    client: DbClient,
+    started_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TransactionInfo {
+    pub started_at: DateTime<Utc>,
+}
+
+// Open transactions by connection name
+#[derive(Default)]
+pub struct Transactions {
+    open: StdMutex<HashMap<String, OpenTransaction>>,
+}
+
+// Pool of exactly one connection that is never replaced: if it drops, the
+// server has rolled the transaction back, so later statements must fail
+// rather than quietly run outside it
+fn dedicated_pool<DB: sqlx::Database>() -> PoolOptions<DB> {
+    let connected = Arc::new(AtomicBool::new(false));
+    PoolOptions::new()
+        .max_connections(1)
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .after_connect(move |_, _| {
+            let reconnect = connected.swap(true, Ordering::SeqCst);
+            Box::pin(async move {
+                if reconnect {
+                    return Err(sqlx::Error::Protocol(
+                        "The transaction's connection was lost and it was rolled back".to_string(),
+                    ));
+                }
+                Ok(())
+            })
+        })
+}
+
+// A client on a single connection of its own, for statements that must share
+// one session (SET, temp tables)
+pub async fn dedicated_client(primary: &DbClient) -> Result<DbClient, String> {
+    match primary {
+        DbClient::Postgres(pool) => Ok(DbClient::Postgres(
+            dedicated_pool()
+                .connect_with((*pool.connect_options()).clone())
+                .await
+                .map_err(|e| e.to_string())?,
+        )),
+        DbClient::Mysql(pool) => Ok(DbClient::Mysql(
+            dedicated_pool()
+                .connect_with((*pool.connect_options()).clone())
+                .await
+                .map_err(|e| e.to_string())?,
+        )),
+        DbClient::Mssql(pool) => Ok(DbClient::Mssql(pool.dedicated().await?)),
+        _ => Err("This database type has no SQL sessions".to_string()),
+    }
+}
+
+async fn run(client: &DbClient, sql: &str) -> Result<(), String> {
+    match client {
+        DbClient::Postgres(pool) => {
+            sqlx::raw_sql(sql)
+                .execute(pool)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        DbClient::Mysql(pool) => {
+            sqlx::raw_sql(sql)
+                .execute(pool)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        DbClient::Mssql(pool) => {
+            let mut client = pool.get().await?;
+            client
+                .simple_query(sql)
+                .await
+                .map_err(|e| e.to_string())?
+                .into_results()
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        _ => return Err("Transactions are not supported for this database type".to_string()),
+    }
+    Ok(())
+}
+
+impl Transactions {
+    // Client a query should run on while a transaction is open
+    pub fn client(&self, name: &str) -> Option<DbClient> {
+        self.open
+            .lock()
+            .unwrap()
+            .get(name)
+            .map(|tx| tx.client.clone())
+    }
+
+    pub fn info(&self, name: &str) -> Option<TransactionInfo> {
+        self.open
+            .lock()
+            .unwrap()
+            .get(name)
+            .map(|tx| TransactionInfo {
+                started_at: tx.started_at,
+            })
+    }
+
+    pub async fn begin(&self, name: &str, primary: &DbClient) -> Result<TransactionInfo, String> {
+        if self.open.lock().unwrap().contains_key(name) {
+            return Err(format!("{} already has an open transaction", name));
+        }
+        if primary.dialect().is_none() {
+            return Err("Transactions are not supported for this database type".to_string());
+        }
+        let client = dedicated_client(primary).await?;
+        let begin = match client {
+            DbClient::Mssql(_) => "BEGIN TRANSACTION",
+            _ => "BEGIN",
+        };
+        run(&client, begin).await?;
+
+        let started_at = Utc::now();
+        let inserted = {
+            let mut open = self.open.lock().unwrap();
+            let free = !open.contains_key(name);
+            if free {
+                let tx = OpenTransaction {
+                    client: client.clone(),
+                    started_at,
+                };
+                open.insert(name.to_string(), tx);
+            }
+            free
+        };
+        if !inserted {
+            // Lost a race with another BEGIN; the dedicated connection just goes away
+            return Err(format!("{} already has an open transaction", name));
+        }
+        Ok(TransactionInfo { started_at })
+    }
+
+    pub async fn commit(&self, name: &str) -> Result<(), String> {
+        let client = self.client(name).ok_or("No transaction is open")?;
+        // Postgres answers COMMIT of a failed transaction by silently rolling
+        // back, so check first and leave it open for an explicit rollback
+        if let DbClient::Postgres(pool) = &client {
+            sqlx::raw_sql("SELECT 1")
+                .execute(pool)
+                .await
+                .map_err(|e| format!("{}; roll the transaction back", e))?;
+        }
+        let result = run(&client, "COMMIT").await;
+        self.open.lock().unwrap().remove(name);
+        close(client).await;
+        result
+    }
+
+    pub async fn rollback(&self, name: &str) -> Result<(), String> {
+        let client = self.client(name).ok_or("No transaction is open")?;
+        let result = run(&client, "ROLLBACK").await;
+        self.open.lock().unwrap().remove(name);
+        close(client).await;
+        result
+    }
+
+    // Forgets the transaction without ending it; for connections being closed,
+    // where the server rolls it back when the session goes away
+    pub fn discard(&self, name: &str) {
+        self.open.lock().unwrap().remove(name);
+    }
+}
+
+// Dedicated pools are closed right away instead of waiting for the last clone
+// to drop; SQL Server pools have no close and go with their last clone
+async fn close(client: DbClient) {
+    match client {
+        DbClient::Postgres(pool) => pool.close().await,
+        DbClient::Mysql(pool) => pool.close().await,
+        _ => {}
+    }
+}
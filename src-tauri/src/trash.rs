@@ -0,0 +1,70 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+use crate::storage;
+
+pub const TRASH_FILE: &str = "trash.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrashKind {
+    Connection,
+    Bookmark,
+    Snippet,
+}
+
+// A deleted saved item, kept whole so it can be put back exactly as it was
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashItem {
+    pub id: String,
+    pub kind: TrashKind,
+    pub name: String,
+    pub deleted_at: DateTime<Utc>,
+    pub item: Value,
+}
+
+impl TrashItem {
+    pub fn into_item<T: serde::de::DeserializeOwned>(self) -> Result<T, String> {
+        serde_json::from_value(self.item).map_err(|e| format!("Trashed item is corrupt: {}", e))
+    }
+}
+
+pub fn list(path: &Path) -> Result<Vec<TrashItem>, String> {
+    let mut items: Vec<TrashItem> = storage::read_json(path)?;
+    items.sort_by_key(|i| std::cmp::Reverse(i.deleted_at));
+    Ok(items)
+}
+
+pub fn put<T: Serialize>(path: &Path, kind: TrashKind, name: &str, item: &T) -> Result<(), String> {
+    let mut items: Vec<TrashItem> = storage::read_json(path)?;
+    items.push(TrashItem {
+        id: uuid::Uuid::new_v4().to_string(),
+        kind,
+        name: name.to_string(),
+        deleted_at: Utc::now(),
+        item: serde_json::to_value(item).map_err(|e| e.to_string())?,
+    });
+    storage::write_json(path, &items)
+}
+
+pub fn get(path: &Path, id: &str) -> Result<TrashItem, String> {
+    let items: Vec<TrashItem> = storage::read_json(path)?;
+    items
+        .into_iter()
+        .find(|i| i.id == id)
+        .ok_or_else(|| "Item not found in trash".to_string())
+}
+
+// Drop the given items, or everything when `ids` is None; returns how many were removed
+pub fn purge(path: &Path, ids: Option<&[String]>) -> Result<usize, String> {
+    let mut items: Vec<TrashItem> = storage::read_json(path)?;
+    let before = items.len();
+    match ids {
+        Some(ids) => items.retain(|i| !ids.contains(&i.id)),
+        None => items.clear(),
+    }
+    storage::write_json(path, &items)?;
+    Ok(before - items.len())
+}
@@ -0,0 +1,126 @@
+// How often each table and column shows up in queries, per connection, so
+// autocomplete can rank the objects a user actually works with first
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::sqltext;
+use crate::storage;
+
+pub const USAGE_FILE: &str = "object_usage.json";
+
+// Per connection; the lowest weighted entries are dropped beyond this
+const MAX_USAGE_ENTRIES: usize = 2000;
+
+// A use counts half as much after this many days
+const HALF_LIFE_DAYS: f64 = 30.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UsageKind {
+    Table,
+    Column,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectUsage {
+    pub kind: UsageKind,
+    pub schema: Option<String>, // tables only, as written in the query
+    pub name: String,
+    pub uses: u64,
+    pub last_used: DateTime<Utc>,
+    // Decayed use count for ranking; computed when listed, not stored
+    #[serde(default, skip_deserializing)]
+    pub weight: f64,
+}
+
+impl ObjectUsage {
+    fn weight_at(&self, now: DateTime<Utc>) -> f64 {
+        let age_days = (now - self.last_used).num_seconds().max(0) as f64 / 86_400.0;
+        self.uses as f64 * 0.5f64.powf(age_days / HALF_LIFE_DAYS)
+    }
+
+    // Identifiers compare case-insensitively, as in the recent tables list
+    fn is(&self, kind: UsageKind, schema: Option<&str>, name: &str) -> bool {
+        self.kind == kind
+            && self.name.eq_ignore_ascii_case(name)
+            && self.schema.as_deref().map(str::to_lowercase) == schema.map(str::to_lowercase)
+    }
+}
+
+type Usage = HashMap<String, Vec<ObjectUsage>>;
+
+// Counts the tables and columns referenced by `sql` as used just now
+pub fn record(path: &Path, connection: &str, sql: &str) -> Result<(), String> {
+    let tables = sqltext::table_refs(sql)
+        .into_iter()
+        .map(|(schema, table)| (UsageKind::Table, schema, table));
+    let columns = sqltext::column_refs(sql)
+        .into_iter()
+        .map(|column| (UsageKind::Column, None, column));
+    let refs: Vec<_> = tables.chain(columns).collect();
+    if refs.is_empty() {
+        return Ok(());
+    }
+
+    let mut all: Usage = storage::read_json(path)?;
+    let entries = all.entry(connection.to_string()).or_default();
+    let now = Utc::now();
+    for (kind, schema, name) in refs {
+        match entries
+            .iter_mut()
+            .find(|e| e.is(kind, schema.as_deref(), &name))
+        {
+            Some(entry) => {
+                entry.uses += 1;
+                entry.last_used = now;
+            }
+            None => entries.push(ObjectUsage {
+                kind,
+                schema,
+                name,
+                uses: 1,
+                last_used: now,
+                weight: 0.0,
+            }),
+        }
+    }
+    if entries.len() > MAX_USAGE_ENTRIES {
+        rank(entries, now);
+        entries.truncate(MAX_USAGE_ENTRIES);
+    }
+    storage::write_json(path, &all)
+}
+
+fn rank(entries: &mut [ObjectUsage], now: DateTime<Utc>) {
+    for entry in entries.iter_mut() {
+        entry.weight = entry.weight_at(now);
+    }
+    entries.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+}
+
+// Highest weight first, optionally only tables or only columns
+pub fn ranking(
+    path: &Path,
+    connection: &str,
+    kind: Option<UsageKind>,
+    limit: Option<usize>,
+) -> Result<Vec<ObjectUsage>, String> {
+    let mut all: Usage = storage::read_json(path)?;
+    let mut entries = all.remove(connection).unwrap_or_default();
+    entries.retain(|e| kind.map_or(true, |k| e.kind == k));
+    rank(&mut entries, Utc::now());
+    if let Some(limit) = limit {
+        entries.truncate(limit);
+    }
+    Ok(entries)
+}
+
+pub fn clear(path: &Path, connection: &str) -> Result<(), String> {
+    let mut all: Usage = storage::read_json(path)?;
+    if all.remove(connection).is_some() {
+        storage::write_json(path, &all)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,756 @@
+// Type-driven decoding of result values. Each engine has a registry mapping the
+// driver's column type name to a decoder, so supporting a new type means
+// registering one function instead of growing a try_get chain.
+use bigdecimal::BigDecimal;
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::ser::SerializeMap;
+use serde::Serialize;
+use serde_json::{json, Value};
+use sqlx::postgres::PgValueFormat;
+use sqlx::{Column, Row, TypeInfo, ValueRef};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::OnceLock;
+use tiberius::ColumnData;
+
+// One result cell. A real NULL serializes as `null`, a value as itself, and a
+// value the decoder couldn't handle as `{"$decode_error": "<type name>"}`, so
+// the grid can tell them apart.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cell {
+    Null,
+    Value(Value),
+    DecodeError { type_name: String },
+}
+
+impl Cell {
+    pub fn value(&self) -> Option<&Value> {
+        match self {
+            Cell::Value(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    // Plain JSON for previews and sampling; undecodable cells become null
+    pub fn into_value(self) -> Value {
+        match self {
+            Cell::Value(v) => v,
+            _ => Value::Null,
+        }
+    }
+
+    // Plain JSON for exports and the undo journal, where a value that couldn't
+    // be read mustn't be written out as null
+    pub fn try_into_value(self) -> Result<Value, String> {
+        match self {
+            Cell::Null => Ok(Value::Null),
+            Cell::Value(v) => Ok(v),
+            Cell::DecodeError { type_name } => {
+                Err(format!("A value of type {} could not be read", type_name))
+            }
+        }
+    }
+}
+
+impl Serialize for Cell {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Cell::Null => serializer.serialize_none(),
+            Cell::Value(v) => v.serialize(serializer),
+            Cell::DecodeError { type_name } => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("$decode_error", type_name)?;
+                map.end()
+            }
+        }
+    }
+}
+
+// Resolved zone used while decoding a result set
+#[derive(Clone, Copy, Debug)]
+pub enum RenderZone {
+    Fixed(FixedOffset),
+    Local,
+}
+
+impl RenderZone {
+    pub fn utc() -> Self {
+        RenderZone::Fixed(FixedOffset::east_opt(0).unwrap())
+    }
+
+    pub fn render(&self, value: DateTime<Utc>) -> String {
+        match self {
+            RenderZone::Fixed(offset) => value.with_timezone(offset).to_rfc3339(),
+            RenderZone::Local => value.with_timezone(&chrono::Local).to_rfc3339(),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            RenderZone::Fixed(offset) => offset.to_string(),
+            RenderZone::Local => "local".to_string(),
+        }
+    }
+}
+
+// Largest integer a JavaScript number holds exactly (2^53 - 1)
+pub const MAX_SAFE_INTEGER: i64 = 9_007_199_254_740_991;
+
+pub fn int64_value(v: i64) -> Value {
+    if (-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&v) {
+        json!(v)
+    } else {
+        json!(v.to_string())
+    }
+}
+
+// Binary values print as hex after the prefix of their server's literals,
+// e.g. \x0a1b on Postgres and 0x0A1B on MySQL and SQL Server
+fn hex_text(prefix: &str, bytes: &[u8], upper: bool) -> String {
+    let mut text = String::with_capacity(prefix.len() + bytes.len() * 2);
+    text.push_str(prefix);
+    for b in bytes {
+        text.push_str(&match upper {
+            true => format!("{:02X}", b),
+            false => format!("{:02x}", b),
+        });
+    }
+    text
+}
+
+// Bytes of a type nothing is registered for: as text when they are some,
+// else as hex
+fn bytes_value(bytes: &[u8]) -> Value {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => json!(text),
+        Err(_) => json!(hex_text("0x", bytes, true)),
+    }
+}
+
+pub fn uint64_value(v: u64) -> Value {
+    if v <= MAX_SAFE_INTEGER as u64 {
+        json!(v)
+    } else {
+        json!(v.to_string())
+    }
+}
+
+// Decodes column `i` of a row; `None` means the value could not be decoded
+pub type Decoder<R> = fn(&R, usize, RenderZone) -> Option<Value>;
+
+pub struct SerializerRegistry<R> {
+    decoders: HashMap<String, Decoder<R>>,
+    // Tried for type names nothing is registered for
+    fallback: Option<Decoder<R>>,
+}
+
+impl<R> Default for SerializerRegistry<R> {
+    fn default() -> Self {
+        Self {
+            decoders: HashMap::new(),
+            fallback: None,
+        }
+    }
+}
+
+impl<R> SerializerRegistry<R> {
+    pub fn register(&mut self, type_names: &[&str], decoder: Decoder<R>) -> &mut Self {
+        for name in type_names {
+            self.decoders.insert(name.to_string(), decoder);
+        }
+        self
+    }
+
+    pub fn fallback(&mut self, decoder: Decoder<R>) -> &mut Self {
+        self.fallback = Some(decoder);
+        self
+    }
+
+    pub fn decoder(&self, type_name: &str) -> Option<Decoder<R>> {
+        self.decoders.get(type_name).copied().or(self.fallback)
+    }
+
+    // Decode a non-null value
+    pub fn decode(&self, type_name: &str, row: &R, i: usize, zone: RenderZone) -> Cell {
+        match self.decoder(type_name).and_then(|d| d(row, i, zone)) {
+            Some(value) => Cell::Value(value),
+            None => Cell::DecodeError {
+                type_name: type_name.to_string(),
+            },
+        }
+    }
+}
+
+type PgRow = sqlx::postgres::PgRow;
+type MySqlRow = sqlx::mysql::MySqlRow;
+
+pub fn pg_registry() -> &'static SerializerRegistry<PgRow> {
+    static REGISTRY: OnceLock<SerializerRegistry<PgRow>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut registry = SerializerRegistry::<PgRow>::default();
+        registry
+            .register(&["BOOL"], |row, i, _| {
+                row.try_get::<bool, _>(i).ok().map(|v| json!(v))
+            })
+            .register(&["INT2"], |row, i, _| {
+                row.try_get::<i16, _>(i).ok().map(|v| json!(v))
+            })
+            .register(&["INT4"], |row, i, _| {
+                row.try_get::<i32, _>(i).ok().map(|v| json!(v))
+            })
+            .register(&["INT8"], |row, i, _| {
+                row.try_get::<i64, _>(i).ok().map(int64_value)
+            })
+            .register(&["FLOAT4"], |row, i, _| {
+                row.try_get::<f32, _>(i).ok().map(|v| json!(v))
+            })
+            .register(&["FLOAT8"], |row, i, _| {
+                row.try_get::<f64, _>(i).ok().map(|v| json!(v))
+            })
+            .register(&["NUMERIC"], |row, i, _| {
+                row.try_get::<BigDecimal, _>(i)
+                    .ok()
+                    .map(|v| json!(v.to_plain_string()))
+            })
+            .register(&["MONEY"], |row, i, _| {
+                // lc_monetary almost always uses two fractional digits
+                row.try_get::<sqlx::postgres::types::PgMoney, _>(i)
+                    .ok()
+                    .map(|v| json!(v.to_bigdecimal(2).to_plain_string()))
+            })
+            .register(&["TEXT", "VARCHAR", "CHAR", "NAME"], |row, i, _| {
+                row.try_get::<String, _>(i).ok().map(Value::String)
+            })
+            .register(&["UUID"], |row, i, _| {
+                row.try_get::<uuid::Uuid, _>(i)
+                    .ok()
+                    .map(|v| json!(v.to_string()))
+            })
+            .register(&["TIMESTAMPTZ"], |row, i, zone| {
+                row.try_get::<DateTime<Utc>, _>(i)
+                    .ok()
+                    .map(|v| json!(zone.render(v)))
+            })
+            .register(&["TIMESTAMP"], |row, i, _| {
+                row.try_get::<chrono::NaiveDateTime, _>(i)
+                    .ok()
+                    .map(|v| json!(v.to_string()))
+            })
+            .register(&["DATE"], |row, i, _| {
+                row.try_get::<chrono::NaiveDate, _>(i)
+                    .ok()
+                    .map(|v| json!(v.to_string()))
+            })
+            .register(&["TIME"], |row, i, _| {
+                row.try_get::<chrono::NaiveTime, _>(i)
+                    .ok()
+                    .map(|v| json!(v.to_string()))
+            })
+            .register(&["JSON", "JSONB"], |row, i, _| {
+                row.try_get::<Value, _>(i).ok()
+            })
+            .register(&["INET", "CIDR"], |row, i, _| pg_wire(row, i, inet_text))
+            .register(&["MACADDR", "MACADDR8"], |row, i, _| {
+                pg_wire(row, i, macaddr_text)
+            })
+            .register(&["INTERVAL"], |row, i, _| pg_wire(row, i, interval_text))
+            .register(&["BYTEA"], |row, i, _| {
+                row.try_get::<Vec<u8>, _>(i)
+                    .ok()
+                    .map(|v| json!(hex_text("\\x", &v, false)))
+            })
+            // Sent as its text in either format
+            .register(&["XML"], |row, i, _| {
+                pg_wire(row, i, |bytes| String::from_utf8(bytes.to_vec()).ok())
+            })
+            // Covers text-like extension types such as citext
+            .fallback(|row, i, _| row.try_get::<String, _>(i).ok().map(Value::String));
+        registry
+    })
+}
+
+pub fn mysql_registry() -> &'static SerializerRegistry<MySqlRow> {
+    static REGISTRY: OnceLock<SerializerRegistry<MySqlRow>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut registry = SerializerRegistry::<MySqlRow>::default();
+        registry
+            .register(&["BOOLEAN"], |row, i, _| {
+                row.try_get::<bool, _>(i).ok().map(|v| json!(v))
+            })
+            .register(&["TINYINT", "SMALLINT", "MEDIUMINT", "INT"], |row, i, _| {
+                row.try_get::<i32, _>(i).ok().map(|v| json!(v))
+            })
+            .register(&["BIGINT"], |row, i, _| {
+                row.try_get::<i64, _>(i).ok().map(int64_value)
+            })
+            .register(
+                &[
+                    "TINYINT UNSIGNED",
+                    "SMALLINT UNSIGNED",
+                    "MEDIUMINT UNSIGNED",
+                    "INT UNSIGNED",
+                    "BIGINT UNSIGNED",
+                ],
+                |row, i, _| row.try_get::<u64, _>(i).ok().map(uint64_value),
+            )
+            .register(&["YEAR"], |row, i, _| {
+                row.try_get_unchecked::<i16, _>(i).ok().map(|v| json!(v))
+            })
+            .register(&["FLOAT"], |row, i, _| {
+                row.try_get::<f32, _>(i).ok().map(|v| json!(v))
+            })
+            .register(&["DOUBLE"], |row, i, _| {
+                row.try_get::<f64, _>(i).ok().map(|v| json!(v))
+            })
+            .register(&["DECIMAL"], |row, i, _| {
+                row.try_get::<BigDecimal, _>(i)
+                    .ok()
+                    .map(|v| json!(v.to_plain_string()))
+            })
+            .register(
+                &[
+                    "CHAR",
+                    "VARCHAR",
+                    "TINYTEXT",
+                    "TEXT",
+                    "MEDIUMTEXT",
+                    "LONGTEXT",
+                    "ENUM",
+                    "SET",
+                ],
+                |row, i, _| row.try_get::<String, _>(i).ok().map(Value::String),
+            )
+            .register(&["TIMESTAMP"], |row, i, zone| {
+                row.try_get::<DateTime<Utc>, _>(i)
+                    .ok()
+                    .map(|v| json!(zone.render(v)))
+            })
+            .register(&["DATETIME"], |row, i, _| {
+                row.try_get::<chrono::NaiveDateTime, _>(i)
+                    .ok()
+                    .map(|v| json!(v.to_string()))
+            })
+            .register(&["DATE"], |row, i, _| {
+                row.try_get::<chrono::NaiveDate, _>(i)
+                    .ok()
+                    .map(|v| json!(v.to_string()))
+            })
+            .register(&["TIME"], |row, i, _| {
+                row.try_get::<chrono::NaiveTime, _>(i)
+                    .ok()
+                    .map(|v| json!(v.to_string()))
+            })
+            .register(&["JSON"], |row, i, _| row.try_get::<Value, _>(i).ok())
+            .register(
+                &[
+                    "BINARY",
+                    "VARBINARY",
+                    "TINYBLOB",
+                    "BLOB",
+                    "MEDIUMBLOB",
+                    "LONGBLOB",
+                    "BIT",
+                    "GEOMETRY",
+                ],
+                // BIT and GEOMETRY aren't among the types sqlx reads as bytes
+                |row, i, _| {
+                    row.try_get_unchecked::<Vec<u8>, _>(i)
+                        .ok()
+                        .map(|v| json!(hex_text("0x", &v, true)))
+                },
+            )
+            .fallback(|row, i, _| {
+                row.try_get_unchecked::<Vec<u8>, _>(i)
+                    .ok()
+                    .map(|v| bytes_value(&v))
+            });
+        registry
+    })
+}
+
+// A type decoded from its wire bytes, rendered the way Postgres prints it.
+// Values that came in text format already are.
+fn pg_wire(row: &PgRow, i: usize, binary: fn(&[u8]) -> Option<String>) -> Option<Value> {
+    let raw = row.try_get_raw(i).ok()?;
+    let text = match raw.format() {
+        PgValueFormat::Text => raw.as_str().ok()?.to_string(),
+        PgValueFormat::Binary => binary(raw.as_bytes().ok()?)?,
+    };
+    Some(Value::String(text))
+}
+
+// Family, netmask bits, cidr flag and address length, then the address. A
+// host address prints without its netmask, as in Postgres.
+fn inet_text(bytes: &[u8]) -> Option<String> {
+    let (&[family, bits, cidr, _], address) = bytes.split_first_chunk::<4>()?;
+    let (address, max_bits) = match family {
+        2 => (IpAddr::from(<[u8; 4]>::try_from(address).ok()?), 32),
+        3 => (IpAddr::from(<[u8; 16]>::try_from(address).ok()?), 128),
+        _ => return None,
+    };
+    Some(match cidr == 0 && bits == max_bits {
+        true => address.to_string(),
+        false => format!("{}/{}", address, bits),
+    })
+}
+
+fn macaddr_text(bytes: &[u8]) -> Option<String> {
+    if bytes.len() != 6 && bytes.len() != 8 {
+        return None;
+    }
+    Some(
+        bytes
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<Vec<_>>()
+            .join(":"),
+    )
+}
+
+// Microseconds, days and months, printed in the default IntervalStyle, e.g.
+// "1 year 2 mons -3 days +04:05:06.5". As on the server, a positive field
+// right after a negative one gets an explicit sign, and units are plural
+// unless the value is exactly 1.
+fn interval_text(bytes: &[u8]) -> Option<String> {
+    let bytes: [u8; 16] = bytes.try_into().ok()?;
+    let (micros, rest) = bytes.split_at(8);
+    let micros = i64::from_be_bytes(micros.try_into().ok()?);
+    let days = i32::from_be_bytes(rest[..4].try_into().ok()?);
+    let months = i32::from_be_bytes(rest[4..].try_into().ok()?);
+
+    let mut parts = Vec::new();
+    let mut after_negative = false;
+    for (n, unit) in [(months / 12, "year"), (months % 12, "mon"), (days, "day")] {
+        if n != 0 {
+            parts.push(format!(
+                "{}{} {}{}",
+                if after_negative && n > 0 { "+" } else { "" },
+                n,
+                unit,
+                if n == 1 { "" } else { "s" }
+            ));
+            after_negative = n < 0;
+        }
+    }
+    if micros != 0 || parts.is_empty() {
+        let sign = match micros < 0 {
+            true => "-",
+            false if after_negative => "+",
+            false => "",
+        };
+        let seconds = micros.unsigned_abs() / 1_000_000;
+        let mut time = format!(
+            "{}{:02}:{:02}:{:02}",
+            sign,
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        );
+        let fraction = micros.unsigned_abs() % 1_000_000;
+        if fraction != 0 {
+            time.push_str(format!(".{:06}", fraction).trim_end_matches('0'));
+        }
+        parts.push(time);
+    }
+    Some(parts.join(" "))
+}
+
+pub fn mssql_registry() -> &'static SerializerRegistry<tiberius::Row> {
+    static REGISTRY: OnceLock<SerializerRegistry<tiberius::Row>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut registry = SerializerRegistry::<tiberius::Row>::default();
+        registry
+            .register(&["Bit", "Bitn"], |row, i, _| {
+                row.try_get::<bool, _>(i).ok().flatten().map(|v| json!(v))
+            })
+            .register(&["Int1"], |row, i, _| {
+                row.try_get::<u8, _>(i).ok().flatten().map(|v| json!(v))
+            })
+            .register(&["Int2"], |row, i, _| {
+                row.try_get::<i16, _>(i).ok().flatten().map(|v| json!(v))
+            })
+            .register(&["Int4"], |row, i, _| {
+                row.try_get::<i32, _>(i).ok().flatten().map(|v| json!(v))
+            })
+            .register(&["Int8"], |row, i, _| {
+                row.try_get::<i64, _>(i).ok().flatten().map(int64_value)
+            })
+            // Nullable integers of any width share one wire type
+            .register(&["Intn"], |row, i, _| match row.cells().nth(i)?.1 {
+                ColumnData::U8(Some(v)) => Some(json!(v)),
+                ColumnData::I16(Some(v)) => Some(json!(v)),
+                ColumnData::I32(Some(v)) => Some(json!(v)),
+                ColumnData::I64(Some(v)) => Some(int64_value(*v)),
+                _ => None,
+            })
+            .register(&["Float4", "Float8", "Floatn"], |row, i, _| {
+                match row.cells().nth(i)?.1 {
+                    ColumnData::F32(Some(v)) => Some(json!(v)),
+                    ColumnData::F64(Some(v)) => Some(json!(v)),
+                    _ => None,
+                }
+            })
+            // The driver hands money over as f64; it has exactly four decimal places
+            .register(&["Money", "Money4"], |row, i, _| {
+                row.try_get::<f64, _>(i)
+                    .ok()
+                    .flatten()
+                    .map(|v| json!(format!("{:.4}", v)))
+            })
+            .register(&["Decimaln", "Numericn"], |row, i, _| {
+                row.try_get::<tiberius::numeric::Numeric, _>(i)
+                    .ok()
+                    .flatten()
+                    .map(|v| json!(v.to_string()))
+            })
+            .register(
+                &[
+                    "BigVarChar",
+                    "BigChar",
+                    "NVarchar",
+                    "NChar",
+                    "Text",
+                    "NText",
+                ],
+                |row, i, _| row.try_get::<&str, _>(i).ok().flatten().map(|v| json!(v)),
+            )
+            .register(&["Guid"], |row, i, _| {
+                row.try_get::<uuid::Uuid, _>(i)
+                    .ok()
+                    .flatten()
+                    .map(|v| json!(v.to_string()))
+            })
+            .register(&["DatetimeOffsetn"], |row, i, zone| {
+                row.try_get::<DateTime<FixedOffset>, _>(i)
+                    .ok()
+                    .flatten()
+                    .map(|v| json!(zone.render(v.with_timezone(&Utc))))
+            })
+            .register(
+                &["Datetime", "Datetime4", "Datetimen", "Datetime2"],
+                |row, i, _| {
+                    row.try_get::<chrono::NaiveDateTime, _>(i)
+                        .ok()
+                        .flatten()
+                        .map(|v| json!(v.to_string()))
+                },
+            )
+            .register(&["Daten"], |row, i, _| {
+                row.try_get::<chrono::NaiveDate, _>(i)
+                    .ok()
+                    .flatten()
+                    .map(|v| json!(v.to_string()))
+            })
+            // Fractional seconds only when there are any, e.g. 08:30:00.1234567
+            .register(&["Timen"], |row, i, _| {
+                row.try_get::<chrono::NaiveTime, _>(i)
+                    .ok()
+                    .flatten()
+                    .map(|v| json!(v.to_string()))
+            })
+            .register(&["BigVarBin", "BigBinary", "Image"], |row, i, _| {
+                row.try_get::<&[u8], _>(i)
+                    .ok()
+                    .flatten()
+                    .map(|v| json!(hex_text("0x", v, true)))
+            })
+            .register(&["Xml"], |row, i, _| {
+                row.try_get::<&tiberius::xml::XmlData, _>(i)
+                    .ok()
+                    .flatten()
+                    .map(|v| json!(v.to_string()))
+            })
+            // User-defined and variant columns by the data they carry
+            .fallback(|row, i, _| match row.cells().nth(i)?.1 {
+                ColumnData::String(Some(v)) => Some(json!(v)),
+                ColumnData::Binary(Some(v)) => Some(bytes_value(v)),
+                ColumnData::Xml(Some(v)) => Some(json!(v.to_string())),
+                _ => None,
+            });
+        registry
+    })
+}
+
+pub fn pg_row_values(row: &PgRow, zone: RenderZone) -> Vec<Cell> {
+    let registry = pg_registry();
+    (0..row.len())
+        .map(|i| {
+            if row.try_get_raw(i).map(|v| v.is_null()).unwrap_or(false) {
+                return Cell::Null;
+            }
+            registry.decode(row.column(i).type_info().name(), row, i, zone)
+        })
+        .collect()
+}
+
+pub fn mysql_row_values(row: &MySqlRow, zone: RenderZone) -> Vec<Cell> {
+    let registry = mysql_registry();
+    (0..row.len())
+        .map(|i| {
+            if row.try_get_raw(i).map(|v| v.is_null()).unwrap_or(false) {
+                return Cell::Null;
+            }
+            registry.decode(row.column(i).type_info().name(), row, i, zone)
+        })
+        .collect()
+}
+
+pub fn serialize_mssql_row(row: &tiberius::Row, zone: RenderZone) -> Vec<Cell> {
+    let registry = mssql_registry();
+    row.cells()
+        .enumerate()
+        .map(|(i, (column, data))| {
+            if mssql_is_null(data) {
+                return Cell::Null;
+            }
+            registry.decode(&mssql_type_name(column), row, i, zone)
+        })
+        .collect()
+}
+
+pub fn mssql_type_name(column: &tiberius::Column) -> String {
+    format!("{:?}", column.column_type())
+}
+
+fn mssql_is_null(data: &ColumnData<'_>) -> bool {
+    matches!(
+        data,
+        ColumnData::U8(None)
+            | ColumnData::I16(None)
+            | ColumnData::I32(None)
+            | ColumnData::I64(None)
+            | ColumnData::F32(None)
+            | ColumnData::F64(None)
+            | ColumnData::Bit(None)
+            | ColumnData::String(None)
+            | ColumnData::Guid(None)
+            | ColumnData::Binary(None)
+            | ColumnData::Numeric(None)
+            | ColumnData::Xml(None)
+            | ColumnData::DateTime(None)
+            | ColumnData::SmallDateTime(None)
+            | ColumnData::Time(None)
+            | ColumnData::Date(None)
+            | ColumnData::DateTime2(None)
+            | ColumnData::DateTimeOffset(None)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Rows of already decoded values, so the registry can be tested alone
+    type TestRow = Vec<Option<Value>>;
+
+    fn registry(fallback: bool) -> SerializerRegistry<TestRow> {
+        let mut registry = SerializerRegistry::<TestRow>::default();
+        registry.register(&["INT4", "INT8"], |row, i, _| row[i].clone());
+        if fallback {
+            registry.fallback(|_, _, _| Some(json!("fallback")));
+        }
+        registry
+    }
+
+    #[test]
+    fn decodes_with_the_registered_decoder() {
+        let row = vec![Some(json!(1)), Some(json!(2))];
+        let registry = registry(false);
+        let zone = RenderZone::utc();
+        assert_eq!(
+            registry.decode("INT4", &row, 0, zone),
+            Cell::Value(json!(1))
+        );
+        assert_eq!(
+            registry.decode("INT8", &row, 1, zone),
+            Cell::Value(json!(2))
+        );
+    }
+
+    #[test]
+    fn unknown_types_use_the_fallback() {
+        let row = vec![Some(json!(1))];
+        let cell = registry(true).decode("CITEXT", &row, 0, RenderZone::utc());
+        assert_eq!(cell, Cell::Value(json!("fallback")));
+    }
+
+    #[test]
+    fn undecodable_values_report_their_type() {
+        let row = vec![None];
+        let zone = RenderZone::utc();
+        let failed = Cell::DecodeError {
+            type_name: "INT4".to_string(),
+        };
+        assert_eq!(registry(false).decode("INT4", &row, 0, zone), failed);
+        let unknown = registry(false).decode("GEOGRAPHY", &row, 0, zone);
+        assert_eq!(
+            unknown,
+            Cell::DecodeError {
+                type_name: "GEOGRAPHY".to_string()
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(&failed).unwrap(),
+            json!({"$decode_error": "INT4"})
+        );
+        assert!(failed.try_into_value().is_err());
+    }
+
+    #[test]
+    fn binary_values_print_as_hex() {
+        assert_eq!(hex_text("\\x", &[0x0a, 0xff], false), "\\x0aff");
+        assert_eq!(hex_text("0x", &[0x0a, 0xff], true), "0x0AFF");
+        assert_eq!(bytes_value(b"<a/>"), json!("<a/>"));
+        assert_eq!(bytes_value(&[0xff, 0x00]), json!("0xFF00"));
+    }
+
+    #[test]
+    fn network_addresses_print_like_the_server() {
+        assert_eq!(
+            inet_text(&[2, 32, 0, 4, 192, 168, 0, 1]).as_deref(),
+            Some("192.168.0.1")
+        );
+        assert_eq!(
+            inet_text(&[2, 24, 1, 4, 10, 0, 0, 0]).as_deref(),
+            Some("10.0.0.0/24")
+        );
+        let mut v6 = vec![3, 128, 0, 16];
+        v6.extend([0; 15]);
+        v6.push(1);
+        assert_eq!(inet_text(&v6).as_deref(), Some("::1"));
+        assert_eq!(inet_text(&[9, 32, 0, 4, 1, 2, 3, 4]), None);
+        assert_eq!(inet_text(&[2, 32, 0, 4, 1, 2]), None);
+
+        assert_eq!(
+            macaddr_text(&[0x08, 0x00, 0x2b, 0x01, 0x02, 0x03]).as_deref(),
+            Some("08:00:2b:01:02:03")
+        );
+        assert_eq!(macaddr_text(&[1, 2, 3, 4, 5]), None);
+    }
+
+    fn interval(micros: i64, days: i32, months: i32) -> Option<String> {
+        let mut bytes = micros.to_be_bytes().to_vec();
+        bytes.extend(days.to_be_bytes());
+        bytes.extend(months.to_be_bytes());
+        interval_text(&bytes)
+    }
+
+    #[test]
+    fn intervals_print_in_the_postgres_style() {
+        assert_eq!(interval(0, 0, 0).as_deref(), Some("00:00:00"));
+        assert_eq!(interval(0, 1, 14).as_deref(), Some("1 year 2 mons 1 day"));
+        assert_eq!(interval(0, 0, -12).as_deref(), Some("-1 years"));
+        assert_eq!(interval(0, -1, 0).as_deref(), Some("-1 days"));
+        assert_eq!(interval(0, 2, -12).as_deref(), Some("-1 years +2 days"));
+        assert_eq!(interval(0, -2, 12).as_deref(), Some("1 year -2 days"));
+        assert_eq!(
+            interval(14_706_500_000, -3, 14).as_deref(),
+            Some("1 year 2 mons -3 days +04:05:06.5")
+        );
+        assert_eq!(
+            interval(-1_000_000, 1, 0).as_deref(),
+            Some("1 day -00:00:01")
+        );
+        assert_eq!(interval_text(&[0; 15]), None);
+    }
+}
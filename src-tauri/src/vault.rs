@@ -0,0 +1,152 @@
+// Connection passwords live in the OS keychain. connections.json keeps the
+// URLs without them plus a reference to the keychain entry holding them.
+use url::Url;
+
+use crate::SavedConnection;
+
+const SERVICE: &str = "com.neondeveloperz.dbms";
+
+fn entry(secret_ref: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(SERVICE, secret_ref).map_err(|e| format!("Keychain unavailable: {}", e))
+}
+
+// Splits the password off a URL. URLs that don't parse (e.g. SQL Server named
+// instances) are left alone rather than risk mangling them.
+fn take_password(url: &str) -> (String, Option<String>) {
+    let Ok(mut parsed) = Url::parse(url) else {
+        return (url.to_string(), None);
+    };
+    match parsed.password().map(str::to_string) {
+        Some(password) if parsed.set_password(None).is_ok() => (parsed.to_string(), Some(password)),
+        _ => (url.to_string(), None),
+    }
+}
+
+// A password in a URL that doesn't parse, which take_password can't split off
+fn unparsed_password(url: &str) -> bool {
+    Url::parse(url).is_err()
+        && url
+            .split_once("://")
+            .and_then(|(_, rest)| rest.rsplit_once('@'))
+            .is_some_and(|(userinfo, _)| userinfo.contains(':'))
+}
+
+fn put_password(url: &str, password: Option<&str>) -> String {
+    let Some(password) = password else {
+        return url.to_string();
+    };
+    let Ok(mut parsed) = Url::parse(url) else {
+        return url.to_string();
+    };
+    match parsed.set_password(Some(password)) {
+        Ok(()) => parsed.to_string(),
+        Err(()) => url.to_string(),
+    }
+}
+
+// True while a connection still has a password in plain text
+pub fn is_exposed(connection: &SavedConnection) -> bool {
+    std::iter::once(&connection.url)
+        .chain(&connection.replicas)
+        .any(|url| take_password(url).1.is_some())
+}
+
+// Refuses connections whose passwords would end up in connections.json
+// because their URLs can't be parsed
+pub fn check_sealable(connection: &SavedConnection) -> Result<(), String> {
+    if std::iter::once(&connection.url)
+        .chain(&connection.replicas)
+        .any(|url| unparsed_password(url))
+    {
+        return Err(format!(
+            "The URL of {} can't be parsed, so its password can't be kept in the keychain. \
+             Fix the URL or leave the password out.",
+            connection.name
+        ));
+    }
+    Ok(())
+}
+
+// Moves the passwords of the URL and its replicas into the keychain. URLs are
+// expected with their passwords, as unseal hands them out. The entry is kept
+// under `fallback_ref` when the connection has no reference of its own yet,
+// so re-saving a connection reuses its entry.
+pub fn seal(connection: &mut SavedConnection, fallback_ref: Option<&str>) -> Result<(), String> {
+    let (url, password) = take_password(&connection.url);
+    let mut passwords = vec![password];
+    let mut replicas = Vec::new();
+    for replica in &connection.replicas {
+        let (url, password) = take_password(replica);
+        replicas.push(url);
+        passwords.push(password);
+    }
+    let existing_ref = connection
+        .secret_ref
+        .clone()
+        .or_else(|| fallback_ref.map(str::to_string));
+    if passwords.iter().all(Option::is_none) {
+        // Saved without any password, so an old entry no longer applies
+        if let Some(secret_ref) = existing_ref {
+            forget(&secret_ref)?;
+        }
+        connection.secret_ref = None;
+        return Ok(());
+    }
+
+    let secret_ref = existing_ref.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let secret = serde_json::to_string(&passwords).map_err(|e| e.to_string())?;
+    entry(&secret_ref)?
+        .set_password(&secret)
+        .map_err(|e| format!("Failed to store password in keychain: {}", e))?;
+    connection.url = url;
+    connection.replicas = replicas;
+    connection.secret_ref = Some(secret_ref);
+    Ok(())
+}
+
+// Puts the passwords back into the URLs, for connecting
+pub fn unseal(connection: &mut SavedConnection) -> Result<(), String> {
+    let Some(secret_ref) = &connection.secret_ref else {
+        return Ok(());
+    };
+    let secret = match entry(secret_ref)?.get_password() {
+        Ok(secret) => secret,
+        // Removed from the keychain by hand; connect without a password
+        Err(keyring::Error::NoEntry) => return Ok(()),
+        Err(e) => return Err(format!("Failed to read password from keychain: {}", e)),
+    };
+    let passwords: Vec<Option<String>> =
+        serde_json::from_str(&secret).map_err(|e| format!("Keychain entry is corrupt: {}", e))?;
+    connection.url = put_password(
+        &connection.url,
+        passwords.first().cloned().flatten().as_deref(),
+    );
+    for (i, replica) in connection.replicas.iter_mut().enumerate() {
+        let password = passwords.get(i + 1).cloned().flatten();
+        *replica = put_password(replica, password.as_deref());
+    }
+    Ok(())
+}
+
+pub fn forget(secret_ref: &str) -> Result<(), String> {
+    match entry(secret_ref)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(format!("Failed to remove password from keychain: {}", e)),
+    }
+}
+
+// A secret of its own under `secret_ref`, for credentials that aren't part of
+// a connection URL (webhook URLs, SMTP passwords)
+pub fn store(secret_ref: &str, secret: &str) -> Result<(), String> {
+    entry(secret_ref)?
+        .set_password(secret)
+        .map_err(|e| format!("Failed to store secret in keychain: {}", e))
+}
+
+pub fn load(secret_ref: &str) -> Result<Option<String>, String> {
+    match entry(secret_ref)?.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Failed to read secret from keychain: {}", e)),
+    }
+}
@@ -0,0 +1,54 @@
+// Warm-up after connecting: a few pool connections are opened and the
+// completion metadata is loaded in the background, so neither the first query
+// nor the first completion waits on it. A "connection-ready" event follows.
+use futures::future::join_all;
+use serde::Serialize;
+use std::time::Instant;
+use tauri::{Emitter, Manager};
+
+use crate::db::{self, DatabaseState};
+
+// Pool connections opened ahead of the first queries
+const WARM_CONNECTIONS: usize = 3;
+
+#[derive(Clone, Serialize)]
+pub struct ConnectionReadyEvent {
+    pub connection: String,
+    pub tables: Option<usize>, // None when no metadata was loaded
+    pub elapsed_ms: u64,
+    pub error: Option<String>, // warm-up failed; the connection still works
+}
+
+pub fn start(app: tauri::AppHandle, name: String) {
+    tokio::spawn(async move {
+        let started = Instant::now();
+        let state = app.state::<DatabaseState>();
+        // Doesn't count as a use, like keep-alive pings
+        let Ok(client) = state.peek(&name) else {
+            return;
+        };
+        let mut event = ConnectionReadyEvent {
+            connection: name.clone(),
+            tables: None,
+            elapsed_ms: 0,
+            error: None,
+        };
+        // Pings in parallel make the pool open connections for each
+        let pings = join_all((0..WARM_CONNECTIONS).map(|_| db::ping(&client))).await;
+        if let Some(Err(e)) = pings.into_iter().find(Result::is_err) {
+            event.error = Some(e);
+        } else if client.dialect().is_some() {
+            match state.metadata.completion(&name, &client).await {
+                Ok(metadata) => event.tables = Some(metadata.tables.len()),
+                Err(e) => event.error = Some(e),
+            }
+        }
+        if let Some(e) = &event.error {
+            log::warn!("Warm-up of {} failed: {}", name, e);
+        }
+        event.elapsed_ms = started.elapsed().as_millis() as u64;
+        if let Err(e) = app.emit("connection-ready", event) {
+            log::warn!("Failed to emit readiness of {}: {}", name, e);
+        }
+    });
+}
@@ -0,0 +1,235 @@
+// Snippets and notebooks kept as plain files in a folder the user picks
+// rather than in app data, so they can be shared and version controlled; git
+// is asked for the state of each file
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use tokio::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetKind {
+    Snippet,  // .sql
+    Notebook, // .sqlnb, JSON cells written by the frontend
+}
+
+impl AssetKind {
+    fn of(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "sql" => Some(AssetKind::Snippet),
+            "sqlnb" => Some(AssetKind::Notebook),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkspaceFile {
+    pub path: String, // relative to the workspace, with forward slashes
+    pub kind: AssetKind,
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GitState {
+    Modified,
+    Added,
+    Deleted,
+    Renamed,
+    Untracked,
+    Conflicted,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GitFileStatus {
+    pub path: String, // absolute
+    pub state: GitState,
+    pub staged: bool, // the change is in the index
+}
+
+// Files not listed are unchanged since the last commit
+#[derive(Debug, Serialize)]
+pub struct GitStatus {
+    pub repository: Option<String>, // None outside a git repository
+    pub branch: Option<String>,     // None on a detached HEAD
+    pub files: Vec<GitFileStatus>,
+}
+
+pub fn root(workspace_dir: Option<&str>) -> Result<PathBuf, String> {
+    let dir = workspace_dir
+        .filter(|d| !d.trim().is_empty())
+        .ok_or("Choose a workspace folder in the settings first")?;
+    let dir = PathBuf::from(dir);
+    if !dir.is_dir() {
+        return Err(format!("Workspace folder {:?} does not exist", dir));
+    }
+    Ok(dir)
+}
+
+// A path inside the workspace for a snippet or notebook; absolute paths and
+// `..` are refused, so nothing outside it is read or written
+fn asset_path(root: &Path, relative: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(relative);
+    let inside = relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !inside || AssetKind::of(relative).is_none() {
+        return Err(format!(
+            "{} is not a snippet (.sql) or notebook (.sqlnb) path in the workspace",
+            relative.display()
+        ));
+    }
+    Ok(root.join(relative))
+}
+
+fn walk(root: &Path, dir: &Path, files: &mut Vec<WorkspaceFile>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read {:?}: {}", dir, e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let path = entry.path();
+        // Hidden folders such as .git are skipped
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let metadata = entry.metadata().map_err(|e| e.to_string())?;
+        if metadata.is_dir() {
+            walk(root, &path, files)?;
+        } else if let Some(kind) = AssetKind::of(&path) {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            files.push(WorkspaceFile {
+                path: relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+                kind,
+                size: metadata.len(),
+                modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+            });
+        }
+    }
+    Ok(())
+}
+
+pub fn list(root: &Path) -> Result<Vec<WorkspaceFile>, String> {
+    let mut files = Vec::new();
+    walk(root, root, &mut files)?;
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(files)
+}
+
+pub fn read(root: &Path, relative: &str) -> Result<String, String> {
+    let path = asset_path(root, relative)?;
+    fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", relative, e))
+}
+
+// Creates the file and any folders on its path, or overwrites it
+pub fn write(root: &Path, relative: &str, content: &str) -> Result<(), String> {
+    let path = asset_path(root, relative)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", relative, e))
+}
+
+pub fn delete(root: &Path, relative: &str) -> Result<(), String> {
+    let path = asset_path(root, relative)?;
+    fs::remove_file(&path).map_err(|e| format!("Failed to delete {}: {}", relative, e))
+}
+
+// Runs git in `dir`; None when git exits with an error, e.g. outside a repository
+async fn git(dir: &Path, args: &[&str]) -> Result<Option<Vec<u8>>, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| format!("Failed to run git (is it installed?): {}", e))?;
+    Ok(output.status.success().then_some(output.stdout))
+}
+
+fn git_state(code: &str) -> Option<(GitState, bool)> {
+    let mut chars = code.chars();
+    let (index, tree) = (chars.next()?, chars.next()?);
+    if code == "??" {
+        return Some((GitState::Untracked, false));
+    }
+    if index == 'U' || tree == 'U' || code == "AA" || code == "DD" {
+        return Some((GitState::Conflicted, false));
+    }
+    let staged = index != ' ';
+    let state = match if tree != ' ' { tree } else { index } {
+        'M' | 'T' => GitState::Modified,
+        'A' => GitState::Added,
+        'D' => GitState::Deleted,
+        'R' | 'C' => GitState::Renamed,
+        _ => return None,
+    };
+    Some((state, staged))
+}
+
+// Git state of a file, or of every file under a folder, e.g. a workspace or
+// SQL project
+pub async fn file_status(path: &str) -> Result<GitStatus, String> {
+    let path = PathBuf::from(path);
+    if !path.exists() {
+        return Err(format!("{} does not exist", path.display()));
+    }
+    let dir = if path.is_dir() {
+        path.clone()
+    } else {
+        path.parent().map(Path::to_path_buf).unwrap_or_default()
+    };
+    let Some(top) = git(&dir, &["rev-parse", "--show-toplevel"]).await? else {
+        return Ok(GitStatus {
+            repository: None,
+            branch: None,
+            files: Vec::new(),
+        });
+    };
+    let repository = PathBuf::from(String::from_utf8_lossy(&top).trim());
+    let branch = git(&repository, &["symbolic-ref", "--short", "-q", "HEAD"])
+        .await?
+        .map(|b| String::from_utf8_lossy(&b).trim().to_string())
+        .filter(|b| !b.is_empty());
+
+    let pathspec = path.to_string_lossy().to_string();
+    let args = [
+        "status",
+        "--porcelain=v1",
+        "-z",
+        "--untracked-files=all",
+        "--",
+        pathspec.as_str(),
+    ];
+    let output = git(&repository, &args).await?.unwrap_or_default();
+    let output = String::from_utf8_lossy(&output);
+    let mut entries = output.split('\0').filter(|e| !e.is_empty());
+    let mut files = Vec::new();
+    while let Some(entry) = entries.next() {
+        let (Some(code), Some(file)) = (entry.get(..2), entry.get(3..)) else {
+            continue;
+        };
+        // A rename is followed by the path it was renamed from
+        if code.starts_with(['R', 'C']) {
+            entries.next();
+        }
+        if let Some((state, staged)) = git_state(code) {
+            files.push(GitFileStatus {
+                path: repository.join(file).to_string_lossy().to_string(),
+                state,
+                staged,
+            });
+        }
+    }
+    Ok(GitStatus {
+        repository: Some(repository.to_string_lossy().to_string()),
+        branch,
+        files,
+    })
+}
@@ -0,0 +1,261 @@
+// Excel exports with typed cells: numbers, booleans and dates are written as
+// spreadsheet values, not text, so they can be summed and sorted in Excel
+use chrono::{Datelike, NaiveDate, Timelike};
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook, Worksheet, XlsxError};
+use serde_json::Value;
+use std::sync::Mutex as StdMutex;
+
+use crate::db::{ColumnMeta, RowBatch};
+use crate::export_columns::{parse_temporal, Temporal};
+
+// Rows in an Excel sheet, the header included
+const MAX_ROWS: u32 = 1_048_576;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CellKind {
+    Number,     // exact decimals and big integers, sent as strings
+    DateOrTime, // rendered as text by the decoders
+    Other,
+}
+
+fn cell_kind(meta: &ColumnMeta) -> CellKind {
+    match meta.value_tag {
+        Some("decimal" | "int64") => return CellKind::Number,
+        Some("date" | "time" | "datetime" | "smalldatetime" | "datetimeoffset") => {
+            return CellKind::DateOrTime
+        }
+        _ => {}
+    }
+    let type_name = meta.type_name.to_uppercase();
+    if type_name.contains("TIMESTAMP")
+        || type_name.contains("DATETIME")
+        || type_name == "DATE"
+        || type_name == "TIME"
+    {
+        CellKind::DateOrTime
+    } else {
+        CellKind::Other
+    }
+}
+
+fn seconds(time: &impl Timelike) -> f64 {
+    time.second() as f64 + time.nanosecond() as f64 / 1e9
+}
+
+fn excel_date(date: &NaiveDate) -> Result<ExcelDateTime, XlsxError> {
+    ExcelDateTime::from_ymd(date.year() as u16, date.month() as u8, date.day() as u8)
+}
+
+// Date and time values keep the wall-clock time they were rendered in.
+// None for anything that doesn't parse, or that Excel can't hold (before 1900).
+fn excel_datetime<'a>(text: &str, formats: &'a Formats) -> Option<(ExcelDateTime, &'a Format)> {
+    let value = match parse_temporal(text)? {
+        Temporal::Date(date) => (excel_date(&date), &formats.date),
+        Temporal::DateTime(value) => {
+            let (hour, minute) = (value.hour() as u16, value.minute() as u8);
+            let datetime =
+                excel_date(&value.date()).and_then(|d| d.and_hms(hour, minute, seconds(&value)));
+            (datetime, &formats.datetime)
+        }
+        Temporal::Time(value) => {
+            let (hour, minute) = (value.hour() as u16, value.minute() as u8);
+            let time = ExcelDateTime::from_hms(hour, minute, seconds(&value));
+            (time, &formats.time)
+        }
+    };
+    match value {
+        (Ok(datetime), format) => Some((datetime, format)),
+        (Err(_), _) => None,
+    }
+}
+
+struct Formats {
+    header: Format,
+    date: Format,
+    datetime: Format,
+    time: Format,
+}
+
+struct Sheet {
+    workbook: Workbook,
+    formats: Formats,
+    kinds: Vec<CellKind>,
+    next_row: u32,
+}
+
+fn write_cell(
+    sheet: &mut Worksheet,
+    formats: &Formats,
+    (row, col): (u32, u16),
+    value: Value,
+    kind: CellKind,
+) -> Result<(), XlsxError> {
+    match value {
+        Value::Null => {}
+        Value::Bool(b) => {
+            sheet.write_boolean(row, col, b)?;
+        }
+        Value::Number(n) => {
+            sheet.write_number(row, col, n.as_f64().unwrap_or_default())?;
+        }
+        Value::String(s) => match kind {
+            CellKind::Number => match s.parse::<f64>() {
+                Ok(n) if n.is_finite() => {
+                    sheet.write_number(row, col, n)?;
+                }
+                _ => {
+                    sheet.write_string(row, col, s)?;
+                }
+            },
+            CellKind::DateOrTime => {
+                match excel_datetime(&s, formats) {
+                    Some((value, format)) => {
+                        sheet.write_datetime_with_format(row, col, &value, format)?
+                    }
+                    None => sheet.write_string(row, col, s)?,
+                };
+            }
+            CellKind::Other => {
+                sheet.write_string(row, col, s)?;
+            }
+        },
+        other => {
+            sheet.write_string(row, col, other.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+// Collects streamed row batches into a single-sheet workbook, saved by `finish`.
+// The whole sheet stays in memory until then, so its columns can be autofit;
+// only the one sheet's row limit bounds it.
+pub struct XlsxSink {
+    sheet: StdMutex<Sheet>,
+    path: String,
+    include_headers: bool,
+}
+
+impl XlsxSink {
+    pub fn create(path: &str, include_headers: bool) -> Self {
+        let mut workbook = Workbook::new();
+        workbook.add_worksheet();
+        XlsxSink {
+            sheet: StdMutex::new(Sheet {
+                workbook,
+                formats: Formats {
+                    header: Format::new().set_bold(),
+                    date: Format::new().set_num_format("yyyy-mm-dd"),
+                    datetime: Format::new().set_num_format("yyyy-mm-dd hh:mm:ss"),
+                    time: Format::new().set_num_format("hh:mm:ss"),
+                },
+                kinds: Vec::new(),
+                next_row: 0,
+            }),
+            path: path.to_string(),
+            include_headers,
+        }
+    }
+
+    pub fn write(&self, batch: RowBatch) -> Result<(), String> {
+        let mut guard = self.sheet.lock().unwrap();
+        let Sheet {
+            workbook,
+            formats,
+            kinds,
+            next_row,
+        } = &mut *guard;
+        let sheet = workbook
+            .worksheet_from_index(0)
+            .map_err(|e| e.to_string())?;
+        if let Some(meta) = &batch.column_meta {
+            *kinds = meta.iter().map(cell_kind).collect();
+        }
+        // Columns are only sent again when they change, as for Mongo documents;
+        // the header is written once
+        if let (Some(columns), true, 0) = (&batch.columns, self.include_headers, *next_row) {
+            for (col, name) in columns.iter().enumerate() {
+                sheet
+                    .write_string_with_format(0, col as u16, name, &formats.header)
+                    .map_err(|e| e.to_string())?;
+            }
+            *next_row = 1;
+        }
+        for row in batch.rows {
+            if *next_row >= MAX_ROWS {
+                return Err(format!(
+                    "An Excel sheet holds at most {} rows; export to CSV instead",
+                    MAX_ROWS
+                ));
+            }
+            for (col, cell) in row.into_iter().enumerate() {
+                let kind = kinds.get(col).copied().unwrap_or(CellKind::Other);
+                write_cell(
+                    sheet,
+                    formats,
+                    (*next_row, col as u16),
+                    cell.try_into_value()?,
+                    kind,
+                )
+                .map_err(|e| e.to_string())?;
+            }
+            *next_row += 1;
+        }
+        Ok(())
+    }
+
+    // Sizes the columns to their contents and writes the file
+    pub fn finish(self) -> Result<(), String> {
+        let mut sheet = self.sheet.into_inner().unwrap();
+        sheet
+            .workbook
+            .worksheet_from_index(0)
+            .map_err(|e| e.to_string())?
+            .autofit();
+        sheet
+            .workbook
+            .save(&self.path)
+            .map_err(|e| format!("Failed to write {}: {}", self.path, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(type_name: &str, value_tag: Option<&'static str>) -> ColumnMeta {
+        ColumnMeta {
+            name: "c".to_string(),
+            type_name: type_name.to_string(),
+            timezone: None,
+            value_tag,
+        }
+    }
+
+    #[test]
+    fn only_decimal_and_int64_tags_are_numbers() {
+        assert_eq!(
+            cell_kind(&meta("NUMERIC", Some("decimal"))),
+            CellKind::Number
+        );
+        assert_eq!(cell_kind(&meta("INT8", Some("int64"))), CellKind::Number);
+        assert_eq!(cell_kind(&meta("string", Some("extjson"))), CellKind::Other);
+        assert_eq!(
+            cell_kind(&meta("hierarchyid", Some("hierarchyid"))),
+            CellKind::Other
+        );
+        assert_eq!(cell_kind(&meta("inet", Some("inet"))), CellKind::Other);
+    }
+
+    #[test]
+    fn sql_server_temporal_tags_are_dates() {
+        for tag in [
+            "date",
+            "time",
+            "datetime",
+            "smalldatetime",
+            "datetimeoffset",
+        ] {
+            assert_eq!(cell_kind(&meta("x", Some(tag))), CellKind::DateOrTime);
+        }
+    }
+}